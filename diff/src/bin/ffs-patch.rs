@@ -0,0 +1,175 @@
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, Command};
+use tracing::{error, warn};
+
+use nodelike::config::{Config, ERROR_STATUS_CLI, ERROR_STATUS_FUSE, Output, POSSIBLE_FORMATS};
+use nodelike::patch::apply;
+use nodelike::{
+    Format, Nodelike, cbor, csv, dotenv, ini, json, json5, msgpack, ndjson, plist, properties,
+    toml, tsv, xml, yaml,
+};
+
+use ffs_diff::{format_of, load, open};
+
+pub fn patch_cli() -> Command {
+    nodelike::config::cli_base("ffs-patch")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("apply a JSON Patch (RFC 6902) or JSON Merge Patch (RFC 7386) to semi-structured data")
+        .arg(
+            Arg::new("OUTPUT")
+                .help("Sets the output file for the patched document (defaults to stdout)")
+                .long("output")
+                .short('o')
+                .value_name("OUTPUT")
+        )
+        .arg(
+            Arg::new("INPLACE")
+                .help("Writes the patched document back over the input file")
+                .long("in-place")
+                .short('i')
+                .overrides_with("OUTPUT")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("SOURCE_FORMAT")
+                .help("Specify the source format explicitly (by default, automatically inferred from filename extension)")
+                .long("source")
+                .short('s')
+                .value_name("SOURCE_FORMAT")
+                .value_parser(POSSIBLE_FORMATS)
+        )
+        .arg(
+            Arg::new("TARGET_FORMAT")
+                .help("Specify the target format explicitly (by default, automatically inferred from filename extension)")
+                .long("target")
+                .short('t')
+                .value_name("TARGET_FORMAT")
+                .value_parser(POSSIBLE_FORMATS)
+        )
+        .arg(
+            Arg::new("PRETTY")
+                .help("Pretty-print output (may increase size)")
+                .long("pretty")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("INPUT")
+                .help("The document to patch ('-' means STDIN)")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("PATCH")
+                .help("The patch, in JSON: an array is a JSON Patch, anything else is a JSON Merge Patch ('-' means STDIN)")
+                .required(true)
+                .index(2),
+        )
+}
+
+fn main() {
+    let (mut config, args) = Config::from_cli(patch_cli);
+    config.pretty = args.get_flag("PRETTY");
+    // we read documents directly rather than as files in a mount, so a
+    // string's final newline is part of it
+    config.add_newlines = false;
+
+    let input = args.get_one::<String>("INPUT").unwrap();
+    let patch = args.get_one::<String>("PATCH").unwrap();
+    if input == "-" && patch == "-" {
+        error!("Only one of the document and the patch can come from STDIN.");
+        std::process::exit(ERROR_STATUS_CLI);
+    }
+
+    config.input_format = format_of(input, args.get_one::<String>("SOURCE_FORMAT"));
+
+    config.output = if args.get_flag("INPLACE") {
+        if input == "-" {
+            error!("Can't patch STDIN in place.");
+            std::process::exit(ERROR_STATUS_CLI);
+        }
+        Output::File(PathBuf::from(input))
+    } else if let Some(output) = args.get_one::<String>("OUTPUT") {
+        Output::File(PathBuf::from(output))
+    } else {
+        Output::Stdout
+    };
+
+    // the target format is given, or it's the output's, or it's the input's
+    config.output_format = match args.get_one::<String>("TARGET_FORMAT") {
+        Some(target) => format_of(input, Some(target)),
+        None => match &config.output {
+            Output::File(output) if !args.get_flag("INPLACE") => {
+                Format::from_path(output).unwrap_or(config.input_format)
+            }
+            _ => config.input_format,
+        },
+    };
+    if config.pretty && !config.output_format.can_be_pretty() {
+        warn!(
+            "There is no pretty printing routine for {}.",
+            config.output_format
+        )
+    }
+
+    let patch = match open(patch)
+        .map_err(nodelike::Error::from)
+        .and_then(|reader| json::Value::from_reader(reader, &config))
+    {
+        Ok(patch) => patch,
+        Err(e) => {
+            error!("Couldn't load the patch {patch}: {e}");
+            std::process::exit(e.exit_status());
+        }
+    };
+
+    fn run_patch<V: Nodelike>(input: &str, patch: &json::Value, config: &Config) {
+        let doc: V = load(input, config.input_format, config).unwrap_or_else(|e| {
+            error!("Couldn't load {input}: {e}");
+            std::process::exit(e.exit_status());
+        });
+
+        let doc = apply(doc, patch, config).unwrap_or_else(|e| {
+            error!("Couldn't patch {input}: {e}");
+            std::process::exit(e.exit_status());
+        });
+
+        let output = match config.output_writer() {
+            Ok(Some(output)) => output,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Couldn't open {} for output: {e}", config.output);
+                std::process::exit(ERROR_STATUS_FUSE);
+            }
+        };
+        let written = output
+            .writer()
+            .map_err(nodelike::Error::from)
+            .and_then(|writer| doc.to_writer(writer, config));
+        if let Err(e) = written
+            .map_err(std::io::Error::other)
+            .and_then(|()| output.commit())
+        {
+            error!("Couldn't save to {}: {e}", config.output);
+            std::process::exit(ERROR_STATUS_FUSE);
+        }
+    }
+
+    match config.output_format {
+        Format::Cbor => run_patch::<cbor::Value>(input, &patch, &config),
+        Format::Csv => run_patch::<csv::Value>(input, &patch, &config),
+        Format::Dotenv => run_patch::<dotenv::Value>(input, &patch, &config),
+        Format::Ini => run_patch::<ini::Value>(input, &patch, &config),
+        Format::Json => run_patch::<json::Value>(input, &patch, &config),
+        Format::Json5 => run_patch::<json5::Value>(input, &patch, &config),
+        Format::MsgPack => run_patch::<msgpack::Value>(input, &patch, &config),
+        Format::Ndjson => run_patch::<ndjson::Value>(input, &patch, &config),
+        Format::Plist => run_patch::<plist::Value>(input, &patch, &config),
+        Format::Properties => run_patch::<properties::Value>(input, &patch, &config),
+        Format::Toml => run_patch::<toml::Value>(input, &patch, &config),
+        Format::Tsv => run_patch::<tsv::Value>(input, &patch, &config),
+        Format::Xml => run_patch::<xml::Value>(input, &patch, &config),
+        Format::Yaml => run_patch::<yaml::Value>(input, &patch, &config),
+    }
+}
@@ -0,0 +1,50 @@
+//! Structural diffs and patches for semi-structured data.
+//!
+//! `ffs-diff` compares two documents and prints the differences, as a list
+//! of paths or as a patch; `ffs-patch` applies a patch to a document. The
+//! documents can be in any format; patches are always JSON. The real work
+//! happens in `nodelike::diff` and `nodelike::patch`.
+
+use std::path::Path;
+
+use nodelike::{Error, Format, Nodelike, ParseFormatError};
+use tracing::warn;
+
+use nodelike::config::Config;
+
+/// The format of the document at `path`: `explicit`, if given, or else
+/// whatever the extension says, or else JSON.
+pub fn format_of(path: &str, explicit: Option<&String>) -> Format {
+    if let Some(format) = explicit.and_then(|s| s.parse::<Format>().ok()) {
+        return format;
+    }
+
+    match Format::from_path(Path::new(path)) {
+        Ok(format) => format,
+        Err(ParseFormatError::NoSuchFormat(_)) => {
+            warn!("Unrecognized format {path}, defaulting to JSON.");
+            Format::Json
+        }
+        Err(ParseFormatError::NoFormatProvided) => Format::Json,
+    }
+}
+
+/// Opens `path` for reading; `-` means STDIN.
+pub fn open(path: &str) -> std::io::Result<Box<dyn std::io::Read>> {
+    if path == "-" {
+        Ok(Box::new(std::io::stdin()))
+    } else {
+        let file = std::fs::File::open(path)?;
+        Ok(Box::new(std::io::BufReader::new(file)))
+    }
+}
+
+/// Loads the document at `path`, which is in `format`, as a `V`; compressed
+/// documents are decompressed (see `Config::compression_for`).
+pub fn load<V: Nodelike>(path: &str, format: Format, config: &Config) -> Result<V, Error> {
+    let reader = match config.compression_for(Path::new(path)) {
+        Some(compression) => compression.decoder(open(path)?)?,
+        None => open(path)?,
+    };
+    nodelike::load(reader, format, config)
+}
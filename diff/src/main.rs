@@ -0,0 +1,152 @@
+use clap::{Arg, ArgAction, Command};
+use tracing::error;
+
+use nodelike::config::{Config, ERROR_STATUS_CLI, ERROR_STATUS_FUSE, POSSIBLE_FORMATS};
+use nodelike::diff::diff;
+use nodelike::patch::{json_patch, merge_patch};
+use nodelike::{
+    Format, Nodelike, cbor, csv, dotenv, ini, json, json5, msgpack, ndjson, plist, properties,
+    toml, tsv, xml, yaml,
+};
+
+use ffs_diff::{format_of, load};
+
+pub fn diff_cli() -> Command {
+    nodelike::config::cli_base("ffs-diff")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("structural diff of semi-structured data")
+        .arg(
+            Arg::new("SOURCE_FORMAT")
+                .help("Specify the format of both documents explicitly (by default, automatically inferred from each filename's extension)")
+                .long("source")
+                .short('s')
+                .value_name("SOURCE_FORMAT")
+                .value_parser(POSSIBLE_FORMATS)
+        )
+        .arg(
+            Arg::new("JSON_PATCH")
+                .help("Print the differences as a JSON Patch (RFC 6902)")
+                .long("json-patch")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("MERGE_PATCH")
+                .help("Print the differences as a JSON Merge Patch (RFC 7386)")
+                .long("merge-patch")
+                .conflicts_with("JSON_PATCH")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("PRETTY")
+                .help("Pretty-print patches")
+                .long("pretty")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("OLD")
+                .help("The original document ('-' means STDIN)")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("NEW")
+                .help("The changed document ('-' means STDIN)")
+                .required(true)
+                .index(2),
+        )
+}
+
+enum Style {
+    Paths,
+    JsonPatch,
+    MergePatch,
+}
+
+fn main() {
+    let (mut config, args) = Config::from_cli(diff_cli);
+    config.pretty = args.get_flag("PRETTY");
+    // we read documents directly rather than as files in a mount, so a
+    // string's final newline is part of it
+    config.add_newlines = false;
+
+    let old = args.get_one::<String>("OLD").unwrap();
+    let new = args.get_one::<String>("NEW").unwrap();
+    if old == "-" && new == "-" {
+        error!("Only one of the documents can come from STDIN.");
+        std::process::exit(ERROR_STATUS_CLI);
+    }
+
+    let source = args.get_one::<String>("SOURCE_FORMAT");
+    let old_format = format_of(old, source);
+    let new_format = format_of(new, source);
+
+    let style = if args.get_flag("JSON_PATCH") {
+        Style::JsonPatch
+    } else if args.get_flag("MERGE_PATCH") {
+        Style::MergePatch
+    } else {
+        Style::Paths
+    };
+
+    // compare in the format of the new document
+    fn run_diff<V: Nodelike + Clone>(
+        old: (&str, Format),
+        new: (&str, Format),
+        style: Style,
+        config: &Config,
+    ) {
+        let load = |(path, format): (&str, Format)| -> V {
+            load(path, format, config).unwrap_or_else(|e| {
+                error!("Couldn't load {path}: {e}");
+                std::process::exit(e.exit_status());
+            })
+        };
+        let old = load(old);
+        let new = load(new);
+
+        let patch = match style {
+            Style::Paths => {
+                // values are printed as JSON, whatever the documents' format
+                for change in diff(old, new, config) {
+                    println!("{}", change.convert::<json::Value>(config));
+                }
+                return;
+            }
+            Style::JsonPatch => json_patch(diff(old, new, config), config),
+            Style::MergePatch => merge_patch(old, new, config),
+        };
+
+        let patch = if config.pretty {
+            serde_json::to_string_pretty(&patch)
+        } else {
+            serde_json::to_string(&patch)
+        };
+        match patch {
+            Ok(patch) => println!("{patch}"),
+            Err(e) => {
+                error!("Couldn't print the patch: {e}");
+                std::process::exit(ERROR_STATUS_FUSE);
+            }
+        }
+    }
+
+    let old = (old.as_str(), old_format);
+    let new = (new.as_str(), new_format);
+    match new_format {
+        Format::Cbor => run_diff::<cbor::Value>(old, new, style, &config),
+        Format::Csv => run_diff::<csv::Value>(old, new, style, &config),
+        Format::Dotenv => run_diff::<dotenv::Value>(old, new, style, &config),
+        Format::Ini => run_diff::<ini::Value>(old, new, style, &config),
+        Format::Json => run_diff::<json::Value>(old, new, style, &config),
+        Format::Json5 => run_diff::<json5::Value>(old, new, style, &config),
+        Format::MsgPack => run_diff::<msgpack::Value>(old, new, style, &config),
+        Format::Ndjson => run_diff::<ndjson::Value>(old, new, style, &config),
+        Format::Plist => run_diff::<plist::Value>(old, new, style, &config),
+        Format::Properties => run_diff::<properties::Value>(old, new, style, &config),
+        Format::Toml => run_diff::<toml::Value>(old, new, style, &config),
+        Format::Tsv => run_diff::<tsv::Value>(old, new, style, &config),
+        Format::Xml => run_diff::<xml::Value>(old, new, style, &config),
+        Format::Yaml => run_diff::<yaml::Value>(old, new, style, &config),
+    }
+}
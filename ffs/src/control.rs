@@ -0,0 +1,73 @@
+/// The control directory, which lives in the root of the filesystem.
+///
+/// Its files report on the mount (e.g., whether there are unsaved changes)
+/// or trigger actions when written to (e.g., saving). The control directory
+/// is never saved.
+pub const CONTROL_DIR: &str = ".ffs";
+
+/// Mode of the control directory itself
+pub const CONTROL_DIR_MODE: u32 = 0o555;
+
+/// Files in the control directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// `true` when there are unsaved changes
+    Dirty,
+    /// `true` when the filesystem has ever been saved
+    Synced,
+    /// `true` when the input changed on disk while there were unsaved changes
+    Conflict,
+    /// Where the filesystem was loaded from
+    Input,
+    /// Where the filesystem will be saved
+    Output,
+    /// The format of the input (and the output, unless `--target` says otherwise)
+    Format,
+    /// The number of inodes in use
+    InodeCount,
+    /// Writing saves the filesystem, even over a conflict
+    Sync,
+    /// Writing re-reads the input, unless there are unsaved changes
+    Reload,
+    /// Writing discards unsaved changes and re-reads the input
+    Revert,
+}
+
+impl Control {
+    pub const ALL: [Control; 10] = [
+        Control::Dirty,
+        Control::Synced,
+        Control::Conflict,
+        Control::Input,
+        Control::Output,
+        Control::Format,
+        Control::InodeCount,
+        Control::Sync,
+        Control::Reload,
+        Control::Revert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Control::Dirty => "dirty",
+            Control::Synced => "synced",
+            Control::Conflict => "conflict",
+            Control::Input => "input",
+            Control::Output => "output",
+            Control::Format => "format",
+            Control::InodeCount => "inode_count",
+            Control::Sync => "sync",
+            Control::Reload => "reload",
+            Control::Revert => "revert",
+        }
+    }
+
+    /// Triggers do something when written to; everything else is read-only.
+    pub fn is_trigger(&self) -> bool {
+        matches!(self, Control::Sync | Control::Reload | Control::Revert)
+    }
+
+    pub fn mode(&self) -> u32 {
+        if self.is_trigger() { 0o200 } else { 0o444 }
+    }
+}
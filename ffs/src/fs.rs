@@ -1,31 +1,41 @@
 use std::collections::BTreeMap;
 use std::ffi::OsStr;
-use std::path::Path;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
 use fuser::{Errno, INodeNo};
 #[cfg(target_os = "linux")]
 use fuser::{
-    FileAttr, FileType, Filesystem, ReplyAttr, ReplyBmap, ReplyCreate, ReplyData, ReplyDirectory,
-    ReplyDirectoryPlus, ReplyEmpty, ReplyEntry, ReplyIoctl, ReplyLock, ReplyLseek, ReplyOpen,
-    ReplyStatfs, ReplyWrite, ReplyXattr, Request, TimeOrNow,
+    FileAttr, FileType, Filesystem, KernelConfig, ReplyAttr, ReplyBmap, ReplyCreate, ReplyData,
+    ReplyDirectory, ReplyDirectoryPlus, ReplyEmpty, ReplyEntry, ReplyIoctl, ReplyLock, ReplyLseek,
+    ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr, Request, TimeOrNow,
 };
 
 use tracing::{debug, error, info, instrument, warn};
 
-use nodelike::config::{Config, ERROR_STATUS_FUSE, Munge, Output};
+use crate::control::{CONTROL_DIR, CONTROL_DIR_MODE, Control};
+
+use nodelike::anchor_base;
+use nodelike::config::{Config, ERROR_STATUS_FUSE, Input, Munge, Output};
 use nodelike::time_ns;
-use nodelike::{Format, Node, Nodelike, Typ, json, toml, yaml};
+use nodelike::{
+    Error, Format, Node, Nodelike, Typ, cbor, csv, dotenv, ini, json, json5, msgpack, ndjson,
+    plist, properties, toml, tsv, xml, yaml,
+};
 
 /// A filesystem `FS` is just a vector of nullable inodes, where the index is
 /// the inode number.
 ///
 /// NB that inode 0 is always invalid.
+///
+/// The state is shared so that the autosave thread (see `Filesystem::init`)
+/// can sync it in the background.
 #[derive(Debug)]
 pub struct FS<V: Nodelike> {
-    pub state: Mutex<FSState<V>>,
+    pub state: Arc<Mutex<FSState<V>>>,
 }
 
 #[derive(Debug)]
@@ -38,6 +48,78 @@ pub struct FSState<V: Nodelike> {
     dirty: bool,
     /// Synced bit: set to `true` if syncing has _ever_ happened
     synced: bool,
+    /// Inodes for anchored values (see `Nodelike::anchor`); later occurrences
+    /// of an anchor are hard links to these.
+    anchors: BTreeMap<String, INodeNo>,
+    /// The control directory (see `control::CONTROL_DIR`), if it's mounted.
+    control: Option<INodeNo>,
+    /// Tells the kernel to forget cached entries after a reload.
+    notifier: Option<fuser::Notifier>,
+    /// The input file as we last read or wrote it (see `--watch`)
+    stamp: Option<Stamp>,
+    /// The text of the input as we last read or wrote it, which saving edits
+    /// (see `--preserve-format`)
+    original: Option<String>,
+    /// How the input was laid out (see `Nodelike::layout`)
+    layout: Option<String>,
+    /// Conflict bit: set to `true` when the input changes on disk while there
+    /// are unsaved changes
+    conflict: bool,
+}
+
+/// Where the last sync saves changes that conflict with the input (see
+/// `FSState::sync`): next to it, with `.ffs-conflict` on the end.
+fn conflict_copy(output: &Path) -> PathBuf {
+    let mut copy = output.as_os_str().to_owned();
+    copy.push(".ffs-conflict");
+    PathBuf::from(copy)
+}
+
+/// Identifies a version of a file, so we can tell when it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stamp {
+    dev: u64,
+    ino: u64,
+    len: u64,
+    mtime: SystemTime,
+}
+
+fn input_stamp(config: &Config) -> Option<Stamp> {
+    match &config.input {
+        Input::File(path) => {
+            let metadata = std::fs::metadata(path).ok()?;
+            Some(Stamp {
+                dev: metadata.dev(),
+                ino: metadata.ino(),
+                len: metadata.len(),
+                mtime: metadata.modified().ok()?,
+            })
+        }
+        Input::Stdin | Input::Empty => None,
+    }
+}
+
+/// Loads a value from `reader`, keeping the text it came from if saving will
+/// need it (see `--preserve-format`).
+fn load<V: Nodelike>(
+    mut reader: Box<dyn std::io::Read>,
+    config: &Config,
+) -> Result<(V, Option<String>), Error> {
+    if !config.preserve_format {
+        return Ok((V::from_reader(reader, config)?, None));
+    }
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let v = V::from_reader(Box::new(std::io::Cursor::new(text.clone())), config)?;
+    Ok((v, Some(text)))
+}
+
+/// Something the kernel may have cached that changed in a reload.
+#[derive(Debug)]
+enum Stale {
+    Entry(INodeNo, String),
+    Inode(INodeNo),
 }
 
 /// Default TTL on information passed to the OS, which caches responses.
@@ -67,24 +149,36 @@ pub struct INode<V: Nodelike> {
     pub ctime: SystemTime,
     /// Time of creation (macOS only)
     pub crtime: SystemTime,
+    /// Number of directory entries referring to this inode, i.e., hard links.
+    pub links: u32,
+    /// The anchor this inode was loaded from, if any; it's saved with the
+    /// same anchor.
+    pub anchor: Option<String>,
     /// The actual file contents.
     pub entry: Entry<V>,
 }
 
-/// File contents. Either a `File` containing bytes or a `Directory`, mapping
-/// names to entries (see `DirEntry`)
+/// File contents. Either a `File` containing bytes, a `Directory`, mapping
+/// names to entries (see `DirEntry`), or a `Symlink` to some path
 ///
-/// Directories come in two kinds (per `DirType`): `DirType::Named` directories
-/// are conventional mappings of names to entries, but `DirType::List`
-/// directories only use name in the filesystem, and most of those names will be
-/// generated (see `format::fs_from_value`). When writing a `DirType::List`
-/// directory back out, only the sort order of the name matters.
+/// Directories come in three kinds (per `DirType`): `DirType::Named`
+/// directories are conventional mappings of names to entries, but
+/// `DirType::List` directories only use name in the filesystem, and most of
+/// those names will be generated (see `format::fs_from_value`). When writing a
+/// `DirType::List` directory back out, only the sort order of the name
+/// matters. `DirType::Documents` directories are just like lists, but hold
+/// the documents of a stream (see `Nodelike::is_documents`).
 #[derive(Debug)]
 pub enum Entry<V: Nodelike> {
     // TODO 2021-06-14 need a 'written' flag to determine whether or not to
     // strip newlines during writeback
     File(Typ, Vec<u8>),
     Directory(DirType, BTreeMap<String, DirEntry>),
+    /// Symbolic links are saved as references to their targets (see
+    /// `Nodelike::from_symlink`).
+    Symlink(PathBuf),
+    /// Files in the control directory, whose contents are computed on demand.
+    Control(Control),
     Lazy(V),
 }
 
@@ -105,6 +199,7 @@ pub struct DirEntry {
 pub enum DirType {
     Named,
     List,
+    Documents,
 }
 
 #[derive(Debug)]
@@ -122,39 +217,34 @@ impl<V: Nodelike> FS<V> {
             Some(reader) => reader,
             None => {
                 // create an empty directory
-                let state = Mutex::new(FSState::empty(config));
+                let mut state = FSState::empty(config);
+                state.install_control();
+                let state = Arc::new(Mutex::new(state));
                 return Self { state };
             }
         };
 
-        let v = time_ns!("reading", V::from_reader(reader), config.timing);
-        if !v.is_dir() {
-            error!(
-                "The root of the filesystem must be a directory, but '{v}' only generates a single file."
-            );
-            std::process::exit(ERROR_STATUS_FUSE);
-        }
-
-        // don't bother with any locks until we've kicked things off
-        let mut state = FSState::rooted(v, config);
-        time_ns!(
-            "loading",
-            {
-                if state.config.eager {
-                    state
-                        .resolve_nodes_transitively(fuser::INodeNo::ROOT)
-                        .expect("resolve_nodes_transitively");
-                } else {
-                    // kick start the root directory
-                    state
-                        .resolve_node(fuser::INodeNo::ROOT)
-                        .expect("resolve_node");
-                }
-            },
-            state.config.timing
-        );
+        let stamp = input_stamp(&config);
+        let (v, original) = match time_ns!("reading", load(reader, &config), config.timing) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                error!("Couldn't load {}: {e}", config.input);
+                std::process::exit(e.exit_status());
+            }
+        };
+        let mut state = match FSState::from_value(v, config) {
+            Ok(state) => state,
+            Err(_) => {
+                error!(
+                    "The root of the filesystem must be a directory, but the input only generates a single file."
+                );
+                std::process::exit(ERROR_STATUS_FUSE);
+            }
+        };
+        state.stamp = stamp;
+        state.original = original;
 
-        let state = Mutex::new(state);
+        let state = Arc::new(Mutex::new(state));
         Self { state }
     }
 }
@@ -173,6 +263,13 @@ impl<V: Nodelike> FSState<V> {
             config,
             dirty,
             synced,
+            anchors: BTreeMap::new(),
+            control: None,
+            notifier: None,
+            stamp: None,
+            original: None,
+            layout: None,
+            conflict: false,
         }
     }
 
@@ -188,6 +285,37 @@ impl<V: Nodelike> FSState<V> {
         )
     }
 
+    /// Builds a filesystem for `v`, with the control directory installed,
+    /// resolving it eagerly or just at the root as `config` says. Fails with
+    /// `ENOTDIR` if `v` would generate a single file rather than a directory.
+    pub fn from_value(v: V, config: Config) -> Result<Self, Errno> {
+        if !v.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+
+        let layout = v.layout();
+        let mut state = FSState::rooted(v, config);
+        state.layout = layout;
+        time_ns!(
+            "loading",
+            {
+                if state.config.eager {
+                    state
+                        .resolve_nodes_transitively(fuser::INodeNo::ROOT)
+                        .expect("resolve_nodes_transitively");
+                } else {
+                    // kick start the root directory
+                    state
+                        .resolve_node(fuser::INodeNo::ROOT)
+                        .expect("resolve_node");
+                }
+            },
+            state.config.timing
+        );
+        state.install_control();
+        Ok(state)
+    }
+
     pub fn empty(config: Config) -> Self {
         Self::from_root(
             INode::new(
@@ -219,408 +347,1669 @@ impl<V: Nodelike> FSState<V> {
         inum
     }
 
-    #[instrument(level = "debug", skip(self))]
-    fn resolve_node(&mut self, inum: INodeNo) -> Result<Option<Vec<INodeNo>>, FSError> {
-        debug!("called");
-
-        let idx = inum.0 as usize;
+    /// Allocates an inode for `child`, a new entry in the directory `parent`.
+    /// If `child` is anchored and its anchor already has an inode, the entry
+    /// is a hard link to that inode instead. Returns the inode number and
+    /// whether or not the inode is fresh.
+    fn child_inode(&mut self, parent: INodeNo, child: V, uid: u32, gid: u32) -> (INodeNo, bool) {
+        let anchor = child.anchor();
 
-        if idx >= self.inodes.len() || idx == 0 {
-            return Err(FSError::NoSuchInode(inum));
+        if let Some(anchor) = &anchor
+            && let Some(&inum) = self.anchors.get(anchor)
+            && let Some(Some(inode)) = self.inodes.get_mut(inum.0 as usize)
+        {
+            debug!("linking anchor {anchor} (inode {inum}) into {parent}");
+            inode.links += 1;
+            return (inum, false);
         }
 
-        {
-            let inode = match self.inodes[idx].as_ref() {
-                Some(inode) => inode,
-                None => return Err(FSError::InvalidInode(inum)),
-            };
-            match inode.entry {
-                Entry::Directory(..) | Entry::File(..) => return Ok(Option::None),
-                Entry::Lazy(..) => {}
-            }
+        let kind = filetype_for(&child);
+        let inum = self.fresh_inode(
+            parent,
+            Entry::Lazy(child),
+            uid,
+            gid,
+            mode(&self.config, kind) as u32,
+        );
+        if let Some(anchor) = anchor {
+            self.inodes[inum.0 as usize].as_mut().unwrap().anchor = Some(anchor.clone());
+            self.anchors.insert(anchor, inum);
         }
+        (inum, true)
+    }
 
-        // Take ownership of the inode so we can move the lazy value out without
-        // needing Default. The slot is temporarily None while we build children.
-        let mut inode = self.inodes[idx].take().unwrap();
-        let uid = inode.uid;
-        let gid = inode.gid;
-        let v = match inode.entry {
-            Entry::Lazy(v) => v,
-            _ => return Err(FSError::InvalidInode(inum)),
+    /// Records that the directory entry for `inum` in `dir` has been removed.
+    ///
+    /// An inode with other links (e.g., an aliased directory; see
+    /// `child_inode`) that was removed from its parent takes another
+    /// directory linking to it as its parent, so that `name_in_parent` and
+    /// `pointer` still work.
+    fn drop_link(&mut self, dir: INodeNo, inum: INodeNo) {
+        let Some(Some(inode)) = self.inodes.get_mut(inum.0 as usize) else {
+            return;
         };
+        inode.links = inode.links.saturating_sub(1);
+        if inode.links == 0 || inode.parent != dir {
+            return;
+        }
 
-        let (entry, new_nodes) = match v.node(&self.config) {
-            Node::Bytes(b) => (Entry::File(Typ::Bytes, b), Option::None),
-            Node::String(t, s) => (Entry::File(t, s.into_bytes()), Option::None),
-            Node::List(vs) => {
-                let mut children = BTreeMap::new();
-                let num_elts = vs.len() as f64;
-                let width = num_elts.log10().ceil() as usize;
+        let links_to = |inode: &INode<V>| match &inode.entry {
+            Entry::Directory(_, files) => files.values().any(|de| de.inum == inum),
+            _ => false,
+        };
+        if self.peek(dir).is_some_and(links_to) {
+            return;
+        }
+        if let Some(parent) = self
+            .inodes
+            .iter()
+            .flatten()
+            .find(|inode| links_to(inode))
+            .map(|inode| inode.inum)
+        {
+            debug!("inode {inum} was unlinked from its parent {dir}; its parent is now {parent}");
+            self.get_mut(inum).unwrap().parent = parent;
+        }
+    }
 
-                let mut new_nodes = Vec::with_capacity(vs.len());
-                for (i, child) in vs.into_iter().enumerate() {
-                    // TODO 2021-06-08 ability to add prefixes
-                    let name = if self.config.pad_element_names {
-                        format!("{i:0width$}")
-                    } else {
-                        format!("{i}")
-                    };
+    /// Lets the filesystem tell the kernel to drop stale cache entries.
+    pub fn set_notifier(&mut self, notifier: fuser::Notifier) {
+        self.notifier = Some(notifier);
+    }
 
-                    let kind = filetype_for(&child);
-                    let child_id = self.fresh_inode(
-                        inum,
-                        Entry::Lazy(child),
-                        uid,
-                        gid,
-                        mode(&self.config, kind) as u32,
-                    );
+    /// Adds the control directory to the root. If the root already has a
+    /// file with that name, there's no control directory.
+    fn install_control(&mut self) {
+        let dirty = self.dirty;
+        let (uid, gid) = (self.config.uid, self.config.gid);
 
-                    children.insert(
-                        name,
-                        DirEntry {
-                            kind,
-                            original_name: None,
-                            inum: child_id,
-                        },
-                    );
-                    new_nodes.push(child_id)
+        let control = match &self.control {
+            Some(control) => *control,
+            None => {
+                let entry = Entry::Directory(DirType::Named, BTreeMap::new());
+                let dir = self.fresh_inode(INodeNo::ROOT, entry, uid, gid, CONTROL_DIR_MODE);
+                for c in Control::ALL {
+                    let inum = self.fresh_inode(dir, Entry::Control(c), uid, gid, c.mode());
+                    if let Some(Some(INode {
+                        entry: Entry::Directory(_, files),
+                        ..
+                    })) = self.inodes.get_mut(dir.0 as usize)
+                    {
+                        files.insert(
+                            c.name().into(),
+                            DirEntry {
+                                kind: FileType::RegularFile,
+                                original_name: None,
+                                inum,
+                            },
+                        );
+                    }
                 }
+                dir
+            }
+        };
 
-                (
-                    Entry::Directory(DirType::List, children),
-                    Option::Some(new_nodes),
-                )
+        match self.get_mut(INodeNo::ROOT) {
+            Ok(INode {
+                entry: Entry::Directory(_, files),
+                ..
+            }) if !files.contains_key(CONTROL_DIR) => {
+                files.insert(
+                    CONTROL_DIR.into(),
+                    DirEntry {
+                        kind: FileType::Directory,
+                        original_name: None,
+                        inum: control,
+                    },
+                );
+                self.control = Some(control);
             }
-            Node::Map(fvs) => {
-                let mut children = BTreeMap::new();
-                let mut new_nodes = Vec::with_capacity(fvs.len());
-                for (field, child) in fvs.into_iter() {
-                    let original = field.clone();
+            _ => {
+                warn!("'{CONTROL_DIR}' is already in use; there's no control directory");
+                self.control = None;
+            }
+        }
 
-                    let nfield = if !self.config.valid_name(&original) {
-                        match self.config.munge {
-                            Munge::Rename => {
-                                let mut nfield = self.config.normalize_name(field);
+        // the control directory is never saved, so adding it changes nothing
+        self.dirty = dirty;
+    }
 
-                                // TODO 2021-07-08 could be better to check fvs, but it's a vec now... :/
-                                while children.contains_key(&nfield) {
-                                    nfield.push('_');
-                                }
+    /// Determines whether `inum` is the control directory or in it.
+    fn is_control(&self, inum: INodeNo) -> bool {
+        match self.control {
+            Some(control) => {
+                inum == control || self.peek(inum).is_some_and(|inode| inode.parent == control)
+            }
+            None => false,
+        }
+    }
 
-                                nfield
-                            }
-                            Munge::Filter => {
-                                warn!("skipping '{field}'");
-                                continue;
-                            }
-                        }
-                    } else {
-                        field
-                    };
+    /// The contents of a file in the control directory
+    fn control_contents(&self, c: Control) -> String {
+        match c {
+            Control::Dirty => format!("{}\n", self.dirty),
+            Control::Conflict => format!("{}\n", self.conflict),
+            Control::Synced => format!("{}\n", self.synced),
+            Control::Input => format!("{}\n", self.config.input),
+            Control::Output => format!("{}\n", self.config.output),
+            Control::Format => format!("{}\n", self.config.input_format),
+            Control::InodeCount => {
+                let count = self
+                    .inodes
+                    .iter()
+                    .flatten()
+                    .filter(|inode| inode.links > 0 && !self.is_control(inode.inum))
+                    .count();
+                format!("{count}\n")
+            }
+            Control::Sync | Control::Reload | Control::Revert => String::new(),
+        }
+    }
 
-                    let kind = filetype_for(&child);
-                    let child_id = self.fresh_inode(
-                        inum,
-                        Entry::Lazy(child),
-                        uid,
-                        gid,
-                        mode(&self.config, kind) as u32,
-                    );
-                    let original_name = if original != nfield {
-                        info!(
-                            "renamed {original} to {nfield} (inode {child_id} with parent {inum})"
-                        );
-                        Some(original)
-                    } else {
-                        assert!(self.config.valid_name(&original));
-                        None
-                    };
+    /// Runs the action for a file in the control directory.
+    fn trigger(&mut self, c: Control) -> Result<(), Errno>
+    where
+        V: Clone,
+    {
+        match c {
+            Control::Sync => {
+                // saving on purpose resolves any conflict in our favor
+                self.conflict = false;
+                self.sync(false).map_err(|e| {
+                    error!("Couldn't save: {e}");
+                    Errno::EIO
+                })
+            }
+            Control::Reload if self.dirty => {
+                warn!("not reloading over unsaved changes; use revert to discard them");
+                Err(Errno::EBUSY)
+            }
+            Control::Reload | Control::Revert => self.reload().map_err(|e| {
+                error!("Couldn't reload {}: {e}", self.config.input);
+                Errno::EIO
+            }),
+            _ => Err(Errno::EPERM),
+        }
+    }
 
-                    children.insert(
-                        nfield,
-                        DirEntry {
-                            kind,
-                            original_name,
-                            inum: child_id,
-                        },
-                    );
+    /// Brings the filesystem up to date with the input, e.g., after it
+    /// changes on disk.
+    ///
+    /// Paths that are still there with the same kind keep their inode
+    /// numbers, so open files and working directories stay put; the kernel is
+    /// told to forget whatever changed. Anchored values that were shared get
+    /// fresh inodes.
+    fn reload(&mut self) -> Result<(), Error> {
+        if let Input::Stdin = self.config.input {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "can't reload from stdin",
+            )
+            .into());
+        }
 
-                    new_nodes.push(child_id);
+        let stamp = input_stamp(&self.config);
+        let mut original = None;
+        let mut layout = None;
+        let entry = match self.config.try_input_reader()? {
+            Some(reader) => {
+                let v: V;
+                (v, original) = load(reader, &self.config)?;
+                if !v.is_dir() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "the root of the filesystem must be a directory",
+                    )
+                    .into());
                 }
-
-                (
-                    Entry::Directory(DirType::Named, children),
-                    Option::Some(new_nodes),
-                )
+                layout = v.layout();
+                Entry::Lazy(v)
             }
+            None => Entry::Directory(DirType::Named, BTreeMap::new()),
         };
 
-        inode.entry = entry;
-        self.inodes[idx] = Some(inode);
+        // the control directory stays as it is
+        if self.control.is_some()
+            && let Some(Some(INode {
+                entry: Entry::Directory(_, files),
+                ..
+            })) = self.inodes.get_mut(INodeNo::ROOT.0 as usize)
+        {
+            files.remove(CONTROL_DIR);
+        }
 
-        if let Some(nodes) = &new_nodes {
-            debug!("new_nodes = {nodes:?}");
+        self.anchors.clear();
+        let (uid, gid) = (self.config.uid, self.config.gid);
+        let mode = mode(&self.config, FileType::Directory) as u32;
+        let root = self.fresh_inode(INodeNo::ROOT, entry, uid, gid, mode);
+        let mut stale = Vec::new();
+        self.reconcile(INodeNo::ROOT, root, &mut stale);
+        if self.config.eager {
+            let mut idx = 1;
+            while idx < self.inodes.len() {
+                let _ = self.resolve_node(INodeNo(idx as u64));
+                idx += 1;
+            }
+        }
+        self.install_control();
+        self.dirty = false;
+        self.conflict = false;
+        self.stamp = stamp;
+        self.original = original;
+        self.layout = layout;
+
+        // the kernel may be holding a lock on a directory while it waits for
+        // us to handle some queued request, so we notify from another thread
+        // and only wait a little while for it to finish
+        if let Some(notifier) = self.notifier.clone()
+            && !stale.is_empty()
+        {
+            let (done, finished) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                for stale in stale {
+                    let res = match &stale {
+                        Stale::Entry(parent, name) => {
+                            notifier.inval_entry(*parent, OsStr::new(name))
+                        }
+                        Stale::Inode(inum) => notifier.inval_inode(*inum, 0, 0),
+                    };
+                    if let Err(e) = res {
+                        debug!("couldn't invalidate {stale:?}: {e}");
+                    }
+                }
+                let _ = done.send(());
+            });
+            if finished.recv_timeout(Duration::from_secs(1)).is_err() {
+                warn!("reloaded, but the kernel may briefly show old files");
+            }
         }
 
-        Ok(new_nodes)
+        Ok(())
     }
 
-    fn resolve_nodes_transitively(&mut self, inum: INodeNo) -> Result<(), FSError> {
-        let mut worklist = match self.resolve_node(inum)? {
-            Some(nodes) => nodes,
-            None => return Ok(()),
+    /// Updates `inum` in place to match `new`, a freshly loaded inode, which
+    /// is then freed. Returns `false`, leaving both alone, if `inum` can't be
+    /// updated in place (e.g., a file became a directory).
+    fn reconcile(&mut self, inum: INodeNo, new: INodeNo, stale: &mut Vec<Stale>) -> bool {
+        let lazy = match (self.peek(inum), self.peek(new)) {
+            (Some(old), Some(fresh)) if old.entry.kind() == fresh.entry.kind() => match old.entry {
+                Entry::Lazy(_) => true,
+                Entry::File(..) | Entry::Directory(..) => false,
+                Entry::Symlink(..) | Entry::Control(..) => return false,
+            },
+            _ => return false,
         };
 
-        while let Some(node) = worklist.pop() {
-            if let Some(nodes) = self.resolve_node(node)? {
-                worklist.extend(nodes);
+        // everything allocated from here on out is part of `new`
+        let before = INodeNo(self.inodes.len() as u64);
+        if !lazy {
+            self.resolve_node(new).expect("resolve_node");
+        }
+        let fresh = self.inodes[new.0 as usize].take().unwrap();
+
+        let old = self.inodes[inum.0 as usize].as_mut().unwrap();
+        let entry = std::mem::replace(&mut old.entry, Entry::File(Typ::Auto, Vec::new()));
+        let (entry, changed) = match (entry, fresh.entry) {
+            (Entry::Lazy(_), entry) => (entry, true),
+            (Entry::File(t, contents), Entry::File(new_t, new_contents)) => {
+                if t == new_t && contents == new_contents {
+                    (Entry::File(t, contents), false)
+                } else {
+                    (Entry::File(new_t, new_contents), true)
+                }
+            }
+            (Entry::Directory(_, files), Entry::Directory(t, new_files)) => {
+                let (files, changed) = self.reconcile_dir(inum, files, new_files, before, stale);
+                (Entry::Directory(t, files), changed)
+            }
+            (entry, new_entry) => unreachable!("reconciling {entry:?} with {new_entry:?}"),
+        };
+
+        let old = self.inodes[inum.0 as usize].as_mut().unwrap();
+        old.entry = entry;
+        old.anchor = fresh.anchor.clone();
+        if changed {
+            old.mtime = fresh.mtime;
+            old.ctime = fresh.ctime;
+            stale.push(Stale::Inode(inum));
+        }
+        if let Some(anchor) = fresh.anchor {
+            self.anchors.insert(anchor, inum);
+        }
+        true
+    }
+
+    /// Merges the freshly loaded `new_files` into the directory `dir`, which
+    /// used to hold `files`. Inodes numbered `fresh` and higher were just
+    /// loaded. Returns the merged entries and whether anything changed.
+    fn reconcile_dir(
+        &mut self,
+        dir: INodeNo,
+        mut files: BTreeMap<String, DirEntry>,
+        new_files: BTreeMap<String, DirEntry>,
+        fresh: INodeNo,
+        stale: &mut Vec<Stale>,
+    ) -> (BTreeMap<String, DirEntry>, bool) {
+        let mut merged = BTreeMap::new();
+        let mut changed = false;
+
+        for (name, de) in new_files {
+            let old = files.remove(&name);
+            let is_fresh = de.inum >= fresh;
+
+            if let Some(old) = &old
+                && is_fresh
+                && old.kind == de.kind
+                && self.peek(old.inum).is_some_and(|inode| inode.links == 1)
+                && self.peek(de.inum).is_some_and(|inode| inode.links == 1)
+                && self.reconcile(old.inum, de.inum, stale)
+            {
+                merged.insert(
+                    name,
+                    DirEntry {
+                        inum: old.inum,
+                        ..de
+                    },
+                );
+                continue;
             }
+
+            if let Some(old) = old {
+                self.drop_tree(dir, old.inum);
+            }
+            if is_fresh && let Some(Some(inode)) = self.inodes.get_mut(de.inum.0 as usize) {
+                inode.parent = dir;
+            }
+            stale.push(Stale::Entry(dir, name.clone()));
+            merged.insert(name, de);
+            changed = true;
+        }
+
+        for (name, old) in files {
+            self.drop_tree(dir, old.inum);
+            stale.push(Stale::Entry(dir, name));
+            changed = true;
+        }
+
+        (merged, changed)
+    }
+
+    /// Removes the directory entry for `inum` in `dir`, freeing it (and, for
+    /// directories, its contents) once nothing else refers to it.
+    fn drop_tree(&mut self, dir: INodeNo, inum: INodeNo) {
+        self.drop_link(dir, inum);
+
+        let idx = inum.0 as usize;
+        if self.peek(inum).is_some_and(|inode| inode.links == 0)
+            && let Some(inode) = self.inodes[idx].take()
+            && let Entry::Directory(_, files) = inode.entry
+        {
+            for de in files.values() {
+                self.drop_tree(inum, de.inum);
+            }
+        }
+    }
+
+    /// Checks whether the input has changed on disk since we last read or
+    /// wrote it (see `--watch`), reloading it if so.
+    ///
+    /// Unsaved changes are never clobbered: instead, we report a conflict and
+    /// refuse to save over the input until it's resolved, either by reverting
+    /// or by explicitly syncing (see `control::Control`).
+    pub fn check_input(&mut self)
+    where
+        V: Clone,
+    {
+        let stamp = match input_stamp(&self.config) {
+            Some(stamp) => stamp,
+            // the file may be in the middle of being replaced
+            None => return,
+        };
+        if self.stamp == Some(stamp) {
+            return;
+        }
+
+        if self.dirty {
+            if !self.conflict {
+                error!(
+                    "{} changed on disk, but there are unsaved changes; not reloading",
+                    self.config.input
+                );
+                self.conflict = true;
+            }
+            return;
+        }
+
+        info!("{} changed on disk; reloading", self.config.input);
+        if let Err(e) = self.reload() {
+            error!("Couldn't reload {}: {e}", self.config.input);
+            // wait for it to change again rather than complaining constantly
+            self.stamp = Some(stamp);
+        }
+    }
+
+    /// Determines whether saving overwrites the input file.
+    fn saves_over_input(&self) -> bool {
+        match (&self.config.input, &self.config.output) {
+            (Input::File(input), Output::File(output)) => input == output,
+            _ => false,
+        }
+    }
+
+    /// Looks up an inode without resolving it.
+    fn peek(&self, inum: INodeNo) -> Option<&INode<V>> {
+        self.inodes.get(inum.0 as usize)?.as_ref()
+    }
+
+    /// Finds the name of `inum` in its parent directory, along with its
+    /// position among the saved entries (which matters for lists).
+    fn name_in_parent(&self, inum: INodeNo) -> Option<(String, usize)> {
+        let parent = self.peek(self.peek(inum)?.parent)?;
+        let Entry::Directory(_, files) = &parent.entry else {
+            return None;
+        };
+        files
+            .iter()
+            .filter(|(name, de)| !self.config.ignored_file(name) && self.control != Some(de.inum))
+            .enumerate()
+            .find(|(_, (_, de))| de.inum == inum)
+            .map(|(i, (name, de))| (de.original_name.as_ref().unwrap_or(name).clone(), i))
+    }
+
+    /// The JSON Pointer (RFC 6901) for `inum`, i.e., the path to it from the
+    /// root of the saved document.
+    fn pointer(&self, inum: INodeNo) -> Option<String> {
+        let mut pointer = String::new();
+        let mut inum = inum;
+        while inum != INodeNo::ROOT {
+            let (name, i) = self.name_in_parent(inum)?;
+            let parent = self.peek(inum)?.parent;
+            let token = match &self.peek(parent)?.entry {
+                Entry::Directory(DirType::List | DirType::Documents, _) => i.to_string(),
+                _ => name.replace('~', "~0").replace('/', "~1"),
+            };
+            pointer.insert_str(0, &format!("/{token}"));
+            inum = parent;
+        }
+        Some(pointer)
+    }
+
+    /// Finds the inode that the symlink `link` points to, following any
+    /// further symlinks. Returns `None` when the target isn't in the
+    /// filesystem (or hasn't been resolved).
+    fn symlink_target(&self, link: INodeNo) -> Option<INodeNo> {
+        // as in Linux, give up after 40 links
+        let mut link = link;
+        for _ in 0..40 {
+            let inode = self.peek(link)?;
+            let Entry::Symlink(target) = &inode.entry else {
+                return Some(link);
+            };
+
+            let (mut inum, target) = if target.is_absolute() {
+                let mount = self.config.mount.as_ref()?;
+                (INodeNo::ROOT, target.strip_prefix(mount).ok()?)
+            } else {
+                (inode.parent, target.as_path())
+            };
+            let mut walked = Vec::new();
+            for component in target.components() {
+                inum = match component {
+                    Component::CurDir => inum,
+                    Component::ParentDir => match walked.pop() {
+                        Some(parent) => parent,
+                        None => self.peek(inum)?.parent,
+                    },
+                    Component::Normal(name) => match &self.peek(inum)?.entry {
+                        Entry::Directory(_, files) => {
+                            walked.push(inum);
+                            files.get(name.to_str()?)?.inum
+                        }
+                        _ => return None,
+                    },
+                    Component::RootDir | Component::Prefix(_) => return None,
+                };
+            }
+            link = inum;
+        }
+        None
+    }
+
+    /// Determines whether `ancestor` is `inum` or one of its parents.
+    fn is_ancestor(&self, ancestor: INodeNo, inum: INodeNo) -> bool {
+        let mut inum = inum;
+        loop {
+            if inum == ancestor {
+                return true;
+            }
+            match self.peek(inum) {
+                Some(inode) if inum != INodeNo::ROOT => inum = inode.parent,
+                _ => return false,
+            }
+        }
+    }
+
+    /// How to save the symlink `link` to `target`: a JSON Pointer to the
+    /// target when it's in the filesystem, along with the target's anchor
+    /// (see `anchor_shared_inodes`); otherwise, just the target itself.
+    fn symlink_reference(&self, link: INodeNo, target: &Path) -> (String, Option<&str>) {
+        if let Some(inum) = self.symlink_target(link)
+            && let Some(pointer) = self.pointer(inum)
+        {
+            // an alias inside its own anchor would be a cycle
+            let anchor = if self.is_ancestor(inum, link) {
+                None
+            } else {
+                self.peek(inum).and_then(|inode| inode.anchor.as_deref())
+            };
+            return (format!("#{pointer}"), anchor);
+        }
+
+        (target.to_string_lossy().into(), None)
+    }
+
+    /// Names every inode that's shared---by hard links or as the target of a
+    /// symlink---so formats that can express sharing (e.g., YAML) save it
+    /// once and refer to it everywhere else.
+    fn anchor_shared_inodes(&mut self) {
+        let needs_anchor = |inode: &INode<V>| {
+            inode.links > 0
+                && inode.anchor.is_none()
+                && (inode.links > 1 || matches!(inode.entry, Entry::Symlink(_)))
+        };
+        if !self.inodes.iter().flatten().any(needs_anchor) {
+            return;
+        }
+
+        // fresh names must avoid every anchor in the document, and symlinks
+        // can point anywhere
+        self.resolve_nodes_transitively(INodeNo::ROOT).unwrap();
+
+        let mut shared = Vec::new();
+        for inode in self.inodes.iter().flatten() {
+            if inode.links == 0 {
+                continue;
+            }
+            if inode.links > 1
+                && inode.anchor.is_none()
+                && !matches!(inode.entry, Entry::Symlink(_))
+            {
+                shared.push(inode.inum);
+            }
+            if let Entry::Symlink(_) = inode.entry
+                && let Some(inum) = self.symlink_target(inode.inum)
+                && !self.is_ancestor(inum, inode.inum)
+                && self
+                    .peek(inum)
+                    .is_some_and(|target| target.anchor.is_none())
+            {
+                shared.push(inum);
+            }
+        }
+        shared.sort_unstable();
+        shared.dedup();
+
+        for inum in shared {
+            let name_of = |inum| {
+                self.name_in_parent(inum)
+                    .map(|(name, _)| name)
+                    .unwrap_or_default()
+            };
+            let parent = self.peek(inum).map_or(INodeNo::ROOT, |inode| inode.parent);
+            let base = anchor_base(&name_of(inum), &name_of(parent));
+
+            let mut name = base.clone();
+            let mut i = 1;
+            while self.anchors.contains_key(&name) {
+                i += 1;
+                name = format!("{base}{i}");
+            }
+            debug!("anchoring shared inode {inum} as {name}");
+            self.anchors.insert(name.clone(), inum);
+            self.get_mut(inum).unwrap().anchor = Some(name);
+        }
+    }
+
+    #[instrument(level = "debug", skip(self))]
+    fn resolve_node(&mut self, inum: INodeNo) -> Result<Option<Vec<INodeNo>>, FSError> {
+        debug!("called");
+
+        let idx = inum.0 as usize;
+
+        if idx >= self.inodes.len() || idx == 0 {
+            return Err(FSError::NoSuchInode(inum));
+        }
+
+        {
+            let inode = match self.inodes[idx].as_ref() {
+                Some(inode) => inode,
+                None => return Err(FSError::InvalidInode(inum)),
+            };
+            match inode.entry {
+                Entry::Directory(..)
+                | Entry::File(..)
+                | Entry::Symlink(..)
+                | Entry::Control(..) => {
+                    return Ok(Option::None);
+                }
+                Entry::Lazy(..) => {}
+            }
+        }
+
+        // Take ownership of the inode so we can move the lazy value out without
+        // needing Default. The slot is temporarily None while we build children.
+        let mut inode = self.inodes[idx].take().unwrap();
+        // resolving doesn't change anything, even though it allocates inodes
+        let dirty = self.dirty;
+        let uid = inode.uid;
+        let gid = inode.gid;
+        let v = match inode.entry {
+            Entry::Lazy(v) => v,
+            _ => return Err(FSError::InvalidInode(inum)),
+        };
+
+        let documents = v.is_documents();
+        let (entry, new_nodes) = match v.node(&self.config) {
+            Node::Bytes(b) => (Entry::File(Typ::Bytes, b), Option::None),
+            Node::String(t, s) => (Entry::File(t, s.into_bytes()), Option::None),
+            Node::List(vs) => {
+                let mut children = BTreeMap::new();
+                let num_elts = vs.len() as f64;
+                let width = num_elts.log10().ceil() as usize;
+
+                let mut new_nodes = Vec::with_capacity(vs.len());
+                for (i, child) in vs.into_iter().enumerate() {
+                    // TODO 2021-06-08 ability to add prefixes
+                    let name = if self.config.pad_element_names {
+                        format!("{i:0width$}")
+                    } else {
+                        format!("{i}")
+                    };
+
+                    let kind = filetype_for(&child);
+                    let (child_id, fresh) = self.child_inode(inum, child, uid, gid);
+
+                    children.insert(
+                        name,
+                        DirEntry {
+                            kind,
+                            original_name: None,
+                            inum: child_id,
+                        },
+                    );
+                    if fresh {
+                        new_nodes.push(child_id)
+                    }
+                }
+
+                let typ = if documents {
+                    DirType::Documents
+                } else {
+                    DirType::List
+                };
+                (Entry::Directory(typ, children), Option::Some(new_nodes))
+            }
+            Node::Map(fvs) => {
+                let mut children = BTreeMap::new();
+                let mut new_nodes = Vec::with_capacity(fvs.len());
+                for (field, child) in fvs.into_iter() {
+                    let original = field.clone();
+
+                    let nfield = if !self.config.valid_name(&original) {
+                        match self.config.munge {
+                            Munge::Rename => {
+                                let mut nfield = self.config.normalize_name(field);
+
+                                // TODO 2021-07-08 could be better to check fvs, but it's a vec now... :/
+                                while children.contains_key(&nfield) {
+                                    nfield.push('_');
+                                }
+
+                                nfield
+                            }
+                            Munge::Filter => {
+                                warn!("skipping '{field}'");
+                                continue;
+                            }
+                        }
+                    } else {
+                        field
+                    };
+
+                    let kind = filetype_for(&child);
+                    let (child_id, fresh) = self.child_inode(inum, child, uid, gid);
+                    let original_name = if original != nfield {
+                        info!(
+                            "renamed {original} to {nfield} (inode {child_id} with parent {inum})"
+                        );
+                        Some(original)
+                    } else {
+                        assert!(self.config.valid_name(&original));
+                        None
+                    };
+
+                    children.insert(
+                        nfield,
+                        DirEntry {
+                            kind,
+                            original_name,
+                            inum: child_id,
+                        },
+                    );
+
+                    if fresh {
+                        new_nodes.push(child_id);
+                    }
+                }
+
+                (
+                    Entry::Directory(DirType::Named, children),
+                    Option::Some(new_nodes),
+                )
+            }
+        };
+
+        inode.entry = entry;
+        self.inodes[idx] = Some(inode);
+        self.dirty = dirty;
+
+        if let Some(nodes) = &new_nodes {
+            debug!("new_nodes = {nodes:?}");
+        }
+
+        Ok(new_nodes)
+    }
+
+    fn resolve_nodes_transitively(&mut self, inum: INodeNo) -> Result<(), FSError> {
+        let mut worklist = match self.resolve_node(inum)? {
+            Some(nodes) => nodes,
+            None => return Ok(()),
+        };
+
+        while let Some(node) = worklist.pop() {
+            if let Some(nodes) = self.resolve_node(node)? {
+                worklist.extend(nodes);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get(&mut self, inum: INodeNo) -> Result<&INode<V>, FSError> {
+        let _new_nodes = self.resolve_node(inum)?;
+
+        let idx = inum.0 as usize;
+
+        if idx >= self.inodes.len() || idx == 0 {
+            return Err(FSError::NoSuchInode(inum));
+        }
+
+        match &self.inodes[idx] {
+            None => Err(FSError::InvalidInode(inum)),
+            Some(inode) => Ok(inode),
+        }
+    }
+
+    fn get_mut(&mut self, inum: INodeNo) -> Result<&mut INode<V>, FSError> {
+        let _new_nodes = self.resolve_node(inum)?;
+
+        let idx = inum.0 as usize;
+
+        if idx >= self.inodes.len() {
+            return Err(FSError::NoSuchInode(inum));
+        }
+
+        match self.inodes.get_mut(idx) {
+            Some(Some(inode)) => Ok(inode),
+            _ => Err(FSError::InvalidInode(inum)),
+        }
+    }
+
+    /// save as a value with the same `Nodelike` type as the input
+    ///
+    /// this is a real cost savings if we ever hit a lazy node (clone vs. recursive resolution and rebuild)
+    fn as_value(&self, inum: INodeNo) -> V
+    where
+        V: Clone,
+    {
+        let inode = self.inodes[inum.0 as usize].as_ref().unwrap();
+        let v = match &inode.entry {
+            Entry::Lazy(v) => v.clone(),
+            Entry::File(typ, contents) => match String::from_utf8(contents.clone()) {
+                Ok(mut contents) if typ != &Typ::Bytes => {
+                    if self.config.add_newlines && contents.ends_with('\n') {
+                        contents.truncate(contents.len() - 1);
+                    }
+
+                    V::from_string(*typ, contents, &self.config)
+                }
+                // TODO 2021-06-24 trim?
+                Ok(_) | Err(_) => V::from_bytes(contents, &self.config),
+            },
+            Entry::Directory(typ @ (DirType::List | DirType::Documents), files) => {
+                let documents = matches!(typ, DirType::Documents);
+                let mut entries = Vec::with_capacity(files.len());
+                let mut files = files.iter().collect::<Vec<_>>();
+                files.sort_unstable_by_key(|(name, _)| *name);
+                for (name, DirEntry { inum, .. }) in files.iter() {
+                    if self.config.ignored_file(name) {
+                        warn!("skipping ignored file '{name}'");
+                        continue;
+                    }
+                    if self.control == Some(*inum) {
+                        continue;
+                    }
+                    entries.push(self.as_value(*inum));
+                }
+                if documents {
+                    V::from_documents(entries, &self.config)
+                } else {
+                    V::from_list_dir(entries, &self.config)
+                }
+            }
+            Entry::Directory(DirType::Named, files) => {
+                let mut entries = BTreeMap::new();
+                for (
+                    name,
+                    DirEntry {
+                        inum,
+                        original_name,
+                        ..
+                    },
+                ) in files.iter()
+                {
+                    if self.config.ignored_file(name) {
+                        warn!("skipping ignored file '{name}'");
+                        continue;
+                    }
+                    if self.control == Some(*inum) {
+                        continue;
+                    }
+                    let v = self.as_value(*inum);
+                    let name = original_name.as_ref().unwrap_or(name).into();
+                    entries.insert(name, v);
+                }
+                V::from_named_dir(entries, &self.config)
+            }
+            Entry::Symlink(target) => {
+                let (reference, anchor) = self.symlink_reference(inum, target);
+                V::from_symlink(reference, anchor, &self.config)
+            }
+            Entry::Control(_) => unreachable!("control files are never saved"),
+        };
+
+        match &inode.anchor {
+            Some(anchor) => V::from_anchored(anchor, v, &self.config),
+            None => v,
+        }
+    }
+
+    #[instrument(level = "trace", skip(self))]
+    fn as_other_value<U>(&mut self, inum: INodeNo) -> U
+    where
+        U: Nodelike,
+    {
+        let inode = self.inodes[inum.0 as usize].as_ref().unwrap();
+        let anchor = inode.anchor.clone();
+        let v = match &inode.entry {
+            Entry::Lazy(_) => {
+                self.resolve_nodes_transitively(inum).unwrap();
+                return self.as_other_value(inum);
+            }
+            Entry::File(typ, contents) => {
+                // TODO 2021-07-01 use _t to try to force the type
+                match String::from_utf8(contents.clone()) {
+                    Ok(mut contents) if typ != &Typ::Bytes => {
+                        if self.config.add_newlines && contents.ends_with('\n') {
+                            contents.truncate(contents.len() - 1);
+                        }
+                        // TODO 2021-06-24 trim?
+                        U::from_string(*typ, contents, &self.config)
+                    }
+                    Ok(_) | Err(_) => U::from_bytes(contents, &self.config),
+                }
+            }
+            Entry::Directory(typ @ (DirType::List | DirType::Documents), files) => {
+                let documents = matches!(typ, DirType::Documents);
+                let mut entries = Vec::with_capacity(files.len());
+                let mut files = files
+                    .iter()
+                    .map(|(name, entry)| (name.clone(), entry.inum))
+                    .collect::<Vec<_>>();
+                files.sort_unstable_by(|(name1, _), (name2, _)| name1.cmp(name2));
+                for (name, inum) in files {
+                    if self.config.ignored_file(&name) {
+                        warn!("skipping ignored file '{name}'");
+                        continue;
+                    }
+                    if self.control == Some(inum) {
+                        continue;
+                    }
+                    let v = self.as_other_value(inum);
+                    entries.push(v);
+                }
+                if documents {
+                    U::from_documents(entries, &self.config)
+                } else {
+                    U::from_list_dir(entries, &self.config)
+                }
+            }
+            Entry::Directory(DirType::Named, files) => {
+                let mut entries = BTreeMap::new();
+
+                let files = files
+                    .iter()
+                    .map(|(name, entry)| (name.clone(), entry.inum, entry.original_name.clone()))
+                    .collect::<Vec<_>>();
+                for (name, inum, original_name) in files.iter() {
+                    if self.config.ignored_file(name) {
+                        warn!("skipping ignored file '{name}'");
+                        continue;
+                    }
+                    if self.control == Some(*inum) {
+                        continue;
+                    }
+                    let v = self.as_other_value(*inum);
+                    let name = original_name.as_ref().unwrap_or(name).into();
+                    entries.insert(name, v);
+                }
+                U::from_named_dir(entries, &self.config)
+            }
+            Entry::Symlink(target) => {
+                let (reference, anchor) = self.symlink_reference(inum, target);
+                U::from_symlink(reference, anchor, &self.config)
+            }
+            Entry::Control(_) => unreachable!("control files are never saved"),
+        };
+
+        match anchor {
+            Some(anchor) => U::from_anchored(&anchor, v, &self.config),
+            None => v,
+        }
+    }
+
+    fn check_access(&self, req: &Request) -> bool {
+        req.uid() == 0 || req.uid() == self.config.uid
+    }
+
+    /// Tries to synchronize the in-memory `FS` with its on-disk representation.
+    ///
+    /// Depending on output conventions and the state of the `FS`, nothing may
+    /// happen. In particular:
+    ///
+    ///   - if a sync has happened before and the `FS` isn't dirty, nothing will
+    ///     happen (to prevent pointless writes)
+    ///
+    ///   - if `self.config.output == Output::Stdout` and `last_sync == false`,
+    ///     nothing will happen (to prevent redundant writes to STDOUT)
+    ///
+    /// If the input changed on disk while we had unsaved changes (see
+    /// `FSState::check_input`), saving over it fails. On the last sync, the
+    /// changes are saved next to the input instead (see `conflict_copy`),
+    /// so they aren't lost.
+    ///
+    /// If saving fails, the `FS` stays dirty and the old output is left as it
+    /// was.
+    #[instrument(level = "debug", skip(self), fields(synced = self.synced, dirty = self.dirty))]
+    pub fn sync(&mut self, last_sync: bool) -> Result<(), Error>
+    where
+        V: Clone,
+    {
+        info!("called");
+
+        if self.synced && !self.dirty {
+            info!("skipping sync; already synced and not dirty");
+            return Ok(());
+        }
+
+        match self.config.output {
+            Output::Stdout if !last_sync => {
+                info!("skipping sync; not last sync, using stdout");
+                return Ok(());
+            }
+            _ => (),
+        };
+
+        if self.conflict && self.saves_over_input() {
+            let message = format!(
+                "{} changed on disk since it was loaded; not overwriting it",
+                self.config.input
+            );
+            if !last_sync {
+                return Err(std::io::Error::other(message).into());
+            }
+
+            let Output::File(output) = &self.config.output else {
+                unreachable!("saving over the input means saving to a file")
+            };
+            let copy = conflict_copy(output);
+            let output = std::mem::replace(&mut self.config.output, Output::File(copy.clone()));
+            let saved = self.save();
+            self.config.output = output;
+            saved?;
+            return Err(std::io::Error::other(format!(
+                "{message}; saved your changes to {} instead",
+                copy.display()
+            ))
+            .into());
+        }
+
+        self.save()?;
+        self.dirty = false;
+        self.synced = true;
+        Ok(())
+    }
+
+    /// Returns `true` when there are changes that haven't been saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Actually output results, using `self.config.output`.
+    ///
+    /// When `self.config.input == self.config.output`, then resolved lazy nodes
+    /// can be directly returned. If the input and output formats are different,
+    /// we eager resolve everything and then save.
+    fn save(&mut self) -> Result<(), Error>
+    where
+        V: Clone,
+    {
+        let output = match self.config.output_writer()? {
+            Some(output) => output,
+            None => return Ok(()),
+        };
+        let mut writer = output.writer()?;
+
+        self.anchor_shared_inodes();
+        if self.config.input_format == self.config.output_format {
+            let v = time_ns!(
+                "saving",
+                self.as_value(fuser::INodeNo::ROOT),
+                self.config.timing
+            );
+            let v = self.laid_out(v);
+            let preserved = self
+                .original
+                .as_ref()
+                .and_then(|original| v.to_string_preserving(original, &self.config));
+            match preserved {
+                Some(text) => {
+                    time_ns!(
+                        "writing",
+                        writer.write_all(text.as_bytes()),
+                        self.config.timing
+                    )?;
+                    // finish compressing before we commit
+                    drop(writer);
+                    if self.saves_over_input() {
+                        self.original = Some(text);
+                    }
+                }
+                None => {
+                    if self.original.is_some() {
+                        warn!(
+                            "Couldn't keep the input's formatting; rewriting the whole document."
+                        );
+                    }
+                    time_ns!(
+                        "writing",
+                        v.to_writer(writer, &self.config),
+                        self.config.timing
+                    )?;
+                }
+            }
+        } else {
+            match self.config.output_format {
+                Format::Cbor => self.save_as::<cbor::Value>(writer)?,
+                Format::Csv => self.save_as::<csv::Value>(writer)?,
+                Format::Dotenv => self.save_as::<dotenv::Value>(writer)?,
+                Format::Ini => self.save_as::<ini::Value>(writer)?,
+                Format::Json => self.save_as::<json::Value>(writer)?,
+                Format::Json5 => self.save_as::<json5::Value>(writer)?,
+                Format::MsgPack => self.save_as::<msgpack::Value>(writer)?,
+                Format::Ndjson => self.save_as::<ndjson::Value>(writer)?,
+                Format::Plist => self.save_as::<plist::Value>(writer)?,
+                Format::Properties => self.save_as::<properties::Value>(writer)?,
+                Format::Toml => self.save_as::<toml::Value>(writer)?,
+                Format::Tsv => self.save_as::<tsv::Value>(writer)?,
+                Format::Xml => self.save_as::<xml::Value>(writer)?,
+                Format::Yaml => self.save_as::<yaml::Value>(writer)?,
+            }
+        }
+
+        output.commit()?;
+        if self.saves_over_input() {
+            // don't mistake our own write for someone else's
+            self.stamp = input_stamp(&self.config);
+        }
+        Ok(())
+    }
+
+    /// Converts the whole filesystem to a value of type `U`, i.e., a format
+    /// other than the input's, and writes it out.
+    fn save_as<U>(&mut self, writer: Box<dyn std::io::Write>) -> Result<(), Error>
+    where
+        U: Nodelike,
+    {
+        let v: U = time_ns!(
+            "saving",
+            self.as_other_value(fuser::INodeNo::ROOT),
+            self.config.timing
+        );
+        let v = self.laid_out(v);
+        time_ns!(
+            "writing",
+            v.to_writer(writer, &self.config),
+            self.config.timing
+        )
+    }
+
+    /// Finds the entry `name` in the directory `parent`.
+    fn child(&mut self, parent: INodeNo, name: &str) -> Result<INodeNo, Errno> {
+        match &self.get(parent).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::Directory(_, files) => match files.get(name) {
+                Some(DirEntry { inum, .. }) => Ok(*inum),
+                None => Err(Errno::ENOENT),
+            },
+            _ => Err(Errno::ENOTDIR),
+        }
+    }
+
+    /// Reads up to `size` bytes of the file `inum`, starting at `offset`.
+    fn read_file(&mut self, inum: INodeNo, offset: u64, size: usize) -> Result<Vec<u8>, Errno> {
+        fn range(contents: &[u8], offset: u64, size: usize) -> Vec<u8> {
+            let start = (offset as usize).min(contents.len());
+            let end = start.saturating_add(size).min(contents.len());
+            contents[start..end].to_vec()
+        }
+
+        match &self.get(inum).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::File(_t, contents) => Ok(range(contents, offset, size)),
+            Entry::Control(c) => {
+                let c = *c;
+                Ok(range(self.control_contents(c).as_bytes(), offset, size))
+            }
+            Entry::Directory(..) => Err(Errno::EISDIR),
+            Entry::Symlink(..) => Err(Errno::EINVAL),
+            Entry::Lazy(..) => panic!("unresolved lazy value in read_file"),
+        }
+    }
+
+    /// Writes `data` into the file `inum` at `offset`, returning the number
+    /// of bytes written. Writing to a control file runs its trigger.
+    fn write_file(&mut self, inum: INodeNo, offset: u64, data: &[u8]) -> Result<usize, Errno>
+    where
+        V: Clone,
+    {
+        // load contents
+        let contents = match &mut self.get_mut(inum).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::File(_t, contents) => contents,
+            Entry::Directory(..) => return Err(Errno::EISDIR),
+            Entry::Symlink(..) => return Err(Errno::EINVAL),
+            Entry::Control(c) => {
+                let c = *c;
+                return self.trigger(c).map(|()| data.len());
+            }
+            Entry::Lazy(..) => panic!("unresolved lazy value in write_file"),
+        };
+
+        // make space
+        let end = offset as usize + data.len();
+        if end > contents.len() {
+            contents.resize(end, 0);
+        }
+
+        // actually write
+        contents[offset as usize..end].copy_from_slice(data);
+        self.dirty = true;
+
+        Ok(data.len())
+    }
+
+    /// Sets the length of the file `inum` to `size`, zero-filling as needed.
+    fn truncate(&mut self, inum: INodeNo, size: u64) -> Result<(), Errno> {
+        match &mut self.get_mut(inum).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::File(_t, contents) => contents.resize(size as usize, 0),
+            Entry::Directory(..) => return Err(Errno::EISDIR),
+            Entry::Symlink(..) => return Err(Errno::EINVAL),
+            // truncating a control file (e.g., `echo >.ffs/sync`) does nothing
+            Entry::Control(..) => return Ok(()),
+            Entry::Lazy(..) => panic!("unresolved lazy value in truncate"),
+        }
+
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Adds `entry` to the directory `parent` as `name`, in a fresh inode.
+    fn add_entry(
+        &mut self,
+        parent: INodeNo,
+        name: &str,
+        entry: Entry<V>,
+        uid: u32,
+        gid: u32,
+        mode: u32,
+    ) -> Result<INodeNo, Errno> {
+        // the control directory's contents are fixed
+        if self.is_control(parent) {
+            return Err(Errno::EPERM);
+        }
+
+        // make sure the parent exists, is a directory, and doesn't have anything with that name
+        match &self.get(parent).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::Directory(_dirtype, files) => {
+                if files.contains_key(name) {
+                    return Err(Errno::EEXIST);
+                }
+            }
+            _ => return Err(Errno::ENOTDIR),
+        }
+
+        // allocate the inode (sets dirty bit)
+        let kind = entry.kind();
+        let inum = self.fresh_inode(parent, entry, uid, gid, mode);
+
+        // update the parent
+        match self.get_mut(parent) {
+            Ok(INode {
+                entry: Entry::Directory(_dirtype, files),
+                ..
+            }) => {
+                files.insert(
+                    name.into(),
+                    DirEntry {
+                        kind,
+                        original_name: None,
+                        inum,
+                    },
+                );
+            }
+            _ => panic!("parent {parent} changed while adding {name}"),
+        }
+
+        Ok(inum)
+    }
+
+    /// Removes the file (or symlink) `name` from the directory `parent`.
+    fn remove_file(&mut self, parent: INodeNo, name: &str) -> Result<(), Errno> {
+        // the control directory's contents are fixed
+        if self.is_control(parent) {
+            return Err(Errno::EPERM);
+        }
+
+        let files = match &mut self.get_mut(parent).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::Directory(_dirtype, files) => files,
+            _ => return Err(Errno::ENOTDIR),
+        };
+
+        // ensure it's a regular file or symlink
+        match files.get(name) {
+            Some(DirEntry {
+                kind: FileType::RegularFile | FileType::Symlink,
+                ..
+            }) => (),
+            Some(_) => return Err(Errno::EPERM),
+            None => return Err(Errno::ENOENT),
+        }
+
+        let DirEntry { inum, .. } = files.remove(name).unwrap();
+        self.drop_link(parent, inum);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Removes the empty directory `name` from the directory `parent`.
+    fn remove_dir(&mut self, parent: INodeNo, name: &str) -> Result<(), Errno> {
+        // find the actual directory being deleted
+        let inum = match &self.get(parent).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::Directory(_dirtype, files) => match files.get(name) {
+                Some(DirEntry {
+                    kind: FileType::Directory,
+                    inum,
+                    ..
+                }) => *inum,
+                Some(_) => return Err(Errno::ENOTDIR),
+                None => return Err(Errno::ENOENT),
+            },
+            _ => return Err(Errno::ENOTDIR),
+        };
+        if self.is_control(inum) {
+            return Err(Errno::EPERM);
+        }
+
+        // make sure it's empty
+        match self.get(inum) {
+            Ok(INode {
+                entry: Entry::Directory(_, files),
+                ..
+            }) => {
+                if !files.is_empty() {
+                    return Err(Errno::ENOTEMPTY);
+                }
+            }
+            Ok(_) => panic!("mismatched metadata on inode {inum} in parent {parent}"),
+            Err(_) => panic!("couldn't find inode {inum} in parent {parent}"),
+        }
+
+        // try to remove it
+        if let Ok(INode {
+            entry: Entry::Directory(_dirtype, files),
+            ..
+        }) = self.get_mut(parent)
+        {
+            files.remove(name);
+        }
+        self.drop_link(parent, inum);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Moves `name` in the directory `parent` to `newname` in `newparent`,
+    /// replacing whatever was there (so long as it's not a non-empty
+    /// directory).
+    fn move_entry(
+        &mut self,
+        parent: INodeNo,
+        name: &str,
+        newparent: INodeNo,
+        newname: &str,
+    ) -> Result<(), Errno> {
+        if name == "." || name == ".." {
+            return Err(Errno::EINVAL);
+        }
+
+        // make sure src exists
+        let (src_kind, src_original, src_inum) = match self.get(parent) {
+            Ok(INode {
+                entry: Entry::Directory(_kind, files),
+                ..
+            }) => match files.get(name) {
+                Some(DirEntry {
+                    kind,
+                    original_name,
+                    inum,
+                }) => (*kind, original_name.clone(), *inum),
+                None => return Err(Errno::ENOENT),
+            },
+            _ => return Err(Errno::ENOENT),
+        };
+
+        // a directory can't be moved into itself (the kernel checks this for
+        // us under FUSE, but not when we're called directly)
+        let mut ancestor = newparent;
+        loop {
+            if ancestor == src_inum {
+                return Err(Errno::EINVAL);
+            }
+            if ancestor == INodeNo::ROOT {
+                break;
+            }
+            ancestor = self.get(ancestor).map_err(|_| Errno::ENOENT)?.parent;
+        }
+
+        // determine whether tgt exists
+        let tgt_info = match self.get(newparent) {
+            Ok(INode {
+                entry: Entry::Directory(_kind, files),
+                ..
+            }) => match files.get(newname) {
+                Some(DirEntry { kind, inum, .. }) => {
+                    match (src_kind, *kind) {
+                        (FileType::Directory, FileType::Directory) => (),
+                        (FileType::Directory, _) => return Err(Errno::ENOTDIR),
+                        (_, FileType::Directory) => return Err(Errno::EISDIR),
+                        _ => (),
+                    }
+                    Some((*kind, *inum))
+                }
+                None => None,
+            },
+            _ => return Err(Errno::ENOENT),
+        };
+
+        // the control directory's contents are fixed
+        if self.is_control(src_inum)
+            || self.is_control(newparent)
+            || tgt_info.is_some_and(|(_, tgt_inum)| self.is_control(tgt_inum))
+        {
+            return Err(Errno::EPERM);
+        }
+
+        // renaming a file to another of its own links does nothing
+        if let Some((_, tgt_inum)) = tgt_info
+            && tgt_inum == src_inum
+        {
+            return Ok(());
+        }
+
+        // if tgt exists and is a directory, make sure it's empty
+        if let Some((FileType::Directory, tgt_inum)) = tgt_info {
+            match self.get(tgt_inum) {
+                Ok(INode {
+                    entry: Entry::Directory(_type, files),
+                    ..
+                }) => {
+                    if !files.is_empty() {
+                        return Err(Errno::ENOTEMPTY);
+                    }
+                }
+                _ => panic!("bad metadata on inode {tgt_inum} in {newparent}"),
+            }
+        }
+
+        // remove src from parent
+        match self.get_mut(parent) {
+            Ok(INode {
+                entry: Entry::Directory(_kind, files),
+                ..
+            }) => files.remove(name),
+            _ => panic!("parent changed"),
+        };
+
+        // add src as tgt to newparent
+        let overwritten = match self.get_mut(newparent) {
+            Ok(INode {
+                entry: Entry::Directory(_kind, files),
+                ..
+            }) => files.insert(
+                newname.into(),
+                DirEntry {
+                    kind: src_kind,
+                    // if the filename is the same, we'll keep the source
+                    // original filename (if it exists; otherwise we overwrite
+                    // it)
+                    original_name: if name == newname { src_original } else { None },
+                    inum: src_inum,
+                },
+            ),
+            _ => panic!("parent changed"),
+        };
+        if let Some(DirEntry { inum, .. }) = overwritten {
+            self.drop_link(newparent, inum);
+        }
+
+        // set src's parent inode
+        match self.get_mut(src_inum) {
+            Ok(inode) => inode.parent = newparent,
+            Err(_) => panic!("missing inode {src_inum} moved from {parent} to {newparent}"),
+        }
+
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Sets the type of `inum` (see `Entry::try_set_typ`).
+    fn set_typ(&mut self, inum: INodeNo, typ: &str) -> Result<(), Errno> {
+        let inode = self.get_mut(inum).map_err(|_| Errno::ENOENT)?;
+        if inode.entry.typ() == typ {
+            return Ok(());
+        }
+        if !inode.entry.try_set_typ(typ) {
+            return Err(Errno::EINVAL);
         }
 
+        self.dirty = true;
         Ok(())
     }
 
-    fn get(&mut self, inum: INodeNo) -> Result<&INode<V>, FSError> {
-        let _new_nodes = self.resolve_node(inum)?;
-
-        let idx = inum.0 as usize;
-
-        if idx >= self.inodes.len() || idx == 0 {
-            return Err(FSError::NoSuchInode(inum));
+    // Path-based operations, for using a filesystem without mounting it.
+    //
+    // Paths are relative to the root of the filesystem (a leading `/` is
+    // fine). Symlinks are never followed. Errors are what the corresponding
+    // system call would return.
+
+    /// Finds the inode at `path`.
+    pub fn lookup(&mut self, path: impl AsRef<Path>) -> Result<INodeNo, Errno> {
+        let mut inum = INodeNo::ROOT;
+        // an aliased directory has several parents, so `..` goes back the
+        // way we came
+        let mut walked = Vec::new();
+
+        for component in path.as_ref().components() {
+            inum = match component {
+                Component::RootDir | Component::CurDir => inum,
+                Component::ParentDir => match walked.pop() {
+                    Some(parent) => parent,
+                    None => self.peek(inum).ok_or(Errno::ENOENT)?.parent,
+                },
+                Component::Normal(name) => {
+                    walked.push(inum);
+                    self.child(inum, name.to_str().ok_or(Errno::ENOENT)?)?
+                }
+                Component::Prefix(_) => return Err(Errno::EINVAL),
+            };
         }
 
-        match &self.inodes[idx] {
-            None => Err(FSError::InvalidInode(inum)),
-            Some(inode) => Ok(inode),
-        }
+        Ok(inum)
     }
 
-    fn get_mut(&mut self, inum: INodeNo) -> Result<&mut INode<V>, FSError> {
-        let _new_nodes = self.resolve_node(inum)?;
+    /// Splits `path` into the inode of its parent directory and its name.
+    fn parent_and_name(&mut self, path: &Path) -> Result<(INodeNo, String), Errno> {
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => return Err(Errno::EINVAL),
+        };
+        let parent = self.lookup(path.parent().unwrap_or(Path::new("")))?;
 
-        let idx = inum.0 as usize;
+        Ok((parent, name))
+    }
 
-        if idx >= self.inodes.len() {
-            return Err(FSError::NoSuchInode(inum));
+    fn check_writable(&self) -> Result<(), Errno> {
+        if self.config.read_only {
+            Err(Errno::EROFS)
+        } else {
+            Ok(())
         }
+    }
 
-        match self.inodes.get_mut(idx) {
-            Some(Some(inode)) => Ok(inode),
-            _ => Err(FSError::InvalidInode(inum)),
-        }
+    /// Returns the contents of the file at `path`.
+    pub fn read(&mut self, path: impl AsRef<Path>) -> Result<Vec<u8>, Errno> {
+        let inum = self.lookup(path)?;
+        self.read_file(inum, 0, usize::MAX)
     }
 
-    /// save as a value with the same `Nodelike` type as the input
-    ///
-    /// this is a real cost savings if we ever hit a lazy node (clone vs. recursive resolution and rebuild)
-    fn as_value(&self, inum: INodeNo) -> V
+    /// Replaces the contents of the file at `path`, creating it if need be.
+    pub fn write(&mut self, path: impl AsRef<Path>, contents: &[u8]) -> Result<(), Errno>
     where
         V: Clone,
     {
-        match &self.inodes[inum.0 as usize].as_ref().unwrap().entry {
-            Entry::Lazy(v) => v.clone(),
-            Entry::File(typ, contents) => match String::from_utf8(contents.clone()) {
-                Ok(mut contents) if typ != &Typ::Bytes => {
-                    if self.config.add_newlines && contents.ends_with('\n') {
-                        contents.truncate(contents.len() - 1);
-                    }
-
-                    V::from_string(*typ, contents, &self.config)
-                }
-                // TODO 2021-06-24 trim?
-                Ok(_) | Err(_) => V::from_bytes(contents, &self.config),
-            },
-            Entry::Directory(DirType::List, files) => {
-                let mut entries = Vec::with_capacity(files.len());
-                let mut files = files.iter().collect::<Vec<_>>();
-                files.sort_unstable_by(|(name1, _), (name2, _)| name1.cmp(name2));
-                for (name, DirEntry { inum, .. }) in files.iter() {
-                    if self.config.ignored_file(name) {
-                        warn!("skipping ignored file '{name}'");
-                        continue;
-                    }
-                    entries.push(self.as_value(*inum));
-                }
-                V::from_list_dir(entries, &self.config)
-            }
-            Entry::Directory(DirType::Named, files) => {
-                let mut entries = BTreeMap::new();
-                for (
-                    name,
-                    DirEntry {
-                        inum,
-                        original_name,
-                        ..
-                    },
-                ) in files.iter()
-                {
-                    if self.config.ignored_file(name) {
-                        warn!("skipping ignored file '{name}'");
-                        continue;
-                    }
-                    let v = self.as_value(*inum);
-                    let name = original_name.as_ref().unwrap_or(name).into();
-                    entries.insert(name, v);
-                }
-                V::from_named_dir(entries, &self.config)
+        self.check_writable()?;
+
+        let path = path.as_ref();
+        let inum = match self.lookup(path) {
+            Ok(inum) => inum,
+            Err(e) if e.code() == Errno::ENOENT.code() => {
+                let (parent, name) = self.parent_and_name(path)?;
+                let entry = Entry::File(Typ::Auto, Vec::new());
+                let mode = mode(&self.config, FileType::RegularFile) as u32;
+                let (uid, gid) = (self.config.uid, self.config.gid);
+                self.add_entry(parent, &name, entry, uid, gid, mode)?
             }
-        }
+            Err(e) => return Err(e),
+        };
+
+        self.truncate(inum, 0)?;
+        self.write_file(inum, 0, contents)?;
+        Ok(())
     }
 
-    #[instrument(level = "trace", skip(self))]
-    fn as_other_value<U>(&mut self, inum: INodeNo) -> U
-    where
-        U: Nodelike,
-    {
-        match &self.inodes[inum.0 as usize].as_ref().unwrap().entry {
-            Entry::Lazy(_) => {
-                self.resolve_nodes_transitively(inum).unwrap();
-                self.as_other_value(inum)
-            }
-            Entry::File(typ, contents) => {
-                // TODO 2021-07-01 use _t to try to force the type
-                match String::from_utf8(contents.clone()) {
-                    Ok(mut contents) if typ != &Typ::Bytes => {
-                        if self.config.add_newlines && contents.ends_with('\n') {
-                            contents.truncate(contents.len() - 1);
-                        }
-                        // TODO 2021-06-24 trim?
-                        U::from_string(*typ, contents, &self.config)
-                    }
-                    Ok(_) | Err(_) => U::from_bytes(contents, &self.config),
-                }
-            }
-            Entry::Directory(DirType::List, files) => {
-                let mut entries = Vec::with_capacity(files.len());
-                let mut files = files
-                    .iter()
-                    .map(|(name, entry)| (name.clone(), entry.inum))
-                    .collect::<Vec<_>>();
-                files.sort_unstable_by(|(name1, _), (name2, _)| name1.cmp(name2));
-                for (name, inum) in files {
-                    if self.config.ignored_file(&name) {
-                        warn!("skipping ignored file '{name}'");
-                        continue;
-                    }
-                    let v = self.as_other_value(inum);
-                    entries.push(v);
-                }
-                U::from_list_dir(entries, &self.config)
-            }
-            Entry::Directory(DirType::Named, files) => {
-                let mut entries = BTreeMap::new();
+    /// Makes an empty (named) directory at `path`.
+    pub fn mkdir(&mut self, path: impl AsRef<Path>) -> Result<(), Errno> {
+        self.check_writable()?;
 
-                let files = files
-                    .iter()
-                    .map(|(name, entry)| (name.clone(), entry.inum, entry.original_name.clone()))
-                    .collect::<Vec<_>>();
-                for (name, inum, original_name) in files.iter() {
-                    if self.config.ignored_file(name) {
-                        warn!("skipping ignored file '{name}'");
-                        continue;
-                    }
-                    let v = self.as_other_value(*inum);
-                    let name = original_name.as_ref().unwrap_or(name).into();
-                    entries.insert(name, v);
-                }
-                U::from_named_dir(entries, &self.config)
-            }
-        }
+        let (parent, name) = self.parent_and_name(path.as_ref())?;
+        let entry = Entry::Directory(DirType::Named, BTreeMap::new());
+        let mode = mode(&self.config, FileType::Directory) as u32;
+        let (uid, gid) = (self.config.uid, self.config.gid);
+        self.add_entry(parent, &name, entry, uid, gid, mode)?;
+        Ok(())
     }
 
-    fn check_access(&self, req: &Request) -> bool {
-        req.uid() == 0 || req.uid() == self.config.uid
+    /// Removes the file or symlink at `path`.
+    pub fn unlink(&mut self, path: impl AsRef<Path>) -> Result<(), Errno> {
+        self.check_writable()?;
+
+        let (parent, name) = self.parent_and_name(path.as_ref())?;
+        self.remove_file(parent, &name)
     }
 
-    /// Tries to synchronize the in-memory `FS` with its on-disk representation.
-    ///
-    /// Depending on output conventions and the state of the `FS`, nothing may
-    /// happen. In particular:
-    ///
-    ///   - if a sync has happened before and the `FS` isn't dirty, nothing will
-    ///     happen (to prevent pointless writes)
-    ///
-    ///   - if `self.config.output == Output::Stdout` and `last_sync == false`,
-    ///     nothing will happen (to prevent redundant writes to STDOUT)
-    #[instrument(level = "debug", skip(self), fields(synced = self.synced, dirty = self.dirty))]
-    pub fn sync(&mut self, last_sync: bool)
-    where
-        V: Clone,
-    {
-        info!("called");
+    /// Removes the empty directory at `path`.
+    pub fn rmdir(&mut self, path: impl AsRef<Path>) -> Result<(), Errno> {
+        self.check_writable()?;
 
-        if self.synced && !self.dirty {
-            info!("skipping sync; already synced and not dirty");
-            return;
-        }
+        let (parent, name) = self.parent_and_name(path.as_ref())?;
+        self.remove_dir(parent, &name)
+    }
 
-        match self.config.output {
-            Output::Stdout if !last_sync => {
-                info!("skipping sync; not last sync, using stdout");
-                return;
-            }
-            _ => (),
-        };
+    /// Moves `from` to `to`, replacing whatever is there.
+    pub fn rename(&mut self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> Result<(), Errno> {
+        self.check_writable()?;
 
-        self.save();
-        self.dirty = false;
-        self.synced = true;
+        let (parent, name) = self.parent_and_name(from.as_ref())?;
+        let (newparent, newname) = self.parent_and_name(to.as_ref())?;
+        self.move_entry(parent, &name, newparent, &newname)
     }
 
-    /// Actually output results, using `self.config.output`.
-    ///
-    /// When `self.config.input == self.config.output`, then resolved lazy nodes
-    /// can be directly returned. If the input and output formats are different,
-    /// we eager resolve everything and then save.
-    fn save(&mut self)
+    /// Returns the type of `path`, as in the `user.type` extended attribute.
+    pub fn typ(&mut self, path: impl AsRef<Path>) -> Result<String, Errno> {
+        let inum = self.lookup(path)?;
+        Ok(self.get(inum).map_err(|_| Errno::ENOENT)?.entry.typ())
+    }
+
+    /// Sets the type of `path`, as with the `user.type` extended attribute.
+    pub fn set_type(&mut self, path: impl AsRef<Path>, typ: &str) -> Result<(), Errno> {
+        self.check_writable()?;
+
+        let inum = self.lookup(path)?;
+        self.set_typ(inum, typ)
+    }
+
+    /// Returns the names in the directory at `path`, in order.
+    pub fn list(&mut self, path: impl AsRef<Path>) -> Result<Vec<String>, Errno> {
+        let inum = self.lookup(path)?;
+        match &self.get(inum).map_err(|_| Errno::ENOENT)?.entry {
+            Entry::Directory(_, files) => Ok(files.keys().cloned().collect()),
+            _ => Err(Errno::ENOTDIR),
+        }
+    }
+
+    /// Returns the whole filesystem as a value, as it would be saved (see
+    /// `FSState::sync`, which actually saves it).
+    pub fn to_value(&mut self) -> V
     where
         V: Clone,
     {
-        let writer = match self.config.output_writer() {
-            Some(writer) => writer,
-            None => return,
-        };
+        self.anchor_shared_inodes();
+        let v = self.as_value(INodeNo::ROOT);
+        self.laid_out(v)
+    }
 
-        if self.config.input_format == self.config.output_format {
-            let v = time_ns!(
-                "saving",
-                self.as_value(fuser::INodeNo::ROOT),
-                self.config.timing
-            );
-            time_ns!(
-                "writing",
-                v.to_writer(writer, self.config.pretty),
-                self.config.timing
-            );
-        } else {
-            let pretty = self.config.pretty;
-            match self.config.output_format {
-                Format::Json => {
-                    let v: json::Value = time_ns!(
-                        "saving",
-                        self.as_other_value(fuser::INodeNo::ROOT),
-                        self.config.timing
-                    );
-                    time_ns!("writing", v.to_writer(writer, pretty), self.config.timing);
-                }
-                Format::Toml => {
-                    let v: toml::Value = time_ns!(
-                        "saving",
-                        self.as_other_value(fuser::INodeNo::ROOT),
-                        self.config.timing
-                    );
-                    time_ns!("writing", v.to_writer(writer, pretty), self.config.timing);
-                }
-                Format::Yaml => {
-                    let v: yaml::Value = time_ns!(
-                        "saving",
-                        self.as_other_value(fuser::INodeNo::ROOT),
-                        self.config.timing
-                    );
-                    time_ns!("writing", v.to_writer(writer, pretty), self.config.timing);
-                }
-            }
+    /// Lays out `v`, the whole filesystem, like the input was.
+    fn laid_out<U: Nodelike>(&self, v: U) -> U {
+        match &self.layout {
+            Some(layout) => v.with_layout(layout),
+            None => v,
         }
     }
 }
@@ -654,6 +2043,8 @@ impl<V: Nodelike> INode<V> {
             crtime: now,
             ctime: now,
             mtime: now,
+            links: 1,
+            anchor: None,
         }
     }
 
@@ -665,12 +2056,13 @@ impl<V: Nodelike> INode<V> {
 
         let nlink: u32 = match &self.entry {
             Entry::Directory(_, files) => {
-                2 + files
-                    .iter()
-                    .filter(|(_, de)| de.kind == FileType::Directory)
-                    .count() as u32
+                1 + self.links
+                    + files
+                        .iter()
+                        .filter(|(_, de)| de.kind == FileType::Directory)
+                        .count() as u32
             }
-            Entry::File(..) => 1,
+            Entry::File(..) | Entry::Symlink(..) | Entry::Control(..) => self.links,
             Entry::Lazy(..) => panic!("unresolved lazy value in Inode::attr"),
         };
 
@@ -697,12 +2089,13 @@ impl<V: Nodelike> INode<V> {
 impl<V: Nodelike> Entry<V> {
     /// Computes the size of an entry
     ///
-    /// Files are simply their length (not capacity)
+    /// Files are simply their length (not capacity); symlinks are the length
+    /// of their target
     ///
     /// Directory size is informed by the object model:
     ///
-    ///   - `DirType::List` directories are only their length (since names won't
-    ///     matter)
+    ///   - `DirType::List` and `DirType::Documents` directories are only their
+    ///     length (since names won't matter)
     ///   - `DirType::Named` directories are the sum of the length of the
     ///     filenames
     pub fn size(&self) -> u64 {
@@ -711,7 +2104,9 @@ impl<V: Nodelike> Entry<V> {
             Entry::Directory(DirType::Named, files) => {
                 files.keys().map(|name| name.len() as u64).sum()
             }
-            Entry::Directory(DirType::List, files) => files.len() as u64,
+            Entry::Directory(DirType::List | DirType::Documents, files) => files.len() as u64,
+            Entry::Symlink(target) => target.as_os_str().len() as u64,
+            Entry::Control(..) => 0,
             Entry::Lazy(v) => v.size() as u64, // give an answer because we can... but should
         }
     }
@@ -721,6 +2116,8 @@ impl<V: Nodelike> Entry<V> {
         match self {
             Entry::File(..) => FileType::RegularFile,
             Entry::Directory(..) => FileType::Directory,
+            Entry::Symlink(..) => FileType::Symlink,
+            Entry::Control(..) => FileType::RegularFile,
             Entry::Lazy(v) => filetype_for(v),
         }
     }
@@ -729,6 +2126,8 @@ impl<V: Nodelike> Entry<V> {
         match self {
             Entry::File(t, _) => t.to_string(),
             Entry::Directory(t, _) => t.to_string(),
+            Entry::Symlink(_) => "symlink".into(),
+            Entry::Control(_) => "control".into(),
             Entry::Lazy(_) => panic!("unresolved lazy value in Entry::typ"),
         }
     }
@@ -751,6 +2150,7 @@ impl<V: Nodelike> Entry<V> {
                 }
                 Err(..) => false,
             },
+            Entry::Symlink(_) | Entry::Control(_) => false,
             Entry::Lazy(_) => todo!("Entry::try_set_typ"),
         }
     }
@@ -764,6 +2164,7 @@ impl std::fmt::Display for DirType {
             match self {
                 DirType::List => "list",
                 DirType::Named => "named",
+                DirType::Documents => "documents",
             }
         )
     }
@@ -794,6 +2195,8 @@ impl FromStr for DirType {
 
         if s == "list" || s == "array" {
             Ok(DirType::List)
+        } else if s == "documents" || s == "stream" {
+            Ok(DirType::Documents)
         } else if s == "named"
             || s == "object"
             || s == "map"
@@ -812,12 +2215,73 @@ impl FromStr for DirType {
 #[cfg(target_os = "linux")]
 const ENOATTR: fuser::Errno = Errno::ENODATA;
 
+impl<V: Nodelike + Clone + 'static> FS<V> {
+    /// Spawns a thread that runs `f` on the state every `interval`.
+    fn every<F>(&self, name: &str, interval: Duration, f: F) -> std::io::Result<()>
+    where
+        F: Fn(&mut FSState<V>) + Send + 'static,
+    {
+        let state = Arc::downgrade(&self.state);
+        std::thread::Builder::new()
+            .name(name.into())
+            .spawn(move || {
+                loop {
+                    std::thread::sleep(interval);
+
+                    let state = match state.upgrade() {
+                        Some(state) => state,
+                        None => return,
+                    };
+                    f(&mut state.lock().unwrap());
+                }
+            })?;
+
+        Ok(())
+    }
+}
+
 impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
+    /// Starts the autosave thread when `--autosave-interval` is set and the
+    /// watch thread when `--watch` is set.
+    ///
+    /// The threads only hold a weak reference to the state, so they stop on
+    /// their own once the `FS` is gone.
+    #[instrument(level = "debug", skip(self, _req, _config))]
+    fn init(&mut self, _req: &Request, _config: &mut KernelConfig) -> std::io::Result<()> {
+        info!("called");
+
+        let (autosave_interval, watch_interval) = {
+            let state = self.state.lock().unwrap();
+            (state.config.autosave_interval, state.config.watch_interval)
+        };
+
+        if let Some(interval) = autosave_interval {
+            self.every("ffs-autosave", interval, |state| {
+                if state.dirty {
+                    debug!("autosaving");
+                    if let Err(e) = state.sync(false) {
+                        error!("Couldn't autosave to {}: {e}", state.config.output);
+                    }
+                }
+            })?;
+        }
+
+        if let Some(interval) = watch_interval {
+            self.every("ffs-watch", interval, FSState::check_input)?;
+        }
+
+        Ok(())
+    }
+
     /// Synchronizes the `FS`, calling `FS::sync` with `last_sync == true`.
     #[instrument(level = "debug", skip(self))]
     fn destroy(&mut self) {
         info!("called");
-        self.state.lock().unwrap().sync(true);
+
+        let mut state = self.state.lock().unwrap();
+        if let Err(e) = state.sync(true) {
+            error!("Couldn't save to {}: {e}", state.config.output);
+        }
     }
 
     #[instrument(level = "debug", skip(self, _req, reply))]
@@ -868,36 +2332,18 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
     }
 
     #[instrument(level = "debug", skip(self, _req, reply))]
-    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
-        info!("called");
-
-        let mut state = self.state.lock().unwrap();
-        let dir = match state.get(parent) {
-            Err(_e) => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Ok(inode) => inode,
-        };
-
-        let filename = match name.to_str() {
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Some(name) => name,
-        };
-
-        let inum = match &dir.entry {
-            Entry::Directory(_kind, files) => match files.get(filename) {
-                None => {
-                    reply.error(Errno::ENOENT);
-                    return;
-                }
-                Some(DirEntry { inum, .. }) => *inum,
-            },
-            _ => {
-                reply.error(Errno::ENOTDIR);
+    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
+        info!("called");
+
+        let mut state = self.state.lock().unwrap();
+        let inum = match name
+            .to_str()
+            .ok_or(Errno::ENOENT)
+            .and_then(|name| state.child(parent, name))
+        {
+            Ok(inum) => inum,
+            Err(e) => {
+                reply.error(e);
                 return;
             }
         };
@@ -1042,25 +2488,10 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
         if let Some(size) = size {
             info!("truncate() to {size}");
 
-            match state.get_mut(ino) {
-                Ok(inode) => match &mut inode.entry {
-                    Entry::File(_t, contents) => {
-                        contents.resize(size as usize, 0);
-                        reply.attr(&TTL, &inode.attr());
-                    }
-                    Entry::Directory(..) => {
-                        reply.error(Errno::EISDIR);
-                        return;
-                    }
-                    Entry::Lazy(..) => panic!("unresolved lazy value found in setattr"),
-                },
-                Err(_) => {
-                    reply.error(Errno::ENOENT);
-                    return;
-                }
-            };
-
-            state.dirty = true;
+            match state.truncate(ino, size) {
+                Ok(()) => reply.attr(&TTL, &state.get(ino).unwrap().attr()),
+                Err(e) => reply.error(e),
+            }
             return;
         }
 
@@ -1182,29 +2613,17 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
             return;
         }
 
-        let file = match state.get_mut(ino) {
-            Err(_e) => {
-                reply.error(Errno::EFAULT);
-                return;
-            }
-            Ok(inode) => inode,
-        };
-
-        if name == "user.type" {
-            match std::str::from_utf8(value) {
-                Err(_) => {
-                    reply.error(Errno::EINVAL);
-                }
-                Ok(s) => {
-                    if file.entry.try_set_typ(s) {
-                        reply.ok()
-                    } else {
-                        reply.error(Errno::EINVAL)
-                    }
-                }
-            }
-        } else {
+        if name != "user.type" {
             reply.error(Errno::EINVAL);
+            return;
+        }
+
+        match std::str::from_utf8(value)
+            .map_err(|_| Errno::EINVAL)
+            .and_then(|typ| state.set_typ(ino, typ))
+        {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
         }
     }
 
@@ -1270,7 +2689,7 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
         ino: INodeNo,
         _fh: fuser::FileHandle,
         offset: u64,
-        _size: u32,
+        size: u32,
         _flags: fuser::OpenFlags,
         _lock: Option<fuser::LockOwner>,
         reply: ReplyData,
@@ -1278,17 +2697,9 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
         info!("called");
 
         let mut state = self.state.lock().unwrap();
-        let file = match state.get(ino) {
-            Err(_e) => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Ok(inode) => inode,
-        };
-
-        match &file.entry {
-            Entry::File(_t, s) => reply.data(&s[offset as usize..]),
-            _ => reply.error(Errno::ENOENT),
+        match state.read_file(ino, offset, size as usize) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e),
         }
     }
 
@@ -1313,7 +2724,9 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
         };
 
         match &inode.entry {
-            Entry::File(..) => reply.error(Errno::ENOTDIR),
+            Entry::File(..) | Entry::Symlink(..) | Entry::Control(..) => {
+                reply.error(Errno::ENOTDIR)
+            }
             Entry::Directory(_kind, files) => {
                 let dot_entries = vec![
                     (ino, FileType::Directory, "."),
@@ -1380,78 +2793,27 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
 
         // make sure we have a good file type
         let file_type: u32 = mode & libc::S_IFMT;
-        if ![libc::S_IFREG, libc::S_IFDIR].contains(&file_type) {
+        let entry = if file_type == libc::S_IFREG {
+            Entry::File(Typ::Auto, Vec::new())
+        } else if file_type == libc::S_IFDIR {
+            Entry::Directory(DirType::Named, BTreeMap::new())
+        } else {
             warn!("mknod only supports regular files and directories; got {mode:o}");
             reply.error(Errno::ENOSYS);
             return;
-        }
-
-        // get the filename
-        let filename = match name.to_str() {
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Some(name) => name,
         };
 
-        // make sure the parent exists, is a directory, and doesn't have that file
-        match state.get(parent) {
-            Err(_e) => {
-                reply.error(Errno::ENOENT);
-                return;
+        let res = name
+            .to_str()
+            .ok_or(Errno::ENOENT)
+            .and_then(|name| state.add_entry(parent, name, entry, req.uid(), req.gid(), mode));
+        match res {
+            Ok(inum) => {
+                reply.entry(&TTL, &state.get(inum).unwrap().attr(), fuser::Generation(0));
+                assert!(state.dirty);
             }
-            Ok(inode) => match &inode.entry {
-                Entry::File(..) => {
-                    reply.error(Errno::ENOTDIR);
-                    return;
-                }
-                Entry::Directory(_dirtype, files) => {
-                    if files.contains_key(filename) {
-                        reply.error(Errno::EEXIST);
-                        return;
-                    }
-                }
-                Entry::Lazy(..) => panic!("unresolved lazy value in mknod"),
-            },
-        };
-
-        // create the inode entry
-        let (entry, kind) = if file_type == libc::S_IFREG {
-            (Entry::File(Typ::Auto, Vec::new()), FileType::RegularFile)
-        } else {
-            assert_eq!(file_type, libc::S_IFDIR);
-            (
-                Entry::Directory(DirType::Named, BTreeMap::new()),
-                FileType::Directory,
-            )
-        };
-
-        // allocate the inode (sets dirty bit)
-        let inum = state.fresh_inode(parent, entry, req.uid(), req.gid(), mode);
-
-        // update the parent
-        // NB we can't get_mut the parent earlier due to borrowing restrictions
-        match state.get_mut(parent) {
-            Err(_e) => panic!("error finding parent again"),
-            Ok(inode) => match &mut inode.entry {
-                Entry::File(..) => panic!("parent changed to a regular file"),
-                Entry::Directory(_dirtype, files) => {
-                    files.insert(
-                        filename.into(),
-                        DirEntry {
-                            kind,
-                            original_name: None,
-                            inum,
-                        },
-                    );
-                }
-                Entry::Lazy(..) => panic!("unresolved lazy value in mknod"),
-            },
-        };
-
-        reply.entry(&TTL, &state.get(inum).unwrap().attr(), fuser::Generation(0));
-        assert!(state.dirty);
+            Err(e) => reply.error(e),
+        }
     }
 
     #[instrument(level = "debug", skip(self, req, reply))]
@@ -1473,65 +2835,18 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
             return;
         }
 
-        // get the new directory name
-        let filename = match name.to_str() {
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Some(name) => name,
-        };
-
-        // make sure the parent exists, is a directory, and doesn't have anything with that name
-        match state.get(parent) {
-            Err(_e) => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Ok(inode) => match &inode.entry {
-                Entry::File(..) => {
-                    reply.error(Errno::ENOTDIR);
-                    return;
-                }
-                Entry::Directory(_dirtype, files) => {
-                    if files.contains_key(filename) {
-                        reply.error(Errno::EEXIST);
-                        return;
-                    }
-                }
-                Entry::Lazy(..) => panic!("unresolved lazy value in mkdir"),
-            },
-        };
-
-        // create the inode entry
         let entry = Entry::Directory(DirType::Named, BTreeMap::new());
-        let kind = FileType::Directory;
-
-        // allocate the inode (sets dirty bit)
-        let inum = state.fresh_inode(parent, entry, req.uid(), req.gid(), mode);
-
-        // update the parent
-        // NB we can't get_mut the parent earlier due to borrowing restrictions
-        match state.get_mut(parent) {
-            Err(_e) => panic!("error finding parent again"),
-            Ok(inode) => match &mut inode.entry {
-                Entry::File(..) => panic!("parent changed to a regular file"),
-                Entry::Directory(_dirtype, files) => {
-                    files.insert(
-                        filename.into(),
-                        DirEntry {
-                            kind,
-                            original_name: None,
-                            inum,
-                        },
-                    );
-                }
-                Entry::Lazy(..) => panic!("unresolved lazy value in mkdir"),
-            },
-        };
-
-        reply.entry(&TTL, &state.get(inum).unwrap().attr(), fuser::Generation(0));
-        assert!(state.dirty);
+        let res = name
+            .to_str()
+            .ok_or(Errno::ENOENT)
+            .and_then(|name| state.add_entry(parent, name, entry, req.uid(), req.gid(), mode));
+        match res {
+            Ok(inum) => {
+                reply.entry(&TTL, &state.get(inum).unwrap().attr(), fuser::Generation(0));
+                assert!(state.dirty);
+            }
+            Err(e) => reply.error(e),
+        }
     }
 
     #[instrument(level = "debug", skip(self, req, reply))]
@@ -1557,37 +2872,10 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
             return;
         }
 
-        // find inode
-        let file = match state.get_mut(ino) {
-            Err(_e) => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Ok(inode) => inode,
-        };
-
-        // load contents
-        let contents = match &mut file.entry {
-            Entry::File(_t, contents) => contents,
-            Entry::Directory(_, _) => {
-                reply.error(Errno::EISDIR);
-                return;
-            }
-            Entry::Lazy(..) => panic!("unresolved lazy value in write"),
-        };
-
-        // make space
-        let extra_bytes = (offset + data.len() as u64) - contents.len() as u64;
-        if extra_bytes > 0 {
-            contents.resize(contents.len() + extra_bytes as usize, 0);
+        match state.write_file(ino, offset, data) {
+            Ok(written) => reply.written(written as u32),
+            Err(e) => reply.error(e),
         }
-
-        // actually write
-        let offset = offset as usize;
-        contents[offset..offset + data.len()].copy_from_slice(data);
-        state.dirty = true;
-
-        reply.written(data.len() as u32);
     }
 
     #[instrument(level = "debug", skip(self, req, reply))]
@@ -1602,55 +2890,14 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
             return;
         }
 
-        // get the filename
-        let filename = match name.to_str() {
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Some(name) => name,
-        };
-
-        // find the parent
-        let files = match state.get_mut(parent) {
-            Err(_e) => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Ok(INode {
-                entry: Entry::Directory(_dirtype, files),
-                ..
-            }) => files,
-            Ok(INode {
-                entry: Entry::File(..),
-                ..
-            }) => {
-                reply.error(Errno::ENOTDIR);
-                return;
-            }
-            Ok(INode {
-                entry: Entry::Lazy(..),
-                ..
-            }) => panic!("unresolved lazy value in unlink"),
-        };
-
-        // ensure it's a regular file
-        match files.get(filename) {
-            Some(DirEntry {
-                kind: FileType::RegularFile,
-                ..
-            }) => (),
-            _ => {
-                reply.error(Errno::EPERM);
-                return;
-            }
+        match name
+            .to_str()
+            .ok_or(Errno::ENOENT)
+            .and_then(|name| state.remove_file(parent, name))
+        {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
         }
-
-        // try to remove it
-        let res = files.remove(filename);
-        assert!(res.is_some());
-        state.dirty = true;
-        reply.ok();
     }
 
     #[instrument(level = "debug", skip(self, req, reply))]
@@ -1665,85 +2912,14 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
             return;
         }
 
-        // get the filename
-        let filename = match name.to_str() {
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Some(name) => name,
-        };
-
-        // find the parent
-        let files = match state.get(parent) {
-            Err(_e) => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Ok(INode {
-                entry: Entry::Directory(_dirtype, files),
-                ..
-            }) => files,
-            Ok(INode {
-                entry: Entry::File(..),
-                ..
-            }) => {
-                reply.error(Errno::ENOTDIR);
-                return;
-            }
-            Ok(INode {
-                entry: Entry::Lazy(..),
-                ..
-            }) => panic!("unresolved lazy value in rmdir"),
-        };
-
-        // find the actual directory being deleted
-        let inum = match files.get(filename) {
-            Some(DirEntry {
-                kind: FileType::Directory,
-                inum,
-                ..
-            }) => *inum,
-            Some(_) => {
-                reply.error(Errno::ENOTDIR);
-                return;
-            }
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-        };
-
-        // make sure it's empty
-        match state.get(inum) {
-            Ok(INode {
-                entry: Entry::Directory(_, dir_files),
-                ..
-            }) => {
-                if !dir_files.is_empty() {
-                    reply.error(Errno::ENOTEMPTY);
-                    return;
-                }
-            }
-            Ok(_) => panic!("mismatched metadata on inode {inum} in parent {parent}"),
-            _ => panic!("couldn't find inode {inum} in parent {parent}"),
-        };
-
-        // find the parent again, mutably
-        let files = match state.get_mut(parent) {
-            Ok(INode {
-                entry: Entry::Directory(_dirtype, files),
-                ..
-            }) => files,
-            Ok(_) => panic!("parent changed to a regular file"),
-            Err(_) => panic!("error finding parent again"),
-        };
-
-        // try to remove it
-        let res = files.remove(filename);
-        assert!(res.is_some());
-        state.dirty = true;
-        reply.ok();
+        match name
+            .to_str()
+            .ok_or(Errno::ENOENT)
+            .and_then(|name| state.remove_dir(parent, name))
+        {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
     }
 
     #[instrument(level = "debug", skip(self, req, reply))]
@@ -1767,121 +2943,18 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
             return;
         }
 
-        let src = match name.to_str() {
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Some(name) => name,
-        };
-
-        if src == "." || src == ".." {
-            reply.error(Errno::EINVAL);
-            return;
-        }
-
-        let tgt = match newname.to_str() {
-            None => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-            Some(name) => name,
-        };
-
-        // make sure src exists
-        let (src_kind, src_original, src_inum) = match state.get(parent) {
-            Ok(INode {
-                entry: Entry::Directory(_kind, files),
-                ..
-            }) => match files.get(src) {
-                Some(DirEntry {
-                    kind,
-                    original_name,
-                    inum,
-                    ..
-                }) => (*kind, original_name.clone(), *inum),
-                None => {
-                    reply.error(Errno::ENOENT);
-                    return;
-                }
-            },
-            _ => {
-                reply.error(Errno::ENOENT);
-                return;
-            }
-        };
-        // determine whether tgt exists
-        let tgt_info = match state.get(newparent) {
-            Ok(INode {
-                entry: Entry::Directory(_kind, files),
-                ..
-            }) => match files.get(tgt) {
-                Some(DirEntry { kind, inum, .. }) => {
-                    if src_kind != *kind {
-                        reply.error(Errno::ENOTDIR);
-                        return;
-                    }
-                    Some((*kind, *inum))
-                }
-                None => None,
-            },
+        let (src, tgt) = match (name.to_str(), newname.to_str()) {
+            (Some(src), Some(tgt)) => (src, tgt),
             _ => {
                 reply.error(Errno::ENOENT);
                 return;
             }
         };
 
-        // if tgt exists and is a directory, make sure it's empty
-        if let Some((FileType::Directory, tgt_inum)) = tgt_info {
-            match state.get(tgt_inum) {
-                Ok(INode {
-                    entry: Entry::Directory(_type, files),
-                    ..
-                }) => {
-                    if !files.is_empty() {
-                        reply.error(Errno::ENOTEMPTY);
-                        return;
-                    }
-                }
-                _ => panic!("bad metadata on inode {tgt_inum} in {newparent}"),
-            }
-        }
-        // remove src from parent
-        match state.get_mut(parent) {
-            Ok(INode {
-                entry: Entry::Directory(_kind, files),
-                ..
-            }) => files.remove(src),
-            _ => panic!("parent changed"),
-        };
-
-        // add src as tgt to newparent
-        match state.get_mut(newparent) {
-            Ok(INode {
-                entry: Entry::Directory(_kind, files),
-                ..
-            }) => files.insert(
-                tgt.into(),
-                DirEntry {
-                    kind: src_kind,
-                    // if the filename is the same, we'll keep the source
-                    // original filename (if it exists; otherwise we overwrite
-                    // it)
-                    original_name: if src == tgt { src_original } else { None },
-                    inum: src_inum,
-                },
-            ),
-            _ => panic!("parent changed"),
-        };
-
-        // set src's parent inode
-        match state.get_mut(src_inum) {
-            Ok(inode) => inode.parent = newparent,
-            Err(_) => panic!("missing inode {src_inum} moved from {parent} to {newparent}"),
+        match state.move_entry(parent, src, newparent, tgt) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
         }
-
-        state.dirty = true;
-        reply.ok();
     }
 
     #[instrument(level = "debug", skip(self, req, reply))]
@@ -1917,7 +2990,7 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
                 ..
             }) => contents,
             Ok(INode {
-                entry: Entry::Directory(..),
+                entry: Entry::Directory(..) | Entry::Symlink(..) | Entry::Control(..),
                 ..
             }) => {
                 reply.error(Errno::EBADF);
@@ -1944,17 +3017,34 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
         reply.ok()
     }
 
+    /// Synchronizes the `FS`, calling `FS::sync` with `last_sync == false`.
+    ///
+    /// Every inode is saved to the same output, so syncing any one of them
+    /// saves the whole filesystem.
     #[instrument(level = "debug", skip(self, _req, reply))]
     fn fsync(
         &self,
         _req: &Request,
-        _ino: INodeNo,
+        ino: INodeNo,
         _fh: fuser::FileHandle,
         _datasync: bool,
         reply: ReplyEmpty,
     ) {
         info!("called");
-        reply.error(Errno::ENOSYS);
+
+        let mut state = self.state.lock().unwrap();
+        if state.get(ino).is_err() {
+            reply.error(Errno::ENOENT);
+            return;
+        }
+
+        match state.sync(false) {
+            Ok(()) => reply.ok(),
+            Err(e) => {
+                error!("Couldn't save to {}: {e}", state.config.output);
+                reply.error(Errno::EIO);
+            }
+        }
     }
 
     // TODO
@@ -2000,46 +3090,201 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
     fn forget(&self, _req: &Request, _ino: INodeNo, _nlookup: u64) {}
 
     #[instrument(level = "debug", skip(self, _req, reply))]
-    fn readlink(&self, _req: &Request, _ino: INodeNo, reply: ReplyData) {
+    fn readlink(&self, _req: &Request, ino: INodeNo, reply: ReplyData) {
         info!("called");
 
-        reply.error(Errno::ENOSYS);
+        let mut state = self.state.lock().unwrap();
+        match state.get(ino) {
+            Ok(INode {
+                entry: Entry::Symlink(target),
+                ..
+            }) => reply.data(target.as_os_str().as_encoded_bytes()),
+            Ok(_) => reply.error(Errno::EINVAL),
+            Err(_) => reply.error(Errno::ENOENT),
+        }
     }
 
-    #[instrument(level = "debug", skip(self, _req, reply))]
+    #[instrument(level = "debug", skip(self, req, reply))]
     fn symlink(
         &self,
-        _req: &Request,
-        _parent: INodeNo,
-        _name: &OsStr,
-        _link: &Path,
+        req: &Request,
+        parent: INodeNo,
+        link_name: &OsStr,
+        target: &Path,
         reply: ReplyEntry,
     ) {
         info!("called");
 
-        reply.error(Errno::ENOSYS);
+        let mut state = self.state.lock().unwrap();
+
+        // access control
+        if !state.check_access(req) {
+            reply.error(Errno::EACCES);
+            return;
+        }
+
+        // the control directory's contents are fixed
+        if state.is_control(parent) {
+            reply.error(Errno::EPERM);
+            return;
+        }
+
+        // get the filename
+        let filename = match link_name.to_str() {
+            None => {
+                reply.error(Errno::ENOENT);
+                return;
+            }
+            Some(name) => name,
+        };
+
+        // make sure the parent exists, is a directory, and doesn't have that file
+        match state.get(parent) {
+            Err(_e) => {
+                reply.error(Errno::ENOENT);
+                return;
+            }
+            Ok(inode) => match &inode.entry {
+                Entry::File(..) | Entry::Symlink(..) | Entry::Control(..) => {
+                    reply.error(Errno::ENOTDIR);
+                    return;
+                }
+                Entry::Directory(_dirtype, files) => {
+                    if files.contains_key(filename) {
+                        reply.error(Errno::EEXIST);
+                        return;
+                    }
+                }
+                Entry::Lazy(..) => panic!("unresolved lazy value in symlink"),
+            },
+        };
+
+        // allocate the inode (sets dirty bit)
+        let entry = Entry::Symlink(target.to_path_buf());
+        let inum = state.fresh_inode(parent, entry, req.uid(), req.gid(), 0o777);
+
+        // update the parent
+        match state.get_mut(parent) {
+            Ok(INode {
+                entry: Entry::Directory(_dirtype, files),
+                ..
+            }) => {
+                files.insert(
+                    filename.into(),
+                    DirEntry {
+                        kind: FileType::Symlink,
+                        original_name: None,
+                        inum,
+                    },
+                );
+            }
+            _ => panic!("parent changed"),
+        };
+
+        reply.entry(&TTL, &state.get(inum).unwrap().attr(), fuser::Generation(0));
+        assert!(state.dirty);
     }
 
-    #[instrument(level = "debug", skip(self, _req, reply))]
+    #[instrument(level = "debug", skip(self, req, reply))]
     fn link(
         &self,
-        _req: &Request,
-        _ino: INodeNo,
-        _newparent: INodeNo,
-        _newname: &OsStr,
+        req: &Request,
+        ino: INodeNo,
+        newparent: INodeNo,
+        newname: &OsStr,
         reply: ReplyEntry,
     ) {
         info!("called");
 
-        reply.error(Errno::ENOSYS);
+        let mut state = self.state.lock().unwrap();
+
+        // access control
+        if !state.check_access(req) {
+            reply.error(Errno::EACCES);
+            return;
+        }
+
+        // the control directory's contents are fixed
+        if state.is_control(ino) || state.is_control(newparent) {
+            reply.error(Errno::EPERM);
+            return;
+        }
+
+        // get the filename
+        let filename = match newname.to_str() {
+            None => {
+                reply.error(Errno::ENOENT);
+                return;
+            }
+            Some(name) => name,
+        };
+
+        // only files and symlinks can be hard linked
+        let kind = match state.get(ino) {
+            Ok(inode) => inode.entry.kind(),
+            Err(_e) => {
+                reply.error(Errno::ENOENT);
+                return;
+            }
+        };
+        if kind == FileType::Directory {
+            reply.error(Errno::EPERM);
+            return;
+        }
+
+        // add the new name to the parent
+        match state.get_mut(newparent) {
+            Err(_e) => {
+                reply.error(Errno::ENOENT);
+                return;
+            }
+            Ok(INode {
+                entry: Entry::Directory(_dirtype, files),
+                ..
+            }) => {
+                if files.contains_key(filename) {
+                    reply.error(Errno::EEXIST);
+                    return;
+                }
+                files.insert(
+                    filename.into(),
+                    DirEntry {
+                        kind,
+                        original_name: None,
+                        inum: ino,
+                    },
+                );
+            }
+            Ok(_) => {
+                reply.error(Errno::ENOTDIR);
+                return;
+            }
+        };
+
+        let inode = state.get_mut(ino).unwrap();
+        inode.links += 1;
+        inode.ctime = SystemTime::now();
+        let attr = inode.attr();
+        state.dirty = true;
+        reply.entry(&TTL, &attr, fuser::Generation(0));
     }
 
     #[instrument(level = "debug", skip(self, _req, reply))]
-    fn open(&self, _req: &Request, _ino: INodeNo, _flags: fuser::OpenFlags, reply: ReplyOpen) {
+    fn open(&self, _req: &Request, ino: INodeNo, _flags: fuser::OpenFlags, reply: ReplyOpen) {
         info!("called");
 
+        // control files have no fixed size, so the kernel mustn't cache them
+        let state = self.state.lock().unwrap();
+        let flags = match state.peek(ino) {
+            Some(INode {
+                entry: Entry::Control(_),
+                ..
+            }) => fuser::FopenFlags::FOPEN_DIRECT_IO,
+            _ => fuser::FopenFlags::empty(),
+        };
+
         // TODO 2021-06-16 access check?
-        reply.opened(fuser::FileHandle(0), fuser::FopenFlags::empty());
+        reply.opened(fuser::FileHandle(0), flags);
     }
 
     #[instrument(level = "debug", skip(self, _req, reply))]
@@ -2106,18 +3351,41 @@ impl<V: Nodelike + Clone + 'static> Filesystem for FS<V> {
         reply.ok();
     }
 
+    /// Synchronizes the `FS`, just like `fsync`.
     #[instrument(level = "debug", skip(self, _req, reply))]
     fn fsyncdir(
         &self,
         _req: &Request,
-        _ino: INodeNo,
+        ino: INodeNo,
         _fh: fuser::FileHandle,
         _datasync: bool,
         reply: ReplyEmpty,
     ) {
         info!("called");
 
-        reply.error(Errno::ENOSYS);
+        let mut state = self.state.lock().unwrap();
+        match state.get(ino) {
+            Ok(INode {
+                entry: Entry::Directory(..),
+                ..
+            }) => (),
+            Ok(_) => {
+                reply.error(Errno::ENOTDIR);
+                return;
+            }
+            Err(_) => {
+                reply.error(Errno::ENOENT);
+                return;
+            }
+        }
+
+        match state.sync(false) {
+            Ok(()) => reply.ok(),
+            Err(e) => {
+                error!("Couldn't save to {}: {e}", state.config.output);
+                reply.error(Errno::EIO);
+            }
+        }
     }
 
     #[instrument(level = "debug", skip(self, _req, reply))]
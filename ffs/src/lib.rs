@@ -0,0 +1,9 @@
+//! The file filesystem: semi-structured data as a filesystem.
+//!
+//! `fs::FS` is the FUSE filesystem that the `ffs` binary mounts. Its state,
+//! `fs::FSState`, can also be used in-process---without mounting
+//! anything---through a path-based API (`lookup`, `read`, `write`, `mkdir`,
+//! `rename`, and so on), which is handy for testing and embedding.
+
+pub mod control;
+pub mod fs;
@@ -1,18 +1,22 @@
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::parser::ValueSource;
 use clap::{Arg, ArgAction, Command, value_parser};
 use tracing::{debug, error, info, warn};
 
 use nodelike::config::{
-    Config, ERROR_STATUS_CLI, ERROR_STATUS_FUSE, Input, Output, POSSIBLE_FORMATS,
+    Compression, Config, ERROR_STATUS_CLI, ERROR_STATUS_FUSE, Input, Output, POSSIBLE_FORMATS,
+};
+use nodelike::{
+    Format, Nodelike, ParseFormatError, cbor, csv, dotenv, ini, json, json5, msgpack, ndjson,
+    plist, properties, toml, tsv, xml, yaml,
 };
-use nodelike::{Format, Nodelike, ParseFormatError, json, toml, yaml};
 
 use fuser::MountOption;
 
-mod fs;
-use fs::FS;
+use ffs::fs::FS;
 
 pub fn ffs_cli() -> Command {
     nodelike::config::cli_base("ffs")
@@ -119,6 +123,29 @@ pub fn ffs_cli() -> Command {
                 .action(ArgAction::SetTrue)
 
         )
+        .arg(
+            Arg::new("PRESERVE_FORMAT")
+                .help("Keep the input's comments and formatting when saving, re-rendering only what changed (TOML and YAML only)")
+                .long("preserve-format")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("AUTOSAVE")
+                .help("Periodically save outstanding changes to the output file, every AUTOSAVE seconds")
+                .long("autosave-interval")
+                .value_name("AUTOSAVE")
+                .value_parser(value_parser!(u64).range(1..))
+        )
+        .arg(
+            Arg::new("WATCH")
+                .help("Reload the input file when it changes on disk, checking every WATCH seconds (default: 1); unsaved changes are never overwritten")
+                .long("watch")
+                .value_name("WATCH")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("1")
+                .value_parser(value_parser!(u64).range(1..))
+        )
         .arg(
             Arg::new("MOUNT")
                 .help("Sets the mountpoint; will be inferred when using a file, but must be specified when running on stdin")
@@ -141,6 +168,15 @@ pub fn ffs_cli() -> Command {
                 .default_value("-")
                 .index(1),
         )
+        .arg(
+            Arg::new("COMMAND")
+                .help("Runs COMMAND in the mounted filesystem, unmounting when it exits; the mountpoint is the working directory and $FFS_MOUNT")
+                .value_name("COMMAND")
+                .num_args(1..)
+                .index(2)
+                .last(true)
+                .value_parser(value_parser!(OsString)),
+        )
 }
 
 /// Parses arguments from `std::env::Args`, via `cli::app().get_matches()`
@@ -235,12 +271,7 @@ pub fn config_from_ffs_args() -> Config {
                             debug!("Inferring output format from input.")
                         }
                     };
-                    match output
-                        .extension()
-                        .and_then(|s| s.to_str())
-                        .ok_or(ParseFormatError::NoFormatProvided)
-                        .and_then(|s| s.parse::<Format>())
-                    {
+                    match Format::from_path(&output) {
                         Ok(format) => format,
                         Err(_) => {
                             error!(
@@ -263,8 +294,9 @@ pub fn config_from_ffs_args() -> Config {
                     Some(mount_point)
                 }
                 None => {
-                    // If the output is to a file foo.EXT, then try to make a directory foo.
-                    let stem = output.file_stem().unwrap_or_else(|| {
+                    // If the output is to a file foo.EXT (or foo.EXT.gz, etc.), then try to make a directory foo.
+                    let name = Compression::strip(&output);
+                    let stem = name.file_stem().unwrap_or_else(|| {
                             error!("Couldn't infer the mountpoint from output '{}'. Use `--mount MOUNT` to specify a mountpoint.", output.display());
                             std::process::exit(ERROR_STATUS_FUSE);
                         });
@@ -364,8 +396,9 @@ pub fn config_from_ffs_args() -> Config {
                             std::process::exit(ERROR_STATUS_CLI);
                         }
                         Input::File(file) => {
-                            // If the input is from a file foo.EXT, then try to make a directory foo.
-                            let stem = file.file_stem().unwrap_or_else(|| {
+                            // If the input is from a file foo.EXT (or foo.EXT.gz, etc.), then try to make a directory foo.
+                            let name = Compression::strip(file);
+                            let stem = name.file_stem().unwrap_or_else(|| {
                                     error!("Couldn't infer the mountpoint from input '{}'. Use `--mount MOUNT` to specify a mountpoint.", file.display());
                                     std::process::exit(ERROR_STATUS_FUSE);
                                 });
@@ -423,12 +456,7 @@ pub fn config_from_ffs_args() -> Config {
                     match &config.input {
                         Input::Stdin => Format::Json,
                         Input::Empty => Format::Json,
-                        Input::File(input_source) => match input_source
-                            .extension()
-                            .and_then(|s| s.to_str())
-                            .ok_or(ParseFormatError::NoFormatProvided)
-                            .and_then(|s| s.parse::<Format>())
-                        {
+                        Input::File(input_source) => match Format::from_path(input_source) {
                             Ok(format) => format,
                             Err(e) => {
                                 match e {
@@ -470,26 +498,55 @@ pub fn config_from_ffs_args() -> Config {
                     };
                     match args
                         .get_one::<String>("OUTPUT")
-                        .and_then(|s| Path::new(s).extension())
-                        .and_then(|s| s.to_str())
+                        .map(|s| Format::from_path(Path::new(s)))
                     {
-                        Some(s) => match s.parse::<Format>() {
-                            Ok(format) => format,
-                            Err(_) => {
-                                warn!(
-                                    "Unrecognized format {s}, defaulting to input format '{}'.",
-                                    config.input_format
-                                );
+                        Some(Ok(format)) => format,
+                        Some(Err(ParseFormatError::NoSuchFormat(s))) => {
+                            warn!(
+                                "Unrecognized format {s}, defaulting to input format '{}'.",
                                 config.input_format
-                            }
-                        },
-                        None => config.input_format,
+                            );
+                            config.input_format
+                        }
+                        Some(Err(ParseFormatError::NoFormatProvided)) | None => config.input_format,
                     }
                 }
             };
         }
     };
 
+    config.command = args
+        .get_many::<OsString>("COMMAND")
+        .map(|command| command.cloned().collect());
+
+    config.autosave_interval = args
+        .get_one::<u64>("AUTOSAVE")
+        .map(|secs| Duration::from_secs(*secs));
+    if config.autosave_interval.is_some() && !matches!(config.output, Output::File(_)) {
+        warn!("`--autosave-interval` only applies when saving to a file (use `-o` or `-i`).");
+    }
+
+    config.watch_interval = args
+        .get_one::<u64>("WATCH")
+        .map(|secs| Duration::from_secs(*secs));
+    if config.watch_interval.is_some() && !matches!(config.input, Input::File(_)) {
+        warn!("`--watch` only applies when loading from a file.");
+    }
+
+    config.preserve_format = args.get_flag("PRESERVE_FORMAT");
+    if config.preserve_format {
+        if !config.input_format.can_preserve() {
+            warn!(
+                "`--preserve-format` doesn't work for {}; saving will rewrite the whole document.",
+                config.input_format
+            );
+            config.preserve_format = false;
+        } else if config.input_format != config.output_format {
+            warn!("`--preserve-format` only applies when saving in the input's format.");
+            config.preserve_format = false;
+        }
+    }
+
     if config.pretty && !config.output_format.can_be_pretty() {
         warn!(
             "There is no pretty printing routine for {}.",
@@ -500,6 +557,61 @@ pub fn config_from_ffs_args() -> Config {
     config
 }
 
+/// Runs `command` in `mount`, returning its exit status (or, like a shell
+/// would, 127 if it couldn't be found, 126 if it couldn't be run, and 128 plus
+/// the signal number if it was killed).
+///
+/// While the command runs, we ignore the signals a terminal sends on ^C and
+/// ^\ (as `system(3)` does): the command gets them, and we still get to save
+/// and unmount when it exits.
+fn run_command(command: &[OsString], mount: &Path) -> i32 {
+    let mount = std::path::absolute(mount).unwrap_or_else(|_| mount.to_path_buf());
+    info!("running {command:?} in {}", mount.display());
+
+    let mut child = match std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .current_dir(&mount)
+        .env("FFS_MOUNT", &mount)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            error!("Couldn't run {}: {e}", command[0].to_string_lossy());
+            return match e.kind() {
+                std::io::ErrorKind::NotFound => 127,
+                _ => 126,
+            };
+        }
+    };
+
+    let handlers = unsafe {
+        [
+            libc::signal(libc::SIGINT, libc::SIG_IGN),
+            libc::signal(libc::SIGQUIT, libc::SIG_IGN),
+        ]
+    };
+    let status = child.wait();
+    unsafe {
+        libc::signal(libc::SIGINT, handlers[0]);
+        libc::signal(libc::SIGQUIT, handlers[1]);
+    }
+
+    match status {
+        Ok(status) => {
+            use std::os::unix::process::ExitStatusExt;
+            match (status.code(), status.signal()) {
+                (Some(code), _) => code,
+                (None, Some(signal)) => 128 + signal,
+                (None, None) => ERROR_STATUS_FUSE,
+            }
+        }
+        Err(e) => {
+            error!("Couldn't wait for {}: {e}", command[0].to_string_lossy());
+            ERROR_STATUS_FUSE
+        }
+    }
+}
+
 fn main() {
     let config = config_from_ffs_args();
     let mut mount_options = vec![MountOption::FSName(format!("{}", config.input))];
@@ -531,10 +643,35 @@ fn main() {
         mount: &std::path::Path,
         fuser_config: &fuser::Config,
     ) -> i32 {
-        match fuser::mount2(FS::<V>::new(config), mount, fuser_config) {
+        let command = config.command.clone();
+        let fs = FS::<V>::new(config);
+        let state = fs.state.clone();
+        let session = match fuser::spawn_mount2(fs, mount, fuser_config) {
+            Ok(session) => session,
+            Err(e) => {
+                error!("I/O error: {e}");
+                return ERROR_STATUS_FUSE;
+            }
+        };
+        state.lock().unwrap().set_notifier(session.notifier());
+
+        // the mount is ready once `spawn_mount2` returns, so there's no race
+        let (status, unmounted) = match command {
+            Some(command) => {
+                let status = run_command(&command, mount);
+                (status, session.umount_and_join())
+            }
+            None => (0, session.join()),
+        };
+        match unmounted {
             Ok(()) => {
                 info!("unmounted");
-                0
+                if state.lock().unwrap().is_dirty() {
+                    error!("Unmounted without saving all changes.");
+                    ERROR_STATUS_FUSE
+                } else {
+                    status
+                }
             }
             Err(e) => {
                 error!("I/O error: {e}");
@@ -544,8 +681,19 @@ fn main() {
     }
     let input_format = config.input_format;
     let status = match input_format {
+        Format::Cbor => run_ffs::<cbor::Value>(config, &mount, &fuser_config),
+        Format::Csv => run_ffs::<csv::Value>(config, &mount, &fuser_config),
+        Format::Dotenv => run_ffs::<dotenv::Value>(config, &mount, &fuser_config),
+        Format::Ini => run_ffs::<ini::Value>(config, &mount, &fuser_config),
         Format::Json => run_ffs::<json::Value>(config, &mount, &fuser_config),
+        Format::Json5 => run_ffs::<json5::Value>(config, &mount, &fuser_config),
+        Format::MsgPack => run_ffs::<msgpack::Value>(config, &mount, &fuser_config),
+        Format::Ndjson => run_ffs::<ndjson::Value>(config, &mount, &fuser_config),
+        Format::Plist => run_ffs::<plist::Value>(config, &mount, &fuser_config),
+        Format::Properties => run_ffs::<properties::Value>(config, &mount, &fuser_config),
         Format::Toml => run_ffs::<toml::Value>(config, &mount, &fuser_config),
+        Format::Tsv => run_ffs::<tsv::Value>(config, &mount, &fuser_config),
+        Format::Xml => run_ffs::<xml::Value>(config, &mount, &fuser_config),
         Format::Yaml => run_ffs::<yaml::Value>(config, &mount, &fuser_config),
     };
 
@@ -0,0 +1,268 @@
+//! Exercises `FSState`'s path-based API directly, without mounting anything.
+
+use fuser::Errno;
+use serde_json::json;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use ffs::fs::FSState;
+use nodelike::Nodelike;
+use nodelike::config::{Config, Munge};
+use nodelike::json::Value;
+use nodelike::{csv, dotenv, plist, yaml};
+
+fn state(v: Value) -> FSState<Value> {
+    FSState::from_value(v, Config::default()).expect("from_value")
+}
+
+/// Writes `v` out in its format (lossily, if that isn't text).
+fn saved<V: Nodelike>(v: &V) -> String {
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buffer = Buffer::default();
+    v.to_writer(Box::new(buffer.clone()), &Config::default())
+        .expect("to_writer");
+    String::from_utf8_lossy(&buffer.0.lock().unwrap()).into_owned()
+}
+
+fn assert_errno<T: std::fmt::Debug>(res: Result<T, Errno>, expected: Errno) {
+    match res {
+        Err(e) => assert_eq!(
+            e.code(),
+            expected.code(),
+            "got {e:?}, expected {expected:?}"
+        ),
+        Ok(v) => panic!("expected {expected:?}, got {v:?}"),
+    }
+}
+
+#[test]
+fn scalars_are_files() {
+    let mut fs = state(json!({"name": "Michael Greenberg", "eyes": 2, "human": true}));
+
+    assert_eq!(fs.read("name").unwrap(), b"Michael Greenberg\n");
+    assert_eq!(fs.read("/eyes").unwrap(), b"2\n");
+    assert_eq!(fs.typ("eyes").unwrap(), "integer");
+    assert_eq!(fs.typ("human").unwrap(), "boolean");
+    assert_eq!(fs.typ("").unwrap(), "named");
+}
+
+#[test]
+fn lists_are_padded() {
+    let mut fs = state(json!({"list": (0..11).collect::<Vec<_>>()}));
+
+    let names = fs.list("list").unwrap();
+    assert_eq!(names.len(), 11);
+    assert_eq!(names[0], "00");
+    assert_eq!(names[10], "10");
+    assert_eq!(fs.typ("list").unwrap(), "list");
+    assert_eq!(fs.read("list/07").unwrap(), b"7\n");
+}
+
+#[test]
+fn nested_directories_load_lazily() {
+    let mut fs = state(json!({"a": {"b": {"c": "deep"}}}));
+
+    assert_eq!(fs.read("a/b/c").unwrap(), b"deep\n");
+    assert_eq!(fs.read("a/b/../b/c").unwrap(), b"deep\n");
+    assert_eq!(fs.list("a").unwrap(), vec!["b"]);
+}
+
+#[test]
+fn control_directory() {
+    let mut fs = state(json!({}));
+
+    assert_eq!(fs.list("").unwrap(), vec![".ffs"]);
+    assert_eq!(fs.read(".ffs/dirty").unwrap(), b"false\n");
+    assert_eq!(fs.read(".ffs/format").unwrap(), b"json\n");
+
+    fs.write("x", b"1").unwrap();
+    assert_eq!(fs.read(".ffs/dirty").unwrap(), b"true\n");
+
+    assert_errno(fs.write(".ffs/dirty", b"false"), Errno::EPERM);
+    assert_errno(fs.unlink(".ffs/dirty"), Errno::EPERM);
+    assert_errno(fs.mkdir(".ffs/new"), Errno::EPERM);
+    assert_errno(fs.rmdir(".ffs"), Errno::EPERM);
+
+    // the control directory is never saved
+    assert_eq!(fs.to_value(), json!({"x": 1}));
+}
+
+#[test]
+fn write_and_save() {
+    let mut fs = state(json!({"name": "Michael Greenberg"}));
+    assert!(!fs.is_dirty());
+
+    fs.write("name", b"Mike").unwrap();
+    fs.write("age", b"42\n").unwrap();
+    assert!(fs.is_dirty());
+    assert_eq!(fs.read("name").unwrap(), b"Mike");
+
+    assert_eq!(fs.to_value(), json!({"name": "Mike", "age": 42}));
+}
+
+#[test]
+fn set_type() {
+    let mut fs = state(json!({"zip": 2139}));
+
+    fs.set_type("zip", "string").unwrap();
+    assert_eq!(fs.typ("zip").unwrap(), "string");
+    assert_eq!(fs.to_value(), json!({"zip": "2139"}));
+
+    assert_errno(fs.set_type("zip", "nonsense"), Errno::EINVAL);
+}
+
+#[test]
+fn directories() {
+    let mut fs = state(json!({"full": {"x": 1}}));
+
+    fs.mkdir("empty").unwrap();
+    assert_eq!(fs.list("empty").unwrap(), Vec::<String>::new());
+    assert_errno(fs.mkdir("empty"), Errno::EEXIST);
+    assert_errno(fs.mkdir("full/x/y"), Errno::ENOTDIR);
+    assert_errno(fs.rmdir("full"), Errno::ENOTEMPTY);
+    assert_errno(fs.unlink("full"), Errno::EPERM);
+    assert_errno(fs.list("full/x"), Errno::ENOTDIR);
+
+    fs.unlink("full/x").unwrap();
+    assert_errno(fs.unlink("full/x"), Errno::ENOENT);
+    fs.rmdir("full").unwrap();
+
+    assert_eq!(fs.to_value(), json!({"empty": {}}));
+}
+
+#[test]
+fn rename() {
+    let mut fs = state(json!({"a": {"one": 1, "d": {}}, "b": {}, "c": "see"}));
+
+    fs.rename("a/one", "b/uno").unwrap();
+    fs.rename("c", "a/c").unwrap();
+    assert_errno(fs.read("c"), Errno::ENOENT);
+    assert_eq!(fs.read("a/c").unwrap(), b"see\n");
+
+    // a directory can't replace a file
+    assert_errno(fs.rename("b", "a/c"), Errno::ENOTDIR);
+
+    // ...nor move into itself
+    assert_errno(fs.rename("a", "a/b"), Errno::EINVAL);
+    assert_errno(fs.rename("a", "a/d/a"), Errno::EINVAL);
+
+    assert_eq!(
+        fs.to_value(),
+        json!({"a": {"c": "see", "d": {}}, "b": {"uno": 1}})
+    );
+}
+
+#[test]
+fn munging() {
+    let v = json!({".": "first", "..": "second", "dot": "third", "dotdot": "fourth"});
+
+    let mut fs = state(v.clone());
+    let names = fs.list("").unwrap();
+    assert_eq!(names.len(), 5, "{names:?}");
+    // renamed fields keep their original names when saved
+    assert_eq!(fs.to_value(), v);
+
+    let config = Config {
+        munge: Munge::Filter,
+        ..Config::default()
+    };
+    let mut fs = FSState::<Value>::from_value(v, config).unwrap();
+    assert_eq!(fs.list("").unwrap(), vec![".ffs", "dot", "dotdot"]);
+    assert_eq!(fs.to_value(), json!({"dot": "third", "dotdot": "fourth"}));
+}
+
+#[test]
+fn read_only() {
+    let config = Config {
+        read_only: true,
+        ..Config::default()
+    };
+    let mut fs = FSState::<Value>::from_value(json!({"x": 1}), config).unwrap();
+
+    assert_eq!(fs.read("x").unwrap(), b"1\n");
+    assert_errno(fs.write("x", b"2"), Errno::EROFS);
+    assert_errno(fs.unlink("x"), Errno::EROFS);
+    assert_errno(fs.rename("x", "y"), Errno::EROFS);
+    assert!(!fs.is_dirty());
+}
+
+#[test]
+fn single_values_are_not_directories() {
+    assert_errno(
+        FSState::<Value>::from_value(json!(5), Config::default()),
+        Errno::ENOTDIR,
+    );
+}
+
+#[test]
+fn aliased_directories() {
+    let text = "a: {x: &e {}, only_a: 1}\nb: {y: *e, only_b: 2}\n";
+    let config = Config::default();
+    let v = yaml::Value::from_reader(Box::new(std::io::Cursor::new(text)), &config).unwrap();
+    let mut fs = FSState::from_value(v, Config::default()).unwrap();
+
+    // one directory, two parents: `..` goes back the way we came
+    assert_eq!(fs.lookup("a/x").unwrap(), fs.lookup("b/y").unwrap());
+    assert_eq!(fs.read("a/x/../only_a").unwrap(), b"1\n");
+    assert_eq!(fs.read("b/y/../only_b").unwrap(), b"2\n");
+
+    // removing or renaming one alias leaves the other
+    fs.rmdir("a/x").unwrap();
+    assert_eq!(fs.list("b/y").unwrap(), Vec::<String>::new());
+    fs.rename("b/y", "b/z").unwrap();
+    fs.mkdir("b/z/w").unwrap();
+    assert_eq!(fs.list("b/z").unwrap(), vec!["w"]);
+    assert_eq!(
+        fs.to_value().to_string(),
+        "---\na:\n  only_a: 1\nb:\n  only_b: 2\n  z:\n    w: {}"
+    );
+}
+
+#[test]
+fn layouts_are_per_document() {
+    let config = Config::default();
+    let read = |text: &str| {
+        let reader = Box::new(std::io::Cursor::new(text.to_string()));
+        csv::Value::from_reader(reader, &config).unwrap()
+    };
+    let mut zyx = FSState::from_value(read("z,y,x\n1,2,3\n"), Config::default()).unwrap();
+    let mut xzy = FSState::from_value(read("x,z,y\n4,5,6\n"), Config::default()).unwrap();
+
+    // each keeps its own columns, whichever was read last
+    zyx.write("0/w", b"0").unwrap();
+    assert_eq!(saved(&zyx.to_value()), "z,y,x,w\n1,2,3,0\n");
+    assert_eq!(saved(&xzy.to_value()), "x,z,y\n4,5,6\n");
+
+    let read = |text: &str| {
+        let reader = Box::new(std::io::Cursor::new(text.to_string()));
+        dotenv::Value::from_reader(reader, &config).unwrap()
+    };
+    let mut exported = FSState::from_value(read("export A=1\n"), Config::default()).unwrap();
+    let mut plain = FSState::from_value(read("B=2\n"), Config::default()).unwrap();
+    exported.write("C", b"3").unwrap();
+    assert_eq!(saved(&exported.to_value()), "export A=1\nexport C=3\n");
+    assert_eq!(saved(&plain.to_value()), "B=2\n");
+
+    let read = |bytes: &[u8]| {
+        let reader = Box::new(std::io::Cursor::new(bytes.to_vec()));
+        plist::Value::from_reader(reader, &config).unwrap()
+    };
+    let binary = include_bytes!("../../binary/settings.plist");
+    let mut binary = FSState::from_value(read(binary), Config::default()).unwrap();
+    let xml = b"<plist><dict><key>a</key><integer>1</integer></dict></plist>";
+    let mut xml = FSState::from_value(read(xml), Config::default()).unwrap();
+    assert!(saved(&binary.to_value()).starts_with("bplist00"));
+    assert!(saved(&xml.to_value()).starts_with("<?xml"));
+}
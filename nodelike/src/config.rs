@@ -1,22 +1,50 @@
+use std::ffi::OsString;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::Format;
 use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
 use clap_complete::{Shell, generate};
+use globset::GlobSet;
 use tracing::{debug, error, warn};
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
 pub const ERROR_STATUS_FUSE: i32 = 1;
 pub const ERROR_STATUS_CLI: i32 = 2;
+pub const ERROR_STATUS_PARSE: i32 = 3;
 
 /// The possible formats.
-pub const POSSIBLE_FORMATS: [&str; 3] = ["json", "toml", "yaml"];
+pub const POSSIBLE_FORMATS: [&str; 14] = [
+    "cbor",
+    "csv",
+    "dotenv",
+    "ini",
+    "json",
+    "json5",
+    "msgpack",
+    "ndjson",
+    "plist",
+    "properties",
+    "toml",
+    "tsv",
+    "xml",
+    "yaml",
+];
 
 /// The possible name munging policies.
 pub const MUNGE_POLICIES: [&str; 2] = ["filter", "rename"];
 
+/// The possible policies for CSV/TSV rows with new columns.
+pub const NEW_COLUMN_POLICIES: [&str; 2] = ["add", "error"];
+
+/// The possible compression algorithms.
+pub const POSSIBLE_COMPRESSIONS: [&str; 4] = ["bzip2", "gzip", "xz", "zstd"];
+
 /// Common clap configuration
 pub fn cli_base(name: impl Into<clap::builder::Str>) -> clap::Command {
     Command::new(name)
@@ -76,6 +104,45 @@ pub fn cli_base(name: impl Into<clap::builder::Str>) -> clap::Command {
                 .long("strict")
                 .action(ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("DELIMITER")
+                .help("Set the CSV/TSV delimiter (defaults to ',' for CSV and a tab for TSV); use 'tab' for a tab")
+                .long("delimiter")
+                .value_name("CHAR")
+        )
+        .arg(
+            Arg::new("NOHEADER")
+                .help("CSV/TSV files have no header row: rows are lists rather than named directories")
+                .long("no-header")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("NEW_COLUMNS")
+                .help("What to do when saving a CSV/TSV row with a column the others don't have")
+                .long("new-columns")
+                .value_name("POLICY")
+                .default_value("add")
+                .value_parser(NEW_COLUMN_POLICIES)
+        )
+        .arg(
+            Arg::new("GLOBAL_SECTION")
+                .help("Put the keys at the top of an INI file (before any section) in a directory named NAME, rather than at the root")
+                .long("global-section")
+                .value_name("NAME")
+        )
+        .arg(
+            Arg::new("NEST_KEYS")
+                .help("Split dotted keys in properties files into nested directories (e.g., 'a.b=1' becomes the file 'a/b')")
+                .long("nest-keys")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("COMPRESS")
+                .help("Decompress input and compress output with ALGORITHM (by default, inferred from a compression extension, e.g., 'data.json.gz')")
+                .long("compress")
+                .value_name("ALGORITHM")
+                .value_parser(POSSIBLE_COMPRESSIONS)
+        )
 }
 
 /// Configuration information
@@ -102,15 +169,51 @@ pub struct Config {
     pub symlink: Symlink,
     pub max_depth: Option<u32>,
     pub allow_symlink_escape: bool,
+    /// Paths (relative to the directory being packed) that `pack` skips
+    pub exclude: Option<GlobSet>,
+    /// If set, `pack` skips files (but not directories) that don't match
+    pub include: Option<GlobSet>,
+    pub respect_gitignore: bool,
+    /// If set, `unpack` updates an existing directory in place
+    pub merge: bool,
+    /// If set, `unpack --merge` removes files that aren't in the input
+    pub delete: bool,
+    /// If set, `unpack` says what it would do rather than doing it
+    pub dry_run: bool,
+    /// A document for `pack` to compare against, rather than saving
+    pub diff: Option<PathBuf>,
     pub munge: Munge,
     pub read_only: bool,
     pub input: Input,
     pub output: Output,
     pub pretty: bool,
+    /// If set, saving keeps the input's comments and formatting, re-rendering
+    /// only what changed (see `Nodelike::to_string_preserving`)
+    pub preserve_format: bool,
+    /// Overrides the CSV/TSV delimiter (`,` and tab, respectively)
+    pub delimiter: Option<u8>,
+    /// If set, CSV/TSV documents start with a row naming the columns, and
+    /// each row is a named directory; otherwise, rows are lists
+    pub header: bool,
+    pub new_columns: NewColumns,
+    /// If set, the keys before any section in an INI document go in a named
+    /// directory with this name; otherwise, they're at the root
+    pub global_section: Option<String>,
+    /// If set, dotted keys in properties documents are nested directories
+    pub nest_keys: bool,
+    /// Overrides the compression inferred from the input's and output's
+    /// extensions (see `Config::compression_for`)
+    pub compression: Option<Compression>,
     pub timing: bool,
     pub mount: Option<PathBuf>,
     pub cleanup_mount: bool,
     pub strict: bool,
+    pub autosave_interval: Option<Duration>,
+    /// How often to check the input file for changes (see `--watch`).
+    pub watch_interval: Option<Duration>,
+    /// A command to run in the mounted filesystem; when it exits, the
+    /// filesystem is unmounted.
+    pub command: Option<Vec<OsString>>,
 }
 
 #[derive(Debug)]
@@ -137,6 +240,191 @@ pub enum Output {
     File(PathBuf),
 }
 
+impl std::fmt::Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Output::Quiet => write!(f, "<quiet>"),
+            Output::Stdout => write!(f, "<stdout>"),
+            Output::File(file) => write!(f, "{}", file.display()),
+        }
+    }
+}
+
+/// A destination for output, as generated by `Config::output_writer`.
+///
+/// Nothing written to an `OutputWriter::File` is visible until `commit` is
+/// called; see `AtomicFile`.
+#[derive(Debug)]
+pub enum OutputWriter {
+    Stdout,
+    /// Special files (e.g., `/dev/stdout` or a FIFO) can't be renamed over,
+    /// so we write to them directly.
+    Direct(File),
+    File(AtomicFile),
+    /// Output that's compressed on its way to another `OutputWriter`.
+    Compressed {
+        inner: Box<OutputWriter>,
+        compression: Compression,
+        /// Where `Encoder` puts the error from finishing the stream, if any.
+        finished: Arc<Mutex<Option<std::io::Error>>>,
+    },
+}
+
+impl OutputWriter {
+    /// Generates a writer for the output; call `commit` when done writing.
+    ///
+    /// Compressed streams are finished when the writer is dropped, so drop it
+    /// before calling `commit`.
+    pub fn writer(&self) -> std::io::Result<Box<dyn Write>> {
+        match self {
+            OutputWriter::Stdout => Ok(Box::new(std::io::stdout())),
+            OutputWriter::Direct(file) => Ok(Box::new(file.try_clone()?)),
+            OutputWriter::File(file) => Ok(Box::new(file.writer()?)),
+            OutputWriter::Compressed {
+                inner,
+                compression,
+                finished,
+            } => Ok(Box::new(Encoder::new(
+                *compression,
+                inner.writer()?,
+                finished.clone(),
+            )?)),
+        }
+    }
+
+    /// Makes the output visible.
+    pub fn commit(self) -> std::io::Result<()> {
+        match self {
+            OutputWriter::Stdout => std::io::stdout().flush(),
+            // pipes and terminals can't be synced, and needn't be
+            OutputWriter::Direct(file) => {
+                if file.metadata()?.is_file() {
+                    file.sync_all()
+                } else {
+                    Ok(())
+                }
+            }
+            OutputWriter::File(file) => file.commit(),
+            OutputWriter::Compressed {
+                inner, finished, ..
+            } => match finished.lock().unwrap().take() {
+                Some(e) => Err(e),
+                None => inner.commit(),
+            },
+        }
+    }
+}
+
+/// A file that is written atomically.
+///
+/// Writes go to a temporary file in the same directory as the destination;
+/// `AtomicFile::commit` syncs that file to disk and renames it over the
+/// destination. If the `AtomicFile` is dropped without being committed
+/// (say, because serialization failed or panicked), the temporary file is
+/// removed and the destination is left untouched.
+///
+/// When the destination already exists, its mode and ownership are copied
+/// over to the temporary file.
+#[derive(Debug)]
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp: PathBuf,
+    file: File,
+    committed: bool,
+}
+
+impl AtomicFile {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        // write next to whatever the path actually refers to, so that we
+        // don't replace symlinks with regular files
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = path
+            .file_name()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("{} is not a file", path.display()),
+                )
+            })?
+            .to_string_lossy()
+            .into_owned();
+
+        let pid = std::process::id();
+        let mut attempt = 0;
+        let (tmp, file) = loop {
+            let tmp = dir.join(format!(".{name}.{pid}.{attempt}.tmp"));
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp)
+            {
+                Ok(file) => break (tmp, file),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        };
+        let atomic = AtomicFile {
+            path,
+            tmp,
+            file,
+            committed: false,
+        };
+
+        if let Ok(meta) = std::fs::metadata(&atomic.path) {
+            atomic.file.set_permissions(meta.permissions())?;
+
+            let tmp_meta = atomic.file.metadata()?;
+            if (tmp_meta.uid(), tmp_meta.gid()) != (meta.uid(), meta.gid())
+                && let Err(e) =
+                    std::os::unix::fs::fchown(&atomic.file, Some(meta.uid()), Some(meta.gid()))
+            {
+                warn!(
+                    "Couldn't preserve ownership of {}: {e}",
+                    atomic.path.display()
+                );
+            }
+        }
+
+        Ok(atomic)
+    }
+
+    /// A handle on the temporary file.
+    pub fn writer(&self) -> std::io::Result<File> {
+        self.file.try_clone()
+    }
+
+    /// Syncs the temporary file and renames it over the destination.
+    pub fn commit(mut self) -> std::io::Result<()> {
+        self.file.sync_all()?;
+        std::fs::rename(&self.tmp, &self.path)?;
+        self.committed = true;
+
+        // make sure the rename itself is durable; not every platform lets us
+        // sync directories, so this is best effort
+        if let Some(dir) = self.path.parent()
+            && let Ok(dir) = File::open(dir)
+        {
+            let _ = dir.sync_all();
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed
+            && let Err(e) = std::fs::remove_file(&self.tmp)
+        {
+            warn!("Couldn't remove temporary file {}: {e}", self.tmp.display());
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Munge {
     Rename,
@@ -168,6 +456,181 @@ impl FromStr for Munge {
     }
 }
 
+/// What saving a CSV/TSV document does with a row that has a column the
+/// others don't.
+#[derive(Debug)]
+pub enum NewColumns {
+    /// Add the column; other rows leave it empty.
+    Add,
+    /// Fail to save.
+    Error,
+}
+
+impl std::fmt::Display for NewColumns {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            NewColumns::Add => write!(f, "add"),
+            NewColumns::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for NewColumns {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim().to_lowercase();
+
+        if s == "add" {
+            Ok(NewColumns::Add)
+        } else if s == "error" {
+            Ok(NewColumns::Error)
+        } else {
+            Err(())
+        }
+    }
+}
+
+/// A compression algorithm for input and output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Bzip2,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Compression::Bzip2 => write!(f, "bzip2"),
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Xz => write!(f, "xz"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Parses algorithms by name or by extension (e.g., `gzip` or `gz`).
+impl FromStr for Compression {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim().to_lowercase().as_str() {
+            "bzip2" | "bz2" => Ok(Compression::Bzip2),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "xz" => Ok(Compression::Xz),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Compression {
+    /// The compression `path`'s extension calls for, if any (e.g.,
+    /// `data.json.gz` is gzipped).
+    pub fn from_path(path: &Path) -> Option<Compression> {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse().ok())
+    }
+
+    /// `path` without its compression extension, if it has one (e.g.,
+    /// `data.json` for `data.json.gz`).
+    pub fn strip(path: &Path) -> PathBuf {
+        match Compression::from_path(path) {
+            Some(_) => path.with_extension(""),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Decompresses `reader`. Each algorithm reads every concatenated stream,
+    /// as its command-line tool does.
+    pub fn decoder(
+        self,
+        reader: Box<dyn std::io::Read>,
+    ) -> std::io::Result<Box<dyn std::io::Read>> {
+        Ok(match self {
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        })
+    }
+}
+
+/// A compressing writer, as generated by `OutputWriter::writer`.
+///
+/// `Nodelike::to_writer` takes its writer by value, so the stream is
+/// finished when the `Encoder` is dropped; since `Drop` can't fail, any error
+/// is left in `finished` for `OutputWriter::commit` to report.
+struct Encoder {
+    stream: Stream,
+    finished: Arc<Mutex<Option<std::io::Error>>>,
+}
+
+enum Stream {
+    Bzip2(bzip2::write::BzEncoder<Box<dyn Write>>),
+    Gzip(flate2::write::GzEncoder<Box<dyn Write>>),
+    Xz(xz2::write::XzEncoder<Box<dyn Write>>),
+    Zstd(zstd::stream::write::Encoder<'static, Box<dyn Write>>),
+}
+
+impl Encoder {
+    fn new(
+        compression: Compression,
+        writer: Box<dyn Write>,
+        finished: Arc<Mutex<Option<std::io::Error>>>,
+    ) -> std::io::Result<Self> {
+        let stream = match compression {
+            Compression::Bzip2 => Stream::Bzip2(bzip2::write::BzEncoder::new(
+                writer,
+                bzip2::Compression::default(),
+            )),
+            Compression::Gzip => Stream::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            Compression::Xz => Stream::Xz(xz2::write::XzEncoder::new(writer, 6)),
+            Compression::Zstd => Stream::Zstd(zstd::stream::write::Encoder::new(writer, 0)?),
+        };
+        Ok(Encoder { stream, finished })
+    }
+
+    fn stream(&mut self) -> &mut dyn Write {
+        match &mut self.stream {
+            Stream::Bzip2(w) => w,
+            Stream::Gzip(w) => w,
+            Stream::Xz(w) => w,
+            Stream::Zstd(w) => w,
+        }
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stream().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream().flush()
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        let result = match &mut self.stream {
+            Stream::Bzip2(w) => w.try_finish(),
+            Stream::Gzip(w) => w.try_finish(),
+            Stream::Xz(w) => w.try_finish(),
+            Stream::Zstd(w) => w.do_finish(),
+        };
+        if let Err(e) = result {
+            *self.finished.lock().unwrap() = Some(e);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Symlink {
     NoFollow,
@@ -188,15 +651,16 @@ impl Config {
         }
 
         if !args.get_flag("QUIET") {
+            // log under the name of the tool we're running (and this library);
+            // binaries like `ffs-diff` log as `ffs_diff`
+            let name = gen_cli().get_name().replace('-', "_");
+            let level = if args.get_flag("DEBUG") {
+                "debug"
+            } else {
+                "warn"
+            };
             let filter_layer = EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_e| {
-                    if args.get_flag("DEBUG") {
-                        EnvFilter::new("pack=debug")
-                    } else {
-                        EnvFilter::new("pack=warn")
-                    }
-                })
-                .add_directive("ffs::config=warn".parse().unwrap());
+                .unwrap_or_else(|_e| EnvFilter::new(format!("{name}={level},nodelike={level}")));
             let fmt_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
             tracing_subscriber::registry()
                 .with(filter_layer)
@@ -221,6 +685,38 @@ impl Config {
             },
         };
 
+        // CSV/TSV
+        config.header = !args.get_flag("NOHEADER");
+        config.new_columns = match args.get_one::<String>("NEW_COLUMNS") {
+            None => NewColumns::Add,
+            Some(s) => match str::parse(s) {
+                Ok(new_columns) => new_columns,
+                Err(_) => {
+                    warn!("Invalid `--new-columns` policy '{s}', using 'add'.");
+                    NewColumns::Add
+                }
+            },
+        };
+        if let Some(s) = args.get_one::<String>("DELIMITER") {
+            config.delimiter = match s.as_str() {
+                "tab" | "\\t" => Some(b'\t'),
+                s if s.len() == 1 && s.is_ascii() => Some(s.as_bytes()[0]),
+                _ => {
+                    error!("The delimiter must be a single ASCII character, not '{s}'.");
+                    std::process::exit(ERROR_STATUS_CLI);
+                }
+            };
+        }
+
+        // INI/properties
+        config.global_section = args.get_one::<String>("GLOBAL_SECTION").cloned();
+        config.nest_keys = args.get_flag("NEST_KEYS");
+
+        // compression
+        config.compression = args
+            .get_one::<String>("COMPRESS")
+            .and_then(|s| s.parse().ok());
+
         (config, args)
     }
 
@@ -258,50 +754,123 @@ impl Config {
         s == "." || s == ".." || self.platform_ignored_file(s)
     }
 
+    /// Returns `true` for paths, relative to the directory being packed, that
+    /// `--exclude` or `--include` leave out.
+    ///
+    /// Directories are never left out for not being included, since they may
+    /// have included files in them.
+    pub fn excluded_path(&self, path: &Path, is_dir: bool) -> bool {
+        if let Some(exclude) = &self.exclude
+            && exclude.is_match(path)
+        {
+            return true;
+        }
+
+        match &self.include {
+            Some(include) => !is_dir && !include.is_match(path),
+            None => false,
+        }
+    }
+
+    /// The compression for `path`: `--compress`, if given, or else whatever
+    /// the extension says.
+    pub fn compression_for(&self, path: &Path) -> Option<Compression> {
+        self.compression.or_else(|| Compression::from_path(path))
+    }
+
+    /// Generate a reader for input, exiting if it can't be opened
+    ///
+    /// A return of `None` means to start from an empty named directory (see
+    /// `Config::try_input_reader`).
+    pub fn input_reader(&self) -> Option<Box<dyn std::io::Read>> {
+        self.try_input_reader().unwrap_or_else(|e| {
+            error!("{e}");
+            std::process::exit(ERROR_STATUS_FUSE);
+        })
+    }
+
     /// Generate a reader for input
     ///
     /// A return of `None` means to start from an empty named directory.
     /// When `--strict` is not set, a zero-byte file is treated as empty.
-    pub fn input_reader(&self) -> Option<Box<dyn std::io::Read>> {
-        match &self.input {
-            Input::Stdin => Some(Box::new(std::io::stdin())),
+    /// Compressed input is decompressed (see `Config::compression_for`).
+    pub fn try_input_reader(&self) -> std::io::Result<Option<Box<dyn std::io::Read>>> {
+        let (reader, compression): (Box<dyn std::io::Read>, _) = match &self.input {
+            Input::Stdin => (Box::new(std::io::stdin()), self.compression),
             Input::File(file) => {
                 if !self.strict
                     && let Ok(meta) = std::fs::metadata(file)
                     && meta.len() == 0
                 {
                     debug!("Empty file detected, treating as empty input");
-                    return None;
+                    return Ok(None);
                 }
                 let fmt = self.input_format;
-                let file = std::fs::File::open(file).unwrap_or_else(|e| {
-                    error!("Unable to open {} for {fmt} input: {e}", file.display());
-                    std::process::exit(ERROR_STATUS_FUSE);
-                });
-                Some(Box::new(file))
+                let reader = std::fs::File::open(file).map_err(|e| {
+                    std::io::Error::new(
+                        e.kind(),
+                        format!("Unable to open {} for {fmt} input: {e}", file.display()),
+                    )
+                })?;
+                (Box::new(reader), self.compression_for(file))
+            }
+            Input::Empty => return Ok(None),
+        };
+
+        match compression {
+            None => Ok(Some(reader)),
+            Some(compression) => {
+                debug!("decompressing {compression} input");
+                let reader = compression.decoder(reader).map_err(|e| {
+                    std::io::Error::new(
+                        e.kind(),
+                        format!("Unable to decompress {} as {compression}: {e}", self.input),
+                    )
+                })?;
+                Ok(Some(reader))
             }
-            Input::Empty => None,
         }
     }
 
     /// Generate a writer for output
     ///
-    /// A return of `None` means no output should be provided
-    pub fn output_writer(&self) -> Option<Box<dyn std::io::Write>> {
-        match &self.output {
+    /// A return of `None` means no output should be provided. Output to a
+    /// regular file is atomic: nothing changes until `OutputWriter::commit`.
+    /// Output is compressed as `Config::compression_for` says.
+    pub fn output_writer(&self) -> std::io::Result<Option<OutputWriter>> {
+        let (output, compression) = match &self.output {
             Output::Stdout => {
                 debug!("outputting on STDOUT");
-                Some(Box::new(std::io::stdout()))
+                (OutputWriter::Stdout, self.compression)
             }
             Output::File(path) => {
                 debug!("output {}", path.display());
-                Some(Box::new(File::create(path).unwrap()))
+                let output = match std::fs::metadata(path) {
+                    Ok(meta) if !meta.is_file() => {
+                        debug!("{} is a special file, writing directly", path.display());
+                        OutputWriter::Direct(File::create(path)?)
+                    }
+                    Ok(_) | Err(_) => OutputWriter::File(AtomicFile::create(path)?),
+                };
+                (output, self.compression_for(path))
             }
             Output::Quiet => {
                 debug!("no output path, skipping");
-                None
+                return Ok(None);
             }
-        }
+        };
+
+        Ok(Some(match compression {
+            None => output,
+            Some(compression) => {
+                debug!("compressing output with {compression}");
+                OutputWriter::Compressed {
+                    inner: Box::new(output),
+                    compression,
+                    finished: Arc::new(Mutex::new(None)),
+                }
+            }
+        }))
     }
 }
 
@@ -323,15 +892,32 @@ impl Default for Config {
             symlink: Symlink::NoFollow,
             max_depth: None,
             allow_symlink_escape: false,
+            exclude: None,
+            include: None,
+            respect_gitignore: false,
+            merge: false,
+            delete: false,
+            dry_run: false,
+            diff: None,
             munge: Munge::Rename,
             read_only: false,
             input: Input::Stdin,
             output: Output::Stdout,
             pretty: false,
+            preserve_format: false,
+            delimiter: None,
+            header: true,
+            new_columns: NewColumns::Add,
+            global_section: None,
+            nest_keys: false,
+            compression: None,
             timing: false,
             mount: None,
             cleanup_mount: false,
             strict: false,
+            autosave_interval: None,
+            watch_interval: None,
+            command: None,
         }
     }
 }
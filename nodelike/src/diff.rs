@@ -0,0 +1,146 @@
+//! Structural differences between two values, keyed by path.
+//!
+//! Paths are JSON Pointers (RFC 6901), e.g., `/dir/0` for the first element
+//! of the list `dir`; the root is the empty pointer.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::config::Config;
+use super::{Node, Nodelike, convert};
+
+/// One difference between an old value and a new one.
+#[derive(Debug)]
+pub enum Change<V> {
+    /// The new value has something at the path that the old one doesn't.
+    Add(String, V),
+    /// The old value has something at the path that the new one doesn't.
+    Remove(String, V),
+    /// The old and new values differ at the path: the old one, then the new.
+    Replace(String, V, V),
+}
+
+impl<V> Change<V> {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Add(path, _) | Change::Remove(path, _) | Change::Replace(path, _, _) => path,
+        }
+    }
+
+    /// Applies `f` to the values in the change.
+    pub fn map<U>(self, f: impl Fn(V) -> U) -> Change<U> {
+        match self {
+            Change::Add(path, new) => Change::Add(path, f(new)),
+            Change::Remove(path, old) => Change::Remove(path, f(old)),
+            Change::Replace(path, old, new) => Change::Replace(path, f(old), f(new)),
+        }
+    }
+}
+
+impl<V: Nodelike> Change<V> {
+    /// Converts the values in the change to another format, e.g., to JSON
+    /// for printing.
+    pub fn convert<U: Nodelike>(self, config: &Config) -> Change<U> {
+        self.map(|v| convert(v, config))
+    }
+}
+
+/// One line per change: `+` for additions, `-` for removals, and `~` for
+/// replacements.
+impl<V: std::fmt::Display> std::fmt::Display for Change<V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Change::Add(path, v) => write!(f, "+ {path}: {v}"),
+            Change::Remove(path, v) => write!(f, "- {path}: {v}"),
+            Change::Replace(path, old, new) => write!(f, "~ {path}: {old} -> {new}"),
+        }
+    }
+}
+
+/// Appends `name` to the JSON Pointer `path`, escaping as need be.
+pub fn pointer_push(path: &str, name: &str) -> String {
+    format!("{path}/{}", name.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Splits the JSON Pointer `pointer` into its (unescaped) tokens; `None` if it
+/// isn't a JSON Pointer.
+pub fn pointer_tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+
+    Some(
+        pointer
+            .strip_prefix('/')?
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Lists the changes that turn `old` into `new`.
+///
+/// Maps are compared field by field, in order of field name; lists are
+/// compared element by element, with elements added or removed at the end.
+/// Removals from a list come last-first, so each change's path is right
+/// after the changes before it are made (as in a JSON Patch).
+pub fn diff<V: Nodelike + Clone>(old: V, new: V, config: &Config) -> Vec<Change<V>> {
+    let mut changes = Vec::new();
+    walk(String::new(), old, new, config, &mut changes);
+    changes
+}
+
+fn walk<V: Nodelike + Clone>(
+    path: String,
+    old: V,
+    new: V,
+    config: &Config,
+    changes: &mut Vec<Change<V>>,
+) {
+    match (old.clone().node(config), new.clone().node(config)) {
+        (Node::String(t1, s1), Node::String(t2, s2)) if t1 == t2 && s1 == s2 => (),
+        (Node::Bytes(b1), Node::Bytes(b2)) if b1 == b2 => (),
+        (Node::List(mut olds), Node::List(mut news)) => {
+            let common = olds.len().min(news.len());
+            let removed = olds.split_off(common);
+            let added = news.split_off(common);
+
+            for (i, (old, new)) in olds.into_iter().zip(news).enumerate() {
+                walk(
+                    pointer_push(&path, &i.to_string()),
+                    old,
+                    new,
+                    config,
+                    changes,
+                );
+            }
+            for (i, new) in added.into_iter().enumerate() {
+                let child = pointer_push(&path, &(common + i).to_string());
+                changes.push(Change::Add(child, new));
+            }
+            for (i, old) in removed.into_iter().enumerate().rev() {
+                let child = pointer_push(&path, &(common + i).to_string());
+                changes.push(Change::Remove(child, old));
+            }
+        }
+        (Node::Map(olds), Node::Map(news)) => {
+            let mut olds = olds.into_iter().collect::<BTreeMap<_, _>>();
+            let mut news = news.into_iter().collect::<BTreeMap<_, _>>();
+            let names = olds
+                .keys()
+                .chain(news.keys())
+                .cloned()
+                .collect::<BTreeSet<_>>();
+
+            for name in names {
+                let child = pointer_push(&path, &name);
+                match (olds.remove(&name), news.remove(&name)) {
+                    (Some(old), Some(new)) => walk(child, old, new, config, changes),
+                    (None, Some(new)) => changes.push(Change::Add(child, new)),
+                    (Some(old), None) => changes.push(Change::Remove(child, old)),
+                    (None, None) => unreachable!("field '{name}' is in neither map"),
+                }
+            }
+        }
+        _ => changes.push(Change::Replace(path, old, new)),
+    }
+}
@@ -1,4 +1,7 @@
 pub mod config;
+pub mod diff;
 pub mod nodelike;
+pub mod patch;
+mod preserve;
 
 pub use nodelike::*;
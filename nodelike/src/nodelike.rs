@@ -3,7 +3,9 @@ use std::str::FromStr;
 
 use tracing::debug;
 
-use super::config::Config;
+use super::config::{Compression, Config, ERROR_STATUS_FUSE, ERROR_STATUS_PARSE};
+use super::diff::diff;
+use super::preserve;
 
 use ::toml as serde_toml;
 
@@ -29,8 +31,19 @@ macro_rules! time_ns {
 /// When extending, don't forget to also extend `cli::POSSIBLE_FORMATS`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Format {
+    Cbor,
+    Csv,
+    Dotenv,
+    Ini,
     Json,
+    Json5,
+    MsgPack,
+    Ndjson,
+    Plist,
+    Properties,
     Toml,
+    Tsv,
+    Xml,
     Yaml,
 }
 
@@ -53,8 +66,19 @@ impl std::fmt::Display for Format {
             f,
             "{}",
             match self {
+                Format::Cbor => "cbor",
+                Format::Csv => "csv",
+                Format::Dotenv => "dotenv",
+                Format::Ini => "ini",
                 Format::Json => "json",
+                Format::Json5 => "json5",
+                Format::MsgPack => "msgpack",
+                Format::Ndjson => "ndjson",
+                Format::Plist => "plist",
+                Format::Properties => "properties",
                 Format::Toml => "toml",
+                Format::Tsv => "tsv",
+                Format::Xml => "xml",
                 Format::Yaml => "yaml",
             }
         )
@@ -86,16 +110,156 @@ pub enum ParseFormatError {
     NoFormatProvided,
 }
 
+/// A position in a document; lines and columns both start at 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// Computes the position of a byte offset into `text`.
+    pub fn from_offset(text: &str, offset: usize) -> Self {
+        let prefix = &text[..text.floor_char_boundary(offset)];
+        let line = prefix.matches('\n').count() + 1;
+        let column = prefix.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        Position { line, column }
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Errors from loading (`Nodelike::from_reader`) and saving
+/// (`Nodelike::to_writer`) documents.
+#[derive(Debug)]
+pub enum Error {
+    /// The input isn't a well formed document in the given format.
+    Parse {
+        format: Format,
+        position: Option<Position>,
+        message: String,
+    },
+    /// The value can't be represented in the given format (e.g., TOML
+    /// documents must be tables).
+    Serialize { format: Format, message: String },
+    /// Reading or writing failed.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Error::Parse {
+                format,
+                position: Some(position),
+                message,
+            } => write!(
+                f,
+                "{} parse error at {position}: {message}",
+                format.to_string().to_uppercase()
+            ),
+            Error::Parse {
+                format,
+                position: None,
+                message,
+            } => write!(
+                f,
+                "{} parse error: {message}",
+                format.to_string().to_uppercase()
+            ),
+            Error::Serialize { format, message } => write!(
+                f,
+                "couldn't save as {}: {message}",
+                format.to_string().to_uppercase()
+            ),
+            Error::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error {
+    /// The exit status for a tool that fails with this error: bad input gets
+    /// its own status, so that it can be told apart from other failures.
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            Error::Parse { .. } => ERROR_STATUS_PARSE,
+            Error::Serialize { .. } | Error::Io(_) => ERROR_STATUS_FUSE,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse { .. } | Error::Serialize { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Some libraries only put the position in their error messages; since we
+/// track it separately, we drop it from the message.
+fn strip_position(message: String, suffix: &str) -> String {
+    match message.strip_suffix(suffix) {
+        Some(message) => message.to_string(),
+        None => message,
+    }
+}
+
+/// Sorts `items` by where their names are in `order`, e.g., the order they
+/// were read in; items whose names aren't there come last, as they were.
+fn in_order<T>(mut items: Vec<(String, T)>, order: &[String]) -> Vec<(String, T)> {
+    let position = order
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.as_str(), i))
+        .collect::<std::collections::HashMap<_, _>>();
+    items.sort_by_key(|(name, _)| position.get(name.as_str()).copied().unwrap_or(usize::MAX));
+    items
+}
+
 impl FromStr for Format {
     type Err = ParseFormatError;
 
     fn from_str(s: &str) -> Result<Self, ParseFormatError> {
         let s = s.trim().to_lowercase();
 
-        if s == "json" {
+        if s == "cbor" {
+            Ok(Format::Cbor)
+        } else if s == "csv" {
+            Ok(Format::Csv)
+        } else if s == "dotenv" || s == "env" {
+            Ok(Format::Dotenv)
+        } else if s == "ini" {
+            Ok(Format::Ini)
+        } else if s == "json" {
             Ok(Format::Json)
+        } else if s == "json5" || s == "jsonc" {
+            Ok(Format::Json5)
+        } else if s == "msgpack" || s == "mpk" {
+            Ok(Format::MsgPack)
+        } else if s == "ndjson" || s == "jsonl" {
+            Ok(Format::Ndjson)
+        } else if s == "plist" {
+            Ok(Format::Plist)
+        } else if s == "properties" {
+            Ok(Format::Properties)
         } else if s == "toml" {
             Ok(Format::Toml)
+        } else if s == "tsv" {
+            Ok(Format::Tsv)
+        } else if s == "xml" {
+            Ok(Format::Xml)
         } else if s == "yaml" || s == "yml" {
             Ok(Format::Yaml)
         } else {
@@ -133,10 +297,66 @@ impl FromStr for Typ {
 }
 
 impl Format {
+    /// The format of the file at `path`, going by its name: usually, that's
+    /// its extension, but dotenv files tend to be called `.env` (or
+    /// `.env.local`, etc.). Compression extensions are skipped, so
+    /// `data.json.gz` is JSON.
+    pub fn from_path(path: &std::path::Path) -> Result<Format, ParseFormatError> {
+        let path = &Compression::strip(path);
+        let name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        if name == ".env" {
+            return Ok(Format::Dotenv);
+        }
+
+        match path.extension().and_then(|s| s.to_str()) {
+            None => Err(ParseFormatError::NoFormatProvided),
+            Some(ext) => ext.parse::<Format>().or_else(|e| {
+                if name.starts_with(".env.") {
+                    Ok(Format::Dotenv)
+                } else {
+                    Err(e)
+                }
+            }),
+        }
+    }
+
     pub fn can_be_pretty(&self) -> bool {
         match self {
-            Format::Json | Format::Toml => true,
-            Format::Yaml => false,
+            Format::Json | Format::Toml | Format::Xml => true,
+            Format::Cbor
+            | Format::Csv
+            | Format::Dotenv
+            | Format::Ini
+            | Format::Json5
+            | Format::MsgPack
+            | Format::Ndjson
+            | Format::Plist
+            | Format::Properties
+            | Format::Tsv
+            | Format::Yaml => false,
+        }
+    }
+
+    /// Can saving keep the input's comments and formatting (see
+    /// `Nodelike::to_string_preserving`)?
+    pub fn can_preserve(&self) -> bool {
+        match self {
+            Format::Toml | Format::Yaml => true,
+            Format::Cbor
+            | Format::Csv
+            | Format::Dotenv
+            | Format::Ini
+            | Format::Json
+            | Format::Json5
+            | Format::MsgPack
+            | Format::Ndjson
+            | Format::Plist
+            | Format::Properties
+            | Format::Tsv
+            | Format::Xml => false,
         }
     }
 }
@@ -218,22 +438,231 @@ where
     where
         Self: Sized;
 
+    /// Names values that occur more than once in a document (e.g., YAML
+    /// anchors and their aliases). Every occurrence has the same name and
+    /// should be the same inode, i.e., a hard link.
+    fn anchor(&self) -> Option<String> {
+        None
+    }
+
+    /// Marks `v` as shared: every value anchored at `name` is the same value.
+    /// Formats that can't express sharing just copy the value.
+    fn from_anchored(_name: &str, v: Self, _config: &Config) -> Self
+    where
+        Self: Sized,
+    {
+        v
+    }
+
+    /// Describes how a document was written, where that's more than its
+    /// directories hold. Named directories don't keep their files in order,
+    /// so, e.g., a CSV document notes the order of its columns. Only
+    /// documents that were read (see `from_reader`) have layouts, and only at
+    /// the root; `with_layout` puts things back the way they were.
+    ///
+    /// Layouts are JSON, so they can be kept as text (e.g., in the
+    /// `user.layout` extended attribute).
+    fn layout(&self) -> Option<String> {
+        None
+    }
+
+    /// Lays out `self` as `layout` (see `layout`) says, as far as it still
+    /// applies. Other formats' layouts are ignored.
+    fn with_layout(self, _layout: &str) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+
+    /// Is this a stream of several documents (e.g., YAML's `---`)? Its `node`
+    /// is the list of documents.
+    fn is_documents(&self) -> bool {
+        false
+    }
+
+    /// Makes a stream of documents. Formats without streams just make a list.
+    fn from_documents(docs: Vec<Self>, config: &Config) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_list_dir(docs, config)
+    }
+
+    /// Converts a symbolic link. When the link points into the document,
+    /// `reference` is a URI fragment holding the target's JSON Pointer (RFC
+    /// 6901), e.g., `#/a/0`, and `anchor` names the target (see
+    /// `from_anchored`); otherwise, `reference` is just the link's target.
+    ///
+    /// By default, links are JSON References, i.e., `{"$ref": reference}`.
+    fn from_symlink(reference: String, _anchor: Option<&str>, config: &Config) -> Self
+    where
+        Self: Sized,
+    {
+        let mut fields = BTreeMap::new();
+        fields.insert(
+            "$ref".into(),
+            Self::from_string(Typ::String, reference, config),
+        );
+        Self::from_named_dir(fields, config)
+    }
+
     /// Loading
-    fn from_reader(reader: Box<dyn std::io::Read>) -> Self
+    fn from_reader(reader: Box<dyn std::io::Read>, config: &Config) -> Result<Self, Error>
     where
         Self: Sized;
 
-    /// Saving, with optional pretty printing
-    fn to_writer(&self, writer: Box<dyn std::io::Write>, pretty: bool);
+    /// Saving, with optional pretty printing (`config.pretty`)
+    fn to_writer(&self, writer: Box<dyn std::io::Write>, config: &Config) -> Result<(), Error>;
+
+    /// Saving by editing `original`, the text of a document in this format
+    /// (e.g., the input), so that only what differs from `self` is
+    /// re-rendered: comments and formatting everywhere else are kept as is.
+    ///
+    /// Returns `None` when that's not possible---by default, it never is---in
+    /// which case callers should fall back on `to_writer`.
+    fn to_string_preserving(&self, _original: &str, _config: &Config) -> Option<String> {
+        None
+    }
 }
 
-impl Format {
-    pub fn from_reader(&self, reader: Box<dyn std::io::Read>) -> Box<dyn Nodelike> {
-        match self {
-            Format::Json => Box::new(json::Value::from_reader(reader)),
-            Format::Toml => Box::new(toml::Value::from_reader(reader)),
-            Format::Yaml => Box::new(yaml::Value::from_reader(reader)),
+/// Converts `v` to another format, `U`, one `Node` at a time. Shared values
+/// stay shared (see `Nodelike::anchor`), as far as `U` can express it, and
+/// the document keeps its layout (see `Nodelike::layout`) if `U` has the same
+/// kind.
+pub fn convert<V: Nodelike, U: Nodelike>(v: V, config: &Config) -> U {
+    let anchor = v.anchor();
+    let layout = v.layout();
+    let documents = v.is_documents();
+    let u = match v.node(config) {
+        Node::String(t, mut s) => {
+            if config.add_newlines && s.ends_with('\n') {
+                s.truncate(s.len() - 1);
+            }
+            U::from_string(t, s, config)
+        }
+        Node::Bytes(b) => U::from_bytes(b, config),
+        Node::List(vs) => {
+            let vs = vs.into_iter().map(|v| convert(v, config)).collect();
+            if documents {
+                U::from_documents(vs, config)
+            } else {
+                U::from_list_dir(vs, config)
+            }
         }
+        Node::Map(fvs) => U::from_named_dir(
+            fvs.into_iter()
+                .map(|(f, v)| (f, convert(v, config)))
+                .collect(),
+            config,
+        ),
+    };
+
+    let u = match anchor {
+        Some(name) => U::from_anchored(&name, u, config),
+        None => u,
+    };
+    match layout {
+        Some(layout) => u.with_layout(&layout),
+        None => u,
+    }
+}
+
+/// Reads a document in `format` from `reader`, converting it to `V` (see
+/// `convert`). Compressed documents should already be decompressed (see
+/// `Config::compression_for`).
+pub fn load<V: Nodelike>(
+    reader: Box<dyn std::io::Read>,
+    format: Format,
+    config: &Config,
+) -> Result<V, Error> {
+    Ok(match format {
+        Format::Cbor => convert(cbor::Value::from_reader(reader, config)?, config),
+        Format::Csv => convert(csv::Value::from_reader(reader, config)?, config),
+        Format::Dotenv => convert(dotenv::Value::from_reader(reader, config)?, config),
+        Format::Ini => convert(ini::Value::from_reader(reader, config)?, config),
+        Format::Json => convert(json::Value::from_reader(reader, config)?, config),
+        Format::Json5 => convert(json5::Value::from_reader(reader, config)?, config),
+        Format::MsgPack => convert(msgpack::Value::from_reader(reader, config)?, config),
+        Format::Ndjson => convert(ndjson::Value::from_reader(reader, config)?, config),
+        Format::Plist => convert(plist::Value::from_reader(reader, config)?, config),
+        Format::Properties => convert(properties::Value::from_reader(reader, config)?, config),
+        Format::Toml => convert(toml::Value::from_reader(reader, config)?, config),
+        Format::Tsv => convert(tsv::Value::from_reader(reader, config)?, config),
+        Format::Xml => convert(xml::Value::from_reader(reader, config)?, config),
+        Format::Yaml => convert(yaml::Value::from_reader(reader, config)?, config),
+    })
+}
+
+/// Tags `layout` with the kind of document it lays out (see
+/// `Nodelike::layout`).
+fn tag_layout(kind: &str, layout: json::Value) -> String {
+    serde_json::json!({ kind: layout }).to_string()
+}
+
+/// The layout in `layout` for the kind of document `kind`, if that's what it
+/// lays out (see `tag_layout`).
+fn tagged_layout(kind: &str, layout: &str) -> Option<json::Value> {
+    match serde_json::from_str(layout) {
+        Ok(json::Value::Object(mut kinds)) => kinds.remove(kind),
+        _ => None,
+    }
+}
+
+/// The strings in the JSON list `v`, if that's all it holds.
+fn strings(v: &json::Value) -> Option<Vec<String>> {
+    v.as_array()?
+        .iter()
+        .map(|s| s.as_str().map(String::from))
+        .collect()
+}
+
+/// Suggests an anchor name for a value named `name` in a directory named
+/// `parent`. Only alphanumerics, `_`, and `-` are kept; list elements, whose
+/// names are just numbers, are named after their list.
+pub fn anchor_base(name: &str, parent: &str) -> String {
+    let sanitize = |name: &str| {
+        name.chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+            .collect::<String>()
+    };
+
+    let base = sanitize(name);
+    if !base.is_empty() && !base.starts_with(|c: char| c.is_ascii_digit()) {
+        return base;
+    }
+
+    let parent = sanitize(parent);
+    match (parent.is_empty(), base.is_empty()) {
+        (true, true) => "link".into(),
+        (true, false) => format!("link{base}"),
+        (false, true) => parent,
+        (false, false) => format!("{parent}_{base}"),
+    }
+}
+
+impl Format {
+    pub fn from_reader(
+        &self,
+        reader: Box<dyn std::io::Read>,
+        config: &Config,
+    ) -> Result<Box<dyn Nodelike>, Error> {
+        Ok(match self {
+            Format::Cbor => Box::new(cbor::Value::from_reader(reader, config)?),
+            Format::Csv => Box::new(csv::Value::from_reader(reader, config)?),
+            Format::Dotenv => Box::new(dotenv::Value::from_reader(reader, config)?),
+            Format::Ini => Box::new(ini::Value::from_reader(reader, config)?),
+            Format::Json => Box::new(json::Value::from_reader(reader, config)?),
+            Format::Json5 => Box::new(json5::Value::from_reader(reader, config)?),
+            Format::MsgPack => Box::new(msgpack::Value::from_reader(reader, config)?),
+            Format::Ndjson => Box::new(ndjson::Value::from_reader(reader, config)?),
+            Format::Plist => Box::new(plist::Value::from_reader(reader, config)?),
+            Format::Properties => Box::new(properties::Value::from_reader(reader, config)?),
+            Format::Toml => Box::new(toml::Value::from_reader(reader, config)?),
+            Format::Tsv => Box::new(tsv::Value::from_reader(reader, config)?),
+            Format::Xml => Box::new(xml::Value::from_reader(reader, config)?),
+            Format::Yaml => Box::new(yaml::Value::from_reader(reader, config)?),
+        })
     }
 }
 
@@ -268,7 +697,18 @@ pub mod json {
             match self {
                 Value::Null => Node::String(Typ::Null, "".into()), // always empty
                 Value::Bool(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
-                Value::Number(n) => Node::String(Typ::Float, format!("{n}{nl}")),
+                Value::Number(n) => {
+                    // numbers keep their original text (serde_json's
+                    // `arbitrary_precision`), so integers too big for 64 bits
+                    // are still integers
+                    let n = n.to_string();
+                    let typ = if n.contains(['.', 'e', 'E']) {
+                        Typ::Float
+                    } else {
+                        Typ::Integer
+                    };
+                    Node::String(typ, format!("{n}{nl}"))
+                }
                 Value::String(s) => {
                     if config.try_decode_base64
                         && let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(&s)
@@ -322,7 +762,7 @@ pub mod json {
                     if let Ok(n) = serde_json::Number::from_str(&contents) {
                         Value::Number(n)
                     } else {
-                        debug!("string '{contents}' tagged as float");
+                        debug!("string '{contents}' tagged as integer");
                         Value::String(contents)
                     }
                 }
@@ -353,28 +793,80 @@ pub mod json {
             Value::Object(files.into_iter().collect())
         }
 
-        fn to_writer(&self, writer: Box<dyn std::io::Write>, pretty: bool) {
-            if pretty {
-                serde_json::to_writer_pretty(writer, self).unwrap();
+        fn to_writer(&self, writer: Box<dyn std::io::Write>, config: &Config) -> Result<(), Error> {
+            let json_error = |e| json_error(Format::Json, e);
+            if config.pretty {
+                serde_json::to_writer_pretty(writer, self).map_err(json_error)
             } else {
-                serde_json::to_writer(writer, self).unwrap();
+                serde_json::to_writer(writer, self).map_err(json_error)
             }
         }
-        fn from_reader(reader: std::boxed::Box<dyn std::io::Read>) -> Self {
-            serde_json::from_reader(reader).expect("JSON")
+
+        fn from_reader(
+            reader: std::boxed::Box<dyn std::io::Read>,
+            _config: &Config,
+        ) -> Result<Self, Error> {
+            serde_json::from_reader(reader).map_err(|e| json_error(Format::Json, e))
+        }
+    }
+
+    /// The text of a value that isn't a directory, for formats where
+    /// everything is text (e.g., CSV cells).
+    pub(super) fn text(v: &Value) -> Option<String> {
+        match v {
+            Value::Null => Some(String::new()),
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::String(s) => Some(s.clone()),
+            Value::Array(_) | Value::Object(_) => None,
+        }
+    }
+
+    /// Types text as in `Typ::Auto`, for formats where everything is text:
+    /// numbers `text` wouldn't give back as written (e.g., `1e3`, which
+    /// serde_json writes as `1e+3`) stay strings.
+    pub(super) fn from_text(s: String, config: &Config) -> Value {
+        match Value::from_string(Typ::Auto, s.clone(), config) {
+            Value::Number(n) if n.to_string() != s => Value::String(s),
+            v => v,
+        }
+    }
+
+    pub(super) fn json_error(format: Format, e: serde_json::Error) -> Error {
+        use serde_json::error::Category;
+
+        match e.classify() {
+            Category::Io => Error::Io(e.into()),
+            Category::Syntax | Category::Eof => {
+                let suffix = format!(" at line {} column {}", e.line(), e.column());
+                Error::Parse {
+                    format,
+                    position: Some(Position {
+                        line: e.line(),
+                        column: e.column(),
+                    }),
+                    message: strip_position(e.to_string(), &suffix),
+                }
+            }
+            Category::Data => Error::Serialize {
+                format,
+                message: e.to_string(),
+            },
         }
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
-/// TOML Nodelike implementation
-pub mod toml {
+/// NDJSON (a.k.a. JSON Lines) Nodelike implementation
+///
+/// Each line holds a JSON value, and the document is the list of them; below
+/// the root, everything is just JSON.
+pub mod ndjson {
     use super::*;
-    use base64::Engine;
-    use serde_toml::Value as Toml;
+    use std::io::{BufRead, Write};
 
-    #[derive(Clone, Debug)]
-    pub struct Value(serde_toml::Value);
+    #[derive(Clone, Debug, Default)]
+    pub struct Value(json::Value);
 
     impl std::fmt::Display for Value {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
@@ -382,374 +874,4543 @@ pub mod toml {
         }
     }
 
-    impl Default for Value {
-        fn default() -> Self {
-            Value(Toml::String("".into()))
-        }
-    }
-
-    fn toml_size(v: &Toml) -> usize {
-        match v {
-            Toml::Boolean(_)
-            | Toml::Datetime(_)
-            | Toml::Float(_)
-            | Toml::Integer(_)
-            | Toml::String(_) => 1,
-            Toml::Array(vs) => vs.iter().map(toml_size).sum::<usize>() + 1,
-            Toml::Table(fvs) => fvs.iter().map(|(_, v)| toml_size(v)).sum::<usize>() + 1,
-        }
-    }
-
     impl Nodelike for Value {
         fn is_dir(&self) -> bool {
-            match self.0 {
-                Toml::Table(_) | Toml::Array(_) => true,
-                Toml::String(_)
-                | Toml::Integer(_)
-                | Toml::Float(_)
-                | Toml::Boolean(_)
-                | Toml::Datetime(_) => false,
-            }
+            self.0.is_dir()
         }
 
         fn size(&self) -> usize {
-            toml_size(&self.0)
+            self.0.size()
         }
 
         fn node(self, config: &Config) -> Node<Self> {
-            let nl = if config.add_newlines { "\n" } else { "" };
-
-            match self.0 {
-                Toml::Boolean(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
-                Toml::Datetime(s) => Node::String(Typ::Datetime, s.to_string()),
-                Toml::Float(n) => Node::String(Typ::Float, format!("{n}{nl}")),
-                Toml::Integer(n) => Node::String(Typ::Integer, format!("{n}{nl}")),
-                Toml::String(s) => {
-                    if config.try_decode_base64
-                        && let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(&s)
-                    {
-                        return Node::Bytes(bytes);
-                    }
-
-                    Node::String(Typ::String, if s.ends_with('\n') { s } else { s + nl })
-                }
-                Toml::Array(vs) => Node::List(vs.into_iter().map(Value).collect()),
-                Toml::Table(fvs) => {
-                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v))).collect())
-                }
+            match self.0.node(config) {
+                Node::String(t, s) => Node::String(t, s),
+                Node::Bytes(b) => Node::Bytes(b),
+                Node::List(vs) => Node::List(vs.into_iter().map(Value).collect()),
+                Node::Map(fvs) => Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v))).collect()),
             }
         }
 
-        fn from_string(typ: Typ, contents: String, _config: &Config) -> Self {
-            let v = match typ {
-                Typ::Auto => {
-                    if contents == "true" {
-                        Toml::Boolean(true)
-                    } else if contents == "false" {
-                        Toml::Boolean(false)
-                    } else if let Ok(n) = i64::from_str(&contents) {
-                        Toml::Integer(n)
-                    } else if let Ok(n) = f64::from_str(&contents) {
-                        Toml::Float(n)
-                    } else if let Ok(datetime) = str::parse(&contents) {
-                        Toml::Datetime(datetime)
-                    } else {
-                        Toml::String(contents)
-                    }
-                }
-                Typ::Boolean => {
-                    if contents == "true" {
-                        Toml::Boolean(true)
-                    } else if contents == "false" {
-                        Toml::Boolean(false)
-                    } else {
-                        debug!("string '{contents}' tagged as boolean");
-                        Toml::String(contents)
-                    }
-                }
-                Typ::Bytes => panic!("from_string called at typ::bytes"),
-                Typ::Datetime => match str::parse(&contents) {
-                    Ok(datetime) => Toml::Datetime(datetime),
-                    Err(e) => {
-                        debug!("string '{contents}' tagged as datetime, didn't parse: {e}");
-                        Toml::String(contents)
-                    }
-                },
-                Typ::Float => {
-                    if let Ok(n) = f64::from_str(&contents) {
-                        Toml::Float(n)
-                    } else {
-                        debug!("string '{contents}' tagged as float");
-                        Toml::String(contents)
-                    }
-                }
-                Typ::Integer => {
-                    if let Ok(n) = i64::from_str(&contents) {
-                        Toml::Integer(n)
-                    } else {
-                        debug!("string '{contents}' tagged as float");
-                        Toml::String(contents)
-                    }
-                }
-                Typ::Null => {
-                    if contents.is_empty() {
-                        Toml::String(contents)
-                    } else {
-                        debug!("string '{contents}' tagged as null");
-                        Toml::String(contents)
-                    }
-                }
-                Typ::String => Toml::String(contents),
-            };
-
-            Value(v)
+        fn from_string(typ: Typ, contents: String, config: &Config) -> Self {
+            Value(json::Value::from_string(typ, contents, config))
         }
 
-        fn from_bytes<T>(contents: T, _config: &Config) -> Self
+        fn from_bytes<T>(contents: T, config: &Config) -> Self
         where
             T: AsRef<[u8]>,
         {
-            Value(Toml::String(
-                base64::engine::general_purpose::STANDARD.encode(contents),
-            ))
+            Value(json::Value::from_bytes(contents, config))
         }
 
-        fn from_list_dir(files: Vec<Self>, _config: &Config) -> Self {
-            Value(Toml::Array(files.into_iter().map(|v| v.0).collect()))
+        fn from_list_dir(files: Vec<Self>, config: &Config) -> Self {
+            Value(json::Value::from_list_dir(
+                files.into_iter().map(|v| v.0).collect(),
+                config,
+            ))
         }
 
-        fn from_named_dir(files: BTreeMap<String, Self>, _config: &Config) -> Self {
-            Value(Toml::Table(
+        fn from_named_dir(files: BTreeMap<String, Self>, config: &Config) -> Self {
+            Value(json::Value::from_named_dir(
                 files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+                config,
             ))
         }
 
-        fn from_reader(mut reader: Box<dyn std::io::Read>) -> Self {
-            let mut text = String::new();
-            let _len = reader.read_to_string(&mut text).unwrap();
-            Value(serde_toml::from_str(&text).expect("TOML"))
+        /// Blank lines are skipped.
+        fn from_reader(reader: Box<dyn std::io::Read>, _config: &Config) -> Result<Self, Error> {
+            let mut vs = Vec::new();
+            for (i, line) in std::io::BufReader::new(reader).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let v = serde_json::from_str(&line).map_err(|e| {
+                    match json::json_error(Format::Ndjson, e) {
+                        Error::Parse {
+                            format,
+                            position,
+                            message,
+                        } => Error::Parse {
+                            format,
+                            position: position.map(|p| Position { line: i + 1, ..p }),
+                            message,
+                        },
+                        e => e,
+                    }
+                })?;
+                vs.push(v);
+            }
+            Ok(Value(json::Value::Array(vs)))
         }
 
-        fn to_writer(&self, mut writer: Box<dyn std::io::Write>, pretty: bool) {
-            let text = if pretty {
-                serde_toml::to_string_pretty(&self.0).unwrap()
+        /// Lists are written one element per line; anything else is a single
+        /// line. There's no pretty printing, since values can't span lines.
+        fn to_writer(
+            &self,
+            writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            let lines = match &self.0 {
+                json::Value::Array(vs) => vs.as_slice(),
+                v => std::slice::from_ref(v),
+            };
+
+            let mut writer = std::io::BufWriter::new(writer);
+            for v in lines {
+                serde_json::to_writer(&mut writer, v)
+                    .map_err(|e| json::json_error(Format::Ndjson, e))?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// CSV and TSV Nodelike implementations
+///
+/// A document is a list of rows. When there's a header row (`config.header`),
+/// each row is a named directory keyed by column; otherwise, rows are lists.
+/// Cells are typed as in `Typ::Auto`, so `42` is an integer and an empty cell
+/// is null, but they're saved just as they were read (see `json::from_text`).
+/// Below the root, everything is just JSON, but only flat rows can be
+/// saved.
+pub mod csv {
+    use super::*;
+    use crate::config::NewColumns;
+    use std::sync::Arc;
+
+    /// A table whose cells are separated by `DELIMITER`, unless
+    /// `config.delimiter` says otherwise, with the columns of its header in
+    /// order, if we read one.
+    #[derive(Clone, Debug, Default)]
+    pub struct Table<const DELIMITER: u8>(json::Value, Option<Arc<Vec<String>>>);
+
+    pub type Value = Table<b','>;
+
+    impl<const DELIMITER: u8> Table<DELIMITER> {
+        fn format() -> Format {
+            if DELIMITER == b'\t' {
+                Format::Tsv
+            } else {
+                Format::Csv
+            }
+        }
+
+        fn delimiter(config: &Config) -> u8 {
+            config.delimiter.unwrap_or(DELIMITER)
+        }
+    }
+
+    impl<const DELIMITER: u8> std::fmt::Display for Table<DELIMITER> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.0.fmt(f)
+        }
+    }
+
+    fn csv_error(format: Format, text: &str, e: ::csv::Error) -> Error {
+        let position = e
+            .position()
+            .map(|p| Position::from_offset(text, p.byte() as usize));
+        let message = match e.kind() {
+            ::csv::ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => format!("the header has {expected_len} columns, but this row has {len}"),
+            ::csv::ErrorKind::Utf8 { err, .. } => err.to_string(),
+            _ => e.to_string(),
+        };
+        match e.into_kind() {
+            ::csv::ErrorKind::Io(e) => Error::Io(e),
+            _ => Error::Parse {
+                format,
+                position,
+                message,
+            },
+        }
+    }
+
+    /// The header for `rows`: the columns we read, `read`, that are still
+    /// around, in order, followed by any new ones (see `config.new_columns`).
+    /// If we didn't read a header, the first row's columns come first.
+    fn columns(
+        rows: &[json::Value],
+        read: &[String],
+        format: Format,
+        config: &Config,
+    ) -> Result<Vec<String>, Error> {
+        let mut columns = read
+            .iter()
+            .filter(|column| rows.iter().any(|row| row.get(column.as_str()).is_some()))
+            .cloned()
+            .collect::<Vec<_>>();
+        if columns.is_empty()
+            && let Some(json::Value::Object(fields)) = rows.first()
+        {
+            columns = fields.keys().cloned().collect();
+        }
+
+        for (i, row) in rows.iter().enumerate() {
+            let json::Value::Object(fields) = row else {
+                return Err(Error::Serialize {
+                    format,
+                    message: format!("row {i} isn't a named directory, but other rows are"),
+                });
+            };
+
+            for name in fields.keys() {
+                if columns.contains(name) {
+                    continue;
+                }
+
+                match config.new_columns {
+                    NewColumns::Add => columns.push(name.clone()),
+                    NewColumns::Error => {
+                        return Err(Error::Serialize {
+                            format,
+                            message: format!(
+                                "row {i} has a column that isn't in the header, '{name}' (see `--new-columns`)"
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(columns)
+    }
+
+    impl<const DELIMITER: u8> Nodelike for Table<DELIMITER> {
+        fn is_dir(&self) -> bool {
+            self.0.is_dir()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            match self.0.node(config) {
+                Node::String(t, s) => Node::String(t, s),
+                Node::Bytes(b) => Node::Bytes(b),
+                Node::List(vs) => Node::List(vs.into_iter().map(|v| Table(v, None)).collect()),
+                Node::Map(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Table(v, None))).collect())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, config: &Config) -> Self {
+            match typ {
+                Typ::Auto => Table(json::from_text(contents, config), None),
+                typ => Table(json::Value::from_string(typ, contents, config), None),
+            }
+        }
+
+        fn from_bytes<T>(contents: T, config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Table(json::Value::from_bytes(contents, config), None)
+        }
+
+        fn from_list_dir(files: Vec<Self>, config: &Config) -> Self {
+            Table(
+                json::Value::from_list_dir(files.into_iter().map(|v| v.0).collect(), config),
+                None,
+            )
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, config: &Config) -> Self {
+            Table(
+                json::Value::from_named_dir(
+                    files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+                    config,
+                ),
+                None,
+            )
+        }
+
+        fn layout(&self) -> Option<String> {
+            let columns = self.1.as_ref()?;
+            Some(tag_layout("columns", columns.as_slice().into()))
+        }
+
+        fn with_layout(self, layout: &str) -> Self {
+            match tagged_layout("columns", layout).as_ref().and_then(strings) {
+                Some(columns) => Table(self.0, Some(Arc::new(columns))),
+                None => self,
+            }
+        }
+
+        /// With a header, every row must have as many cells as the header
+        /// does; without one, rows can be ragged.
+        fn from_reader(mut reader: Box<dyn std::io::Read>, config: &Config) -> Result<Self, Error> {
+            let format = Self::format();
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+
+            let mut reader = ::csv::ReaderBuilder::new()
+                .delimiter(Self::delimiter(config))
+                .has_headers(config.header)
+                .flexible(!config.header)
+                .from_reader(text.as_bytes());
+
+            let columns = if config.header {
+                let header = reader.headers().map_err(|e| csv_error(format, &text, e))?;
+                let columns = header.iter().map(String::from).collect::<Vec<_>>();
+                for (i, column) in columns.iter().enumerate() {
+                    if columns[..i].contains(column) {
+                        return Err(Error::Parse {
+                            format,
+                            position: Some(Position { line: 1, column: 1 }),
+                            message: format!("the header has two columns named '{column}'"),
+                        });
+                    }
+                }
+                Some(columns)
+            } else {
+                None
+            };
+
+            let value = |s: &str| json::from_text(s.to_string(), config);
+            let mut rows = Vec::new();
+            for record in reader.records() {
+                let record = record.map_err(|e| csv_error(format, &text, e))?;
+                rows.push(match &columns {
+                    Some(columns) => json::Value::Object(
+                        columns
+                            .iter()
+                            .cloned()
+                            .zip(record.iter().map(value))
+                            .collect(),
+                    ),
+                    None => json::Value::Array(record.iter().map(value).collect()),
+                });
+            }
+            Ok(Table(json::Value::Array(rows), columns.map(Arc::new)))
+        }
+
+        /// Rows that are named directories are written under a header (unless
+        /// `config.header` is off); rows that are lists are written as is.
+        fn to_writer(&self, writer: Box<dyn std::io::Write>, config: &Config) -> Result<(), Error> {
+            let format = Self::format();
+            let serialize_error = |message: String| Error::Serialize { format, message };
+            let csv_error = |e: ::csv::Error| match e.into_kind() {
+                ::csv::ErrorKind::Io(e) => Error::Io(e),
+                kind => serialize_error(format!("{kind:?}")),
+            };
+
+            let rows = match &self.0 {
+                json::Value::Array(rows) => rows.as_slice(),
+                // an empty file loads as an empty named directory
+                json::Value::Object(fields) if fields.is_empty() => &[],
+                _ => return Err(serialize_error("documents must be lists of rows".into())),
+            };
+            let text = |i: usize, column: &str, v: Option<&json::Value>| match v {
+                None => Ok(String::new()),
+                Some(v) => json::text(v).ok_or_else(|| {
+                    serialize_error(format!(
+                        "row {i} has a directory in column {column}, but cells can only hold values"
+                    ))
+                }),
+            };
+
+            // check everything before writing anything
+            let mut records = Vec::with_capacity(rows.len() + 1);
+            let read = self.1.as_deref().map_or(&[][..], Vec::as_slice);
+            if rows.is_empty() {
+                // with no rows to hold them, a header's columns are all we have
+                if config.header && !read.is_empty() {
+                    records.push(read.to_vec());
+                }
+            } else if rows.iter().any(|row| matches!(row, json::Value::Object(_))) {
+                let columns = columns(rows, read, format, config)?;
+                for (i, row) in rows.iter().enumerate() {
+                    records.push(
+                        columns
+                            .iter()
+                            .map(|column| text(i, &format!("'{column}'"), row.get(column)))
+                            .collect::<Result<Vec<_>, _>>()?,
+                    );
+                }
+                if config.header {
+                    records.insert(0, columns);
+                }
+            } else {
+                for (i, row) in rows.iter().enumerate() {
+                    records.push(match row {
+                        json::Value::Array(vs) => vs
+                            .iter()
+                            .enumerate()
+                            .map(|(j, v)| text(i, &j.to_string(), Some(v)))
+                            .collect::<Result<Vec<_>, _>>()?,
+                        v => vec![text(i, "0", Some(v))?],
+                    });
+                }
+            }
+
+            let mut writer = ::csv::WriterBuilder::new()
+                .delimiter(Self::delimiter(config))
+                .flexible(true)
+                .from_writer(writer);
+            for record in records {
+                writer.write_record(&record).map_err(csv_error)?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// TSV Nodelike implementation: CSV, but with tabs (see `csv`)
+pub mod tsv {
+    pub type Value = super::csv::Table<b'\t'>;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// XML Nodelike implementation
+///
+/// A document is a named directory holding its root element. An element with
+/// nothing but text in it is a file; any other element is a named directory,
+/// where attributes are files named with an `@` (e.g., `@id`), text is in
+/// `#text`, and child elements are named after their tags---repeated siblings
+/// make a list. Mixed content (text and elements together) is a list,
+/// `#content`, of text and named directories holding one element each. Names
+/// keep their namespace prefixes (e.g., `xs:element`), and `xmlns`
+/// declarations are attributes like any other. Comments and processing
+/// instructions are dropped. Below the root, everything is just JSON.
+pub mod xml {
+    use super::*;
+    use crate::diff::pointer_push;
+    use quick_xml::escape::{escape, partial_escape};
+    use quick_xml::events::{BytesStart, Event};
+    use std::collections::HashMap;
+    use std::fmt::Write as _;
+    use std::sync::Arc;
+
+    /// Attributes are files whose names start with this.
+    pub const ATTRIBUTE_PREFIX: char = '@';
+    /// Text content is in a file with this name.
+    pub const TEXT: &str = "#text";
+    /// Mixed content (i.e., text and elements together) is in a list with
+    /// this name.
+    pub const CONTENT: &str = "#content";
+
+    /// A document, with its layout if we read it.
+    #[derive(Clone, Debug, Default)]
+    pub struct Value(json::Value, Option<Arc<Layout>>);
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.0.fmt(f)
+        }
+    }
+
+    /// Where a document's elements were, and what came before them.
+    #[derive(Debug)]
+    struct Layout {
+        /// The XML declaration and `DOCTYPE`, if any.
+        prolog: Vec<String>,
+        /// The children of each element (by JSON Pointer), in order, as names
+        /// and indices into their lists (0 for children that aren't lists).
+        order: HashMap<String, Vec<(String, usize)>>,
+    }
+
+    impl Default for Layout {
+        fn default() -> Self {
+            Layout {
+                prolog: vec![r#"<?xml version="1.0" encoding="UTF-8"?>"#.into()],
+                order: HashMap::new(),
+            }
+        }
+    }
+
+    impl Layout {
+        fn to_json(&self) -> json::Value {
+            let order = self
+                .order
+                .iter()
+                .map(|(pointer, children)| {
+                    let children = children
+                        .iter()
+                        .map(|(name, i)| serde_json::json!([name, i]))
+                        .collect();
+                    (pointer.clone(), json::Value::Array(children))
+                })
+                .collect::<serde_json::Map<_, _>>();
+            serde_json::json!({ "prolog": self.prolog, "order": order })
+        }
+
+        fn from_json(layout: &json::Value) -> Option<Layout> {
+            let prolog = layout
+                .get("prolog")?
+                .as_array()?
+                .iter()
+                .map(|line| line.as_str().map(String::from))
+                .collect::<Option<_>>()?;
+            let mut order = HashMap::new();
+            for (pointer, children) in layout.get("order")?.as_object()? {
+                let children = children
+                    .as_array()?
+                    .iter()
+                    .map(|child| {
+                        Some((
+                            child.get(0)?.as_str()?.into(),
+                            child.get(1)?.as_u64()? as usize,
+                        ))
+                    })
+                    .collect::<Option<_>>()?;
+                order.insert(pointer.clone(), children);
+            }
+            Some(Layout { prolog, order })
+        }
+    }
+
+    /// An element as it's parsed, before its children are grouped by name.
+    struct Element {
+        name: String,
+        attributes: Vec<(String, String)>,
+        children: Vec<Child>,
+    }
+
+    enum Child {
+        Element(Element),
+        Text(String),
+    }
+
+    impl Element {
+        fn new(start: &BytesStart) -> Result<Self, String> {
+            let mut attributes = Vec::new();
+            for attribute in start.attributes() {
+                let attribute = attribute.map_err(|e| e.to_string())?;
+                let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+                attributes.push((
+                    String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                    value.into_owned(),
+                ));
+            }
+
+            Ok(Element {
+                name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+                attributes,
+                children: Vec::new(),
+            })
+        }
+
+        fn push_text(&mut self, text: &str) {
+            match self.children.last_mut() {
+                Some(Child::Text(s)) => s.push_str(text),
+                _ => self.children.push(Child::Text(text.into())),
+            }
+        }
+
+        /// Converts the element at `pointer` to a value, noting the order of
+        /// its children in `layout`.
+        fn into_value(self, pointer: &str, layout: &mut Layout, config: &Config) -> json::Value {
+            let value = |s: String| json::from_text(s, config);
+
+            let mut children = self.children;
+            let has_elements = children
+                .iter()
+                .any(|child| matches!(child, Child::Element(_)));
+            let mixed = has_elements
+                && children
+                    .iter()
+                    .any(|child| matches!(child, Child::Text(s) if !s.trim().is_empty()));
+            if has_elements && !mixed {
+                // whitespace between elements is just indentation
+                children.retain(|child| matches!(child, Child::Element(_)));
+            }
+
+            if self.attributes.is_empty() && !has_elements {
+                return value(
+                    children
+                        .into_iter()
+                        .filter_map(|child| match child {
+                            Child::Text(s) => Some(s),
+                            Child::Element(_) => None,
+                        })
+                        .collect(),
+                );
+            }
+
+            let mut fields = serde_json::Map::new();
+            for (name, v) in self.attributes {
+                fields.insert(format!("{ATTRIBUTE_PREFIX}{name}"), value(v));
+            }
+
+            if mixed {
+                // mixed content is a list, so that it stays in order wherever
+                // it goes
+                let content_pointer = pointer_push(pointer, CONTENT);
+                let content = children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, child)| match child {
+                        Child::Text(s) => value(s),
+                        Child::Element(e) => {
+                            let name = e.name.clone();
+                            let item = pointer_push(&content_pointer, &i.to_string());
+                            let v = e.into_value(&pointer_push(&item, &name), layout, config);
+                            let mut element = serde_json::Map::new();
+                            element.insert(name, v);
+                            json::Value::Object(element)
+                        }
+                    })
+                    .collect();
+                fields.insert(CONTENT.into(), json::Value::Array(content));
+                return json::Value::Object(fields);
+            }
+
+            let mut elements = Vec::new();
+            for child in children {
+                match child {
+                    Child::Text(s) => {
+                        fields.insert(TEXT.into(), value(s));
+                    }
+                    Child::Element(e) => elements.push(e),
+                }
+            }
+
+            let mut counts = HashMap::<String, usize>::new();
+            for e in &elements {
+                *counts.entry(e.name.clone()).or_default() += 1;
+            }
+
+            let mut order = Vec::with_capacity(elements.len());
+            let mut seen = HashMap::<String, usize>::new();
+            for e in elements {
+                let name = e.name.clone();
+                let index = seen.entry(name.clone()).or_default();
+                let i = *index;
+                *index += 1;
+                order.push((name.clone(), i));
+
+                if counts[&name] > 1 {
+                    let child = pointer_push(&pointer_push(pointer, &name), &i.to_string());
+                    let v = e.into_value(&child, layout, config);
+                    if let json::Value::Array(vs) = fields
+                        .entry(name)
+                        .or_insert_with(|| json::Value::Array(Vec::new()))
+                    {
+                        vs.push(v);
+                    }
+                } else {
+                    let v = e.into_value(&pointer_push(pointer, &name), layout, config);
+                    fields.insert(name, v);
+                }
+            }
+
+            if order.len() > 1 {
+                layout.order.insert(pointer.into(), order);
+            }
+            json::Value::Object(fields)
+        }
+    }
+
+    /// Adds a finished element to its parent, or makes it the root.
+    fn close(
+        element: Element,
+        stack: &mut [Element],
+        root: &mut Option<Element>,
+    ) -> Result<(), String> {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(Child::Element(element)),
+            None if root.is_some() => return Err("there's more than one root element".into()),
+            None => *root = Some(element),
+        }
+        Ok(())
+    }
+
+    /// Element and attribute names; we don't check which characters are
+    /// allowed where as carefully as the XML spec does.
+    fn check_name(name: &str) -> Result<(), String> {
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+            && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'));
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("'{name}' isn't a valid XML name"))
+        }
+    }
+
+    /// Writes the element `name`, whose content is `v`, at `pointer`. When
+    /// `indent` is set, child elements go on their own lines, unless there's
+    /// text among them.
+    fn write_element(
+        out: &mut String,
+        name: &str,
+        v: &json::Value,
+        pointer: &str,
+        layout: &Layout,
+        indent: Option<usize>,
+    ) -> Result<(), String> {
+        check_name(name)?;
+        let _ = write!(out, "<{name}");
+
+        let fields = match v {
+            json::Value::Object(fields) => fields,
+            json::Value::Array(_) => {
+                return Err(format!(
+                    "{pointer} is a list in a list, which XML can't express"
+                ));
+            }
+            v => {
+                let text = json::text(v).unwrap_or_default();
+                if text.is_empty() {
+                    out.push_str("/>");
+                } else {
+                    let _ = write!(out, ">{}</{name}>", partial_escape(text.as_str()));
+                }
+                return Ok(());
+            }
+        };
+
+        let mut text = None;
+        let mut content = None;
+        let mut items = Vec::new();
+        for (field, v) in fields {
+            let child = pointer_push(pointer, field);
+            if let Some(attribute) = field.strip_prefix(ATTRIBUTE_PREFIX) {
+                check_name(attribute)?;
+                let value = json::text(v).ok_or_else(|| {
+                    format!("{child} is an attribute, so it can't be a directory")
+                })?;
+                let _ = write!(out, " {attribute}=\"{}\"", escape(value.as_str()));
+            } else if field == TEXT {
+                text = Some(
+                    json::text(v)
+                        .ok_or_else(|| format!("{child} is text, so it can't be a directory"))?,
+                );
+            } else if field == CONTENT {
+                let json::Value::Array(vs) = v else {
+                    return Err(format!("{child} is mixed content, so it must be a list"));
+                };
+                content = Some((child, vs));
+            } else {
+                match v {
+                    json::Value::Array(vs) => items.extend(
+                        vs.iter()
+                            .enumerate()
+                            .map(|(i, v)| (field.as_str(), Some(i), v)),
+                    ),
+                    v => items.push((field.as_str(), None, v)),
+                }
+            }
+        }
+        if let Some(order) = layout.order.get(pointer) {
+            items.sort_by_key(|(name, i, _)| {
+                order
+                    .iter()
+                    .position(|(n, j)| n == name && *j == i.unwrap_or(0))
+                    .unwrap_or(usize::MAX)
+            });
+        }
+
+        if text.is_none() && content.is_none() && items.is_empty() {
+            out.push_str("/>");
+            return Ok(());
+        }
+        out.push('>');
+
+        if let Some(text) = &text {
+            out.push_str(&partial_escape(text.as_str()));
+        }
+        if let Some((content_pointer, vs)) = &content {
+            for (i, v) in vs.iter().enumerate() {
+                let item = pointer_push(content_pointer, &i.to_string());
+                match v {
+                    json::Value::Object(element) if element.len() == 1 => {
+                        let (name, v) = element.iter().next().unwrap();
+                        write_element(out, name, v, &pointer_push(&item, name), layout, None)?;
+                    }
+                    v => match json::text(v) {
+                        Some(text) => out.push_str(&partial_escape(text.as_str())),
+                        None => {
+                            return Err(format!(
+                                "{item} is mixed content, so it must be text or a directory holding one element"
+                            ));
+                        }
+                    },
+                }
+            }
+        }
+
+        let indent = indent.filter(|_| text.is_none() && content.is_none());
+        for (field, i, v) in items {
+            let child = pointer_push(pointer, field);
+            let child = match i {
+                Some(i) => pointer_push(&child, &i.to_string()),
+                None => child,
+            };
+
+            if let Some(n) = indent {
+                let _ = write!(out, "\n{}", "  ".repeat(n + 1));
+            }
+            write_element(out, field, v, &child, layout, indent.map(|n| n + 1))?;
+        }
+        if let Some(n) = indent {
+            let _ = write!(out, "\n{}", "  ".repeat(n));
+        }
+        let _ = write!(out, "</{name}>");
+        Ok(())
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            self.0.is_dir()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            match self.0.node(config) {
+                Node::String(t, s) => Node::String(t, s),
+                Node::Bytes(b) => Node::Bytes(b),
+                Node::List(vs) => Node::List(vs.into_iter().map(|v| Value(v, None)).collect()),
+                Node::Map(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v, None))).collect())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, config: &Config) -> Self {
+            match typ {
+                Typ::Auto => Value(json::from_text(contents, config), None),
+                typ => Value(json::Value::from_string(typ, contents, config), None),
+            }
+        }
+
+        fn from_bytes<T>(contents: T, config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(json::Value::from_bytes(contents, config), None)
+        }
+
+        fn from_list_dir(files: Vec<Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_list_dir(files.into_iter().map(|v| v.0).collect(), config),
+                None,
+            )
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_named_dir(
+                    files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+                    config,
+                ),
+                None,
+            )
+        }
+
+        fn layout(&self) -> Option<String> {
+            Some(tag_layout("xml", self.1.as_ref()?.to_json()))
+        }
+
+        fn with_layout(self, layout: &str) -> Self {
+            match tagged_layout("xml", layout).and_then(|layout| Layout::from_json(&layout)) {
+                Some(layout) => Value(self.0, Some(Arc::new(layout))),
+                None => self,
+            }
+        }
+
+        fn from_reader(mut reader: Box<dyn std::io::Read>, config: &Config) -> Result<Self, Error> {
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+            let parse_error = |offset: u64, message: String| Error::Parse {
+                format: Format::Xml,
+                position: Some(Position::from_offset(&text, offset as usize)),
+                message,
+            };
+
+            let mut reader = quick_xml::Reader::from_str(&text);
+            let mut prolog = Vec::new();
+            let mut stack: Vec<Element> = Vec::new();
+            let mut root = None;
+            loop {
+                let event = reader
+                    .read_event()
+                    .map_err(|e| parse_error(reader.error_position(), e.to_string()))?;
+                let position = reader.buffer_position();
+                match event {
+                    Event::Decl(decl) => {
+                        prolog.push(format!("<?{}?>", String::from_utf8_lossy(&decl)));
+                    }
+                    Event::DocType(doctype) => {
+                        prolog.push(format!(
+                            "<!DOCTYPE {}>",
+                            String::from_utf8_lossy(&doctype).trim()
+                        ));
+                    }
+                    Event::Start(start) => {
+                        stack.push(Element::new(&start).map_err(|e| parse_error(position, e))?);
+                    }
+                    Event::Empty(start) => {
+                        let element = Element::new(&start).map_err(|e| parse_error(position, e))?;
+                        close(element, &mut stack, &mut root)
+                            .map_err(|e| parse_error(position, e))?;
+                    }
+                    Event::End(_) => {
+                        let element = stack.pop().ok_or_else(|| {
+                            parse_error(position, "unexpected closing tag".into())
+                        })?;
+                        close(element, &mut stack, &mut root)
+                            .map_err(|e| parse_error(position, e))?;
+                    }
+                    Event::Text(t) => {
+                        let t = t
+                            .unescape()
+                            .map_err(|e| parse_error(position, e.to_string()))?;
+                        match stack.last_mut() {
+                            Some(element) => element.push_text(&t),
+                            None if t.trim().is_empty() => (),
+                            None => {
+                                return Err(parse_error(
+                                    position,
+                                    "there's text outside of the root element".into(),
+                                ));
+                            }
+                        }
+                    }
+                    Event::CData(cdata) => {
+                        let t = cdata
+                            .decode()
+                            .map_err(|e| parse_error(position, e.to_string()))?;
+                        match stack.last_mut() {
+                            Some(element) => element.push_text(&t),
+                            None => {
+                                return Err(parse_error(
+                                    position,
+                                    "there's text outside of the root element".into(),
+                                ));
+                            }
+                        }
+                    }
+                    Event::Comment(_) | Event::PI(_) => (),
+                    Event::Eof => break,
+                }
+            }
+
+            if let Some(element) = stack.last() {
+                return Err(parse_error(
+                    text.len() as u64,
+                    format!("'{}' is never closed", element.name),
+                ));
+            }
+            let Some(root) = root else {
+                return Err(Error::Parse {
+                    format: Format::Xml,
+                    position: None,
+                    message: "there's no root element".into(),
+                });
+            };
+
+            let mut layout = Layout {
+                prolog,
+                order: HashMap::new(),
+            };
+            let name = root.name.clone();
+            let v = root.into_value(&pointer_push("", &name), &mut layout, config);
+
+            let mut fields = serde_json::Map::new();
+            fields.insert(name, v);
+            Ok(Value(json::Value::Object(fields), Some(Arc::new(layout))))
+        }
+
+        /// Elements and text are written in the order they were read in; new
+        /// ones go at the end.
+        fn to_writer(
+            &self,
+            mut writer: Box<dyn std::io::Write>,
+            config: &Config,
+        ) -> Result<(), Error> {
+            let serialize_error = |message: String| Error::Serialize {
+                format: Format::Xml,
+                message,
+            };
+
+            let root = match &self.0 {
+                json::Value::Object(fields) if fields.len() == 1 => fields.iter().next(),
+                _ => None,
+            };
+            let Some((name, v)) = root else {
+                return Err(serialize_error(
+                    "documents must be named directories holding just the root element".into(),
+                ));
+            };
+            if v.is_array() {
+                return Err(serialize_error(format!(
+                    "'{name}' is a list, but there can only be one root element"
+                )));
+            }
+
+            let default = Layout::default();
+            let layout = self.1.as_deref().unwrap_or(&default);
+            let mut out = String::new();
+            for line in &layout.prolog {
+                out.push_str(line);
+                out.push('\n');
+            }
+            write_element(
+                &mut out,
+                name,
+                v,
+                &pointer_push("", name),
+                layout,
+                config.pretty.then_some(0),
+            )
+            .map_err(serialize_error)?;
+            out.push('\n');
+
+            writer.write_all(out.as_bytes())?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// INI Nodelike implementation
+///
+/// A document is a named directory of sections, each a named directory of
+/// keys. Keys before the first section are files at the root, or go in a
+/// directory named by `config.global_section`. Keys and values are separated
+/// by `=` or `:`, and values are typed automatically. Comments (lines starting
+/// with `;` or `#`) are dropped. Below the root, everything is just JSON.
+pub mod ini {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// A document, with its layout if we read it.
+    #[derive(Clone, Debug, Default)]
+    pub struct Value(json::Value, Option<Arc<Layout>>);
+
+    /// The sections and keys of a document, in order; the global section is
+    /// `None`.
+    #[derive(Debug, Default)]
+    struct Layout {
+        sections: Vec<String>,
+        keys: HashMap<Option<String>, Vec<String>>,
+    }
+
+    impl Layout {
+        fn to_json(&self) -> json::Value {
+            let keys = self
+                .keys
+                .iter()
+                .map(|(section, keys)| serde_json::json!([section, keys]))
+                .collect::<Vec<_>>();
+            serde_json::json!({ "sections": self.sections, "keys": keys })
+        }
+
+        fn from_json(layout: &json::Value) -> Option<Layout> {
+            let sections = strings(layout.get("sections")?)?;
+            let mut keys = HashMap::new();
+            for entry in layout.get("keys")?.as_array()? {
+                let section = match entry.get(0)? {
+                    json::Value::Null => None,
+                    section => Some(section.as_str()?.to_string()),
+                };
+                keys.insert(section, strings(entry.get(1)?)?);
+            }
+            Some(Layout { sections, keys })
+        }
+    }
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.0.fmt(f)
+        }
+    }
+
+    fn check_key(key: &str) -> Result<(), String> {
+        if key.is_empty()
+            || key.trim() != key
+            || key.contains(['=', ':', '\n', '\r'])
+            || key.starts_with(['[', ';', '#'])
+        {
+            Err(format!("'{key}' isn't a valid INI key"))
+        } else {
+            Ok(())
+        }
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            self.0.is_dir()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            match self.0.node(config) {
+                Node::String(t, s) => Node::String(t, s),
+                Node::Bytes(b) => Node::Bytes(b),
+                Node::List(vs) => Node::List(vs.into_iter().map(|v| Value(v, None)).collect()),
+                Node::Map(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v, None))).collect())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, config: &Config) -> Self {
+            match typ {
+                Typ::Auto => Value(json::from_text(contents, config), None),
+                typ => Value(json::Value::from_string(typ, contents, config), None),
+            }
+        }
+
+        fn from_bytes<T>(contents: T, config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(json::Value::from_bytes(contents, config), None)
+        }
+
+        fn from_list_dir(files: Vec<Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_list_dir(files.into_iter().map(|v| v.0).collect(), config),
+                None,
+            )
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_named_dir(
+                    files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+                    config,
+                ),
+                None,
+            )
+        }
+
+        fn layout(&self) -> Option<String> {
+            Some(tag_layout("ini", self.1.as_ref()?.to_json()))
+        }
+
+        fn with_layout(self, layout: &str) -> Self {
+            match tagged_layout("ini", layout).and_then(|layout| Layout::from_json(&layout)) {
+                Some(layout) => Value(self.0, Some(Arc::new(layout))),
+                None => self,
+            }
+        }
+
+        /// Sections with the same name are merged, but setting a key twice in
+        /// the same section is an error.
+        fn from_reader(mut reader: Box<dyn std::io::Read>, config: &Config) -> Result<Self, Error> {
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+
+            let parse_error = |line: usize, message: String| Error::Parse {
+                format: Format::Ini,
+                position: Some(Position { line, column: 1 }),
+                message,
+            };
+
+            let mut layout = Layout::default();
+            let mut global = serde_json::Map::new();
+            let mut sections = serde_json::Map::new();
+            let mut section: Option<String> = None;
+            for (i, line) in text.lines().enumerate() {
+                let line_no = i + 1;
+                let line = line.trim();
+                if line.is_empty() || line.starts_with([';', '#']) {
+                    continue;
+                }
+
+                if let Some(header) = line.strip_prefix('[') {
+                    let Some(name) = header.strip_suffix(']') else {
+                        return Err(parse_error(
+                            line_no,
+                            "the section name is never closed".into(),
+                        ));
+                    };
+                    let name = name.trim().to_string();
+                    if config.global_section.as_ref() == Some(&name) {
+                        return Err(parse_error(
+                            line_no,
+                            format!(
+                                "there's a section named '{name}', which is where the global keys go (see `--global-section`)"
+                            ),
+                        ));
+                    }
+                    if config.global_section.is_none() && global.contains_key(&name) {
+                        return Err(parse_error(
+                            line_no,
+                            format!(
+                                "'{name}' is both a global key and a section (see `--global-section`)"
+                            ),
+                        ));
+                    }
+                    if !layout.sections.contains(&name) {
+                        layout.sections.push(name.clone());
+                    }
+                    sections
+                        .entry(name.clone())
+                        .or_insert_with(|| json::Value::Object(serde_json::Map::new()));
+                    section = Some(name);
+                    continue;
+                }
+
+                let Some((key, value)) = line.split_once(['=', ':']) else {
+                    return Err(parse_error(
+                        line_no,
+                        "expected 'key = value' or '[section]'".into(),
+                    ));
+                };
+                let key = key.trim_end();
+                if key.is_empty() {
+                    return Err(parse_error(line_no, "there's a value without a key".into()));
+                }
+                if section.is_none()
+                    && config.global_section.is_none()
+                    && sections.contains_key(key)
+                {
+                    return Err(parse_error(
+                        line_no,
+                        format!(
+                            "'{key}' is both a global key and a section (see `--global-section`)"
+                        ),
+                    ));
+                }
+
+                let fields = match &section {
+                    None => &mut global,
+                    Some(name) => match sections.get_mut(name) {
+                        Some(json::Value::Object(fields)) => fields,
+                        _ => unreachable!("sections are always named directories"),
+                    },
+                };
+                if fields.contains_key(key) {
+                    let place = match &section {
+                        None => "before any section".to_string(),
+                        Some(name) => format!("in section '{name}'"),
+                    };
+                    return Err(parse_error(
+                        line_no,
+                        format!("'{key}' is set twice {place}"),
+                    ));
+                }
+                fields.insert(
+                    key.to_string(),
+                    json::from_text(value.trim_start().to_string(), config),
+                );
+                layout
+                    .keys
+                    .entry(section.clone())
+                    .or_default()
+                    .push(key.to_string());
+            }
+
+            let root = match &config.global_section {
+                None => {
+                    global.extend(sections);
+                    global
+                }
+                Some(name) => {
+                    if !global.is_empty() {
+                        sections.insert(name.clone(), json::Value::Object(global));
+                    }
+                    sections
+                }
+            };
+            Ok(Value(json::Value::Object(root), Some(Arc::new(layout))))
+        }
+
+        /// Files at the root are global keys, as is everything in the
+        /// directory named by `config.global_section`; other directories at
+        /// the root are sections.
+        fn to_writer(
+            &self,
+            mut writer: Box<dyn std::io::Write>,
+            config: &Config,
+        ) -> Result<(), Error> {
+            let serialize_error = |message: String| Error::Serialize {
+                format: Format::Ini,
+                message,
+            };
+            let json::Value::Object(root) = &self.0 else {
+                return Err(serialize_error(
+                    "documents must be named directories of sections".into(),
+                ));
+            };
+
+            let mut global = Vec::new();
+            let mut sections = Vec::new();
+            for (name, v) in root {
+                match v {
+                    json::Value::Object(fields) if config.global_section.as_ref() == Some(name) => {
+                        global.extend(fields.iter().map(|(key, v)| (key.clone(), (name, v))));
+                    }
+                    json::Value::Object(fields) => sections.push((name.clone(), fields)),
+                    json::Value::Array(_) => {
+                        return Err(serialize_error(format!(
+                            "'{name}' is a list, but INI files only have sections and keys"
+                        )));
+                    }
+                    v => global.push((name.clone(), (name, v))),
+                }
+            }
+
+            // check everything before writing anything
+            let default = Layout::default();
+            let layout = self.1.as_deref().unwrap_or(&default);
+            let no_keys = Vec::new();
+            let line = |key: &str, path: &str, v: &json::Value| {
+                check_key(key).map_err(serialize_error)?;
+                match json::text(v) {
+                    Some(text) if text.contains(['\n', '\r']) => Err(serialize_error(format!(
+                        "'{path}' spans several lines, but INI values can't"
+                    ))),
+                    Some(text) if text.is_empty() => Ok(format!("{key} =\n")),
+                    Some(text) => Ok(format!("{key} = {text}\n")),
+                    None => Err(serialize_error(format!(
+                        "'{path}' is a directory, but INI values can't be"
+                    ))),
+                }
+            };
+
+            let mut out = String::new();
+            let order = layout.keys.get(&None).unwrap_or(&no_keys);
+            for (key, (name, v)) in in_order(global, order) {
+                let path = if name == &key {
+                    key.clone()
+                } else {
+                    format!("{name}/{key}")
+                };
+                out.push_str(&line(&key, &path, v)?);
+            }
+            for (name, fields) in in_order(sections, &layout.sections) {
+                if name.is_empty() || name.contains([']', '\n', '\r']) {
+                    return Err(serialize_error(format!(
+                        "'{name}' isn't a valid INI section name"
+                    )));
+                }
+                if !out.is_empty() {
+                    out.push('\n');
+                }
+                out.push_str(&format!("[{name}]\n"));
+
+                let order = layout.keys.get(&Some(name.clone())).unwrap_or(&no_keys);
+                let fields = fields.iter().map(|(key, v)| (key.clone(), v)).collect();
+                for (key, v) in in_order(fields, order) {
+                    out.push_str(&line(&key, &format!("{name}/{key}"), v)?);
+                }
+            }
+
+            writer.write_all(out.as_bytes())?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Java properties Nodelike implementation
+///
+/// A document is a named directory of keys, or, with `config.nest_keys`, of
+/// nested directories: `a.b=1` is the file `a/b`. Saving always joins nested
+/// directories with dots. Values are typed automatically. Comments (lines
+/// starting with `#` or `!`) are dropped. Below the root, everything is just
+/// JSON.
+pub mod properties {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A document, with its keys in order if we read it.
+    #[derive(Clone, Debug, Default)]
+    pub struct Value(json::Value, Option<Arc<Vec<String>>>);
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.0.fmt(f)
+        }
+    }
+
+    const WHITESPACE: [char; 3] = [' ', '\t', '\x0c'];
+
+    /// Reads the escapes in a key or value: `\t`, `\n`, `\r`, `\f`, `\uXXXX`,
+    /// and a backslash before any other character is just that character.
+    fn unescape(s: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(s.len());
+        let mut chars = s.chars();
+        let hex = |chars: &mut std::str::Chars| {
+            let digits = chars.by_ref().take(4).collect::<String>();
+            match u16::from_str_radix(&digits, 16) {
+                Ok(unit) if digits.len() == 4 => Ok(unit),
+                _ => Err(format!("'\\u{digits}' isn't a valid escape")),
+            }
+        };
+
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('f') => out.push('\x0c'),
+                Some('u') => {
+                    let mut units = vec![hex(&mut chars)?];
+                    // characters outside the BMP are written as surrogate pairs
+                    if (0xD800..0xDC00).contains(&units[0]) && chars.as_str().starts_with("\\u") {
+                        chars.nth(1);
+                        units.push(hex(&mut chars)?);
+                    }
+                    for c in char::decode_utf16(units) {
+                        out.push(c.map_err(|e| e.to_string())?);
+                    }
+                }
+                Some(c) => out.push(c),
+                None => (),
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Writes a key or value the way Java does: non-ASCII characters are
+    /// `\uXXXX` escapes, so that the file can be read as ISO 8859-1 or UTF-8.
+    fn escape(s: &str, is_key: bool) -> String {
+        let mut out = String::with_capacity(s.len());
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\x0c' => out.push_str("\\f"),
+                ' ' if is_key || i == 0 => out.push_str("\\ "),
+                '=' | ':' | '#' | '!' if is_key => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                ' '..='~' => out.push(c),
+                c => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        out.push_str(&format!("\\u{unit:04X}"));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Splits a logical line into its (still escaped) key and value: the key
+    /// ends at the first unescaped `=`, `:`, or whitespace.
+    fn split(line: &str) -> (&str, &str) {
+        let mut chars = line.char_indices();
+        let mut end = line.len();
+        while let Some((i, c)) = chars.next() {
+            if c == '\\' {
+                chars.next();
+            } else if c == '=' || c == ':' || WHITESPACE.contains(&c) {
+                end = i;
+                break;
+            }
+        }
+
+        let (key, rest) = line.split_at(end);
+        let rest = rest.trim_start_matches(WHITESPACE);
+        let rest = rest.strip_prefix(['=', ':']).unwrap_or(rest);
+        (key, rest.trim_start_matches(WHITESPACE))
+    }
+
+    /// Puts `v` at `key`, which is split on dots.
+    fn insert_nested(
+        root: &mut serde_json::Map<String, json::Value>,
+        key: &str,
+        v: json::Value,
+    ) -> Result<(), String> {
+        let parts = key.split('.').collect::<Vec<_>>();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(format!(
+                "'{key}' has an empty part, so it can't be split (see `--nest-keys`)"
+            ));
+        }
+
+        let (last, prefixes) = parts.split_last().expect("split always has a part");
+        let mut fields = root;
+        for (i, part) in prefixes.iter().enumerate() {
+            let entry = fields
+                .entry(part.to_string())
+                .or_insert_with(|| json::Value::Object(serde_json::Map::new()));
+            fields = match entry {
+                json::Value::Object(fields) => fields,
+                _ => {
+                    return Err(format!(
+                        "'{key}' puts keys under '{}', which already has a value (see `--nest-keys`)",
+                        parts[..=i].join(".")
+                    ));
+                }
+            };
+        }
+
+        if let Some(json::Value::Object(_)) = fields.get(*last) {
+            return Err(format!(
+                "'{key}' already has keys under it, so it can't have a value (see `--nest-keys`)"
+            ));
+        }
+        fields.insert(last.to_string(), v);
+        Ok(())
+    }
+
+    /// Every key and value in `fields`, with nested keys joined by dots.
+    fn flatten(
+        fields: &serde_json::Map<String, json::Value>,
+        prefix: &str,
+        out: &mut Vec<(String, String)>,
+    ) -> Result<(), Error> {
+        for (name, v) in fields {
+            let key = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{prefix}.{name}")
+            };
+
+            match v {
+                json::Value::Object(fields) => flatten(fields, &key, out)?,
+                json::Value::Array(_) => {
+                    return Err(Error::Serialize {
+                        format: Format::Properties,
+                        message: format!(
+                            "'{key}' is a list, but properties files only have keys and values"
+                        ),
+                    });
+                }
+                v => out.push((key, json::text(v).expect("only directories have no text"))),
+            }
+        }
+        Ok(())
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            self.0.is_dir()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            match self.0.node(config) {
+                Node::String(t, s) => Node::String(t, s),
+                Node::Bytes(b) => Node::Bytes(b),
+                Node::List(vs) => Node::List(vs.into_iter().map(|v| Value(v, None)).collect()),
+                Node::Map(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v, None))).collect())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, config: &Config) -> Self {
+            match typ {
+                Typ::Auto => Value(json::from_text(contents, config), None),
+                typ => Value(json::Value::from_string(typ, contents, config), None),
+            }
+        }
+
+        fn from_bytes<T>(contents: T, config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(json::Value::from_bytes(contents, config), None)
+        }
+
+        fn from_list_dir(files: Vec<Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_list_dir(files.into_iter().map(|v| v.0).collect(), config),
+                None,
+            )
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_named_dir(
+                    files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+                    config,
+                ),
+                None,
+            )
+        }
+
+        fn layout(&self) -> Option<String> {
+            let keys = self.1.as_ref()?;
+            Some(tag_layout("properties", keys.as_slice().into()))
+        }
+
+        fn with_layout(self, layout: &str) -> Self {
+            match tagged_layout("properties", layout)
+                .as_ref()
+                .and_then(strings)
+            {
+                Some(keys) => Value(self.0, Some(Arc::new(keys))),
+                None => self,
+            }
+        }
+
+        /// Lines ending in a backslash continue onto the next line. As in
+        /// Java, when a key is set twice, the last value wins.
+        fn from_reader(mut reader: Box<dyn std::io::Read>, config: &Config) -> Result<Self, Error> {
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+
+            let parse_error = |line: usize, message: String| Error::Parse {
+                format: Format::Properties,
+                position: Some(Position { line, column: 1 }),
+                message,
+            };
+
+            let mut keys = Vec::new();
+            let mut root = serde_json::Map::new();
+            let mut lines = text.lines().enumerate();
+            while let Some((i, line)) = lines.next() {
+                let line_no = i + 1;
+                let mut line = line.trim_start_matches(WHITESPACE).to_string();
+                if line.is_empty() || line.starts_with(['#', '!']) {
+                    continue;
+                }
+
+                // an odd number of trailing backslashes continues the line
+                while (line.len() - line.trim_end_matches('\\').len()) % 2 == 1 {
+                    line.pop();
+                    match lines.next() {
+                        Some((_, next)) => line.push_str(next.trim_start_matches(WHITESPACE)),
+                        None => break,
+                    }
+                }
+
+                let (key, value) = split(&line);
+                let key = unescape(key).map_err(|message| parse_error(line_no, message))?;
+                let value = unescape(value).map_err(|message| parse_error(line_no, message))?;
+                let value = json::from_text(value, config);
+
+                if config.nest_keys {
+                    insert_nested(&mut root, &key, value)
+                        .map_err(|message| parse_error(line_no, message))?;
+                } else {
+                    root.insert(key.clone(), value);
+                }
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+
+            Ok(Value(json::Value::Object(root), Some(Arc::new(keys))))
+        }
+
+        fn to_writer(
+            &self,
+            mut writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            let json::Value::Object(root) = &self.0 else {
+                return Err(Error::Serialize {
+                    format: Format::Properties,
+                    message: "documents must be named directories".into(),
+                });
+            };
+
+            let mut properties = Vec::new();
+            flatten(root, "", &mut properties)?;
+
+            let mut out = String::new();
+            let keys = self.1.as_deref().map_or(&[][..], Vec::as_slice);
+            for (key, value) in in_order(properties, keys) {
+                out.push_str(&format!(
+                    "{}={}\n",
+                    escape(&key, true),
+                    escape(&value, false)
+                ));
+            }
+            writer.write_all(out.as_bytes())?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// JSON5 (and JSONC) Nodelike implementation
+///
+/// JSON5 is JSON with comments, trailing commas, unquoted keys, single-quoted
+/// strings, and more liberal numbers; JSONC (e.g., `tsconfig.json`) is JSON
+/// with comments and trailing commas, which makes it JSON5, too. Below the
+/// root, everything is just JSON.
+///
+/// Comments stay with the key or element they're next to: a comment on the
+/// lines before a key, or after it on the same line, is saved in the same
+/// place, as long as the key is still around. Saving writes JSON, with
+/// comments, keeping keys in order and unquoted keys unquoted. `Infinity` and
+/// `NaN` are floats, which are saved bare; formats without them (e.g., JSON)
+/// get strings.
+pub mod json5 {
+    use super::*;
+    use crate::diff::pointer_push;
+    use std::collections::{HashMap, HashSet};
+    use std::fmt::Write as _;
+    use std::sync::Arc;
+
+    /// A document, with its layout if we read it.
+    #[derive(Clone, Debug, Default)]
+    pub struct Value(json::Value, Option<Arc<Layout>>);
+
+    /// The comments next to a key or element.
+    #[derive(Debug, Default)]
+    struct Comments {
+        /// On the lines before it
+        before: Vec<String>,
+        /// After it, on the same line
+        after: Option<String>,
+        /// Before the closing bracket, when it's an object or array
+        end: Vec<String>,
+    }
+
+    /// How a document was laid out; everything is by JSON Pointer.
+    #[derive(Debug)]
+    struct Layout {
+        indent: String,
+        comments: HashMap<String, Comments>,
+        /// Comments after the root value
+        trailing: Vec<String>,
+        /// The keys of each object, in order
+        order: HashMap<String, Vec<String>>,
+        /// The keys that weren't quoted
+        bare: HashSet<String>,
+    }
+
+    impl Default for Layout {
+        fn default() -> Self {
+            Layout {
+                indent: "  ".into(),
+                comments: HashMap::new(),
+                trailing: Vec::new(),
+                order: HashMap::new(),
+                bare: HashSet::new(),
+            }
+        }
+    }
+
+    impl Comments {
+        fn to_json(&self) -> json::Value {
+            serde_json::json!({ "before": self.before, "after": self.after, "end": self.end })
+        }
+
+        fn from_json(comments: &json::Value) -> Option<Comments> {
+            Some(Comments {
+                before: strings(comments.get("before")?)?,
+                after: comments.get("after")?.as_str().map(String::from),
+                end: strings(comments.get("end")?)?,
+            })
+        }
+    }
+
+    impl Layout {
+        fn to_json(&self) -> json::Value {
+            let comments = self
+                .comments
+                .iter()
+                .map(|(pointer, comments)| (pointer.clone(), comments.to_json()))
+                .collect::<serde_json::Map<_, _>>();
+            serde_json::json!({
+                "indent": self.indent,
+                "comments": comments,
+                "trailing": self.trailing,
+                "order": self.order,
+                "bare": self.bare,
+            })
+        }
+
+        fn from_json(layout: &json::Value) -> Option<Layout> {
+            let mut comments = HashMap::new();
+            for (pointer, c) in layout.get("comments")?.as_object()? {
+                comments.insert(pointer.clone(), Comments::from_json(c)?);
+            }
+            let mut order = HashMap::new();
+            for (pointer, keys) in layout.get("order")?.as_object()? {
+                order.insert(pointer.clone(), strings(keys)?);
+            }
+            Some(Layout {
+                indent: layout.get("indent")?.as_str()?.to_string(),
+                comments,
+                trailing: strings(layout.get("trailing")?)?,
+                order,
+                bare: strings(layout.get("bare")?)?.into_iter().collect(),
+            })
+        }
+    }
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.0.fmt(f)
+        }
+    }
+
+    fn is_identifier(s: &str) -> bool {
+        let mut chars = s.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    }
+
+    struct Parser<'a> {
+        text: &'a str,
+        pos: usize,
+        layout: Layout,
+        /// Comments waiting for the next key or element
+        pending: Vec<String>,
+    }
+
+    impl<'a> Parser<'a> {
+        fn error(&self, message: impl Into<String>) -> Error {
+            Error::Parse {
+                format: Format::Json5,
+                position: Some(Position::from_offset(self.text, self.pos)),
+                message: message.into(),
+            }
+        }
+
+        fn peek(&self) -> Option<char> {
+            self.text[self.pos..].chars().next()
+        }
+
+        fn next(&mut self) -> Option<char> {
+            let c = self.peek()?;
+            self.pos += c.len_utf8();
+            Some(c)
+        }
+
+        fn expect(&mut self, expected: char) -> Result<(), Error> {
+            match self.peek() {
+                Some(c) if c == expected => {
+                    self.pos += 1;
+                    Ok(())
+                }
+                Some(c) => Err(self.error(format!("expected '{expected}', found '{c}'"))),
+                None => Err(self.error(format!("expected '{expected}', found the end"))),
+            }
+        }
+
+        /// Skips whitespace and comments, returning each comment and whether
+        /// there was a line break before it.
+        fn trivia(&mut self) -> Result<Vec<(bool, String)>, Error> {
+            let mut comments = Vec::new();
+            let mut newline = false;
+            loop {
+                let rest = &self.text[self.pos..];
+                if rest.starts_with("//") {
+                    let len = rest.find('\n').unwrap_or(rest.len());
+                    comments.push((newline, rest[..len].trim_end().to_string()));
+                    self.pos += len;
+                } else if let Some(body) = rest.strip_prefix("/*") {
+                    let Some(len) = body.find("*/") else {
+                        return Err(self.error("the comment is never closed"));
+                    };
+                    comments.push((newline, rest[..len + 4].to_string()));
+                    self.pos += len + 4;
+                } else {
+                    match self.peek() {
+                        Some(c) if c.is_whitespace() || c == '\u{FEFF}' => {
+                            newline |= c == '\n' || c == '\u{2028}' || c == '\u{2029}';
+                            self.pos += c.len_utf8();
+                        }
+                        _ => return Ok(comments),
+                    }
+                }
+            }
+        }
+
+        fn comments(&mut self, pointer: &str) -> &mut Comments {
+            self.layout.comments.entry(pointer.to_string()).or_default()
+        }
+
+        /// The comments before a key or element are whatever's pending.
+        fn attach_before(&mut self, pointer: &str) {
+            if !self.pending.is_empty() {
+                let before = std::mem::take(&mut self.pending);
+                self.comments(pointer).before = before;
+            }
+        }
+
+        /// Skips to the next key or element: a comment on the same line as
+        /// `pointer`'s value (before or after the comma) belongs to it;
+        /// everything else is pending. Returns whether there was a comma.
+        fn separator(&mut self, pointer: &str) -> Result<bool, Error> {
+            let mut comments = self.trivia()?;
+            let comma = self.peek() == Some(',');
+            if comma {
+                self.pos += 1;
+                comments.extend(self.trivia()?);
+            }
+
+            let mut comments = comments.into_iter();
+            if let Some((newline, comment)) = comments.next() {
+                if newline {
+                    self.pending.push(comment);
+                } else {
+                    self.comments(pointer).after = Some(comment);
+                }
+            }
+            self.pending
+                .extend(comments.map(|(_newline, comment)| comment));
+            Ok(comma)
+        }
+
+        /// Collects the comments before a key, an element, or a closing
+        /// bracket; the first line's indentation is the document's.
+        fn before_item(&mut self, pointer: &str, first: bool) -> Result<(), Error> {
+            let comments = self.trivia()?;
+            self.pending
+                .extend(comments.into_iter().map(|(_newline, comment)| comment));
+
+            if first && pointer.is_empty() {
+                let line = &self.text[..self.pos];
+                if let Some(i) = line.rfind('\n') {
+                    let indent = &line[i + 1..];
+                    if !indent.is_empty() && indent.chars().all(|c| c == ' ' || c == '\t') {
+                        self.layout.indent = indent.to_string();
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// Collects the comments before a closing bracket.
+        fn attach_end(&mut self, pointer: &str) {
+            if !self.pending.is_empty() {
+                let end = std::mem::take(&mut self.pending);
+                self.comments(pointer).end = end;
+            }
+        }
+
+        fn value(&mut self, pointer: &str) -> Result<json::Value, Error> {
+            match self.peek() {
+                Some('{') => self.object(pointer),
+                Some('[') => self.array(pointer),
+                Some(quote @ ('"' | '\'')) => {
+                    self.pos += 1;
+                    Ok(json::Value::String(self.string(quote)?))
+                }
+                Some(_) => self.literal(),
+                None => Err(self.error("expected a value, found the end")),
+            }
+        }
+
+        fn object(&mut self, pointer: &str) -> Result<json::Value, Error> {
+            self.expect('{')?;
+            let mut fields = serde_json::Map::new();
+            let mut order = Vec::new();
+            loop {
+                self.before_item(pointer, fields.is_empty())?;
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    self.attach_end(pointer);
+                    break;
+                }
+
+                let (key, bare) = match self.peek() {
+                    Some(quote @ ('"' | '\'')) => {
+                        self.pos += 1;
+                        (self.string(quote)?, false)
+                    }
+                    _ => {
+                        let rest = &self.text[self.pos..];
+                        let len = rest
+                            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                            .unwrap_or(rest.len());
+                        if !is_identifier(&rest[..len]) {
+                            return Err(self.error("expected a key"));
+                        }
+                        self.pos += len;
+                        (rest[..len].to_string(), true)
+                    }
+                };
+                let child = pointer_push(pointer, &key);
+                if bare {
+                    self.layout.bare.insert(child.clone());
+                }
+
+                self.before_item(&child, false)?;
+                self.expect(':')?;
+                self.before_item(&child, false)?;
+                self.attach_before(&child);
+
+                // as in JavaScript, when a key is set twice, the last value wins
+                let v = self.value(&child)?;
+                if fields.insert(key.clone(), v).is_none() {
+                    order.push(key);
+                }
+
+                if !self.separator(&child)? && self.peek() != Some('}') {
+                    self.before_item(pointer, false)?;
+                    return Err(self.error("expected ',' or '}'"));
+                }
+            }
+
+            self.layout.order.insert(pointer.to_string(), order);
+            Ok(json::Value::Object(fields))
+        }
+
+        fn array(&mut self, pointer: &str) -> Result<json::Value, Error> {
+            self.expect('[')?;
+            let mut vs = Vec::new();
+            loop {
+                self.before_item(pointer, vs.is_empty())?;
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    self.attach_end(pointer);
+                    break;
+                }
+
+                let child = pointer_push(pointer, &vs.len().to_string());
+                self.attach_before(&child);
+                vs.push(self.value(&child)?);
+
+                if !self.separator(&child)? && self.peek() != Some(']') {
+                    self.before_item(pointer, false)?;
+                    return Err(self.error("expected ',' or ']'"));
+                }
+            }
+            Ok(json::Value::Array(vs))
+        }
+
+        /// Reads a string up to its closing `quote`.
+        fn string(&mut self, quote: char) -> Result<String, Error> {
+            let mut s = String::new();
+            loop {
+                let Some(c) = self.next() else {
+                    return Err(self.error("the string is never closed"));
+                };
+                match c {
+                    c if c == quote => return Ok(s),
+                    '\n' | '\r' => return Err(self.error("strings can't span lines")),
+                    '\\' => self.escape(&mut s)?,
+                    c => s.push(c),
+                }
+            }
+        }
+
+        fn escape(&mut self, s: &mut String) -> Result<(), Error> {
+            let Some(c) = self.next() else {
+                return Err(self.error("the string is never closed"));
+            };
+            match c {
+                'b' => s.push('\x08'),
+                'f' => s.push('\x0c'),
+                'n' => s.push('\n'),
+                'r' => s.push('\r'),
+                't' => s.push('\t'),
+                'v' => s.push('\x0b'),
+                '0' if !self.peek().is_some_and(|c| c.is_ascii_digit()) => s.push('\0'),
+                'x' => {
+                    let unit = self.hex(2)?;
+                    s.push(char::from(unit as u8));
+                }
+                'u' => {
+                    let mut units = vec![self.hex(4)?];
+                    // characters outside the BMP are written as surrogate pairs
+                    if (0xD800..0xDC00).contains(&units[0])
+                        && self.text[self.pos..].starts_with("\\u")
+                    {
+                        self.pos += 2;
+                        units.push(self.hex(4)?);
+                    }
+                    for c in char::decode_utf16(units) {
+                        s.push(c.map_err(|e| self.error(e.to_string()))?);
+                    }
+                }
+                // line continuations
+                '\r' => {
+                    if self.peek() == Some('\n') {
+                        self.pos += 1;
+                    }
+                }
+                '\n' | '\u{2028}' | '\u{2029}' => (),
+                c if c.is_ascii_digit() => {
+                    return Err(self.error(format!("'\\{c}' isn't a valid escape")));
+                }
+                c => s.push(c),
+            }
+            Ok(())
+        }
+
+        fn hex(&mut self, digits: usize) -> Result<u16, Error> {
+            let rest = &self.text[self.pos..];
+            let hex = rest.get(..digits).unwrap_or(rest);
+            match u16::from_str_radix(hex, 16) {
+                Ok(unit) if hex.len() == digits && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    self.pos += digits;
+                    Ok(unit)
+                }
+                _ => Err(self.error("expected hexadecimal digits in an escape")),
+            }
+        }
+
+        /// Numbers, `true`, `false`, and `null`.
+        fn literal(&mut self) -> Result<json::Value, Error> {
+            let rest = &self.text[self.pos..];
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-')))
+                .unwrap_or(rest.len());
+            let token = &rest[..len];
+
+            let v = match token {
+                "null" => Some(json::Value::Null),
+                "true" => Some(json::Value::Bool(true)),
+                "false" => Some(json::Value::Bool(false)),
+                _ => non_finite(token)
+                    .or_else(|| number(token))
+                    .map(json::Value::Number),
+            };
+            match v {
+                Some(v) => {
+                    self.pos += len;
+                    Ok(v)
+                }
+                None if token.is_empty() => {
+                    let c = self.peek().unwrap_or_default();
+                    Err(self.error(format!("expected a value, found '{c}'")))
+                }
+                None => Err(self.error(format!("'{token}' isn't a valid value"))),
+            }
+        }
+    }
+
+    /// Reads a JSON5 number, e.g., `+1`, `.5`, `5.`, or `0xFF`.
+    fn number(token: &str) -> Option<serde_json::Number> {
+        let (sign, digits) = match token.as_bytes().first()? {
+            b'-' => ("-", &token[1..]),
+            b'+' => ("", &token[1..]),
+            _ => ("", token),
+        };
+
+        if let Some(hex) = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            let n = u128::from_str_radix(hex, 16).ok()?;
+            return serde_json::Number::from_str(&format!("{sign}{n}")).ok();
+        }
+
+        let mut digits = digits.to_string();
+        if digits.starts_with('.') {
+            digits.insert(0, '0');
+        }
+        if let Some(i) = digits.find('.')
+            && !digits[i + 1..].starts_with(|c: char| c.is_ascii_digit())
+        {
+            digits.insert(i + 1, '0');
+        }
+        serde_json::Number::from_str(&format!("{sign}{digits}")).ok()
+    }
+
+    /// Reads `Infinity`, `-Infinity`, or `NaN` (which may have a sign).
+    ///
+    /// JSON has no such numbers, but serde_json keeps a number's text as is
+    /// (see `arbitrary_precision`), so they're floats that we write bare.
+    fn non_finite(token: &str) -> Option<serde_json::Number> {
+        let n = match token.strip_prefix('+').unwrap_or(token) {
+            "NaN" | "-NaN" => "NaN",
+            n @ ("Infinity" | "-Infinity") => n,
+            _ => return None,
+        };
+        Some(serde_json::Number::from_string_unchecked(n.to_string()))
+    }
+
+    /// Writes `v`, which is at `pointer`, indented to `depth`.
+    fn write_value(
+        out: &mut String,
+        v: &json::Value,
+        pointer: &str,
+        depth: usize,
+        layout: &Layout,
+    ) {
+        let (open, close, items) = match v {
+            json::Value::Object(fields) => {
+                let no_keys = Vec::new();
+                let order = layout.order.get(pointer).unwrap_or(&no_keys);
+                let fields = fields.iter().map(|(key, v)| (key.clone(), v)).collect();
+                let items = in_order(fields, order)
+                    .into_iter()
+                    .map(|(key, v)| {
+                        let child = pointer_push(pointer, &key);
+                        let key = if layout.bare.contains(&child) && is_identifier(&key) {
+                            key
+                        } else {
+                            serde_json::to_string(&key).expect("strings serialize")
+                        };
+                        (child, Some(key), v)
+                    })
+                    .collect::<Vec<_>>();
+                ('{', '}', items)
+            }
+            json::Value::Array(vs) => {
+                let items = vs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (pointer_push(pointer, &i.to_string()), None, v))
+                    .collect();
+                ('[', ']', items)
+            }
+            v => {
+                out.push_str(&serde_json::to_string(v).expect("values serialize"));
+                return;
+            }
+        };
+
+        let end = layout
+            .comments
+            .get(pointer)
+            .map(|comments| comments.end.as_slice())
+            .unwrap_or_default();
+        if items.is_empty() && end.is_empty() {
+            out.push(open);
+            out.push(close);
+            return;
+        }
+
+        let indent = layout.indent.repeat(depth + 1);
+        out.push(open);
+        out.push('\n');
+        let last = items.len().saturating_sub(1);
+        for (i, (child, key, v)) in items.into_iter().enumerate() {
+            let comments = layout.comments.get(&child);
+            for comment in comments.iter().flat_map(|comments| &comments.before) {
+                let _ = writeln!(out, "{indent}{comment}");
+            }
+            out.push_str(&indent);
+            if let Some(key) = key {
+                let _ = write!(out, "{key}: ");
+            }
+            write_value(out, v, &child, depth + 1, layout);
+            if i != last {
+                out.push(',');
+            }
+            if let Some(comment) = comments.and_then(|comments| comments.after.as_ref()) {
+                let _ = write!(out, " {comment}");
+            }
+            out.push('\n');
+        }
+        for comment in end {
+            let _ = writeln!(out, "{indent}{comment}");
+        }
+        out.push_str(&layout.indent.repeat(depth));
+        out.push(close);
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            self.0.is_dir()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            if let json::Value::Number(n) = &self.0
+                && non_finite(n.as_str()).is_some()
+            {
+                let nl = if config.add_newlines { "\n" } else { "" };
+                return Node::String(Typ::Float, format!("{n}{nl}"));
+            }
+
+            match self.0.node(config) {
+                Node::String(t, s) => Node::String(t, s),
+                Node::Bytes(b) => Node::Bytes(b),
+                Node::List(vs) => Node::List(vs.into_iter().map(|v| Value(v, None)).collect()),
+                Node::Map(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v, None))).collect())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, config: &Config) -> Self {
+            match typ {
+                Typ::Float if let Some(n) = non_finite(&contents) => {
+                    Value(json::Value::Number(n), None)
+                }
+                typ => Value(json::Value::from_string(typ, contents, config), None),
+            }
+        }
+
+        fn from_bytes<T>(contents: T, config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(json::Value::from_bytes(contents, config), None)
+        }
+
+        fn from_list_dir(files: Vec<Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_list_dir(files.into_iter().map(|v| v.0).collect(), config),
+                None,
+            )
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_named_dir(
+                    files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+                    config,
+                ),
+                None,
+            )
+        }
+
+        fn layout(&self) -> Option<String> {
+            Some(tag_layout("json5", self.1.as_ref()?.to_json()))
+        }
+
+        fn with_layout(self, layout: &str) -> Self {
+            match tagged_layout("json5", layout).and_then(|layout| Layout::from_json(&layout)) {
+                Some(layout) => Value(self.0, Some(Arc::new(layout))),
+                None => self,
+            }
+        }
+
+        fn from_reader(
+            mut reader: Box<dyn std::io::Read>,
+            _config: &Config,
+        ) -> Result<Self, Error> {
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+
+            let mut parser = Parser {
+                text: &text,
+                pos: 0,
+                layout: Layout::default(),
+                pending: Vec::new(),
+            };
+            parser.before_item("", false)?;
+            parser.attach_before("");
+            let v = parser.value("")?;
+            let trailing = parser.trivia()?;
+            if parser.pos < text.len() {
+                return Err(parser.error("there's more after the end of the document"));
+            }
+
+            let mut trailing = trailing.into_iter().peekable();
+            if let Some((false, _)) = trailing.peek()
+                && let Some((_newline, comment)) = trailing.next()
+            {
+                parser.comments("").after = Some(comment);
+            }
+            let mut layout = parser.layout;
+            layout.trailing = trailing.map(|(_newline, comment)| comment).collect();
+            Ok(Value(v, Some(Arc::new(layout))))
+        }
+
+        /// Documents are always laid out over several lines, so there's room
+        /// for comments.
+        fn to_writer(
+            &self,
+            mut writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            let default = Layout::default();
+            let layout = self.1.as_deref().unwrap_or(&default);
+            let mut out = String::new();
+            if let Some(comments) = layout.comments.get("") {
+                for comment in &comments.before {
+                    let _ = writeln!(out, "{comment}");
+                }
+            }
+            write_value(&mut out, &self.0, "", 0, layout);
+            if let Some(comment) = layout.comments.get("").and_then(|c| c.after.as_ref()) {
+                let _ = write!(out, " {comment}");
+            }
+            out.push('\n');
+            for comment in &layout.trailing {
+                let _ = writeln!(out, "{comment}");
+            }
+
+            writer.write_all(out.as_bytes())?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Dotenv Nodelike implementation
+///
+/// A document is a named directory of `KEY=value` lines, which may start
+/// with `export`. Unquoted values are typed automatically, and a `#` after
+/// a space starts a comment; quoted values are strings. Single quotes are
+/// literal; double quotes have escapes (e.g., `\n`) and can span lines.
+/// Variables (e.g., `${HOME}`) aren't expanded. Comments are dropped. Below the
+/// root, everything is just JSON, but dotenv files can't hold directories.
+pub mod dotenv {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    /// A document, with its layout if we read it.
+    #[derive(Clone, Debug, Default)]
+    pub struct Value(json::Value, Option<Arc<Layout>>);
+
+    /// How a document was written.
+    #[derive(Debug, Default)]
+    struct Layout {
+        /// The keys, in order
+        keys: Vec<String>,
+        /// The keys with `export` in front of them
+        exported: HashSet<String>,
+        /// The keys whose values were in single quotes
+        single_quoted: HashSet<String>,
+    }
+
+    impl Layout {
+        fn to_json(&self) -> json::Value {
+            serde_json::json!({
+                "keys": self.keys,
+                "exported": self.exported,
+                "single_quoted": self.single_quoted,
+            })
+        }
+
+        fn from_json(layout: &json::Value) -> Option<Layout> {
+            Some(Layout {
+                keys: strings(layout.get("keys")?)?,
+                exported: strings(layout.get("exported")?)?.into_iter().collect(),
+                single_quoted: strings(layout.get("single_quoted")?)?.into_iter().collect(),
+            })
+        }
+    }
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.0.fmt(f)
+        }
+    }
+
+    fn check_key(key: &str) -> Result<(), String> {
+        if key.is_empty()
+            || key.starts_with('#')
+            || key.contains(|c: char| c == '=' || c.is_whitespace())
+        {
+            Err(format!("'{key}' isn't a valid dotenv key"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads a value starting at the beginning of `rest`, returning it (and
+    /// whether it was quoted, and with what) and how many lines it took up.
+    fn value(rest: &str) -> Result<(String, Option<char>, usize), String> {
+        let quote = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => {
+                let line = rest.lines().next().unwrap_or_default();
+                let line = match line.find(" #").or_else(|| line.find("\t#")) {
+                    Some(i) => &line[..i],
+                    None => line,
+                };
+                return Ok((line.trim().to_string(), None, 1));
+            }
+        };
+
+        let mut s = String::new();
+        let mut chars = rest[1..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    let after = rest[i + 2..].lines().next().unwrap_or_default().trim();
+                    if !after.is_empty() && !after.starts_with('#') {
+                        return Err(format!("there's '{after}' after the closing quote"));
+                    }
+                    let lines = rest[..i + 2].matches('\n').count() + 1;
+                    return Ok((s, Some(quote), lines));
+                }
+                '\\' if quote == '"' => match chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, c @ ('\\' | '"' | '\'' | '$'))) => s.push(c),
+                    Some((_, c)) => {
+                        s.push('\\');
+                        s.push(c);
+                    }
+                    None => break,
+                },
+                c => s.push(c),
+            }
+        }
+        Err(format!("the {quote}-quoted value never ends"))
+    }
+
+    /// Writes a value, quoting it if reading it back unquoted would change
+    /// it---or if a shell would split it, since dotenv files are often
+    /// `source`d.
+    fn quote(v: &json::Value, single_quoted: bool) -> Option<String> {
+        let text = json::text(v)?;
+        let json::Value::String(s) = v else {
+            return Some(text);
+        };
+
+        let auto = json::from_text(s.clone(), &Config::default());
+        let plain = matches!(auto, json::Value::String(_))
+            && !s.contains(char::is_whitespace)
+            && !s.contains(['"', '\'', '\\', '#', '$']);
+        if plain {
+            Some(text)
+        } else if single_quoted && !s.contains(['\'', '\n', '\r']) {
+            Some(format!("'{s}'"))
+        } else {
+            let mut quoted = String::from('"');
+            for c in s.chars() {
+                match c {
+                    '\n' => quoted.push_str("\\n"),
+                    '\r' => quoted.push_str("\\r"),
+                    '\\' | '"' | '$' => {
+                        quoted.push('\\');
+                        quoted.push(c);
+                    }
+                    c => quoted.push(c),
+                }
+            }
+            quoted.push('"');
+            Some(quoted)
+        }
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            self.0.is_dir()
+        }
+
+        fn size(&self) -> usize {
+            self.0.size()
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            match self.0.node(config) {
+                Node::String(t, s) => Node::String(t, s),
+                Node::Bytes(b) => Node::Bytes(b),
+                Node::List(vs) => Node::List(vs.into_iter().map(|v| Value(v, None)).collect()),
+                Node::Map(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v, None))).collect())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, config: &Config) -> Self {
+            match typ {
+                Typ::Auto => Value(json::from_text(contents, config), None),
+                typ => Value(json::Value::from_string(typ, contents, config), None),
+            }
+        }
+
+        fn from_bytes<T>(contents: T, config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(json::Value::from_bytes(contents, config), None)
+        }
+
+        fn from_list_dir(files: Vec<Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_list_dir(files.into_iter().map(|v| v.0).collect(), config),
+                None,
+            )
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, config: &Config) -> Self {
+            Value(
+                json::Value::from_named_dir(
+                    files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+                    config,
+                ),
+                None,
+            )
+        }
+
+        fn layout(&self) -> Option<String> {
+            Some(tag_layout("dotenv", self.1.as_ref()?.to_json()))
+        }
+
+        fn with_layout(self, layout: &str) -> Self {
+            match tagged_layout("dotenv", layout).and_then(|layout| Layout::from_json(&layout)) {
+                Some(layout) => Value(self.0, Some(Arc::new(layout))),
+                None => self,
+            }
+        }
+
+        /// As in a shell, when a key is set twice, the last value wins.
+        fn from_reader(mut reader: Box<dyn std::io::Read>, config: &Config) -> Result<Self, Error> {
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+
+            let parse_error = |line: usize, message: String| Error::Parse {
+                format: Format::Dotenv,
+                position: Some(Position { line, column: 1 }),
+                message,
+            };
+
+            let mut layout = Layout::default();
+            let mut fields = serde_json::Map::new();
+            let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+            let mut i = 0;
+            while i < lines.len() {
+                let line_no = i + 1;
+                let line = lines[i].trim_start();
+                if line.trim().is_empty() || line.starts_with('#') {
+                    i += 1;
+                    continue;
+                }
+
+                let (exported, line) = match line.strip_prefix("export") {
+                    Some(rest) if rest.starts_with([' ', '\t']) => (true, rest.trim_start()),
+                    _ => (false, line),
+                };
+                let Some((key, rest)) = line.split_once('=') else {
+                    return Err(parse_error(line_no, "expected 'KEY=value'".into()));
+                };
+                let key = key.trim_end();
+                check_key(key).map_err(|message| parse_error(line_no, message))?;
+
+                // a quoted value can span lines, so it gets the rest of the text
+                let rest = rest.trim_start_matches([' ', '\t']);
+                let rest = if rest.starts_with(['"', '\'']) {
+                    lines[i..].concat()[lines[i].len() - rest.len()..].to_string()
+                } else {
+                    rest.to_string()
+                };
+                let (value, quote, len) =
+                    value(&rest).map_err(|message| parse_error(line_no, message))?;
+                i += len;
+
+                let value = if quote.is_some() {
+                    json::Value::String(value)
+                } else {
+                    json::from_text(value, config)
+                };
+                fields.insert(key.to_string(), value);
+                if !layout.keys.iter().any(|k| k == key) {
+                    layout.keys.push(key.to_string());
+                }
+                if exported {
+                    layout.exported.insert(key.to_string());
+                }
+                if quote == Some('\'') {
+                    layout.single_quoted.insert(key.to_string());
+                }
+            }
+
+            Ok(Value(json::Value::Object(fields), Some(Arc::new(layout))))
+        }
+
+        /// New keys are exported if every key we read was.
+        fn to_writer(
+            &self,
+            mut writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            let serialize_error = |message: String| Error::Serialize {
+                format: Format::Dotenv,
+                message,
+            };
+            let json::Value::Object(fields) = &self.0 else {
+                return Err(serialize_error(
+                    "documents must be named directories".into(),
+                ));
+            };
+
+            let default = Layout::default();
+            let layout = self.1.as_deref().unwrap_or(&default);
+            let export_all = !layout.keys.is_empty() && layout.keys.len() == layout.exported.len();
+
+            // check everything before writing anything
+            let mut out = String::new();
+            let fields = fields.iter().map(|(key, v)| (key.clone(), v)).collect();
+            for (key, v) in in_order(fields, &layout.keys) {
+                check_key(&key).map_err(serialize_error)?;
+                let Some(value) = quote(v, layout.single_quoted.contains(&key)) else {
+                    return Err(serialize_error(format!(
+                        "'{key}' is a directory, but dotenv files only hold values"
+                    )));
+                };
+
+                let known = layout.keys.contains(&key);
+                if layout.exported.contains(&key) || (!known && export_all) {
+                    out.push_str("export ");
+                }
+                out.push_str(&format!("{key}={value}\n"));
+            }
+
+            writer.write_all(out.as_bytes())?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// TOML Nodelike implementation
+pub mod toml {
+    use super::*;
+    use base64::Engine;
+    use serde_toml::Value as Toml;
+
+    #[derive(Clone, Debug)]
+    pub struct Value(serde_toml::Value);
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.0.fmt(f)
+        }
+    }
+
+    impl Default for Value {
+        fn default() -> Self {
+            Value(Toml::String("".into()))
+        }
+    }
+
+    fn toml_size(v: &Toml) -> usize {
+        match v {
+            Toml::Boolean(_)
+            | Toml::Datetime(_)
+            | Toml::Float(_)
+            | Toml::Integer(_)
+            | Toml::String(_) => 1,
+            Toml::Array(vs) => vs.iter().map(toml_size).sum::<usize>() + 1,
+            Toml::Table(fvs) => fvs.iter().map(|(_, v)| toml_size(v)).sum::<usize>() + 1,
+        }
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            match self.0 {
+                Toml::Table(_) | Toml::Array(_) => true,
+                Toml::String(_)
+                | Toml::Integer(_)
+                | Toml::Float(_)
+                | Toml::Boolean(_)
+                | Toml::Datetime(_) => false,
+            }
+        }
+
+        fn size(&self) -> usize {
+            toml_size(&self.0)
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            let nl = if config.add_newlines { "\n" } else { "" };
+
+            match self.0 {
+                Toml::Boolean(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
+                Toml::Datetime(s) => Node::String(Typ::Datetime, s.to_string()),
+                Toml::Float(n) => Node::String(Typ::Float, format!("{n}{nl}")),
+                Toml::Integer(n) => Node::String(Typ::Integer, format!("{n}{nl}")),
+                Toml::String(s) => {
+                    if config.try_decode_base64
+                        && let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(&s)
+                    {
+                        return Node::Bytes(bytes);
+                    }
+
+                    Node::String(Typ::String, if s.ends_with('\n') { s } else { s + nl })
+                }
+                Toml::Array(vs) => Node::List(vs.into_iter().map(Value).collect()),
+                Toml::Table(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(f, v)| (f, Value(v))).collect())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, _config: &Config) -> Self {
+            let v = match typ {
+                Typ::Auto => {
+                    if contents == "true" {
+                        Toml::Boolean(true)
+                    } else if contents == "false" {
+                        Toml::Boolean(false)
+                    } else if let Ok(n) = i64::from_str(&contents) {
+                        Toml::Integer(n)
+                    } else if let Ok(n) = f64::from_str(&contents) {
+                        Toml::Float(n)
+                    } else if let Ok(datetime) = str::parse(&contents) {
+                        Toml::Datetime(datetime)
+                    } else {
+                        Toml::String(contents)
+                    }
+                }
+                Typ::Boolean => {
+                    if contents == "true" {
+                        Toml::Boolean(true)
+                    } else if contents == "false" {
+                        Toml::Boolean(false)
+                    } else {
+                        debug!("string '{contents}' tagged as boolean");
+                        Toml::String(contents)
+                    }
+                }
+                Typ::Bytes => panic!("from_string called at typ::bytes"),
+                Typ::Datetime => match str::parse(&contents) {
+                    Ok(datetime) => Toml::Datetime(datetime),
+                    Err(e) => {
+                        debug!("string '{contents}' tagged as datetime, didn't parse: {e}");
+                        Toml::String(contents)
+                    }
+                },
+                Typ::Float => {
+                    if let Ok(n) = f64::from_str(&contents) {
+                        Toml::Float(n)
+                    } else {
+                        debug!("string '{contents}' tagged as float");
+                        Toml::String(contents)
+                    }
+                }
+                Typ::Integer => {
+                    if let Ok(n) = i64::from_str(&contents) {
+                        Toml::Integer(n)
+                    } else {
+                        debug!("string '{contents}' tagged as float");
+                        Toml::String(contents)
+                    }
+                }
+                Typ::Null => {
+                    if contents.is_empty() {
+                        Toml::String(contents)
+                    } else {
+                        debug!("string '{contents}' tagged as null");
+                        Toml::String(contents)
+                    }
+                }
+                Typ::String => Toml::String(contents),
+            };
+
+            Value(v)
+        }
+
+        fn from_bytes<T>(contents: T, _config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(Toml::String(
+                base64::engine::general_purpose::STANDARD.encode(contents),
+            ))
+        }
+
+        fn from_list_dir(files: Vec<Self>, _config: &Config) -> Self {
+            Value(Toml::Array(files.into_iter().map(|v| v.0).collect()))
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, _config: &Config) -> Self {
+            Value(Toml::Table(
+                files.into_iter().map(|(f, v)| (f, v.0)).collect(),
+            ))
+        }
+
+        fn from_reader(
+            mut reader: Box<dyn std::io::Read>,
+            _config: &Config,
+        ) -> Result<Self, Error> {
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+            serde_toml::from_str(&text)
+                .map(Value)
+                .map_err(|e| Error::Parse {
+                    format: Format::Toml,
+                    position: e
+                        .span()
+                        .map(|span| Position::from_offset(&text, span.start)),
+                    message: e.message().to_string(),
+                })
+        }
+
+        fn to_writer(
+            &self,
+            mut writer: Box<dyn std::io::Write>,
+            config: &Config,
+        ) -> Result<(), Error> {
+            let text = if config.pretty {
+                serde_toml::to_string_pretty(&self.0)
+            } else {
+                serde_toml::to_string(&self.0)
+            }
+            .map_err(|e| Error::Serialize {
+                format: Format::Toml,
+                message: e.to_string(),
+            })?;
+            writer.write_all(text.as_bytes())?;
+            Ok(())
+        }
+
+        fn to_string_preserving(&self, original: &str, config: &Config) -> Option<String> {
+            let old = serde_toml::from_str(original).map(Value).ok()?;
+            let changes = diff(old, self.clone(), config)
+                .into_iter()
+                .map(|change| change.map(|v| v.0))
+                .collect();
+            let text = preserve::toml(original, changes)?;
+            preserve::verified(text, self, config)
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// YAML Nodelike implementation
+pub mod yaml {
+    use super::*;
+    use base64::Engine;
+    use std::collections::{BTreeSet, HashSet};
+    use std::hash::{Hash, Hasher};
+    use std::sync::{Arc, LazyLock};
+    use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+    use yaml_rust::scanner::{Marker, TScalarStyle, Token, TokenType};
+    use yaml_rust::{EmitError, Yaml};
+
+    /// A YAML value, along with the anchors it (or its children) might refer
+    /// to.
+    ///
+    /// Anchored values and their aliases are both represented in the tree as
+    /// `Yaml::Alias(id)`, where `id` identifies the anchor by its name (see
+    /// `anchor_id`); the name and value live in the `Anchors`, which are shared
+    /// between all of the values in a document.
+    ///
+    /// A stream of several documents is a `Yaml::Array` of them, marked as a
+    /// stream by the last field (see `Nodelike::is_documents`).
+    #[derive(Clone, Debug)]
+    pub struct Value(Yaml, Anchors, bool);
+
+    type Anchors = Arc<BTreeMap<usize, Anchored>>;
+
+    #[derive(Clone, Debug)]
+    struct Anchored {
+        name: String,
+        value: Yaml,
+        /// Set on values built by `Nodelike::from_anchored`, which supersede
+        /// loaded values (which might have been edited since).
+        fresh: bool,
+    }
+
+    static NO_ANCHORS: LazyLock<Anchors> = LazyLock::new(|| Arc::new(BTreeMap::new()));
+
+    /// A `Yaml::Alias` only has room for a number, so anchors are identified
+    /// by a hash of their names; values built separately (e.g., by
+    /// `Nodelike::from_symlink`) agree on them without sharing any state.
+    fn anchor_id(name: &str) -> usize {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        name.hash(&mut hasher);
+        hasher.finish() as usize
+    }
+
+    impl Value {
+        fn new(v: Yaml) -> Self {
+            Value(v, NO_ANCHORS.clone(), false)
+        }
+
+        /// The value, looking through aliases.
+        fn resolved(&self) -> &Yaml {
+            resolve(&self.0, &self.1)
+        }
+
+        /// Writes the value out: one document, or each of a stream's in turn.
+        fn emit(&self, writer: &mut dyn std::fmt::Write) -> EmitResult {
+            match &self.0 {
+                Yaml::Array(docs) if self.2 => {
+                    for (i, doc) in docs.iter().enumerate() {
+                        if i > 0 {
+                            writeln!(writer)?;
+                        }
+                        Emitter::new(writer, &self.1).dump(doc)?;
+                    }
+                    Ok(())
+                }
+                v => Emitter::new(writer, &self.1).dump(v),
+            }
+        }
+    }
+
+    fn resolve<'a>(v: &'a Yaml, anchors: &'a BTreeMap<usize, Anchored>) -> &'a Yaml {
+        match v {
+            Yaml::Alias(id) => match anchors.get(id) {
+                Some(anchored) => &anchored.value,
+                None => &Yaml::BadValue,
+            },
+            v => v,
+        }
+    }
+
+    /// Collects the anchors used by the elements of a collection. Fresh
+    /// anchored values win out over loaded ones.
+    fn merge_anchors<'a>(all: impl Iterator<Item = &'a Anchors>) -> Anchors {
+        let mut merged = NO_ANCHORS.clone();
+        for anchors in all {
+            if anchors.is_empty() || Arc::ptr_eq(&merged, anchors) {
+                continue;
+            }
+            if merged.is_empty() {
+                merged = anchors.clone();
+                continue;
+            }
+
+            let merged = Arc::make_mut(&mut merged);
+            for (id, anchored) in anchors.iter() {
+                match merged.get(id) {
+                    Some(existing) if existing.fresh || !anchored.fresh => (),
+                    Some(_) | None => {
+                        merged.insert(*id, anchored.clone());
+                    }
+                }
+            }
+        }
+        merged
+    }
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            self.emit(f).map_err(|e| match e {
+                EmitError::FmtError(e) => e,
+                EmitError::BadHashmapKey => {
+                    panic!("unrecoverable YAML display error: BadHashmapKey")
+                }
+            })
+        }
+    }
+
+    impl Default for Value {
+        fn default() -> Self {
+            Value::new(Yaml::Null)
+        }
+    }
+
+    fn yaml_size(v: &Yaml) -> usize {
+        match v {
+            Yaml::Real(_)
+            | Yaml::Integer(_)
+            | Yaml::String(_)
+            | Yaml::Boolean(_)
+            | Yaml::Null
+            | Yaml::BadValue
+            | Yaml::Alias(_) => 1,
+            Yaml::Array(vs) => vs.iter().map(yaml_size).sum::<usize>() + 1,
+            Yaml::Hash(fvs) => fvs.iter().map(|(_, v)| yaml_size(v)).sum::<usize>() + 1,
+        }
+    }
+
+    fn yaml_key_to_string(v: Yaml, anchors: &BTreeMap<usize, Anchored>) -> String {
+        match v {
+            Yaml::Boolean(b) => format!("{b}"),
+            Yaml::Real(s) => s,
+            Yaml::Integer(n) => format!("{n}"),
+            Yaml::String(s) => s,
+            Yaml::Alias(n) => match anchors.get(&n) {
+                Some(anchored) => yaml_key_to_string(anchored.value.clone(), anchors),
+                None => format!("alias{n}"),
+            },
+            Yaml::Array(vs) => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                vs.hash(&mut hasher);
+                format!("{}", hasher.finish())
+            }
+            Yaml::Hash(fvs) => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                fvs.hash(&mut hasher);
+                format!("{}", hasher.finish())
+            }
+            Yaml::Null => "null".into(),
+            Yaml::BadValue => "badvalue".into(),
+        }
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            match self.resolved() {
+                Yaml::Array(_) | Yaml::Hash(_) => true,
+                Yaml::Real(_)
+                | Yaml::Integer(_)
+                | Yaml::String(_)
+                | Yaml::Boolean(_)
+                | Yaml::Alias(_)
+                | Yaml::Null
+                | Yaml::BadValue => false,
+            }
+        }
+
+        fn size(&self) -> usize {
+            yaml_size(self.resolved())
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            let nl = if config.add_newlines { "\n" } else { "" };
+            let Value(v, anchors, _) = self;
+
+            match v {
+                Yaml::Null => Node::String(Typ::Null, "".into()),
+                Yaml::Boolean(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
+                Yaml::Real(s) => Node::String(Typ::Float, s + nl),
+                Yaml::Integer(n) => Node::String(Typ::Integer, format!("{n}{nl}")),
+                Yaml::String(s) => {
+                    if config.try_decode_base64
+                        && let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(&s)
+                    {
+                        return Node::Bytes(bytes);
+                    }
+
+                    Node::String(Typ::String, if s.ends_with('\n') { s } else { s + nl })
+                }
+                Yaml::Array(vs) => {
+                    let vs = vs.into_iter().map(|v| Value(v, anchors.clone(), false));
+                    Node::List(vs.collect())
+                }
+                Yaml::Hash(fvs) => Node::Map(
+                    fvs.into_iter()
+                        .map(|(k, v)| {
+                            let name = yaml_key_to_string(k, &anchors);
+                            (name, Value(v, anchors.clone(), false))
+                        })
+                        .collect(),
+                ),
+                Yaml::Alias(n) => match anchors.get(&n) {
+                    Some(anchored) => Value(anchored.value.clone(), anchors, false).node(config),
+                    None => Node::Bytes("bad YAML alias".into()),
+                },
+                Yaml::BadValue => Node::Bytes("bad YAML value".into()),
+            }
+        }
+
+        fn anchor(&self) -> Option<String> {
+            match self.0 {
+                Yaml::Alias(id) => self.1.get(&id).map(|anchored| anchored.name.clone()),
+                _ => None,
+            }
+        }
+
+        fn from_anchored(name: &str, v: Self, _config: &Config) -> Self {
+            let id = anchor_id(name);
+            if let Yaml::Alias(existing) = v.0
+                && existing == id
+            {
+                return v;
+            }
+
+            let Value(v, anchors, _) = v;
+            let mut anchors = (*anchors).clone();
+            anchors.insert(
+                id,
+                Anchored {
+                    name: name.to_string(),
+                    value: v,
+                    fresh: true,
+                },
+            );
+            Value(Yaml::Alias(id), Arc::new(anchors), false)
+        }
+
+        /// Links to anchored targets are aliases; the target's anchored value
+        /// fills in the alias when the document is assembled.
+        fn from_symlink(reference: String, anchor: Option<&str>, config: &Config) -> Self {
+            match anchor {
+                Some(name) => Value::new(Yaml::Alias(anchor_id(name))),
+                None => {
+                    let mut fields = BTreeMap::new();
+                    fields.insert(
+                        "$ref".into(),
+                        Self::from_string(Typ::String, reference, config),
+                    );
+                    Self::from_named_dir(fields, config)
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, _config: &Config) -> Self {
+            let v = match typ {
+                Typ::Auto => {
+                    if contents.is_empty() {
+                        Yaml::Null
+                    } else if contents == "true" {
+                        Yaml::Boolean(true)
+                    } else if contents == "false" {
+                        Yaml::Boolean(false)
+                    } else if let Ok(n) = i64::from_str(&contents) {
+                        Yaml::Integer(n)
+                    } else if let Ok(_n) = f64::from_str(&contents) {
+                        Yaml::Real(contents)
+                    } else {
+                        Yaml::String(contents)
+                    }
+                }
+                Typ::Boolean => {
+                    if contents == "true" {
+                        Yaml::Boolean(true)
+                    } else if contents == "false" {
+                        Yaml::Boolean(false)
+                    } else {
+                        debug!("string '{contents}' tagged as boolean");
+                        Yaml::String(contents)
+                    }
+                }
+                Typ::Bytes => panic!("from_string called at typ::bytes"),
+                Typ::Datetime => Yaml::String(contents),
+                Typ::Float => {
+                    if let Ok(_n) = f64::from_str(&contents) {
+                        Yaml::Real(contents)
+                    } else {
+                        debug!("string '{contents}' tagged as float");
+                        Yaml::String(contents)
+                    }
+                }
+                Typ::Integer => {
+                    if let Ok(n) = i64::from_str(&contents) {
+                        Yaml::Integer(n)
+                    } else {
+                        debug!("string '{contents}' tagged as float");
+                        Yaml::String(contents)
+                    }
+                }
+                Typ::Null => {
+                    if contents.is_empty() {
+                        Yaml::Null
+                    } else {
+                        debug!("string '{contents}' tagged as null");
+                        Yaml::String(contents)
+                    }
+                }
+                Typ::String => Yaml::String(contents),
+            };
+
+            Value::new(v)
+        }
+
+        fn from_bytes<T>(contents: T, _config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value::new(Yaml::String(
+                base64::engine::general_purpose::STANDARD.encode(contents),
+            ))
+        }
+
+        fn from_list_dir(vs: Vec<Self>, _config: &Config) -> Self {
+            let anchors = merge_anchors(vs.iter().map(|v| &v.1));
+            Value(
+                Yaml::Array(vs.into_iter().map(|v| v.0).collect()),
+                anchors,
+                false,
+            )
+        }
+
+        fn from_named_dir(fvs: BTreeMap<String, Self>, config: &Config) -> Self {
+            let anchors = merge_anchors(fvs.values().map(|v| &v.1));
+            Value(
+                Yaml::Hash(
+                    fvs.into_iter()
+                        .map(|(k, v)| (Value::from_string(Typ::String, k, config).0, v.0))
+                        .collect(),
+                ),
+                anchors,
+                false,
+            )
+        }
+
+        fn is_documents(&self) -> bool {
+            self.2
+        }
+
+        /// Streams only make sense at the root: inside another value, a
+        /// stream is just a list.
+        fn from_documents(docs: Vec<Self>, config: &Config) -> Self {
+            let Value(v, anchors, _) = Value::from_list_dir(docs, config);
+            Value(v, anchors, true)
+        }
+
+        fn from_reader(
+            mut reader: Box<dyn std::io::Read>,
+            _config: &Config,
+        ) -> Result<Self, Error> {
+            let mut text = String::new();
+            let _len = reader.read_to_string(&mut text)?;
+            let mut loader = Loader::new(&text);
+            Parser::new(text.chars())
+                .load(&mut loader, true)
+                .map(|()| {
+                    let anchors = Arc::new(loader.anchors);
+                    let mut vs = loader.docs;
+                    if vs.len() == 1 {
+                        Value(vs.pop().unwrap(), anchors, false)
+                    } else {
+                        Value(Yaml::Array(vs), anchors, true)
+                    }
+                })
+                .map_err(|e| {
+                    let marker = *e.marker();
+                    let suffix = format!(" at line {} column {}", marker.line(), marker.col() + 1);
+                    Error::Parse {
+                        format: Format::Yaml,
+                        position: Some(Position {
+                            line: marker.line(),
+                            column: marker.col() + 1,
+                        }),
+                        message: strip_position(e.to_string(), &suffix),
+                    }
+                })
+        }
+
+        fn to_writer(
+            &self,
+            mut writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            let mut text = String::new();
+            self.emit(&mut text).map_err(|e| Error::Serialize {
+                format: Format::Yaml,
+                message: match e {
+                    EmitError::FmtError(e) => e.to_string(),
+                    EmitError::BadHashmapKey => "bad hashmap key".into(),
+                },
+            })?;
+            writer.write_all(text.as_bytes())?;
+            Ok(())
+        }
+
+        /// Changes to scalars and to block collections' entries are made
+        /// in place (see `preserve::yaml`); anything else, e.g., adding to
+        /// a flow collection, means rendering the whole document.
+        fn to_string_preserving(&self, original: &str, config: &Config) -> Option<String> {
+            let reader = Box::new(std::io::Cursor::new(original.to_string()));
+            let old = Value::from_reader(reader, config).ok()?;
+
+            let changes = diff(old, self.clone(), config)
+                .into_iter()
+                .map(|change| change.map(|v| resolve(&v.0, &v.1).clone()))
+                .collect();
+            let text = preserve::yaml(original, changes)?;
+            preserve::verified(text, self, config)
+        }
+    }
+
+    /// Loads documents like `yaml_rust::YamlLoader`, but rather than copying
+    /// anchored values into each alias, it keeps them in `anchors`.
+    struct Loader {
+        docs: Vec<Yaml>,
+        /// (current node, anchor id) pairs
+        doc_stack: Vec<(Yaml, usize)>,
+        key_stack: Vec<Yaml>,
+        /// The parser numbers anchors from 1 in the order it sees them; this
+        /// maps those numbers to names.
+        names: Vec<String>,
+        anchors: BTreeMap<usize, Anchored>,
+    }
+
+    impl Loader {
+        fn new(text: &str) -> Self {
+            // the parser forgets anchor names, so we find them ourselves
+            let mut names = vec![String::new()];
+            if text.contains('&') {
+                let mut seen = HashSet::new();
+                for Token(_, token) in yaml_rust::scanner::Scanner::new(text.chars()) {
+                    if let TokenType::Anchor(name) = token {
+                        // anchors can be redefined, but our names must be unique
+                        let mut unique = name.clone();
+                        let mut i = 1;
+                        while !seen.insert(unique.clone()) {
+                            i += 1;
+                            unique = format!("{name}{i}");
+                        }
+                        names.push(unique);
+                    }
+                }
+            }
+
+            Loader {
+                docs: Vec::new(),
+                doc_stack: Vec::new(),
+                key_stack: Vec::new(),
+                names,
+                anchors: BTreeMap::new(),
+            }
+        }
+
+        /// Is the next node a mapping key? We don't use aliases for keys.
+        fn at_key(&self) -> bool {
+            matches!(self.doc_stack.last(), Some((Yaml::Hash(_), _)))
+                && self.key_stack.last().is_some_and(|k| k.is_badvalue())
+        }
+
+        fn insert_new_node(&mut self, (mut node, aid): (Yaml, usize)) {
+            // valid anchor ids start from 1
+            if aid > 0
+                && let Some(name) = self.names.get(aid)
+            {
+                let id = anchor_id(name);
+                self.anchors.insert(
+                    id,
+                    Anchored {
+                        name: name.clone(),
+                        value: node.clone(),
+                        fresh: false,
+                    },
+                );
+                if !self.at_key() {
+                    node = Yaml::Alias(id);
+                }
+            }
+
+            if self.doc_stack.is_empty() {
+                self.doc_stack.push((node, 0));
+            } else {
+                let parent = self.doc_stack.last_mut().unwrap();
+                match parent {
+                    (Yaml::Array(v), _) => v.push(node),
+                    (Yaml::Hash(h), _) => {
+                        let cur_key = self.key_stack.last_mut().unwrap();
+                        if cur_key.is_badvalue() {
+                            *cur_key = node;
+                        } else {
+                            let key = std::mem::replace(cur_key, Yaml::BadValue);
+                            h.insert(key, node);
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
+    impl MarkedEventReceiver for Loader {
+        fn on_event(&mut self, ev: Event, _: Marker) {
+            match ev {
+                Event::DocumentEnd => match self.doc_stack.len() {
+                    // empty document
+                    0 => self.docs.push(Yaml::BadValue),
+                    1 => self.docs.push(self.doc_stack.pop().unwrap().0),
+                    _ => unreachable!(),
+                },
+                Event::SequenceStart(aid) => {
+                    self.doc_stack.push((Yaml::Array(Vec::new()), aid));
+                }
+                Event::MappingStart(aid) => {
+                    self.doc_stack.push((Yaml::Hash(Default::default()), aid));
+                    self.key_stack.push(Yaml::BadValue);
+                }
+                Event::SequenceEnd => {
+                    let node = self.doc_stack.pop().unwrap();
+                    self.insert_new_node(node);
+                }
+                Event::MappingEnd => {
+                    self.key_stack.pop().unwrap();
+                    let node = self.doc_stack.pop().unwrap();
+                    self.insert_new_node(node);
+                }
+                Event::Scalar(v, style, aid, tag) => {
+                    let node = if style != TScalarStyle::Plain {
+                        Yaml::String(v)
+                    } else if let Some(TokenType::Tag(handle, suffix)) = tag {
+                        if handle == "!!" {
+                            match suffix.as_ref() {
+                                "bool" => match v.parse::<bool>() {
+                                    Ok(b) => Yaml::Boolean(b),
+                                    Err(_) => Yaml::BadValue,
+                                },
+                                "int" => match v.parse::<i64>() {
+                                    Ok(n) => Yaml::Integer(n),
+                                    Err(_) => Yaml::BadValue,
+                                },
+                                "float" => match Yaml::from_str(&v) {
+                                    Yaml::Real(_) | Yaml::Integer(_) => Yaml::Real(v),
+                                    _ => Yaml::BadValue,
+                                },
+                                "null" => match v.as_ref() {
+                                    "~" | "null" => Yaml::Null,
+                                    _ => Yaml::BadValue,
+                                },
+                                _ => Yaml::String(v),
+                            }
+                        } else {
+                            Yaml::String(v)
+                        }
+                    } else {
+                        Yaml::from_str(&v)
+                    };
+                    self.insert_new_node((node, aid));
+                }
+                Event::Alias(aid) => {
+                    let node = match self
+                        .names
+                        .get(aid)
+                        .and_then(|name| self.anchors.get_key_value(&anchor_id(name)))
+                    {
+                        Some((_, anchored)) if self.at_key() => anchored.value.clone(),
+                        Some((id, _)) => Yaml::Alias(*id),
+                        None => Yaml::BadValue,
+                    };
+                    self.insert_new_node((node, 0));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Emits documents like `yaml_rust::YamlEmitter`, but also writes anchors
+    /// and aliases for values that are used more than once.
+    struct Emitter<'a> {
+        writer: &'a mut dyn std::fmt::Write,
+        level: isize,
+        anchors: &'a BTreeMap<usize, Anchored>,
+        /// How many times each anchor is used
+        uses: BTreeMap<usize, usize>,
+        /// Anchors that have already been written
+        written: BTreeSet<usize>,
+    }
+
+    type EmitResult = Result<(), EmitError>;
+
+    impl<'a> Emitter<'a> {
+        fn new(
+            writer: &'a mut dyn std::fmt::Write,
+            anchors: &'a BTreeMap<usize, Anchored>,
+        ) -> Self {
+            Emitter {
+                writer,
+                level: -1,
+                anchors,
+                uses: BTreeMap::new(),
+                written: BTreeSet::new(),
+            }
+        }
+
+        fn dump(&mut self, doc: &Yaml) -> EmitResult {
+            self.count_uses(doc);
+            writeln!(self.writer, "---")?;
+            self.level = -1;
+            self.emit_node(doc)
+        }
+
+        fn count_uses(&mut self, v: &Yaml) {
+            let anchors = self.anchors;
+            match v {
+                Yaml::Alias(id) => {
+                    let uses = self.uses.entry(*id).or_insert(0);
+                    *uses += 1;
+                    if *uses == 1
+                        && let Some(anchored) = anchors.get(id)
+                    {
+                        self.count_uses(&anchored.value);
+                    }
+                }
+                Yaml::Array(vs) => vs.iter().for_each(|v| self.count_uses(v)),
+                Yaml::Hash(fvs) => fvs.iter().for_each(|(k, v)| {
+                    self.count_uses(k);
+                    self.count_uses(v);
+                }),
+                _ => (),
+            }
+        }
+
+        fn write_indent(&mut self) -> EmitResult {
+            for _ in 0..self.level.max(0) {
+                write!(self.writer, "  ")?;
+            }
+            Ok(())
+        }
+
+        /// Emits a value without an anchor, using `yaml_rust`'s emitter for
+        /// scalars (and its quoting rules).
+        fn emit_node(&mut self, node: &Yaml) -> EmitResult {
+            match resolve(node, self.anchors) {
+                Yaml::Array(v) => self.emit_array(v),
+                Yaml::Hash(h) => self.emit_hash(h),
+                scalar => {
+                    let mut text = String::new();
+                    yaml_rust::YamlEmitter::new(&mut text).dump(scalar)?;
+                    let text = text.strip_prefix("---\n").unwrap_or(&text);
+                    self.writer.write_str(text)?;
+                    Ok(())
+                }
+            }
+        }
+
+        fn emit_array(&mut self, v: &[Yaml]) -> EmitResult {
+            if v.is_empty() {
+                write!(self.writer, "[]")?;
+            } else {
+                self.level += 1;
+                for (cnt, x) in v.iter().enumerate() {
+                    if cnt > 0 {
+                        writeln!(self.writer)?;
+                        self.write_indent()?;
+                    }
+                    write!(self.writer, "-")?;
+                    self.emit_val(true, x)?;
+                }
+                self.level -= 1;
+            }
+            Ok(())
+        }
+
+        fn emit_hash(&mut self, h: &yaml_rust::yaml::Hash) -> EmitResult {
+            if h.is_empty() {
+                self.writer.write_str("{}")?;
             } else {
-                serde_toml::to_string(&self.0).unwrap()
+                self.level += 1;
+                for (cnt, (k, v)) in h.iter().enumerate() {
+                    let k = resolve(k, self.anchors);
+                    let complex_key = matches!(k, Yaml::Hash(_) | Yaml::Array(_));
+                    if cnt > 0 {
+                        writeln!(self.writer)?;
+                        self.write_indent()?;
+                    }
+                    if complex_key {
+                        write!(self.writer, "?")?;
+                        self.emit_val(true, k)?;
+                        writeln!(self.writer)?;
+                        self.write_indent()?;
+                        write!(self.writer, ":")?;
+                        self.emit_val(true, v)?;
+                    } else {
+                        // `yaml_rust` quotes merge keys, which makes them
+                        // ordinary keys
+                        if k.as_str() == Some("<<") {
+                            write!(self.writer, "<<")?;
+                        } else {
+                            self.emit_node(k)?;
+                        }
+                        write!(self.writer, ":")?;
+                        self.emit_val(false, v)?;
+                    }
+                }
+                self.level -= 1;
+            }
+            Ok(())
+        }
+
+        /// Emits a value following a ":" or "-"; see
+        /// `yaml_rust::YamlEmitter::emit_val`.
+        fn emit_val(&mut self, inline: bool, val: &Yaml) -> EmitResult {
+            let anchors = self.anchors;
+            match val {
+                Yaml::Alias(id) => match anchors.get(id) {
+                    Some(anchored) if self.uses.get(id).copied().unwrap_or(0) > 1 => {
+                        let name = &anchored.name;
+                        if !self.written.insert(*id) {
+                            write!(self.writer, " *{name}")?;
+                            return Ok(());
+                        }
+
+                        // nodes with anchors can't use the compact notation
+                        write!(self.writer, " &{name}")?;
+                        match &anchored.value {
+                            Yaml::Array(v) if !v.is_empty() => {
+                                self.newline_indent()?;
+                                self.emit_array(v)
+                            }
+                            Yaml::Hash(h) if !h.is_empty() => {
+                                self.newline_indent()?;
+                                self.emit_hash(h)
+                            }
+                            v => {
+                                write!(self.writer, " ")?;
+                                self.emit_node(v)
+                            }
+                        }
+                    }
+                    Some(anchored) => self.emit_val(inline, &anchored.value),
+                    None => {
+                        write!(self.writer, " ~")?;
+                        Ok(())
+                    }
+                },
+                Yaml::Array(v) => {
+                    if inline || v.is_empty() {
+                        write!(self.writer, " ")?;
+                    } else {
+                        self.newline_indent()?;
+                    }
+                    self.emit_array(v)
+                }
+                Yaml::Hash(h) => {
+                    if inline || h.is_empty() {
+                        write!(self.writer, " ")?;
+                    } else {
+                        self.newline_indent()?;
+                    }
+                    self.emit_hash(h)
+                }
+                _ => {
+                    write!(self.writer, " ")?;
+                    self.emit_node(val)
+                }
+            }
+        }
+
+        fn newline_indent(&mut self) -> EmitResult {
+            writeln!(self.writer)?;
+            self.level += 1;
+            self.write_indent()?;
+            self.level -= 1;
+            Ok(())
+        }
+    }
+}
+
+/// Parse errors for the binary formats, which have no lines or columns.
+fn binary_parse_error(format: Format, offset: Option<usize>, message: &str) -> Error {
+    Error::Parse {
+        format,
+        position: None,
+        message: match offset {
+            Some(offset) => format!("{message} at byte {offset}"),
+            None => message.to_string(),
+        },
+    }
+}
+
+/// Where the JSON Pointer `pointer` is, for error messages.
+fn location(pointer: &str) -> String {
+    if pointer.is_empty() {
+        "the root".into()
+    } else {
+        format!("'{pointer}'")
+    }
+}
+
+/// Binary formats read a single value; anything left over is an error.
+fn check_trailing(format: Format, reader: &mut impl std::io::Read) -> Result<(), Error> {
+    let mut byte = [0u8; 1];
+    if reader.read(&mut byte)? != 0 {
+        return Err(binary_parse_error(
+            format,
+            None,
+            "trailing data after the end of the value",
+        ));
+    }
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// CBOR Nodelike implementation
+pub mod cbor {
+    use super::*;
+    use crate::diff::pointer_push;
+    use ciborium::Value as Cbor;
+    use ciborium::value::Integer;
+
+    /// The CBOR tag for RFC 3339 date/time strings.
+    const TAG_DATETIME: u64 = 0;
+
+    #[derive(Clone, Debug)]
+    pub struct Value(Cbor);
+
+    /// Writes CBOR's diagnostic notation (RFC 8949 §8).
+    fn cbor_fmt(v: &Cbor, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match v {
+            Cbor::Integer(n) => write!(f, "{}", i128::from(*n)),
+            Cbor::Bytes(bs) => {
+                write!(f, "h'")?;
+                for b in bs {
+                    write!(f, "{b:02x}")?;
+                }
+                write!(f, "'")
+            }
+            Cbor::Float(n) => write!(f, "{n:?}"),
+            Cbor::Text(s) => write!(f, "{s:?}"),
+            Cbor::Bool(b) => write!(f, "{b}"),
+            Cbor::Null => write!(f, "null"),
+            Cbor::Tag(t, v) => {
+                write!(f, "{t}(")?;
+                cbor_fmt(v, f)?;
+                write!(f, ")")
+            }
+            Cbor::Array(vs) => {
+                write!(f, "[")?;
+                for (i, v) in vs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    cbor_fmt(v, f)?;
+                }
+                write!(f, "]")
+            }
+            Cbor::Map(fvs) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in fvs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    cbor_fmt(k, f)?;
+                    write!(f, ": ")?;
+                    cbor_fmt(v, f)?;
+                }
+                write!(f, "}}")
+            }
+            _ => write!(f, "undefined"),
+        }
+    }
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            cbor_fmt(&self.0, f)
+        }
+    }
+
+    impl Default for Value {
+        fn default() -> Self {
+            Value(Cbor::Null)
+        }
+    }
+
+    fn cbor_size(v: &Cbor) -> usize {
+        match v {
+            Cbor::Array(vs) => vs.iter().map(cbor_size).sum::<usize>() + 1,
+            Cbor::Map(fvs) => fvs.iter().map(|(_, v)| cbor_size(v)).sum::<usize>() + 1,
+            Cbor::Tag(_, v) => cbor_size(v),
+            _ => 1,
+        }
+    }
+
+    /// Files and directories can't hold tags (other than datetimes) or keys
+    /// that aren't text, so rather than lose them when saving, we refuse to
+    /// load them.
+    fn check(v: &Cbor, pointer: &str) -> Result<(), String> {
+        match v {
+            Cbor::Tag(TAG_DATETIME, v) if matches!(**v, Cbor::Text(_)) => Ok(()),
+            Cbor::Tag(t, _) => Err(format!(
+                "{} has tag {t}, which isn't supported",
+                location(pointer)
+            )),
+            Cbor::Array(vs) => vs
+                .iter()
+                .enumerate()
+                .try_for_each(|(i, v)| check(v, &pointer_push(pointer, &i.to_string()))),
+            Cbor::Map(fvs) => fvs.iter().try_for_each(|(k, v)| match k {
+                Cbor::Text(k) => check(v, &pointer_push(pointer, k)),
+                k => Err(format!(
+                    "{} has the key {}, but only text keys are supported",
+                    location(pointer),
+                    Value(k.clone())
+                )),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn cbor_key_to_string(v: Cbor) -> String {
+        match v {
+            Cbor::Text(s) => s,
+            Cbor::Integer(n) => format!("{}", i128::from(n)),
+            Cbor::Tag(_, v) => cbor_key_to_string(*v),
+            v => Value(v).to_string(),
+        }
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            match &self.0 {
+                Cbor::Array(_) | Cbor::Map(_) => true,
+                Cbor::Tag(_, v) => matches!(**v, Cbor::Array(_) | Cbor::Map(_)),
+                _ => false,
+            }
+        }
+
+        fn size(&self) -> usize {
+            cbor_size(&self.0)
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            let nl = if config.add_newlines { "\n" } else { "" };
+
+            match self.0 {
+                Cbor::Null => Node::String(Typ::Null, "".into()),
+                Cbor::Bool(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
+                Cbor::Integer(n) => Node::String(Typ::Integer, format!("{}{nl}", i128::from(n))),
+                Cbor::Float(n) => Node::String(Typ::Float, format!("{n:?}{nl}")),
+                Cbor::Text(s) => {
+                    Node::String(Typ::String, if s.ends_with('\n') { s } else { s + nl })
+                }
+                Cbor::Bytes(bs) => Node::Bytes(bs),
+                Cbor::Array(vs) => Node::List(vs.into_iter().map(Value).collect()),
+                Cbor::Map(fvs) => Node::Map(
+                    fvs.into_iter()
+                        .map(|(k, v)| (cbor_key_to_string(k), Value(v)))
+                        .collect(),
+                ),
+                Cbor::Tag(TAG_DATETIME, v) if matches!(*v, Cbor::Text(_)) => {
+                    let Cbor::Text(s) = *v else { unreachable!() };
+                    Node::String(Typ::Datetime, s + nl)
+                }
+                Cbor::Tag(t, v) => {
+                    debug!("dropping CBOR tag {t}");
+                    Value(*v).node(config)
+                }
+                v => {
+                    debug!("unsupported CBOR value {}", Value(v));
+                    Node::String(Typ::Null, "".into())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, _config: &Config) -> Self {
+            let v = match typ {
+                Typ::Auto => {
+                    if contents.is_empty() {
+                        Cbor::Null
+                    } else if contents == "true" {
+                        Cbor::Bool(true)
+                    } else if contents == "false" {
+                        Cbor::Bool(false)
+                    } else if let Some(n) = i128::from_str(&contents)
+                        .ok()
+                        .and_then(|n| Integer::try_from(n).ok())
+                    {
+                        Cbor::Integer(n)
+                    } else if let Ok(n) = f64::from_str(&contents) {
+                        Cbor::Float(n)
+                    } else {
+                        Cbor::Text(contents)
+                    }
+                }
+                Typ::Boolean => {
+                    if contents == "true" {
+                        Cbor::Bool(true)
+                    } else if contents == "false" {
+                        Cbor::Bool(false)
+                    } else {
+                        debug!("string '{contents}' tagged as boolean");
+                        Cbor::Text(contents)
+                    }
+                }
+                Typ::Bytes => panic!("from_string called at typ::bytes"),
+                Typ::Datetime => Cbor::Tag(TAG_DATETIME, Box::new(Cbor::Text(contents))),
+                Typ::Float => {
+                    if let Ok(n) = f64::from_str(&contents) {
+                        Cbor::Float(n)
+                    } else {
+                        debug!("string '{contents}' tagged as float");
+                        Cbor::Text(contents)
+                    }
+                }
+                Typ::Integer => {
+                    if let Some(n) = i128::from_str(&contents)
+                        .ok()
+                        .and_then(|n| Integer::try_from(n).ok())
+                    {
+                        Cbor::Integer(n)
+                    } else {
+                        debug!("string '{contents}' tagged as integer");
+                        Cbor::Text(contents)
+                    }
+                }
+                Typ::Null => {
+                    if contents.is_empty() {
+                        Cbor::Null
+                    } else {
+                        debug!("string '{contents}' tagged as null");
+                        Cbor::Text(contents)
+                    }
+                }
+                Typ::String => Cbor::Text(contents),
             };
-            writer.write_all(text.as_bytes()).unwrap();
+
+            Value(v)
+        }
+
+        fn from_bytes<T>(contents: T, _config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(Cbor::Bytes(contents.as_ref().to_vec()))
+        }
+
+        fn from_list_dir(files: Vec<Self>, _config: &Config) -> Self {
+            Value(Cbor::Array(files.into_iter().map(|v| v.0).collect()))
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, _config: &Config) -> Self {
+            Value(Cbor::Map(
+                files
+                    .into_iter()
+                    .map(|(f, v)| (Cbor::Text(f), v.0))
+                    .collect(),
+            ))
+        }
+
+        fn from_reader(reader: Box<dyn std::io::Read>, _config: &Config) -> Result<Self, Error> {
+            use ciborium::de::Error as DeError;
+
+            let mut reader = std::io::BufReader::new(reader);
+            let v = ciborium::de::from_reader(&mut reader).map_err(|e| match e {
+                DeError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    binary_parse_error(Format::Cbor, None, "unexpected end of input")
+                }
+                DeError::Io(e) => Error::Io(e),
+                DeError::Syntax(offset) => {
+                    binary_parse_error(Format::Cbor, Some(offset), "invalid CBOR")
+                }
+                DeError::Semantic(offset, message) => {
+                    binary_parse_error(Format::Cbor, offset, &message)
+                }
+                DeError::RecursionLimitExceeded => {
+                    binary_parse_error(Format::Cbor, None, "nesting is too deep")
+                }
+            })?;
+            check_trailing(Format::Cbor, &mut reader)?;
+            check(&v, "").map_err(|message| binary_parse_error(Format::Cbor, None, &message))?;
+            Ok(Value(v))
+        }
+
+        fn to_writer(
+            &self,
+            writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            use ciborium::ser::Error as SerError;
+
+            let mut writer = std::io::BufWriter::new(writer);
+            ciborium::ser::into_writer(&self.0, &mut writer).map_err(|e| match e {
+                SerError::Io(e) => Error::Io(e),
+                SerError::Value(message) => Error::Serialize {
+                    format: Format::Cbor,
+                    message,
+                },
+            })?;
+            std::io::Write::flush(&mut writer)?;
+            Ok(())
         }
     }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
-/// YAML Nodelike implementation
-pub mod yaml {
+/// MessagePack Nodelike implementation
+pub mod msgpack {
     use super::*;
-    use base64::Engine;
-    use std::hash::{Hash, Hasher};
-    use yaml_rust::Yaml;
+    use crate::diff::pointer_push;
+    use rmpv::Value as MsgPack;
 
     #[derive(Clone, Debug)]
-    pub struct Value(Yaml);
+    pub struct Value(MsgPack);
 
     impl std::fmt::Display for Value {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-            let mut emitter = yaml_rust::YamlEmitter::new(f);
-            emitter.dump(&self.0).map_err(|e| match e {
-                yaml_rust::EmitError::FmtError(e) => e,
-                yaml_rust::EmitError::BadHashmapKey => {
-                    panic!("unrecoverable YAML display error: BadHashmapKey")
-                }
-            })
+            self.0.fmt(f)
         }
     }
 
     impl Default for Value {
         fn default() -> Self {
-            Value(Yaml::Null)
+            Value(MsgPack::Nil)
         }
     }
 
-    fn yaml_size(v: &Yaml) -> usize {
+    fn msgpack_size(v: &MsgPack) -> usize {
         match v {
-            Yaml::Real(_)
-            | Yaml::Integer(_)
-            | Yaml::String(_)
-            | Yaml::Boolean(_)
-            | Yaml::Null
-            | Yaml::BadValue
-            | Yaml::Alias(_) => 1,
-            Yaml::Array(vs) => vs.iter().map(yaml_size).sum::<usize>() + 1,
-            Yaml::Hash(fvs) => fvs.iter().map(|(_, v)| yaml_size(v)).sum::<usize>() + 1,
+            MsgPack::Nil
+            | MsgPack::Boolean(_)
+            | MsgPack::Integer(_)
+            | MsgPack::F32(_)
+            | MsgPack::F64(_)
+            | MsgPack::String(_)
+            | MsgPack::Binary(_)
+            | MsgPack::Ext(_, _) => 1,
+            MsgPack::Array(vs) => vs.iter().map(msgpack_size).sum::<usize>() + 1,
+            MsgPack::Map(fvs) => fvs.iter().map(|(_, v)| msgpack_size(v)).sum::<usize>() + 1,
         }
     }
 
-    fn yaml_key_to_string(v: Yaml) -> String {
+    /// Files and directories can't hold extension types or keys that aren't
+    /// strings, so rather than lose them when saving, we refuse to load them.
+    fn check(v: &MsgPack, pointer: &str) -> Result<(), String> {
         match v {
-            Yaml::Boolean(b) => format!("{b}"),
-            Yaml::Real(s) => s,
-            Yaml::Integer(n) => format!("{n}"),
-            Yaml::String(s) => s,
-            Yaml::Alias(n) => format!("alias{n}"),
-            Yaml::Array(vs) => {
-                let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                vs.hash(&mut hasher);
-                format!("{}", hasher.finish())
-            }
-            Yaml::Hash(fvs) => {
-                let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                fvs.hash(&mut hasher);
-                format!("{}", hasher.finish())
-            }
-            Yaml::Null => "null".into(),
-            Yaml::BadValue => "badvalue".into(),
+            MsgPack::Ext(t, _) => Err(format!(
+                "{} has extension type {t}, which isn't supported",
+                location(pointer)
+            )),
+            MsgPack::Array(vs) => vs
+                .iter()
+                .enumerate()
+                .try_for_each(|(i, v)| check(v, &pointer_push(pointer, &i.to_string()))),
+            MsgPack::Map(fvs) => fvs.iter().try_for_each(|(k, v)| match k {
+                MsgPack::String(k) => check(
+                    v,
+                    &pointer_push(pointer, &String::from_utf8_lossy(k.as_bytes())),
+                ),
+                k => Err(format!(
+                    "{} has the key {k}, but only string keys are supported",
+                    location(pointer)
+                )),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn msgpack_key_to_string(v: MsgPack) -> String {
+        match v {
+            MsgPack::String(s) => String::from_utf8_lossy(s.as_bytes()).into_owned(),
+            v => v.to_string(),
+        }
+    }
+
+    fn parse_integer(contents: &str) -> Option<MsgPack> {
+        if let Ok(n) = i64::from_str(contents) {
+            Some(MsgPack::from(n))
+        } else if let Ok(n) = u64::from_str(contents) {
+            Some(MsgPack::from(n))
+        } else {
+            None
         }
     }
 
     impl Nodelike for Value {
         fn is_dir(&self) -> bool {
-            match &self.0 {
-                Yaml::Array(_) | Yaml::Hash(_) => true,
-                Yaml::Real(_)
-                | Yaml::Integer(_)
-                | Yaml::String(_)
-                | Yaml::Boolean(_)
-                | Yaml::Alias(_)
-                | Yaml::Null
-                | Yaml::BadValue => false,
+            match self.0 {
+                MsgPack::Array(_) | MsgPack::Map(_) => true,
+                MsgPack::Nil
+                | MsgPack::Boolean(_)
+                | MsgPack::Integer(_)
+                | MsgPack::F32(_)
+                | MsgPack::F64(_)
+                | MsgPack::String(_)
+                | MsgPack::Binary(_)
+                | MsgPack::Ext(_, _) => false,
             }
         }
 
         fn size(&self) -> usize {
-            yaml_size(&self.0)
+            msgpack_size(&self.0)
         }
 
         fn node(self, config: &Config) -> Node<Self> {
             let nl = if config.add_newlines { "\n" } else { "" };
 
             match self.0 {
-                Yaml::Null => Node::String(Typ::Null, "".into()),
-                Yaml::Boolean(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
-                Yaml::Real(s) => Node::String(Typ::Float, s + nl),
-                Yaml::Integer(n) => Node::String(Typ::Integer, format!("{n}{nl}")),
-                Yaml::String(s) => {
-                    if config.try_decode_base64
-                        && let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(&s)
-                    {
-                        return Node::Bytes(bytes);
+                MsgPack::Nil => Node::String(Typ::Null, "".into()),
+                MsgPack::Boolean(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
+                MsgPack::Integer(n) => Node::String(Typ::Integer, format!("{n}{nl}")),
+                MsgPack::F32(n) => Node::String(Typ::Float, format!("{n:?}{nl}")),
+                MsgPack::F64(n) => Node::String(Typ::Float, format!("{n:?}{nl}")),
+                MsgPack::String(s) => {
+                    if s.is_str() {
+                        let s = s.into_str().unwrap();
+                        Node::String(Typ::String, if s.ends_with('\n') { s } else { s + nl })
+                    } else {
+                        debug!("MessagePack string isn't UTF-8, treating as bytes");
+                        Node::Bytes(s.into_bytes())
                     }
-
-                    Node::String(Typ::String, if s.ends_with('\n') { s } else { s + nl })
                 }
-                Yaml::Array(vs) => Node::List(vs.into_iter().map(Value).collect()),
-                Yaml::Hash(fvs) => Node::Map(
+                MsgPack::Binary(bs) => Node::Bytes(bs),
+                MsgPack::Ext(t, bs) => {
+                    debug!("dropping MessagePack extension type {t}");
+                    Node::Bytes(bs)
+                }
+                MsgPack::Array(vs) => Node::List(vs.into_iter().map(Value).collect()),
+                MsgPack::Map(fvs) => Node::Map(
                     fvs.into_iter()
-                        .map(|(k, v)| (yaml_key_to_string(k), Value(v)))
+                        .map(|(k, v)| (msgpack_key_to_string(k), Value(v)))
                         .collect(),
                 ),
-                // ??? 2021-06-21 support aliases w/hard links?
-                Yaml::Alias(n) => Node::Bytes(format!("alias{n}{nl}").into_bytes()),
-                Yaml::BadValue => Node::Bytes("bad YAML value".into()),
             }
         }
 
         fn from_string(typ: Typ, contents: String, _config: &Config) -> Self {
-            match typ {
+            let v = match typ {
                 Typ::Auto => {
                     if contents.is_empty() {
-                        Value(Yaml::Null)
+                        MsgPack::Nil
                     } else if contents == "true" {
-                        Value(Yaml::Boolean(true))
+                        MsgPack::Boolean(true)
                     } else if contents == "false" {
-                        Value(Yaml::Boolean(false))
-                    } else if let Ok(n) = i64::from_str(&contents) {
-                        Value(Yaml::Integer(n))
-                    } else if let Ok(_n) = f64::from_str(&contents) {
-                        Value(Yaml::Real(contents))
+                        MsgPack::Boolean(false)
+                    } else if let Some(n) = parse_integer(&contents) {
+                        n
+                    } else if let Ok(n) = f64::from_str(&contents) {
+                        MsgPack::F64(n)
                     } else {
-                        Value(Yaml::String(contents))
+                        MsgPack::from(contents)
                     }
                 }
                 Typ::Boolean => {
                     if contents == "true" {
-                        Value(Yaml::Boolean(true))
+                        MsgPack::Boolean(true)
                     } else if contents == "false" {
-                        Value(Yaml::Boolean(false))
+                        MsgPack::Boolean(false)
                     } else {
                         debug!("string '{contents}' tagged as boolean");
-                        Value(Yaml::String(contents))
+                        MsgPack::from(contents)
                     }
                 }
                 Typ::Bytes => panic!("from_string called at typ::bytes"),
-                Typ::Datetime => Value(Yaml::String(contents)),
+                Typ::Datetime => MsgPack::from(contents),
                 Typ::Float => {
-                    if let Ok(_n) = f64::from_str(&contents) {
-                        Value(Yaml::Real(contents))
+                    if let Ok(n) = f64::from_str(&contents) {
+                        MsgPack::F64(n)
                     } else {
                         debug!("string '{contents}' tagged as float");
-                        Value(Yaml::String(contents))
+                        MsgPack::from(contents)
                     }
                 }
-                Typ::Integer => {
-                    if let Ok(n) = i64::from_str(&contents) {
-                        Value(Yaml::Integer(n))
-                    } else {
-                        debug!("string '{contents}' tagged as float");
-                        Value(Yaml::String(contents))
+                Typ::Integer => match parse_integer(&contents) {
+                    Some(n) => n,
+                    None => {
+                        debug!("string '{contents}' tagged as integer");
+                        MsgPack::from(contents)
                     }
-                }
+                },
                 Typ::Null => {
                     if contents.is_empty() {
-                        Value(Yaml::Null)
+                        MsgPack::Nil
                     } else {
                         debug!("string '{contents}' tagged as null");
-                        Value(Yaml::String(contents))
+                        MsgPack::from(contents)
                     }
                 }
-                Typ::String => Value(Yaml::String(contents)),
-            }
+                Typ::String => MsgPack::from(contents),
+            };
+
+            Value(v)
         }
 
         fn from_bytes<T>(contents: T, _config: &Config) -> Self
         where
             T: AsRef<[u8]>,
         {
-            Value(Yaml::String(
-                base64::engine::general_purpose::STANDARD.encode(contents),
-            ))
+            Value(MsgPack::Binary(contents.as_ref().to_vec()))
         }
 
-        fn from_list_dir(vs: Vec<Self>, _config: &Config) -> Self {
-            Value(Yaml::Array(vs.into_iter().map(|v| v.0).collect()))
+        fn from_list_dir(files: Vec<Self>, _config: &Config) -> Self {
+            Value(MsgPack::Array(files.into_iter().map(|v| v.0).collect()))
         }
 
-        fn from_named_dir(fvs: BTreeMap<String, Self>, config: &Config) -> Self {
-            Value(Yaml::Hash(
-                fvs.into_iter()
-                    .map(|(k, v)| (Value::from_string(Typ::String, k, config).0, v.0))
+        fn from_named_dir(files: BTreeMap<String, Self>, _config: &Config) -> Self {
+            Value(MsgPack::Map(
+                files
+                    .into_iter()
+                    .map(|(f, v)| (MsgPack::from(f), v.0))
                     .collect(),
             ))
         }
 
-        fn from_reader(mut reader: Box<dyn std::io::Read>) -> Self {
-            let mut text = String::new();
-            let _len = reader.read_to_string(&mut text).unwrap();
-            yaml_rust::YamlLoader::load_from_str(&text)
-                .map(|vs| {
-                    Value(if vs.len() == 1 {
-                        vs.into_iter().next().unwrap()
+        fn from_reader(reader: Box<dyn std::io::Read>, _config: &Config) -> Result<Self, Error> {
+            use rmpv::decode::Error as DecodeError;
+
+            let mut reader = std::io::BufReader::new(reader);
+            let v = rmpv::decode::read_value(&mut reader).map_err(|e| match e {
+                DecodeError::InvalidMarkerRead(e) | DecodeError::InvalidDataRead(e)
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    binary_parse_error(Format::MsgPack, None, "unexpected end of input")
+                }
+                DecodeError::InvalidMarkerRead(e) | DecodeError::InvalidDataRead(e) => Error::Io(e),
+                DecodeError::DepthLimitExceeded => {
+                    binary_parse_error(Format::MsgPack, None, "nesting is too deep")
+                }
+            })?;
+            check_trailing(Format::MsgPack, &mut reader)?;
+            check(&v, "").map_err(|message| binary_parse_error(Format::MsgPack, None, &message))?;
+            Ok(Value(v))
+        }
+
+        fn to_writer(
+            &self,
+            writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            let mut writer = std::io::BufWriter::new(writer);
+            rmpv::encode::write_value(&mut writer, &self.0).map_err(|e| match e {
+                rmpv::encode::Error::InvalidMarkerWrite(e)
+                | rmpv::encode::Error::InvalidDataWrite(e) => Error::Io(e),
+            })?;
+            std::io::Write::flush(&mut writer)?;
+            Ok(())
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Property list Nodelike implementation
+///
+/// Reads XML, binary, and (old-style) ASCII plists; saving writes a binary plist
+/// if that's what we read, and XML otherwise. `<data>` is bytes and `<date>` is
+/// a datetime. Plists have no null, so nulls are empty strings. Binary plists'
+/// UIDs (e.g., from `NSKeyedArchiver`) are written the way `plutil` does, as
+/// a named directory holding an integer, `CF$UID`.
+pub mod plist {
+    use super::*;
+    use ::plist::{Date, Dictionary, Integer, Uid, Value as Plist};
+
+    /// The key of the dictionary that stands for a UID.
+    const UID: &str = "CF$UID";
+
+    /// A plist, and whether it's a document we read from a binary plist.
+    #[derive(Clone, Debug)]
+    pub struct Value(Plist, bool);
+
+    impl std::fmt::Display for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    impl Default for Value {
+        fn default() -> Self {
+            Value(Plist::String(String::new()), false)
+        }
+    }
+
+    fn plist_size(v: &Plist) -> usize {
+        match v {
+            Plist::Array(vs) => vs.iter().map(plist_size).sum::<usize>() + 1,
+            Plist::Dictionary(fvs) => fvs.values().map(plist_size).sum::<usize>() + 1,
+            Plist::Uid(_) => 2,
+            _ => 1,
+        }
+    }
+
+    fn parse_integer(contents: &str) -> Option<Integer> {
+        i64::from_str(contents)
+            .map(Integer::from)
+            .or_else(|_| u64::from_str(contents).map(Integer::from))
+            .ok()
+    }
+
+    /// Turns `CF$UID` dictionaries back into UIDs, for binary plists.
+    fn uids(v: Plist) -> Plist {
+        match v {
+            Plist::Array(vs) => Plist::Array(vs.into_iter().map(uids).collect()),
+            Plist::Dictionary(fields) => {
+                if fields.len() == 1
+                    && let Some(uid) = fields.get(UID).and_then(Plist::as_unsigned_integer)
+                {
+                    return Plist::Uid(Uid::new(uid));
+                }
+                Plist::Dictionary(fields.into_iter().map(|(k, v)| (k, uids(v))).collect())
+            }
+            v => v,
+        }
+    }
+
+    /// `plist`'s errors are only available as text, e.g., `InvalidXmlSyntax
+    /// (offset 12)`. We read from memory, so I/O errors mean the plist is
+    /// broken (e.g., an offset past the end).
+    fn plist_error(e: ::plist::Error, bytes: &[u8]) -> Error {
+        let e = match e.into_io() {
+            Ok(e) => return binary_parse_error(Format::Plist, None, &e.to_string()),
+            Err(e) => e,
+        };
+        if e.is_eof() {
+            return binary_parse_error(Format::Plist, None, "unexpected end of input");
+        }
+
+        let message = e.to_string();
+        let (kind, offset) = match message.rsplit_once(" (offset ") {
+            Some((kind, offset)) => (
+                kind.to_string(),
+                offset
+                    .strip_suffix(')')
+                    .and_then(|n| usize::from_str(n).ok()),
+            ),
+            None => (message, None),
+        };
+        // e.g., `InvalidXmlSyntax` is "invalid xml syntax"
+        let mut words = String::new();
+        for c in kind.chars() {
+            if c.is_uppercase() && !words.is_empty() {
+                words.push(' ');
+            }
+            words.extend(c.to_lowercase());
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(text) if !bytes.starts_with(b"bplist") => Error::Parse {
+                format: Format::Plist,
+                position: offset.map(|offset| Position::from_offset(text, offset)),
+                message: words,
+            },
+            _ => binary_parse_error(Format::Plist, offset, &words),
+        }
+    }
+
+    impl Nodelike for Value {
+        fn is_dir(&self) -> bool {
+            matches!(
+                self.0,
+                Plist::Array(_) | Plist::Dictionary(_) | Plist::Uid(_)
+            )
+        }
+
+        fn size(&self) -> usize {
+            plist_size(&self.0)
+        }
+
+        fn node(self, config: &Config) -> Node<Self> {
+            let nl = if config.add_newlines { "\n" } else { "" };
+
+            match self.0 {
+                Plist::Boolean(b) => Node::String(Typ::Boolean, format!("{b}{nl}")),
+                Plist::Integer(n) => Node::String(Typ::Integer, format!("{n}{nl}")),
+                Plist::Real(n) => Node::String(Typ::Float, format!("{n:?}{nl}")),
+                Plist::Date(d) => Node::String(Typ::Datetime, format!("{}{nl}", d.to_xml_format())),
+                Plist::String(s) => {
+                    Node::String(Typ::String, if s.ends_with('\n') { s } else { s + nl })
+                }
+                Plist::Data(bs) => Node::Bytes(bs),
+                Plist::Array(vs) => Node::List(vs.into_iter().map(|v| Value(v, false)).collect()),
+                Plist::Dictionary(fvs) => {
+                    Node::Map(fvs.into_iter().map(|(k, v)| (k, Value(v, false))).collect())
+                }
+                Plist::Uid(uid) => Node::Map(vec![(
+                    UID.into(),
+                    Value(Plist::Integer(Integer::from(uid.get())), false),
+                )]),
+                v => {
+                    debug!("unsupported plist value {v:?}");
+                    Node::String(Typ::String, "".into())
+                }
+            }
+        }
+
+        fn from_string(typ: Typ, contents: String, _config: &Config) -> Self {
+            let v = match typ {
+                Typ::Auto => {
+                    if contents == "true" {
+                        Plist::Boolean(true)
+                    } else if contents == "false" {
+                        Plist::Boolean(false)
+                    } else if let Some(n) = parse_integer(&contents) {
+                        Plist::Integer(n)
+                    } else if let Ok(n) = f64::from_str(&contents) {
+                        Plist::Real(n)
                     } else {
-                        Yaml::Array(vs)
-                    })
-                })
-                .expect("YAML")
+                        Plist::String(contents)
+                    }
+                }
+                Typ::Boolean => {
+                    if contents == "true" {
+                        Plist::Boolean(true)
+                    } else if contents == "false" {
+                        Plist::Boolean(false)
+                    } else {
+                        debug!("string '{contents}' tagged as boolean");
+                        Plist::String(contents)
+                    }
+                }
+                Typ::Bytes => panic!("from_string called at typ::bytes"),
+                Typ::Datetime => match Date::from_xml_format(&contents) {
+                    Ok(d) => Plist::Date(d),
+                    Err(_) => {
+                        debug!("string '{contents}' tagged as datetime");
+                        Plist::String(contents)
+                    }
+                },
+                Typ::Float => {
+                    if let Ok(n) = f64::from_str(&contents) {
+                        Plist::Real(n)
+                    } else {
+                        debug!("string '{contents}' tagged as float");
+                        Plist::String(contents)
+                    }
+                }
+                Typ::Integer => {
+                    if let Some(n) = parse_integer(&contents) {
+                        Plist::Integer(n)
+                    } else {
+                        debug!("string '{contents}' tagged as integer");
+                        Plist::String(contents)
+                    }
+                }
+                Typ::Null => {
+                    if !contents.is_empty() {
+                        debug!("string '{contents}' tagged as null");
+                    }
+                    Plist::String(contents)
+                }
+                Typ::String => Plist::String(contents),
+            };
+
+            Value(v, false)
         }
 
-        fn to_writer(&self, mut writer: Box<dyn std::io::Write>, _pretty: bool) {
-            let mut text = String::new();
-            let mut emitter = yaml_rust::YamlEmitter::new(&mut text);
-            emitter.dump(&self.0).unwrap();
-            writer.write_all(text.as_bytes()).unwrap();
+        fn from_bytes<T>(contents: T, _config: &Config) -> Self
+        where
+            T: AsRef<[u8]>,
+        {
+            Value(Plist::Data(contents.as_ref().to_vec()), false)
+        }
+
+        fn from_list_dir(files: Vec<Self>, _config: &Config) -> Self {
+            Value(
+                Plist::Array(files.into_iter().map(|v| v.0).collect()),
+                false,
+            )
+        }
+
+        fn from_named_dir(files: BTreeMap<String, Self>, _config: &Config) -> Self {
+            Value(
+                Plist::Dictionary(
+                    files
+                        .into_iter()
+                        .map(|(f, v)| (f, v.0))
+                        .collect::<Dictionary>(),
+                ),
+                false,
+            )
+        }
+
+        fn layout(&self) -> Option<String> {
+            self.1
+                .then(|| tag_layout("plist", serde_json::json!({ "binary": true })))
+        }
+
+        fn with_layout(self, layout: &str) -> Self {
+            match tagged_layout("plist", layout).and_then(|layout| layout.get("binary")?.as_bool())
+            {
+                Some(binary) => Value(self.0, binary),
+                None => self,
+            }
+        }
+
+        fn from_reader(
+            mut reader: Box<dyn std::io::Read>,
+            _config: &Config,
+        ) -> Result<Self, Error> {
+            let mut bytes = Vec::new();
+            let _len = reader.read_to_end(&mut bytes)?;
+
+            let v = Plist::from_reader(std::io::Cursor::new(&bytes))
+                .map_err(|e| plist_error(e, &bytes))?;
+            Ok(Value(v, bytes.starts_with(b"bplist")))
+        }
+
+        fn to_writer(
+            &self,
+            writer: Box<dyn std::io::Write>,
+            _config: &Config,
+        ) -> Result<(), Error> {
+            let mut writer = std::io::BufWriter::new(writer);
+            let written = if self.1 {
+                uids(self.0.clone()).to_writer_binary(&mut writer)
+            } else {
+                self.0.to_writer_xml(&mut writer)
+            };
+            written.map_err(|e| match e.into_io() {
+                Ok(e) => Error::Io(e),
+                Err(e) => Error::Serialize {
+                    format: Format::Plist,
+                    message: e.to_string(),
+                },
+            })?;
+            if !self.1 {
+                std::io::Write::write_all(&mut writer, b"\n")?;
+            }
+            std::io::Write::flush(&mut writer)?;
+            Ok(())
         }
     }
 }
@@ -0,0 +1,354 @@
+//! Patches: JSON Patch (RFC 6902) and JSON Merge Patch (RFC 7386).
+//!
+//! Patches are always JSON, but the documents they're made from and applied
+//! to can be in any format. Applying a patch works on the document's `Node`s,
+//! so types JSON doesn't have (e.g., TOML datetimes or CBOR bytes) survive.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value as Json};
+
+use super::config::{Config, ERROR_STATUS_FUSE, ERROR_STATUS_PARSE};
+use super::diff::{Change, diff, pointer_tokens};
+use super::{Node, Nodelike, Typ, convert, json};
+
+/// Errors from applying a patch.
+#[derive(Debug)]
+pub enum PatchError {
+    /// The patch isn't a well-formed JSON Patch.
+    Malformed(String),
+    /// An operation refers to a path that isn't in the document.
+    NoSuchPath(String),
+    /// A `test` operation failed.
+    TestFailed(String),
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            PatchError::Malformed(msg) => write!(f, "malformed patch: {msg}"),
+            PatchError::NoSuchPath(path) => write!(f, "no such path '{path}'"),
+            PatchError::TestFailed(path) => write!(f, "test failed at '{path}'"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl PatchError {
+    /// The exit status for a tool that fails with this error.
+    pub fn exit_status(&self) -> i32 {
+        match self {
+            PatchError::Malformed(_) => ERROR_STATUS_PARSE,
+            PatchError::NoSuchPath(_) | PatchError::TestFailed(_) => ERROR_STATUS_FUSE,
+        }
+    }
+}
+
+/// Converts `changes` (see `diff::diff`) into a JSON Patch.
+pub fn json_patch<V: Nodelike>(changes: Vec<Change<V>>, config: &Config) -> Json {
+    let op = |op: &str, path: String, value: Option<V>| {
+        let mut fields = Map::new();
+        fields.insert("op".into(), Json::String(op.into()));
+        fields.insert("path".into(), Json::String(path));
+        if let Some(value) = value {
+            fields.insert("value".into(), convert::<V, json::Value>(value, config));
+        }
+        Json::Object(fields)
+    };
+
+    Json::Array(
+        changes
+            .into_iter()
+            .map(|change| match change {
+                Change::Add(path, new) => op("add", path, Some(new)),
+                Change::Remove(path, _old) => op("remove", path, None),
+                Change::Replace(path, _old, new) => op("replace", path, Some(new)),
+            })
+            .collect(),
+    )
+}
+
+/// Computes a JSON Merge Patch that turns `old` into `new`.
+///
+/// Merge patches can't say anything about lists except to replace them
+/// entirely, and they can't set a field to `null`.
+pub fn merge_patch<V: Nodelike + Clone>(old: V, new: V, config: &Config) -> Json {
+    match (old.clone().node(config), new.clone().node(config)) {
+        (Node::Map(olds), Node::Map(news)) => {
+            let mut olds = olds.into_iter().collect::<BTreeMap<_, _>>();
+            let mut fields = Map::new();
+
+            for (name, new) in news {
+                match olds.remove(&name) {
+                    Some(old) => {
+                        if !diff(old.clone(), new.clone(), config).is_empty() {
+                            fields.insert(name, merge_patch(old, new, config));
+                        }
+                    }
+                    None => {
+                        fields.insert(name, convert::<V, json::Value>(new, config));
+                    }
+                }
+            }
+            for name in olds.into_keys() {
+                fields.insert(name, Json::Null);
+            }
+
+            Json::Object(fields)
+        }
+        _ => convert::<V, json::Value>(new, config),
+    }
+}
+
+/// Applies `patch` to `doc`: arrays are JSON Patches; anything else is a
+/// JSON Merge Patch.
+pub fn apply<V: Nodelike>(doc: V, patch: &Json, config: &Config) -> Result<V, PatchError> {
+    let documents = doc.is_documents();
+    let doc = Tree::from_value(doc, config);
+    let doc = match patch {
+        Json::Array(ops) => ops
+            .iter()
+            .try_fold(doc, |doc, op| apply_op(doc, op, config))?,
+        patch => merge(doc, patch, config),
+    };
+
+    // a stream of documents stays one, as long as it's still a list
+    match doc {
+        Tree::List(ts) if documents => Ok(V::from_documents(
+            ts.into_iter().map(|t| t.into_value(config)).collect(),
+            config,
+        )),
+        doc => Ok(doc.into_value(config)),
+    }
+}
+
+/// A whole document, as `Node`s all the way down.
+#[derive(Clone, Debug, PartialEq)]
+enum Tree {
+    String(Typ, String),
+    Bytes(Vec<u8>),
+    List(Vec<Tree>),
+    Map(Vec<(String, Tree)>),
+}
+
+impl Tree {
+    fn from_value<V: Nodelike>(v: V, config: &Config) -> Self {
+        match v.node(config) {
+            Node::String(t, mut s) => {
+                if config.add_newlines && s.ends_with('\n') {
+                    s.truncate(s.len() - 1);
+                }
+                Tree::String(t, s)
+            }
+            Node::Bytes(b) => Tree::Bytes(b),
+            Node::List(vs) => Tree::List(
+                vs.into_iter()
+                    .map(|v| Tree::from_value(v, config))
+                    .collect(),
+            ),
+            Node::Map(fvs) => Tree::Map(
+                fvs.into_iter()
+                    .map(|(f, v)| (f, Tree::from_value(v, config)))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn from_json(v: &Json, config: &Config) -> Self {
+        Tree::from_value(v.clone(), config)
+    }
+
+    fn into_value<V: Nodelike>(self, config: &Config) -> V {
+        match self {
+            Tree::String(t, s) => V::from_string(t, s, config),
+            Tree::Bytes(b) => V::from_bytes(b, config),
+            Tree::List(ts) => V::from_list_dir(
+                ts.into_iter().map(|t| t.into_value(config)).collect(),
+                config,
+            ),
+            Tree::Map(fts) => V::from_named_dir(
+                fts.into_iter()
+                    .map(|(f, t)| (f, t.into_value(config)))
+                    .collect(),
+                config,
+            ),
+        }
+    }
+
+    /// Finds the child named `token`.
+    fn child(&mut self, token: &str) -> Option<&mut Tree> {
+        match self {
+            Tree::Map(fields) => fields
+                .iter_mut()
+                .find(|(name, _)| name == token)
+                .map(|(_, t)| t),
+            Tree::List(elts) => elts.get_mut(index(token)?),
+            Tree::String(..) | Tree::Bytes(_) => None,
+        }
+    }
+
+    fn get(&mut self, tokens: &[String]) -> Option<&mut Tree> {
+        tokens.iter().try_fold(self, |t, token| t.child(token))
+    }
+}
+
+/// Parses a JSON Pointer into its (unescaped) tokens.
+fn tokens(pointer: &str) -> Result<Vec<String>, PatchError> {
+    pointer_tokens(pointer)
+        .ok_or_else(|| PatchError::Malformed(format!("'{pointer}' is not a JSON Pointer")))
+}
+
+/// Parses a list index; leading zeroes aren't allowed.
+fn index(token: &str) -> Option<usize> {
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn apply_op(mut doc: Tree, op: &Json, config: &Config) -> Result<Tree, PatchError> {
+    let field = |name: &str| -> Result<&Json, PatchError> {
+        op.get(name)
+            .ok_or_else(|| PatchError::Malformed(format!("operation without '{name}': {op}")))
+    };
+    let pointer = |name: &str| -> Result<(String, Vec<String>), PatchError> {
+        match field(name)? {
+            Json::String(path) => Ok((path.clone(), tokens(path)?)),
+            v => Err(PatchError::Malformed(format!(
+                "'{name}' is not a string: {v}"
+            ))),
+        }
+    };
+
+    let (path, at) = pointer("path")?;
+    match field("op")?.as_str() {
+        Some("add") => {
+            add(
+                &mut doc,
+                &path,
+                &at,
+                Tree::from_json(field("value")?, config),
+            )?;
+        }
+        Some("remove") => {
+            remove(&mut doc, &path, &at)?;
+        }
+        Some("replace") => {
+            let value = Tree::from_json(field("value")?, config);
+            *doc.get(&at)
+                .ok_or_else(|| PatchError::NoSuchPath(path.clone()))? = value;
+        }
+        Some("move") => {
+            let (from_path, from) = pointer("from")?;
+            if at.len() > from.len() && at.starts_with(&from) {
+                return Err(PatchError::Malformed(format!(
+                    "can't move '{from_path}' into itself"
+                )));
+            }
+            let value = remove(&mut doc, &from_path, &from)?;
+            add(&mut doc, &path, &at, value)?;
+        }
+        Some("copy") => {
+            let (from_path, from) = pointer("from")?;
+            let value = doc
+                .get(&from)
+                .ok_or(PatchError::NoSuchPath(from_path))?
+                .clone();
+            add(&mut doc, &path, &at, value)?;
+        }
+        Some("test") => {
+            let value = Tree::from_json(field("value")?, config);
+            if doc.get(&at).as_deref() != Some(&value) {
+                return Err(PatchError::TestFailed(path));
+            }
+        }
+        _ => {
+            return Err(PatchError::Malformed(format!("unknown operation: {op}")));
+        }
+    }
+
+    Ok(doc)
+}
+
+/// Adds `value` at `at`: a new field, a new list element (`-` means the end),
+/// or, at the root, a whole new document.
+fn add(doc: &mut Tree, path: &str, at: &[String], value: Tree) -> Result<(), PatchError> {
+    let Some((last, parent)) = at.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+
+    match doc.get(parent) {
+        Some(Tree::Map(fields)) => {
+            match fields.iter_mut().find(|(name, _)| name == last) {
+                Some((_, t)) => *t = value,
+                None => fields.push((last.clone(), value)),
+            }
+            Ok(())
+        }
+        Some(Tree::List(elts)) => {
+            let i = if last == "-" {
+                elts.len()
+            } else {
+                match index(last) {
+                    Some(i) if i <= elts.len() => i,
+                    _ => return Err(PatchError::NoSuchPath(path.into())),
+                }
+            };
+            elts.insert(i, value);
+            Ok(())
+        }
+        Some(Tree::String(..) | Tree::Bytes(_)) | None => Err(PatchError::NoSuchPath(path.into())),
+    }
+}
+
+/// Removes and returns whatever is at `at`.
+fn remove(doc: &mut Tree, path: &str, at: &[String]) -> Result<Tree, PatchError> {
+    let no_such_path = || PatchError::NoSuchPath(path.into());
+    let (last, parent) = at.split_last().ok_or_else(no_such_path)?;
+
+    match doc.get(parent) {
+        Some(Tree::Map(fields)) => {
+            let i = fields
+                .iter()
+                .position(|(name, _)| name == last)
+                .ok_or_else(no_such_path)?;
+            Ok(fields.remove(i).1)
+        }
+        Some(Tree::List(elts)) => match index(last) {
+            Some(i) if i < elts.len() => Ok(elts.remove(i)),
+            _ => Err(no_such_path()),
+        },
+        Some(Tree::String(..) | Tree::Bytes(_)) | None => Err(no_such_path()),
+    }
+}
+
+/// Applies the merge patch `patch` to `doc`, as in RFC 7386.
+fn merge(doc: Tree, patch: &Json, config: &Config) -> Tree {
+    let Json::Object(patch) = patch else {
+        return Tree::from_json(patch, config);
+    };
+
+    let mut fields = match doc {
+        Tree::Map(fields) => fields,
+        _ => Vec::new(),
+    };
+    for (name, patch) in patch {
+        let i = fields.iter().position(|(field, _)| field == name);
+        match (i, patch) {
+            (Some(i), Json::Null) => {
+                fields.remove(i);
+            }
+            (None, Json::Null) => (),
+            (Some(i), patch) => {
+                let (_, t) = &mut fields[i];
+                *t = merge(std::mem::replace(t, Tree::Map(Vec::new())), patch, config);
+            }
+            (None, patch) => {
+                fields.push((name.clone(), merge(Tree::Map(Vec::new()), patch, config)));
+            }
+        }
+    }
+    Tree::Map(fields)
+}
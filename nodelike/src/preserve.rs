@@ -0,0 +1,674 @@
+//! Format-preserving saves, i.e., `Nodelike::to_string_preserving`.
+//!
+//! Rather than rendering a whole document, we take the changes between the
+//! document as it was loaded and as it is now (see `diff::diff`) and make
+//! just those changes to the original text. Everything else---comments, blank
+//! lines, key order, string styles---stays byte-for-byte the same.
+//!
+//! We always check our work: if the edited text doesn't parse back to the
+//! value we meant to save, callers fall back on rendering the whole thing.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use ::toml::Value as Toml;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value as TomlValue};
+use tracing::debug;
+use yaml_rust::Yaml;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+
+use super::Nodelike;
+use super::config::Config;
+use super::diff::{Change, diff, pointer_push, pointer_tokens};
+
+/// Returns `text` if it parses as `expected`.
+pub(crate) fn verified<V: Nodelike + Clone>(
+    text: String,
+    expected: &V,
+    config: &Config,
+) -> Option<String> {
+    let reader = Box::new(std::io::Cursor::new(text.clone()));
+    match V::from_reader(reader, config) {
+        Ok(v) => {
+            if diff(v, expected.clone(), config).is_empty() {
+                Some(text)
+            } else {
+                debug!("edited document doesn't match what we're saving");
+                None
+            }
+        }
+        Err(e) => {
+            debug!("edited document doesn't parse: {e}");
+            None
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TOML, via `toml_edit`
+
+/// Makes `changes` to the TOML document `original`.
+pub(crate) fn toml(original: &str, changes: Vec<Change<Toml>>) -> Option<String> {
+    let mut doc = original.parse::<DocumentMut>().ok()?;
+    for change in changes {
+        let path = change.path().to_string();
+        if edit_toml(doc.as_item_mut(), change).is_none() {
+            debug!("couldn't edit TOML at '{path}'");
+            return None;
+        }
+    }
+    Some(doc.to_string())
+}
+
+/// Somewhere in a TOML document that holds named or numbered values.
+enum Slot<'a> {
+    Item(&'a mut Item),
+    Table(&'a mut Table),
+    Value(&'a mut TomlValue),
+}
+
+impl<'a> Slot<'a> {
+    fn child(self, token: &str) -> Option<Slot<'a>> {
+        match self {
+            Slot::Item(Item::Table(t)) | Slot::Table(t) => t.get_mut(token).map(Slot::Item),
+            Slot::Item(Item::ArrayOfTables(ts)) => ts.get_mut(index(token)?).map(Slot::Table),
+            Slot::Item(Item::Value(v)) | Slot::Value(v) => match v {
+                TomlValue::InlineTable(t) => t.get_mut(token).map(Slot::Value),
+                TomlValue::Array(vs) => vs.get_mut(index(token)?).map(Slot::Value),
+                _ => None,
+            },
+            Slot::Item(Item::None) => None,
+        }
+    }
+}
+
+fn index(token: &str) -> Option<usize> {
+    token.parse().ok()
+}
+
+fn edit_toml(root: &mut Item, change: Change<Toml>) -> Option<()> {
+    let tokens = pointer_tokens(change.path())?;
+    // the root is always a table, so it's never replaced wholesale
+    let (name, parent) = tokens.split_last()?;
+    let parent = parent
+        .iter()
+        .try_fold(Slot::Item(root), |slot, token| slot.child(token))?;
+
+    match change {
+        Change::Add(_, new) | Change::Replace(_, _, new) => set_toml(parent, name, &new),
+        Change::Remove(..) => remove_toml(parent, name),
+    }
+}
+
+/// Sets `name` in `parent` to `new`, keeping whatever decorates the old value
+/// (e.g., a trailing comment).
+fn set_toml(parent: Slot, name: &str, new: &Toml) -> Option<()> {
+    match parent {
+        Slot::Item(Item::Table(t)) | Slot::Table(t) => {
+            match t.get_mut(name) {
+                Some(Item::Value(old)) => replace_value(old, toml_value(new)),
+                Some(old) => *old = toml_item(new),
+                None => {
+                    t.insert(name, toml_item(new));
+                }
+            }
+            Some(())
+        }
+        Slot::Item(Item::ArrayOfTables(ts)) => {
+            let Item::Table(new) = toml_item(new) else {
+                return None;
+            };
+            let i = index(name)?;
+            if i == ts.len() {
+                ts.push(new);
+            } else {
+                *ts.get_mut(i)? = new;
+            }
+            Some(())
+        }
+        Slot::Item(Item::Value(v)) | Slot::Value(v) => match v {
+            TomlValue::InlineTable(t) => {
+                match t.get_mut(name) {
+                    Some(old) => replace_value(old, toml_value(new)),
+                    None => {
+                        t.insert(name, toml_value(new));
+                    }
+                }
+                Some(())
+            }
+            TomlValue::Array(vs) => {
+                let i = index(name)?;
+                if i == vs.len() {
+                    vs.push(toml_value(new));
+                } else {
+                    replace_value(vs.get_mut(i)?, toml_value(new));
+                }
+                Some(())
+            }
+            _ => None,
+        },
+        Slot::Item(Item::None) => None,
+    }
+}
+
+fn remove_toml(parent: Slot, name: &str) -> Option<()> {
+    match parent {
+        Slot::Item(Item::Table(t)) | Slot::Table(t) => t.remove(name).map(|_| ()),
+        Slot::Item(Item::ArrayOfTables(ts)) => {
+            let i = index(name).filter(|i| *i < ts.len())?;
+            ts.remove(i);
+            Some(())
+        }
+        Slot::Item(Item::Value(v)) | Slot::Value(v) => match v {
+            TomlValue::InlineTable(t) => t.remove(name).map(|_| ()),
+            TomlValue::Array(vs) => {
+                let i = index(name).filter(|i| *i < vs.len())?;
+                vs.remove(i);
+                Some(())
+            }
+            _ => None,
+        },
+        Slot::Item(Item::None) => None,
+    }
+}
+
+fn replace_value(old: &mut TomlValue, mut new: TomlValue) {
+    *new.decor_mut() = old.decor().clone();
+    *old = new;
+}
+
+/// Renders `v` as it'd appear in a table: tables get their own section.
+fn toml_item(v: &Toml) -> Item {
+    match v {
+        Toml::Table(fields) => {
+            let mut t = Table::new();
+            for (name, v) in fields {
+                t.insert(name, toml_item(v));
+            }
+            // a table that only holds tables needs no header of its own
+            t.set_implicit(!fields.is_empty() && fields.values().all(Toml::is_table));
+            Item::Table(t)
+        }
+        Toml::Array(vs) if !vs.is_empty() && vs.iter().all(Toml::is_table) => {
+            let mut ts = ArrayOfTables::new();
+            for v in vs {
+                if let Item::Table(t) = toml_item(v) {
+                    ts.push(t);
+                }
+            }
+            Item::ArrayOfTables(ts)
+        }
+        v => Item::Value(toml_value(v)),
+    }
+}
+
+/// Renders `v` inline.
+fn toml_value(v: &Toml) -> TomlValue {
+    match v {
+        Toml::String(s) => s.as_str().into(),
+        Toml::Integer(n) => (*n).into(),
+        Toml::Float(n) => (*n).into(),
+        Toml::Boolean(b) => (*b).into(),
+        Toml::Datetime(d) => (*d).into(),
+        Toml::Array(vs) => vs.iter().map(toml_value).collect(),
+        Toml::Table(fields) => fields
+            .iter()
+            .map(|(name, v)| (name.as_str(), toml_value(v)))
+            .collect(),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// YAML, by splicing scalars and block entries
+
+/// Makes `changes` to the YAML document `original`. Scalars we can find in the
+/// text are replaced where they are; entries of block collections (a `key:` or
+/// `-` and the lines under it) are added, removed, or rewritten whole. Anything
+/// else---say, a change inside a flow collection like `[a, b]`---can't be made.
+pub(crate) fn yaml(original: &str, changes: Vec<Change<Yaml>>) -> Option<String> {
+    let layout = Layout::find(original)?;
+
+    let mut splices = Vec::with_capacity(changes.len());
+    for change in changes {
+        let path = change.path().to_string();
+        let splice = match change {
+            Change::Replace(_, _, new) => layout.replace(&path, &new),
+            Change::Add(_, new) => layout.add(&path, &new),
+            Change::Remove(..) => layout.remove(&path),
+        };
+        let Some(splice) = splice else {
+            debug!("couldn't edit the YAML at '{path}'");
+            return None;
+        };
+        splices.push(splice);
+    }
+
+    // back to front, so earlier ranges stay put; entries added in the same
+    // place keep their order
+    let mut splices = splices.into_iter().enumerate().collect::<Vec<_>>();
+    splices.sort_by_key(|(i, (range, _))| std::cmp::Reverse((range.start, *i)));
+    let mut text = original.to_string();
+    let mut edited = text.len();
+    for (_, (range, new)) in splices {
+        if range.end > edited {
+            debug!("overlapping YAML edits at {range:?}");
+            return None;
+        }
+        edited = range.start;
+        text.replace_range(range, &new);
+    }
+    Some(text)
+}
+
+/// Renders a scalar, keeping the quotes of the scalar it replaces if it's
+/// still a string.
+fn yaml_scalar(v: &Yaml, style: TScalarStyle) -> Option<String> {
+    match (v, style) {
+        (Yaml::String(s), TScalarStyle::DoubleQuoted) => serde_json::to_string(s).ok(),
+        (Yaml::String(s), TScalarStyle::SingleQuoted) if !s.contains(char::is_control) => {
+            Some(format!("'{}'", s.replace('\'', "''")))
+        }
+        (Yaml::Array(_) | Yaml::Hash(_) | Yaml::Alias(_) | Yaml::BadValue, _) => None,
+        (v, _) => {
+            let mut text = String::new();
+            yaml_rust::YamlEmitter::new(&mut text).dump(v).ok()?;
+            Some(text.strip_prefix("---\n").unwrap_or(&text).to_string())
+        }
+    }
+}
+
+/// Renders an entry of a block collection---`name: v` in a mapping, `- v` in
+/// a sequence---with every line but the first indented by `indent`.
+fn yaml_entry(name: Option<&str>, v: &Yaml, indent: usize) -> Option<String> {
+    // aliases are to anchors we'd have to find in the text
+    if has_alias(v) {
+        return None;
+    }
+
+    let entry = match name {
+        Some(name) => Yaml::Hash(
+            [(Yaml::String(name.to_string()), v.clone())]
+                .into_iter()
+                .collect(),
+        ),
+        None => Yaml::Array(vec![v.clone()]),
+    };
+    let mut text = String::new();
+    yaml_rust::YamlEmitter::new(&mut text).dump(&entry).ok()?;
+    let text = text.strip_prefix("---\n").unwrap_or(&text);
+    Some(text.replace('\n', &format!("\n{}", " ".repeat(indent))))
+}
+
+fn has_alias(v: &Yaml) -> bool {
+    match v {
+        Yaml::Alias(_) => true,
+        Yaml::Array(vs) => vs.iter().any(has_alias),
+        Yaml::Hash(fields) => fields.iter().any(|(k, v)| has_alias(k) || has_alias(v)),
+        _ => false,
+    }
+}
+
+/// Where things are in a YAML document, by path: the text of each scalar, and
+/// the entries of each block collection.
+///
+/// Block scalars (`|` and `>`), scalars that span lines, and scalars with
+/// anchors or tags are left out. So are keys, which means a document with a
+/// collection as a key has no sensible paths at all.
+struct Layout<'a> {
+    text: &'a str,
+    scalars: BTreeMap<String, (Range<usize>, TScalarStyle)>,
+    blocks: BTreeMap<String, Block>,
+}
+
+/// A block collection, i.e., one written with indentation rather than
+/// brackets.
+struct Block {
+    map: bool,
+    /// The column its entries start at
+    indent: usize,
+    /// Each entry's name (a key or an index) and where it starts
+    entries: Vec<(String, usize)>,
+    /// Where whatever comes after it starts
+    end: usize,
+    /// Its index, if it's a mapping in a block sequence, until we know where
+    /// that entry starts: at the `-` before its first key
+    item: Option<String>,
+}
+
+impl<'a> Layout<'a> {
+    fn find(text: &'a str) -> Option<Layout<'a>> {
+        let mut events = Events {
+            text,
+            offsets: text.char_indices().map(|(i, _)| i).collect(),
+            docs: Vec::new(),
+            stack: Vec::new(),
+            complex_keys: false,
+        };
+        Parser::new(text.chars()).load(&mut events, true).ok()?;
+        if events.complex_keys {
+            return None;
+        }
+
+        // several documents are a list (see `yaml::Value::from_reader`)
+        if events.docs.len() == 1 {
+            return events.docs.pop();
+        }
+        let mut layout = Layout::new(text);
+        for (i, doc) in events.docs.into_iter().enumerate() {
+            let prefix = pointer_push("", &i.to_string());
+            let path = |path| format!("{prefix}{path}");
+            layout
+                .scalars
+                .extend(doc.scalars.into_iter().map(|(p, v)| (path(p), v)));
+            layout
+                .blocks
+                .extend(doc.blocks.into_iter().map(|(p, v)| (path(p), v)));
+        }
+        Some(layout)
+    }
+
+    fn new(text: &'a str) -> Layout<'a> {
+        Layout {
+            text,
+            scalars: BTreeMap::new(),
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// Replaces the node at `path`: just the scalar, if we can, or else the
+    /// whole entry.
+    fn replace(&self, path: &str, new: &Yaml) -> Option<(Range<usize>, String)> {
+        if let Some((range, style)) = self.scalars.get(path)
+            && let Some(text) = yaml_scalar(new, *style)
+        {
+            return Some((range.clone(), text));
+        }
+
+        let (block, i) = self.entry(path)?;
+        let (range, starts_line) = block.span(self.text, i)?;
+        let name = block.map.then_some(block.entries[i].0.as_str());
+        let mut text = yaml_entry(name, new, block.indent)?;
+        if starts_line {
+            text.insert_str(0, &" ".repeat(block.indent));
+        }
+        if self.text[..range.end].ends_with('\n') {
+            text.push('\n');
+        }
+        Some((range, text))
+    }
+
+    /// Adds an entry after the last one in its collection.
+    fn add(&self, path: &str, new: &Yaml) -> Option<(Range<usize>, String)> {
+        let (parent, name) = parent(path)?;
+        let block = self.blocks.get(parent)?;
+        if !block.map && name != block.entries.len().to_string() {
+            return None;
+        }
+
+        let (_, last) = block.entries.last()?;
+        let at = content_end(self.text, *last, block.end);
+        let entry = yaml_entry(block.map.then_some(name.as_str()), new, block.indent)?;
+        let indent = " ".repeat(block.indent);
+        let text = if self.text[..at].ends_with('\n') {
+            format!("{indent}{entry}\n")
+        } else {
+            format!("\n{indent}{entry}")
+        };
+        Some((at..at, text))
+    }
+
+    /// Removes an entry, lines and all.
+    fn remove(&self, path: &str) -> Option<(Range<usize>, String)> {
+        let (block, i) = self.entry(path)?;
+        let (range, starts_line) = block.span(self.text, i)?;
+        if starts_line {
+            return Some((range, String::new()));
+        }
+
+        // something else comes first on the line (e.g., `- a: 1`), so the
+        // next entry takes its place
+        let (_, next) = block.entries.get(i + 1)?;
+        Some((range.start..*next, String::new()))
+    }
+
+    /// The block collection the node at `path` is an entry of, and which
+    /// entry it is.
+    fn entry(&self, path: &str) -> Option<(&Block, usize)> {
+        let (parent, name) = parent(path)?;
+        let block = self.blocks.get(parent)?;
+        let i = block.entries.iter().position(|(n, _)| *n == name)?;
+        Some((block, i))
+    }
+}
+
+/// Splits a path into its parent's path and its name.
+fn parent(path: &str) -> Option<(&str, String)> {
+    let (parent, _) = path.rsplit_once('/')?;
+    Some((parent, pointer_tokens(path)?.pop()?))
+}
+
+impl Block {
+    /// Where entry `i` is in `text`: from the start of its line (or just from
+    /// where it starts, if something comes before it on its line) through its
+    /// last line that isn't blank or a comment. Also says whether it starts
+    /// its line.
+    fn span(&self, text: &str, i: usize) -> Option<(Range<usize>, bool)> {
+        let (_, start) = self.entries.get(i)?;
+        let next = self.entries.get(i + 1).map_or(self.end, |(_, next)| *next);
+        let end = content_end(text, *start, next);
+
+        let line = text[..*start].rfind('\n').map_or(0, |nl| nl + 1);
+        if text[line..*start].trim().is_empty() {
+            Some((line..end, true))
+        } else {
+            Some((*start..end, false))
+        }
+    }
+}
+
+/// The end of the last line in `text[from..to]` that isn't blank or a
+/// comment, after its line break.
+fn content_end(text: &str, from: usize, to: usize) -> usize {
+    let mut end = from;
+    let mut pos = from;
+    for line in text[from..to].split_inclusive('\n') {
+        pos += line.len();
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            end = pos;
+        }
+    }
+    end
+}
+
+/// Builds a `Layout` for each document from parser events.
+struct Events<'a> {
+    text: &'a str,
+    /// The byte offset of each character, since markers count characters
+    offsets: Vec<usize>,
+    docs: Vec<Layout<'a>>,
+    /// The collections we're in, with their text if they're block collections
+    stack: Vec<(Frame, Option<Block>)>,
+    complex_keys: bool,
+}
+
+enum Frame {
+    /// A list's path and the index of its next element
+    List(String, usize),
+    /// A map's path and the key of the value we're waiting for, if any
+    Map(String, Option<String>),
+}
+
+impl<'a> Events<'a> {
+    /// Is the next node a key?
+    fn at_key(&self) -> bool {
+        matches!(self.stack.last(), Some((Frame::Map(_, None), _)))
+    }
+
+    /// The path of the next node, which isn't a key, and its index if it's
+    /// in a block sequence.
+    fn next_path(&mut self) -> (String, Option<String>) {
+        match self.stack.last_mut() {
+            None => (String::new(), None),
+            Some((Frame::List(path, i), block)) => {
+                *i += 1;
+                let name = (*i - 1).to_string();
+                (pointer_push(path, &name), block.is_some().then_some(name))
+            }
+            Some((Frame::Map(path, key), _)) => {
+                (pointer_push(path, &key.take().unwrap_or_default()), None)
+            }
+        }
+    }
+
+    /// Notes that entry `name` of the block sequence `depth` deep in the stack
+    /// starts at the `-` before `at`.
+    fn item(&mut self, depth: usize, name: String, at: usize) {
+        let text = self.text;
+        if let Some((_, block)) = self.stack.get_mut(depth)
+            && let Some(b) = block
+        {
+            match text[..at].trim_end_matches(' ').strip_suffix('-') {
+                Some(before) => b.entries.push((name, before.len())),
+                // e.g., an anchor or a tag between the `-` and the node
+                None => *block = None,
+            }
+        }
+    }
+
+    /// The byte offset of `mark`.
+    fn offset(&self, mark: Marker) -> usize {
+        self.offsets
+            .get(mark.index())
+            .copied()
+            .unwrap_or(self.text.len())
+    }
+
+    /// Where the scalar `v`, starting at `mark`, is in the text.
+    fn span(&self, v: &str, style: TScalarStyle, mark: Marker) -> Option<Range<usize>> {
+        let start = *self.offsets.get(mark.index())?;
+        let rest = &self.text[start..];
+
+        let len = match style {
+            // a plain scalar that spans lines has its line breaks folded
+            TScalarStyle::Plain if rest.starts_with(v) => v.len(),
+            TScalarStyle::SingleQuoted => {
+                let mut end = 1;
+                loop {
+                    end += rest[end..].find('\'')?;
+                    if rest[end + 1..].starts_with('\'') {
+                        end += 2;
+                    } else {
+                        break;
+                    }
+                }
+                let quoted = &rest[1..end];
+                if quoted.contains('\n') || quoted.replace("''", "'") != v {
+                    return None;
+                }
+                end + 1
+            }
+            TScalarStyle::DoubleQuoted => {
+                let bytes = rest.as_bytes();
+                let mut end = 1;
+                loop {
+                    match bytes.get(end)? {
+                        b'\\' => end += 2,
+                        b'"' => break,
+                        b'\n' => return None,
+                        _ => end += 1,
+                    }
+                }
+                end + 1
+            }
+            _ => return None,
+        };
+
+        Some(start..start + len)
+    }
+}
+
+impl<'a> MarkedEventReceiver for Events<'a> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::DocumentStart => self.docs.push(Layout::new(self.text)),
+            Event::Scalar(v, style, aid, tag) => {
+                let at = self.offset(mark);
+                let depth = self.stack.len().wrapping_sub(1);
+                if let Some((Frame::Map(_, key @ None), block)) = self.stack.last_mut() {
+                    let mut item = None;
+                    if let Some(block) = block {
+                        if block.entries.is_empty() {
+                            block.indent = mark.col();
+                            item = block.item.take();
+                        }
+                        block.entries.push((v.clone(), at));
+                    }
+                    *key = Some(v);
+                    if let Some(name) = item {
+                        self.item(depth - 1, name, at);
+                    }
+                    return;
+                }
+
+                let (path, item) = self.next_path();
+                if let Some(name) = item {
+                    self.item(depth, name, at);
+                }
+                if aid == 0
+                    && tag.is_none()
+                    && let Some(span) = self.span(&v, style, mark)
+                    && let Some(doc) = self.docs.last_mut()
+                {
+                    doc.scalars.insert(path, (span, style));
+                }
+            }
+            Event::SequenceStart(_) | Event::MappingStart(_) => {
+                if self.at_key() {
+                    self.complex_keys = true;
+                }
+                let (path, mut item) = self.next_path();
+                let at = self.offset(mark);
+                let map = matches!(ev, Event::MappingStart(_));
+                let flow = self.text[at..].starts_with(['[', '{']);
+                // a block mapping starts at its first `:`, so we wait for
+                // its first key
+                if let Some(name) = item.take_if(|_| flow || !map) {
+                    self.item(self.stack.len() - 1, name, at);
+                }
+                let block = (!flow).then(|| Block {
+                    map,
+                    indent: mark.col(),
+                    entries: Vec::new(),
+                    end: at,
+                    item,
+                });
+                let frame = match ev {
+                    Event::SequenceStart(_) => Frame::List(path, 0),
+                    _ => Frame::Map(path, None),
+                };
+                self.stack.push((frame, block));
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                let end = self.offset(mark);
+                if let Some((Frame::List(path, _) | Frame::Map(path, _), Some(block))) =
+                    self.stack.pop()
+                    && let Some(doc) = self.docs.last_mut()
+                {
+                    doc.blocks.insert(path, Block { end, ..block });
+                }
+            }
+            Event::Alias(_) => {
+                if self.at_key() {
+                    self.complex_keys = true;
+                }
+                let (_, item) = self.next_path();
+                if let Some(name) = item {
+                    self.item(self.stack.len() - 1, name, self.offset(mark));
+                }
+            }
+            _ => (),
+        }
+    }
+}
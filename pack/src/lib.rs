@@ -0,0 +1,646 @@
+//! Turns directories into semi-structured data; the inverse of `unpack`.
+//!
+//! The `pack` binary is a thin command-line interface to `pack_dir` and
+//! `Pack`.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::io::BufReader;
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str;
+use std::str::FromStr;
+
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use tracing::{debug, warn};
+
+use nodelike::Format;
+use nodelike::Nodelike;
+use nodelike::Typ;
+use nodelike::anchor_base;
+use nodelike::cbor::Value as CborValue;
+use nodelike::config::Config;
+use nodelike::config::ERROR_STATUS_FUSE;
+use nodelike::config::Symlink;
+use nodelike::csv::Value as CsvValue;
+use nodelike::dotenv::Value as DotenvValue;
+use nodelike::ini::Value as IniValue;
+use nodelike::json::Value as JsonValue;
+use nodelike::json5::Value as Json5Value;
+use nodelike::msgpack::Value as MsgPackValue;
+use nodelike::ndjson::Value as NdjsonValue;
+use nodelike::plist::Value as PlistValue;
+use nodelike::properties::Value as PropertiesValue;
+use nodelike::toml::Value as TomlValue;
+use nodelike::tsv::Value as TsvValue;
+use nodelike::xml::Value as XmlValue;
+use nodelike::yaml::Value as YamlValue;
+
+use regex::Regex;
+
+/// Errors from packing a directory.
+#[derive(Debug)]
+pub enum PackError {
+    /// Following symlinks (`Symlink::Follow`) ran into a loop.
+    SymlinkLoop(PathBuf),
+    /// A symlink points to a directory containing it, so packing would never
+    /// finish.
+    SymlinkToAncestor { link: PathBuf, target: PathBuf },
+    /// A file's `user.type` extended attribute isn't a type we know.
+    UnknownType { path: PathBuf, typ: String },
+    /// Reading the directory failed.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for PackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            PackError::SymlinkLoop(link) => {
+                write!(f, "Symlink loop detected at {}.", link.display())
+            }
+            PackError::SymlinkToAncestor { link, target } => write!(
+                f,
+                "The symlink {} points to some ancestor directory: {}, causing an infinite loop.",
+                link.display(),
+                target.display()
+            ),
+            PackError::UnknownType { path, typ } => write!(
+                f,
+                "Received undetected and unknown type '{typ}' for file '{}'",
+                path.display()
+            ),
+            PackError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl PackError {
+    /// The exit status for a tool that fails with this error.
+    pub fn exit_status(&self) -> i32 {
+        ERROR_STATUS_FUSE
+    }
+}
+
+impl std::error::Error for PackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PackError::Io(e) => Some(e),
+            PackError::SymlinkLoop(_)
+            | PackError::SymlinkToAncestor { .. }
+            | PackError::UnknownType { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PackError {
+    fn from(e: std::io::Error) -> Self {
+        PackError::Io(e)
+    }
+}
+
+/// Packs the directory at `path` into a value, per `config` (which determines,
+/// e.g., whether to follow symlinks or which files to skip).
+pub fn pack_dir<V>(path: impl AsRef<Path>, config: &Config) -> Result<V, PackError>
+where
+    V: Nodelike + std::fmt::Display + Default,
+{
+    let root = path.as_ref().canonicalize()?;
+    let mut packer = Pack::new(root.clone());
+    match packer.pack(root, config)? {
+        Some(v) => Ok(v),
+        None => Ok(V::from_named_dir(BTreeMap::new(), config)),
+    }
+}
+
+pub struct SymlinkMapData {
+    link: PathBuf,
+    is_broken: bool,
+}
+
+pub struct Pack {
+    // mapping of symlink to:
+    // PathBuf of link destination
+    // bool of whether symlink chain ends in a broken link
+    pub symlinks: HashMap<PathBuf, SymlinkMapData>,
+    // anchor names for files with more than one hard link, by (device, inode)
+    links: HashMap<(u64, u64), String>,
+    anchors: HashSet<String>,
+    // the directory being packed
+    root: PathBuf,
+    // `.gitignore` and `.ignore` files of the directories being packed, from
+    // the outermost in (only with `--respect-gitignore`)
+    ignores: Vec<Gitignore>,
+    depth: u32,
+    regex: Regex,
+}
+
+impl Pack {
+    /// Makes a packer for the directory `root`, which should be canonical.
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            symlinks: HashMap::new(),
+            links: HashMap::new(),
+            anchors: HashSet::new(),
+            root,
+            ignores: Vec::new(),
+            depth: 0,
+            regex: Regex::new("^-?[0-9]+").unwrap(),
+        }
+    }
+
+    /// Packs `path`, which is in the directory being packed. Returns `None`
+    /// if `path` should be skipped (e.g., it's a symlink we don't follow).
+    pub fn pack<V>(&mut self, path: PathBuf, config: &Config) -> Result<Option<V>, PackError>
+    where
+        V: Nodelike + std::fmt::Display + Default,
+    {
+        // don't continue packing if max depth is reached
+        if config
+            .max_depth
+            .is_some_and(|max_depth| self.depth > max_depth)
+        {
+            return Ok(None);
+        }
+
+        // get the type of data from xattr if it exists
+        let mut path_type: Vec<u8> = Vec::new();
+
+        if path.is_symlink() {
+            match &config.symlink {
+                Symlink::NoFollow => {
+                    // early return because we want to ignore symlinks,
+                    return Ok(None);
+                }
+                Symlink::Follow => {
+                    let mut link_trail = Vec::new();
+                    let mut link_follower = path.clone();
+                    while link_follower.is_symlink() {
+                        if link_trail.contains(&link_follower) {
+                            return Err(PackError::SymlinkLoop(link_follower));
+                        }
+                        link_trail.push(link_follower.clone());
+
+                        if path_type.is_empty() {
+                            // get the xattr of the first symlink that has it defined.
+                            // this has the effect of inheriting xattrs from links down the
+                            // chain.
+                            match xattr::get(&link_follower, "user.type") {
+                                Ok(Some(xattr)) if config.allow_xattr => path_type = xattr,
+                                Ok(_) | Err(_) => (),
+                                // TODO(nad) 2023-08-07: maybe unnecessary to check for ._ as
+                                // symlink?
+                                // Err(_) => {
+                                //     // Cannot call xattr::get on ._ file
+                                //     warn!(
+                                //         "._ files, like {}, prevent xattr calls. It will be encoded in base64.",
+                                //         link_follower.display()
+                                //     );
+                                //     path_type = b"bytes".to_vec()
+                                // }
+                            };
+                        }
+
+                        // add the link to the mapping to reduce future read_link calls for each
+                        // symlink on the chain.
+                        if !self.symlinks.contains_key(&link_follower) {
+                            let link = link_follower.read_link()?;
+                            self.symlinks.insert(
+                                link_follower.clone(),
+                                SymlinkMapData {
+                                    link: if link.is_absolute() {
+                                        link
+                                    } else {
+                                        link_follower.clone().parent().unwrap().join(link)
+                                    },
+                                    is_broken: false,
+                                },
+                            );
+                        }
+                        if self.symlinks[&link_follower].is_broken {
+                            // .1 is a bool to tell if symlink is broken
+                            // the symlink either is broken or links to a broken symlink.
+                            // stop the traversal immediately and update mapping if possible
+                            break;
+                        }
+                        link_follower = self.symlinks[&link_follower].link.clone();
+                    }
+
+                    if self.symlinks[link_trail.last().unwrap()].is_broken
+                        || !link_follower.exists()
+                    {
+                        // the symlink is broken, so don't pack this file.
+                        warn!(
+                            "The symlink at the end of the chain starting from '{}' is broken.",
+                            path.display()
+                        );
+                        for link in link_trail {
+                            let symlink_map_data = &self.symlinks[&link];
+                            self.symlinks.insert(
+                                link,
+                                SymlinkMapData {
+                                    link: symlink_map_data.link.to_path_buf(),
+                                    is_broken: true,
+                                },
+                            );
+                        }
+                        return Ok(None);
+                    }
+
+                    // pack reached the actual destination
+                    let canonicalized = link_follower.canonicalize()?;
+                    if path.starts_with(&canonicalized) {
+                        return Err(PackError::SymlinkToAncestor {
+                            link: path,
+                            target: canonicalized,
+                        });
+                    }
+                    if !config.allow_symlink_escape && !canonicalized.starts_with(&self.root) {
+                        warn!(
+                            "The symlink {} points to some file outside of the directory being packed. \
+                              Specify --allow-symlink-escape to allow pack to follow this symlink.",
+                            path.display()
+                        );
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        // if the xattr is still not set, either path is not a symlink or
+        // none of the symlinks on the chain have an xattr. Use the actual file's xattr
+        if path_type.is_empty() {
+            let canonicalized = path.canonicalize()?;
+            path_type = match xattr::get(canonicalized, "user.type") {
+                Ok(Some(xattr_type)) if config.allow_xattr => xattr_type,
+                Ok(_) => b"auto".to_vec(),
+                Err(_) => {
+                    // Cannot call xattr::get on ._ file
+                    warn!(
+                        "._ files, like {}, prevent xattr calls. It will be encoded in base64.",
+                        path.display(),
+                    );
+                    b"bytes".to_vec()
+                }
+            };
+        }
+
+        // convert detected xattr from Vec to str
+        let mut path_type: &str = match str::from_utf8(&path_type) {
+            Ok(path_type) => path_type,
+            Err(_) => {
+                return Err(PackError::UnknownType {
+                    path,
+                    typ: String::from_utf8_lossy(&path_type).into(),
+                });
+            }
+        };
+
+        // resolve path type if it is 'auto'
+        if path.is_dir()
+            && (path_type == "auto"
+                || path_type != "named" && path_type != "list" && path_type != "documents")
+        {
+            if path_type != "auto" {
+                warn!(
+                    "Unknown directory type '{path_type}'. Possible types are 'named', 'list', or 'documents'. \
+                    Resolving type automatically."
+                );
+            }
+            let mut count = 0;
+            let all_files_begin_with_num = fs::read_dir(path.clone())?
+                .map(|res| res.map(|e| e.path()))
+                .map(|e| e.unwrap().file_name().unwrap().to_str().unwrap().to_owned())
+                .all(|filename| {
+                    count += 1;
+
+                    filename.chars().nth(0).unwrap().is_ascii_digit()
+                        || filename.len() > 1
+                            && filename.chars().nth(0).unwrap() == '-'
+                            && filename.chars().nth(1).unwrap().is_ascii_digit()
+                });
+            if all_files_begin_with_num && count > 0 {
+                path_type = "list"
+            } else {
+                path_type = "named"
+            };
+        }
+
+        // return the value based on determined type
+        match path_type {
+            "named" => {
+                let mut children = fs::read_dir(path.clone())?
+                    .map(|res| res.map(|e| e.path()))
+                    .collect::<Result<Vec<_>, std::io::Error>>()?;
+                children.sort_unstable_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+                let mut entries = BTreeMap::new();
+
+                self.enter(&path, config);
+                for child in &children {
+                    let child_name = child.file_name().unwrap().to_str().unwrap();
+                    if config.ignored_file(child_name) {
+                        warn!("skipping ignored file {}", child.display());
+                        continue;
+                    }
+                    if self.skipped(child, config) {
+                        debug!("skipping {}", child.display());
+                        continue;
+                    }
+                    let name: String;
+                    match xattr::get(child, "user.original_name") {
+                        Ok(Some(original_name)) if config.allow_xattr => {
+                            let old_name = str::from_utf8(&original_name).unwrap();
+                            if !config.valid_name(old_name) {
+                                // original name must have been munged, so restore original
+                                name = old_name.to_string();
+                            } else {
+                                // original name wasn't munged, keep the current name
+                                // in case it was renamed
+                                name = child_name.to_string();
+                            }
+                        }
+                        Ok(_) | Err(_) => {
+                            // use current name because either --no-xattr is set,
+                            // xattr is None, or getting xattr on file (like ._ files) errors
+                            name = child_name.to_string();
+                        }
+                    }
+                    self.depth += 1;
+                    let value = self.pack(child.clone(), config)?;
+                    self.depth -= 1;
+                    if let Some(value) = value {
+                        entries.insert(name, value);
+                    }
+                }
+                self.leave(config);
+
+                let v = V::from_named_dir(entries, config);
+                Ok(Some(self.laid_out(&path, v, config)))
+            }
+            "list" | "documents" => {
+                let mut numbers_filenames_paths = fs::read_dir(path.clone())?
+                    .map(|res| res.map(|e| e.path()))
+                    .map(|p| {
+                        (
+                            p.as_ref()
+                                .unwrap()
+                                .file_name()
+                                .unwrap()
+                                .to_str()
+                                .unwrap()
+                                .to_owned(),
+                            p.unwrap(),
+                        )
+                    })
+                    .map(|(filename, p)| {
+                        // store a triple (integer, file basename, full pathbuf)
+                        // full pathbuf must be retained for symlink support.
+                        (
+                            match self.regex.find(&filename) {
+                                Some(m) => filename[m.range()].parse::<i32>().unwrap(),
+                                // use max i32 to give a default functionality for directories
+                                // that are forced into being lists, which doesn't guarantee
+                                // that filenames start with integers.
+                                None => i32::MAX,
+                            },
+                            // filenames in a directory are guaranteed to be different, so it
+                            // probably is the case that the PathBuf is never compared. Also,
+                            // filename is much shorter than the entire path, so that also saves
+                            // time.
+                            filename,
+                            p,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                numbers_filenames_paths.sort();
+
+                let mut entries = Vec::with_capacity(numbers_filenames_paths.len());
+                self.enter(&path, config);
+                for (_, filename, child) in numbers_filenames_paths {
+                    if config.ignored_file(&filename) {
+                        warn!("skipping ignored file {}", child.display());
+                        continue;
+                    }
+                    if self.skipped(&child, config) {
+                        debug!("skipping {}", child.display());
+                        continue;
+                    }
+                    self.depth += 1;
+                    let value = self.pack(child, config)?;
+                    self.depth -= 1;
+                    if let Some(value) = value {
+                        entries.push(value);
+                    }
+                }
+                self.leave(config);
+
+                let v = if path_type == "documents" {
+                    V::from_documents(entries, config)
+                } else {
+                    V::from_list_dir(entries, config)
+                };
+                Ok(Some(self.laid_out(&path, v, config)))
+            }
+            typ => {
+                if let Ok(t) = Typ::from_str(typ) {
+                    let file = fs::File::open(&path)?;
+                    let mut reader = BufReader::new(&file);
+                    let mut contents: Vec<u8> = Vec::new();
+                    reader.read_to_end(&mut contents)?;
+                    let v = match String::from_utf8(contents.clone()) {
+                        Ok(mut contents) if t != Typ::Bytes => {
+                            if config.add_newlines && contents.ends_with('\n') {
+                                contents.truncate(contents.len() - 1);
+                            }
+                            V::from_string(t, contents, config)
+                        }
+                        Ok(_) | Err(_) => V::from_bytes(contents, config),
+                    };
+                    Ok(Some(self.anchored(&path, v, config)?))
+                } else {
+                    Err(PackError::UnknownType {
+                        typ: typ.into(),
+                        path,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Starts packing the directory `dir`, loading its ignore files.
+    fn enter(&mut self, dir: &Path, config: &Config) {
+        if !config.respect_gitignore {
+            return;
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        for file in [".gitignore", ".ignore"] {
+            let file = dir.join(file);
+            if file.is_file()
+                && let Some(e) = builder.add(&file)
+            {
+                warn!("Problem reading {}: {e}", file.display());
+            }
+        }
+        match builder.build() {
+            Ok(ignore) => self.ignores.push(ignore),
+            Err(e) => {
+                warn!("Couldn't use ignore files in {}: {e}", dir.display());
+                self.ignores.push(Gitignore::empty());
+            }
+        }
+    }
+
+    /// Finishes packing the directory most recently `enter`ed.
+    fn leave(&mut self, config: &Config) {
+        if config.respect_gitignore {
+            self.ignores.pop();
+        }
+    }
+
+    /// Determines whether `path` should be left out, per `--exclude`,
+    /// `--include`, and `--respect-gitignore`.
+    fn skipped(&self, path: &Path, config: &Config) -> bool {
+        let is_dir = path.is_dir();
+
+        if let Ok(relative) = path.strip_prefix(&self.root)
+            && config.excluded_path(relative, is_dir)
+        {
+            return true;
+        }
+
+        if config.respect_gitignore {
+            if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+                return true;
+            }
+
+            // the innermost ignore file that says anything wins
+            for ignore in self.ignores.iter().rev() {
+                match ignore.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => (),
+                }
+            }
+        }
+
+        false
+    }
+
+    /// The directory being packed is laid out like the document it was
+    /// unpacked from, if `unpack` noted how in `user.layout` (see
+    /// `Nodelike::layout`).
+    fn laid_out<V: Nodelike>(&self, path: &Path, v: V, config: &Config) -> V {
+        if !config.allow_xattr || path != self.root {
+            return v;
+        }
+
+        match xattr::get(path, "user.layout") {
+            Ok(Some(layout)) => match String::from_utf8(layout) {
+                Ok(layout) => v.with_layout(&layout),
+                Err(_) => {
+                    warn!(
+                        "Ignoring the layout of {}, which isn't UTF-8.",
+                        path.display()
+                    );
+                    v
+                }
+            },
+            Ok(None) | Err(_) => v,
+        }
+    }
+
+    /// Files with more than one hard link are anchored, so that formats that
+    /// can express sharing (like YAML) save them just once.
+    fn anchored<V: Nodelike>(
+        &mut self,
+        path: &Path,
+        v: V,
+        config: &Config,
+    ) -> Result<V, PackError> {
+        let meta = fs::symlink_metadata(path)?;
+        if !meta.is_file() || meta.nlink() < 2 {
+            return Ok(v);
+        }
+
+        let name = match self.links.get(&(meta.dev(), meta.ino())) {
+            Some(name) => name.clone(),
+            None => {
+                let file_name = |path: &Path| path.file_name()?.to_str().map(String::from);
+                let base = anchor_base(
+                    &file_name(path).unwrap_or_default(),
+                    &path.parent().and_then(file_name).unwrap_or_default(),
+                );
+
+                let mut name = base.clone();
+                let mut i = 1;
+                while self.anchors.contains(&name) {
+                    i += 1;
+                    name = format!("{base}{i}");
+                }
+                self.anchors.insert(name.clone());
+                self.links.insert((meta.dev(), meta.ino()), name.clone());
+                name
+            }
+        };
+
+        Ok(V::from_anchored(&name, v, config))
+    }
+
+    /// Packs `path` into a value in `config.output_format`.
+    pub fn pack_boxed(
+        &mut self,
+        path: PathBuf,
+        config: &Config,
+    ) -> Result<Option<Box<dyn Nodelike>>, PackError> {
+        match &config.output_format {
+            Format::Cbor => Ok(self
+                .pack::<CborValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Csv => Ok(self
+                .pack::<CsvValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Dotenv => Ok(self
+                .pack::<DotenvValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Ini => Ok(self
+                .pack::<IniValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Json => Ok(self
+                .pack::<JsonValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Json5 => Ok(self
+                .pack::<Json5Value>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::MsgPack => Ok(self
+                .pack::<MsgPackValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Ndjson => Ok(self
+                .pack::<NdjsonValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Plist => Ok(self
+                .pack::<PlistValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Properties => Ok(self
+                .pack::<PropertiesValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Toml => Ok(self
+                .pack::<TomlValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Tsv => Ok(self
+                .pack::<TsvValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Xml => Ok(self
+                .pack::<XmlValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+            Format::Yaml => Ok(self
+                .pack::<YamlValue>(path, config)?
+                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
+        }
+    }
+}
@@ -1,19 +1,11 @@
-use std::fs;
-
-use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::io::BufReader;
-use std::io::Error;
-use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
-use std::str;
-use std::str::FromStr;
 
 use clap::Arg;
 use clap::ArgAction;
 use clap::Command;
 use clap::value_parser;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use nodelike::ParseFormatError;
 use nodelike::config::Input;
 use nodelike::config::Output;
@@ -22,17 +14,17 @@ use tracing::debug;
 use tracing::{error, warn};
 
 use nodelike::Format;
-use nodelike::Nodelike;
-use nodelike::Typ;
 use nodelike::config::Config;
 use nodelike::config::Symlink;
 use nodelike::config::{ERROR_STATUS_CLI, ERROR_STATUS_FUSE};
-use nodelike::json::Value as JsonValue;
+use nodelike::diff::diff;
 use nodelike::time_ns;
-use nodelike::toml::Value as TomlValue;
-use nodelike::yaml::Value as YamlValue;
+use nodelike::{
+    Error, Nodelike, cbor, csv, dotenv, ini, json, json5, load, msgpack, ndjson, plist, properties,
+    toml, tsv, xml, yaml,
+};
 
-use regex::Regex;
+use pack::{Pack, pack_dir};
 
 pub fn pack_cli() -> Command {
     nodelike::config::cli_base("pack")
@@ -66,6 +58,26 @@ pub fn pack_cli() -> Command {
                 .long("allow-symlink-escape")
                 .action(ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("EXCLUDE")
+                .help("Skip paths (relative to the directory being packed) matching the glob PATTERN; may be given more than once")
+                .long("exclude")
+                .value_name("PATTERN")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("INCLUDE")
+                .help("Only pack files (relative to the directory being packed) matching the glob PATTERN; may be given more than once")
+                .long("include")
+                .value_name("PATTERN")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("RESPECT_GITIGNORE")
+                .help("Skip files ignored by .gitignore and .ignore files (and .git directories)")
+                .long("respect-gitignore")
+                .action(ArgAction::SetTrue)
+        )
         .arg(
             Arg::new("KEEPMACOSDOT")
                 .help("Include ._* extended attribute/resource fork files on macOS")
@@ -86,6 +98,13 @@ pub fn pack_cli() -> Command {
                 .overrides_with("OUTPUT")
                 .action(ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("DIFF")
+                .help("Rather than saving, print the differences between OTHER and the packed directory, one path per line")
+                .long("diff")
+                .value_name("OTHER")
+                .conflicts_with_all(["OUTPUT", "NOOUTPUT"])
+        )
         .arg(
             Arg::new("TARGET_FORMAT")
                 .help("Specify the target format explicitly (by default, automatically inferred from filename extension)")
@@ -116,6 +135,10 @@ pub fn config_from_pack_args() -> Config {
     config.allow_symlink_escape = args.get_flag("ALLOW_SYMLINK_ESCAPE");
     config.keep_macos_xattr_file = args.get_flag("KEEPMACOSDOT");
     config.pretty = args.get_flag("PRETTY");
+    config.respect_gitignore = args.get_flag("RESPECT_GITIGNORE");
+
+    config.exclude = globs(&args, "EXCLUDE");
+    config.include = globs(&args, "INCLUDE");
 
     config.symlink = if args.get_flag("FOLLOW_SYMLINKS") {
         Symlink::Follow
@@ -125,6 +148,8 @@ pub fn config_from_pack_args() -> Config {
 
     config.max_depth = args.get_one::<u32>("MAXDEPTH").copied();
 
+    config.diff = args.get_one::<String>("DIFF").map(PathBuf::from);
+
     // configure input
     config.input = match args.get_one::<String>("INPUT") {
         Some(input_source) => {
@@ -163,7 +188,8 @@ pub fn config_from_pack_args() -> Config {
     //
     // first see if it's specified and parses okay.
     //
-    // then see if we can pull it out of the extension (if specified)
+    // then see if we can pull it out of the extension of the output (or the
+    // document we're comparing against), if specified
     //
     // then give up and use the input format
     config.output_format = match args
@@ -183,20 +209,18 @@ pub fn config_from_pack_args() -> Config {
             };
             match args
                 .get_one::<String>("OUTPUT")
-                .and_then(|s| Path::new(s).extension())
-                .and_then(|s| s.to_str())
+                .or_else(|| args.get_one::<String>("DIFF"))
+                .map(|s| Format::from_path(Path::new(s)))
             {
-                Some(s) => match s.parse::<Format>() {
-                    Ok(format) => format,
-                    Err(_) => {
-                        warn!(
-                            "Unrecognized format {s}, defaulting to input format '{}'.",
-                            config.input_format
-                        );
+                Some(Ok(format)) => format,
+                Some(Err(ParseFormatError::NoSuchFormat(s))) => {
+                    warn!(
+                        "Unrecognized format {s}, defaulting to input format '{}'.",
                         config.input_format
-                    }
-                },
-                None => config.input_format,
+                    );
+                    config.input_format
+                }
+                Some(Err(ParseFormatError::NoFormatProvided)) | None => config.input_format,
             }
         }
     };
@@ -211,344 +235,67 @@ pub fn config_from_pack_args() -> Config {
     config
 }
 
-pub struct SymlinkMapData {
-    link: PathBuf,
-    is_broken: bool,
-}
-
-pub struct Pack {
-    // mapping of symlink to:
-    // PathBuf of link destination
-    // bool of whether symlink chain ends in a broken link
-    pub symlinks: HashMap<PathBuf, SymlinkMapData>,
-    depth: u32,
-    regex: Regex,
-}
-
-impl Default for Pack {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+/// Compiles the patterns given for `arg`, if any.
+fn globs(args: &clap::ArgMatches, arg: &str) -> Option<GlobSet> {
+    let patterns = args.get_many::<String>(arg)?;
 
-impl Pack {
-    pub fn new() -> Self {
-        Self {
-            symlinks: HashMap::new(),
-            depth: 0,
-            regex: Regex::new("^-?[0-9]+").unwrap(),
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => {
+                error!("Invalid pattern: {e}");
+                std::process::exit(ERROR_STATUS_CLI);
+            }
         }
     }
 
-    pub fn pack<V>(&mut self, path: PathBuf, config: &Config) -> std::io::Result<Option<V>>
-    where
-        V: Nodelike + std::fmt::Display + Default,
-    {
-        // don't continue packing if max depth is reached
-        if config
-            .max_depth
-            .is_some_and(|max_depth| self.depth > max_depth)
-        {
-            return Ok(None);
-        }
-
-        // get the type of data from xattr if it exists
-        let mut path_type: Vec<u8> = Vec::new();
-
-        if path.is_symlink() {
-            match &config.symlink {
-                Symlink::NoFollow => {
-                    // early return because we want to ignore symlinks,
-                    return Ok(None);
-                }
-                Symlink::Follow => {
-                    let mut link_trail = Vec::new();
-                    let mut link_follower = path.clone();
-                    while link_follower.is_symlink() {
-                        if link_trail.contains(&link_follower) {
-                            error!("Symlink loop detected at {}.", link_follower.display());
-                            std::process::exit(ERROR_STATUS_FUSE);
-                        }
-                        link_trail.push(link_follower.clone());
-
-                        if path_type.is_empty() {
-                            // get the xattr of the first symlink that has it defined.
-                            // this has the effect of inheriting xattrs from links down the
-                            // chain.
-                            match xattr::get(&link_follower, "user.type") {
-                                Ok(Some(xattr)) if config.allow_xattr => path_type = xattr,
-                                Ok(_) | Err(_) => (),
-                                // TODO(nad) 2023-08-07: maybe unnecessary to check for ._ as
-                                // symlink?
-                                // Err(_) => {
-                                //     // Cannot call xattr::get on ._ file
-                                //     warn!(
-                                //         "._ files, like {}, prevent xattr calls. It will be encoded in base64.",
-                                //         link_follower.display()
-                                //     );
-                                //     path_type = b"bytes".to_vec()
-                                // }
-                            };
-                        }
-
-                        // add the link to the mapping to reduce future read_link calls for each
-                        // symlink on the chain.
-                        if !self.symlinks.contains_key(&link_follower) {
-                            let link = link_follower.read_link()?;
-                            self.symlinks.insert(
-                                link_follower.clone(),
-                                SymlinkMapData {
-                                    link: if link.is_absolute() {
-                                        link
-                                    } else {
-                                        link_follower.clone().parent().unwrap().join(link)
-                                    },
-                                    is_broken: false,
-                                },
-                            );
-                        }
-                        if self.symlinks[&link_follower].is_broken {
-                            // .1 is a bool to tell if symlink is broken
-                            // the symlink either is broken or links to a broken symlink.
-                            // stop the traversal immediately and update mapping if possible
-                            break;
-                        }
-                        link_follower = self.symlinks[&link_follower].link.clone();
-                    }
-
-                    if self.symlinks[link_trail.last().unwrap()].is_broken
-                        || !link_follower.exists()
-                    {
-                        // the symlink is broken, so don't pack this file.
-                        warn!(
-                            "The symlink at the end of the chain starting from '{}' is broken.",
-                            path.display()
-                        );
-                        for link in link_trail {
-                            let symlink_map_data = &self.symlinks[&link];
-                            self.symlinks.insert(
-                                link,
-                                SymlinkMapData {
-                                    link: symlink_map_data.link.to_path_buf(),
-                                    is_broken: true,
-                                },
-                            );
-                        }
-                        return Ok(None);
-                    }
-
-                    // pack reached the actual destination
-                    let canonicalized = link_follower.canonicalize()?;
-                    if path.starts_with(&canonicalized) {
-                        error!(
-                            "The symlink {} points to some ancestor directory: {}, causing an infinite loop.",
-                            path.display(),
-                            canonicalized.display(),
-                        );
-                        std::process::exit(ERROR_STATUS_FUSE);
-                    }
-                    if !config.allow_symlink_escape
-                        && !canonicalized.starts_with(config.mount.as_ref().unwrap())
-                    {
-                        warn!(
-                            "The symlink {} points to some file outside of the directory being packed. \
-                              Specify --allow-symlink-escape to allow pack to follow this symlink.",
-                            path.display()
-                        );
-                        return Ok(None);
-                    }
-                }
-            }
+    match builder.build() {
+        Ok(globs) => Some(globs),
+        Err(e) => {
+            error!("Invalid patterns: {e}");
+            std::process::exit(ERROR_STATUS_CLI);
         }
+    }
+}
 
-        // if the xattr is still not set, either path is not a symlink or
-        // none of the symlinks on the chain have an xattr. Use the actual file's xattr
-        if path_type.is_empty() {
-            let canonicalized = path.canonicalize()?;
-            path_type = match xattr::get(canonicalized, "user.type") {
-                Ok(Some(xattr_type)) if config.allow_xattr => xattr_type,
-                Ok(_) => b"auto".to_vec(),
-                Err(_) => {
-                    // Cannot call xattr::get on ._ file
-                    warn!(
-                        "._ files, like {}, prevent xattr calls. It will be encoded in base64.",
-                        path.display(),
-                    );
-                    b"bytes".to_vec()
-                }
-            };
+/// Packs `folder` and prints how it differs from the document `other`.
+fn print_diff<V>(folder: &Path, other: &Path, config: &Config)
+where
+    V: Nodelike + Clone + Default + 'static,
+{
+    let new: V = match pack_dir(folder, config) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(e.exit_status());
         }
+    };
 
-        // convert detected xattr from Vec to str
-        let mut path_type: &str = str::from_utf8(&path_type).unwrap();
-
-        // resolve path type if it is 'auto'
-        if path.is_dir() && (path_type == "auto" || path_type != "named" && path_type != "list") {
-            if path_type != "auto" {
-                warn!(
-                    "Unknown directory type '{path_type}'. Possible types are 'named' or 'list'. \
-                    Resolving type automatically."
-                );
-            }
-            let mut count = 0;
-            let all_files_begin_with_num = fs::read_dir(path.clone())?
-                .map(|res| res.map(|e| e.path()))
-                .map(|e| e.unwrap().file_name().unwrap().to_str().unwrap().to_owned())
-                .all(|filename| {
-                    count += 1;
-
-                    filename.chars().nth(0).unwrap().is_ascii_digit()
-                        || filename.len() > 1
-                            && filename.chars().nth(0).unwrap() == '-'
-                            && filename.chars().nth(1).unwrap().is_ascii_digit()
-                });
-            if all_files_begin_with_num && count > 0 {
-                path_type = "list"
-            } else {
-                path_type = "named"
+    // read `other` in its own format, then compare in the packed format
+    let format = Format::from_path(other).unwrap_or(config.output_format);
+    let old = std::fs::File::open(other)
+        .map_err(Error::from)
+        .and_then(|f| {
+            let reader: Box<dyn std::io::Read> = Box::new(std::io::BufReader::new(f));
+            let reader = match config.compression_for(other) {
+                Some(compression) => compression.decoder(reader)?,
+                None => reader,
             };
+            load::<V>(reader, format, config)
+        });
+    let old = match old {
+        Ok(old) => old,
+        Err(e) => {
+            error!("Couldn't load {}: {e}", other.display());
+            std::process::exit(e.exit_status());
         }
+    };
 
-        // return the value based on determined type
-        match path_type {
-            "named" => {
-                let mut children = fs::read_dir(path.clone())?
-                    .map(|res| res.map(|e| e.path()))
-                    .collect::<Result<Vec<_>, Error>>()?;
-                children.sort_unstable_by(|a, b| a.file_name().cmp(&b.file_name()));
-
-                let mut entries = BTreeMap::new();
-
-                for child in &children {
-                    let child_name = child.file_name().unwrap().to_str().unwrap();
-                    if config.ignored_file(child_name) {
-                        warn!("skipping ignored file {}", child.display());
-                        continue;
-                    }
-                    let name: String;
-                    match xattr::get(child, "user.original_name") {
-                        Ok(Some(original_name)) if config.allow_xattr => {
-                            let old_name = str::from_utf8(&original_name).unwrap();
-                            if !config.valid_name(old_name) {
-                                // original name must have been munged, so restore original
-                                name = old_name.to_string();
-                            } else {
-                                // original name wasn't munged, keep the current name
-                                // in case it was renamed
-                                name = child_name.to_string();
-                            }
-                        }
-                        Ok(_) | Err(_) => {
-                            // use current name because either --no-xattr is set,
-                            // xattr is None, or getting xattr on file (like ._ files) errors
-                            name = child_name.to_string();
-                        }
-                    }
-                    self.depth += 1;
-                    let value = self.pack(child.clone(), config)?;
-                    self.depth -= 1;
-                    if let Some(value) = value {
-                        entries.insert(name, value);
-                    }
-                }
-
-                Ok(Some(V::from_named_dir(entries, config)))
-            }
-            "list" => {
-                let mut numbers_filenames_paths = fs::read_dir(path.clone())?
-                    .map(|res| res.map(|e| e.path()))
-                    .map(|p| {
-                        (
-                            p.as_ref()
-                                .unwrap()
-                                .file_name()
-                                .unwrap()
-                                .to_str()
-                                .unwrap()
-                                .to_owned(),
-                            p.unwrap(),
-                        )
-                    })
-                    .map(|(filename, p)| {
-                        // store a triple (integer, file basename, full pathbuf)
-                        // full pathbuf must be retained for symlink support.
-                        (
-                            match self.regex.find(&filename) {
-                                Some(m) => filename[m.range()].parse::<i32>().unwrap(),
-                                // use max i32 to give a default functionality for directories
-                                // that are forced into being lists, which doesn't guarantee
-                                // that filenames start with integers.
-                                None => i32::MAX,
-                            },
-                            // filenames in a directory are guaranteed to be different, so it
-                            // probably is the case that the PathBuf is never compared. Also,
-                            // filename is much shorter than the entire path, so that also saves
-                            // time.
-                            filename,
-                            p,
-                        )
-                    })
-                    .collect::<Vec<_>>();
-                numbers_filenames_paths.sort();
-
-                let mut entries = Vec::with_capacity(numbers_filenames_paths.len());
-                for (_, filename, child) in numbers_filenames_paths {
-                    if config.ignored_file(&filename) {
-                        warn!("skipping ignored file {}", child.display());
-                        continue;
-                    }
-                    self.depth += 1;
-                    let value = self.pack(child, config)?;
-                    self.depth -= 1;
-                    if let Some(value) = value {
-                        entries.push(value);
-                    }
-                }
-
-                Ok(Some(V::from_list_dir(entries, config)))
-            }
-            typ => {
-                if let Ok(t) = Typ::from_str(typ) {
-                    let file = fs::File::open(&path).unwrap();
-                    let mut reader = BufReader::new(&file);
-                    let mut contents: Vec<u8> = Vec::new();
-                    reader.read_to_end(&mut contents).unwrap();
-                    match String::from_utf8(contents.clone()) {
-                        Ok(mut contents) if t != Typ::Bytes => {
-                            if config.add_newlines && contents.ends_with('\n') {
-                                contents.truncate(contents.len() - 1);
-                            }
-                            Ok(Some(V::from_string(t, contents, config)))
-                        }
-                        Ok(_) | Err(_) => Ok(Some(V::from_bytes(contents, config))),
-                    }
-                } else {
-                    error!(
-                        "Received undetected and unknown type '{typ}' for file '{}'",
-                        path.display()
-                    );
-                    std::process::exit(ERROR_STATUS_FUSE);
-                }
-            }
-        }
-    }
-
-    pub fn pack_boxed(
-        &mut self,
-        path: PathBuf,
-        config: &Config,
-    ) -> std::io::Result<Option<Box<dyn Nodelike>>> {
-        match &config.output_format {
-            Format::Json => Ok(self
-                .pack::<JsonValue>(path, config)?
-                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
-            Format::Toml => Ok(self
-                .pack::<TomlValue>(path, config)?
-                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
-            Format::Yaml => Ok(self
-                .pack::<YamlValue>(path, config)?
-                .map(|v| Box::new(v) as Box<dyn Nodelike>)),
-        }
+    for change in diff(old, new, config) {
+        println!("{}", change.convert::<json::Value>(config));
     }
 }
 
@@ -565,15 +312,65 @@ fn main() -> std::io::Result<()> {
 
     let folder = PathBuf::from(mount);
 
-    let writer = match config.output_writer() {
-        Some(writer) => writer,
-        None => return Ok(()),
+    if let Some(other) = &config.diff {
+        match config.output_format {
+            Format::Cbor => print_diff::<cbor::Value>(&folder, other, &config),
+            Format::Csv => print_diff::<csv::Value>(&folder, other, &config),
+            Format::Dotenv => print_diff::<dotenv::Value>(&folder, other, &config),
+            Format::Ini => print_diff::<ini::Value>(&folder, other, &config),
+            Format::Json => print_diff::<json::Value>(&folder, other, &config),
+            Format::Json5 => print_diff::<json5::Value>(&folder, other, &config),
+            Format::MsgPack => print_diff::<msgpack::Value>(&folder, other, &config),
+            Format::Ndjson => print_diff::<ndjson::Value>(&folder, other, &config),
+            Format::Plist => print_diff::<plist::Value>(&folder, other, &config),
+            Format::Properties => print_diff::<properties::Value>(&folder, other, &config),
+            Format::Toml => print_diff::<toml::Value>(&folder, other, &config),
+            Format::Tsv => print_diff::<tsv::Value>(&folder, other, &config),
+            Format::Xml => print_diff::<xml::Value>(&folder, other, &config),
+            Format::Yaml => print_diff::<yaml::Value>(&folder, other, &config),
+        }
+        return Ok(());
+    }
+
+    if config.output == Output::Quiet {
+        return Ok(());
+    }
+
+    let mut packer = Pack::new(folder.clone());
+    let v = match time_ns!("saving", packer.pack_boxed(folder, &config), config.timing) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(e.exit_status());
+        }
+    };
+
+    // only open the output now, so we don't pack our own temporary file
+    let output = match config.output_writer() {
+        Ok(Some(output)) => output,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            error!("Couldn't open {} for output: {e}", config.output);
+            std::process::exit(ERROR_STATUS_FUSE);
+        }
     };
 
-    let mut packer: Pack = Pack::new();
+    if let Some(v) = v
+        && let Err(e) = time_ns!(
+            "writing",
+            v.to_writer(output.writer()?, &config),
+            config.timing
+        )
+    {
+        error!("Couldn't save to {}: {e}", config.output);
+        // clean up the temporary file before exiting
+        drop(output);
+        std::process::exit(ERROR_STATUS_FUSE);
+    }
 
-    if let Some(v) = time_ns!("saving", packer.pack_boxed(folder, &config)?, config.timing) {
-        time_ns!("writing", v.to_writer(writer, config.pretty), config.timing);
+    if let Err(e) = output.commit() {
+        error!("Couldn't save to {}: {e}", config.output);
+        std::process::exit(ERROR_STATUS_FUSE);
     }
 
     Ok(())
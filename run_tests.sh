@@ -24,6 +24,13 @@ detect_tools() {
     fi
     command -v pack >/dev/null 2>&1 && command -v unpack >/dev/null 2>&1 && HAVE_PACKUNPACK=1
 
+    if ! command -v ffs-diff >/dev/null 2>&1
+    then
+        [ -x "$DEBUG/ffs-diff" ] && PATH="$DEBUG:$PATH"
+        [ -x "$RELEASE/ffs-diff" ] && PATH="$RELEASE:$PATH"
+    fi
+    command -v ffs-diff >/dev/null 2>&1 && command -v ffs-patch >/dev/null 2>&1 && HAVE_DIFF=1
+
     [ "$HAVE_FFS" ] || [ "$HAVE_PACKUNPACK" ]
 }
 
@@ -33,7 +40,7 @@ then
     (cd "$FFS_TOP"
      if [ "$(uname -s)" = "Darwin" ]
      then
-         cargo build --bin pack --bin unpack
+         cargo build --bin pack --bin unpack --bin ffs-diff --bin ffs-patch
      else
          cargo build --workspace
      fi)
@@ -64,6 +71,7 @@ do
     case "$tool" in
         (ffs) [ "$HAVE_FFS" ] || continue;;
         (packunpack) [ "$HAVE_PACKUNPACK" ] || continue;;
+        (diff) [ "$HAVE_DIFF" ] || continue;;
     esac
 
     printf "========== STARTING TEST: $tname\n"
@@ -88,6 +96,7 @@ do
     case "$tool" in
         (ffs) [ "$HAVE_FFS" ] || continue;;
         (packunpack) [ "$HAVE_PACKUNPACK" ] || continue;;
+        (diff) [ "$HAVE_DIFF" ] || continue;;
     esac
 
     if [ "$(cat $LOG/$tname.ec)" -eq 0 ]
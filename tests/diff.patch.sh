@@ -0,0 +1,58 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$DIR"
+    exit 1
+}
+
+DIR=$(mktemp -d)
+OLD="$DIR"/old.json
+NEW="$DIR"/new.json
+PATCH="$DIR"/patch.json
+OUT="$DIR"/out
+
+echo '{"name":"Michael","eyes":2,"gone":{"x":1},"list":["a","b","c"],"deep":{"k":1,"j":[1]},"a/b~":1}' >"$OLD"
+echo '{"name":"Mike","eyes":2,"list":["a"],"deep":{"k":2,"j":[1]},"new":[1,2]}' >"$NEW"
+
+# JSON Patches round trip
+ffs-diff --json-patch "$OLD" "$NEW" >"$PATCH" || fail json_patch
+grep -q '"op":"remove","path":"/a~1b~0"' "$PATCH" || fail escaped
+ffs-patch "$OLD" "$PATCH" >"$OUT" || fail apply_json_patch
+[ "$(cat $OUT)" = '{"deep":{"j":[1],"k":2},"eyes":2,"list":["a"],"name":"Mike","new":[1,2]}' ] || fail json_patch_output
+
+# as do merge patches
+ffs-diff --merge-patch "$OLD" "$NEW" >"$PATCH" || fail merge_patch
+[ "$(cat $PATCH)" = '{"a/b~":null,"deep":{"k":2},"gone":null,"list":["a"],"name":"Mike","new":[1,2]}' ] || fail merge_patch_output
+ffs-patch "$OLD" "$PATCH" >"$OUT" || fail apply_merge_patch
+[ "$(cat $OUT)" = '{"deep":{"j":[1],"k":2},"eyes":2,"list":["a"],"name":"Mike","new":[1,2]}' ] || fail merge_patch_result
+
+# all of RFC 6902's operations
+echo '[{"op":"test","path":"/eyes","value":2},{"op":"copy","from":"/eyes","path":"/list/-"},{"op":"move","from":"/gone","path":"/here"},{"op":"add","path":"/list/0","value":"z"}]' >"$PATCH"
+ffs-patch "$OLD" "$PATCH" >"$OUT" || fail ops
+[ "$(cat $OUT)" = '{"a/b~":1,"deep":{"j":[1],"k":1},"eyes":2,"here":{"x":1},"list":["z","a","b","c",2],"name":"Michael"}' ] || fail ops_output
+
+# failed tests and bad paths leave the document alone
+echo '[{"op":"remove","path":"/name"},{"op":"test","path":"/eyes","value":3}]' >"$PATCH"
+ffs-patch -i "$OLD" "$PATCH" 2>/dev/null && fail test
+echo '[{"op":"remove","path":"/nope"}]' >"$PATCH"
+ffs-patch -i "$OLD" "$PATCH" 2>/dev/null && fail missing
+grep -q Michael "$OLD" || fail unchanged
+
+# strings' final newlines are part of them
+printf '%s\n' '{"s":"line\n","t":"x"}' >"$DIR"/nl.json
+printf '%s\n' '{"s":"line\n","t":"x\n"}' >"$DIR"/nl_new.json
+echo '[]' | ffs-patch "$DIR"/nl.json - >"$OUT" || fail newline_patch
+[ "$(cat $OUT)" = "$(cat "$DIR"/nl.json)" ] || fail newline_kept
+ffs-diff "$DIR"/nl.json "$DIR"/nl_new.json >"$OUT" || fail newline_diff_status
+[ -s "$OUT" ] || fail newline_equal
+grep -q -F '"x\n"' "$OUT" || fail newline_diff
+
+# patching in place, keeping types JSON doesn't have
+TOML="$DIR"/doc.toml
+printf 'when = 1979-05-27T07:32:00Z\nname = "x"\n' >"$TOML"
+echo '{"name":"y"}' | ffs-patch -i "$TOML" - || fail in_place
+[ "$(cat $TOML)" = 'name = "y"
+when = 1979-05-27T07:32:00Z' ] || fail in_place_output
+
+rm -r "$DIR"
@@ -0,0 +1,38 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$DIR"
+    exit 1
+}
+
+DIR=$(mktemp -d)
+OLD="$DIR"/old.json
+NEW="$DIR"/new.yaml
+OUT="$DIR"/out
+
+echo '{"name":"Michael","eyes":2,"gone":{"x":1},"list":["a","b","c"]}' >"$OLD"
+printf 'name: Mike\neyes: 2\nlist: [a]\nnew: true\n' >"$NEW"
+
+# no differences, no output
+ffs-diff "$OLD" "$OLD" >"$OUT" || fail same
+[ -s "$OUT" ] && fail same_output
+
+# documents may be in different formats; paths are JSON Pointers
+ffs-diff "$OLD" "$NEW" >"$OUT" || fail diff
+[ "$(cat $OUT)" = '- /gone: {"x":1}
+- /list/2: "c"
+- /list/1: "b"
+~ /name: "Michael" -> "Mike"
++ /new: true' ] || fail diff_output
+
+# names with slashes and tildes are escaped
+echo '{"a/b":1,"c~":2}' >"$OLD"
+echo '{"a/b":2}' | ffs-diff "$OLD" - >"$OUT" || fail escape
+[ "$(cat $OUT)" = '~ /a~1b: 1 -> 2
+- /c~0: 2' ] || fail escape_output
+
+# only one document can come from stdin
+ffs-diff - - </dev/null 2>/dev/null && fail stdin
+
+rm -r "$DIR"
@@ -0,0 +1,47 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+D=$(mktemp -d)
+MNT="$D"/object
+
+testcase_cleanup() { rm -rf "$D"; }
+
+cp ../json/object.json "$D"/object.json
+chmod 600 "$D"/object.json
+mkdir "$MNT"
+
+# in-place saves keep the file's permissions
+ffs -m "$MNT" -i "$D"/object.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+echo Mikey Indiana >"$MNT"/name
+"$WAITFOR" umount "$MNT" || fail unmount1
+"$WAITFOR" exit $PID || fail process1
+
+ls -l "$D"/object.json | grep -e 'rw-------' >/dev/null 2>&1 || fail mode
+grep -e '"name":"Mikey Indiana"' "$D"/object.json >/dev/null 2>&1 || fail output1
+case $(ls -A "$D") in
+    (object*object.json) ;;
+    (*) fail tmpfile1;;
+esac
+
+# a failed save leaves the old output alone
+echo 'title = "original"' >"$D"/out.toml
+ffs -m "$MNT" -o "$D"/out.toml ../json/list.json 2>/dev/null &
+PID=$!
+"$WAITFOR" mount "$MNT"
+echo 4 >"$MNT"/4
+"$WAITFOR" umount "$MNT" || fail unmount2
+"$WAITFOR" exit $PID || fail process2
+wait $PID
+[ $? -eq 1 ] || fail status
+
+[ "$(cat "$D"/out.toml)" = 'title = "original"' ] || fail output2
+case $(ls -A "$D") in
+    (object*object.json*out.toml) ;;
+    (*) fail tmpfile2;;
+esac
+
+rm -r "$D"
@@ -0,0 +1,28 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+OUT=$(mktemp)
+
+testcase_cleanup() { rm -f "$OUT"; }
+
+ffs -m "$MNT" -o "$OUT" --autosave-interval 1 ../json/object.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+echo Mikey Indiana >"$MNT"/name
+sleep 2
+grep -e '"name":"Mikey Indiana"' "$OUT" >/dev/null || fail autosave1
+
+echo 1 >"$MNT"/nose
+sleep 2
+grep -e '"nose":1' "$OUT" >/dev/null || fail autosave2
+
+"$WAITFOR" umount "$MNT" || fail unmount
+"$WAITFOR" exit $PID || fail process
+
+grep -e '"nose":1' "$OUT" >/dev/null || fail output
+
+rmdir "$MNT" || fail mount
+rm "$OUT"
@@ -0,0 +1,43 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+CBOR=$(mktemp).cbor
+MSGPACK=$(mktemp).msgpack
+
+testcase_cleanup() { rm -f "$CBOR" "$MSGPACK"; }
+
+# inferring the target format from the output's extension
+ffs -o "$CBOR" -m "$MNT" ../toml/eg.toml &
+PID=$!
+"$WAITFOR" mount "$MNT"
+cp ../binary/twitter.ico "$MNT"/favicon
+"$WAITFOR" umount "$MNT" || fail unmount1
+"$WAITFOR" exit $PID || fail process1
+
+# bytes are stored natively, not as base64
+grep AAABAAEAEBAA "$CBOR" >/dev/null 2>&1 && fail base64
+
+ffs -o "$MSGPACK" -m "$MNT" "$CBOR" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+[ "$(cat $MNT/title)" = "TOML Example" ] || fail title1
+[ "$(cat $MNT/owner/dob)" = "1979-05-27T07:32:00-08:00" ] || fail dob
+echo 5001 >"$MNT/database/connection_max"
+"$WAITFOR" umount "$MNT" || fail unmount2
+"$WAITFOR" exit $PID || fail process2
+
+ffs --readonly --no-output -m "$MNT" "$MSGPACK" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+[ "$(cat $MNT/title)" = "TOML Example" ] || fail title2
+[ "$(cat $MNT/database/connection_max)" = "5001" ] || fail connection_max
+[ "$(cat $MNT/clients/hosts/1)" = "omega" ] || fail hosts
+cmp ../binary/twitter.ico "$MNT"/favicon || fail favicon
+"$WAITFOR" umount "$MNT" || fail unmount3
+"$WAITFOR" exit $PID || fail process3
+
+rmdir "$MNT" || fail mount
+rm "$CBOR" "$MSGPACK"
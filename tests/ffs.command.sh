@@ -0,0 +1,38 @@
+#!/bin/sh
+
+. ./fail.def
+
+DIR=$(mktemp -d)
+OBJ="$(pwd)/../json/object.json"
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+cp "$OBJ" object.json
+
+# runs in the mount, saves, and passes along the exit status
+ffs -i object.json -- sh -c 'echo 3 >eyes; [ "$(pwd)" = "$FFS_MOUNT" ] || exit 5; exit 4'
+[ $? -eq 4 ] || fail status
+grep -e '"eyes":3' object.json >/dev/null || fail saved
+[ -e object ] && fail cleanup
+
+# the command's output is its own; the output goes where it's told
+[ "$(ffs --no-output object.json -- cat name)" = "Michael Greenberg" ] || fail cat
+[ -e object ] && fail cleanup_cat
+
+# commands that can't be run
+ffs --no-output object.json -- ./nonexistent 2>/dev/null
+[ $? -eq 127 ] || fail not_found
+
+# signals
+ffs --no-output object.json -- sh -c 'kill -TERM $$'
+[ $? -eq 143 ] || fail signal
+
+# explicit mountpoints are left alone
+mkdir mnt
+ffs -m mnt -o out.json object.json -- touch new || fail explicit
+[ -d mnt ] || fail explicit_mount
+grep -e '"new":null' out.json >/dev/null || fail explicit_saved
+
+cd - >/dev/null
+rm -r "$DIR"
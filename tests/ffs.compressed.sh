@@ -0,0 +1,51 @@
+#!/bin/sh
+
+. ./fail.def
+
+DIR=$(mktemp -d)
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+
+cat >config.yaml <<'EOF2'
+# servers
+name: frontend # for now
+port: 8080
+EOF2
+gzip -c config.yaml >config.yaml.gz
+cp config.yaml plain.yaml
+bzip2 plain.yaml
+
+# formats and mountpoints are inferred without the compression extension
+[ "$(ffs --no-output config.yaml.gz -- cat name)" = "frontend" ] || fail read
+[ -e config ] && fail mount_cleanup
+
+# saving in place compresses with the same algorithm, keeping comments
+ffs -i --preserve-format config.yaml.gz -- sh -c 'echo 9090 >port' || fail edit_gz
+gzip -dc config.yaml.gz >got.yaml || fail gunzip
+cat >expected.yaml <<'EOF2'
+# servers
+name: frontend # for now
+port: 9090
+EOF2
+diff expected.yaml got.yaml || fail saved_gz
+
+ffs -i plain.yaml.bz2 -- sh -c 'echo backend >name' || fail edit_bz2
+[ "$(bzip2 -dc plain.yaml.bz2 | grep name)" = "name: backend" ] || fail saved_bz2
+
+# changing compression (or dropping it) is just a matter of the output's name
+ffs -o config.json.xz config.yaml.gz -- true || fail to_xz
+ffs -o config.json config.json.xz -- true || fail from_xz
+grep -e '"port": *9090' config.json >/dev/null || fail xz
+
+# `--compress` overrides the extension, for input and output alike
+ffs -o config.json.zst config.json -- true || fail to_zstd
+mv config.json.zst config.zstd.json
+[ "$(ffs --no-output --compress zstd config.zstd.json -- cat port)" = "9090" ] || fail zstd
+ffs -i --compress zstd config.zstd.json -- sh -c 'echo 80 >port' || fail edit_zstd
+[ "$(od -An -tx1 -N4 config.zstd.json | tr -d ' \n')" = "28b52ffd" ] || fail not_compressed
+[ "$(ffs --no-output --compress zstd config.zstd.json -- cat port)" = "80" ] || fail saved_zstd
+
+cd - >/dev/null
+rm -r "$DIR"
@@ -0,0 +1,70 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+FILE=$(mktemp --suffix=.json)
+
+testcase_cleanup() { rm -f "$FILE"; }
+
+cp ../json/object.json "$FILE"
+
+ffs -m "$MNT" -i "$FILE" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+
+# status
+[ -d "$MNT"/.ffs ] || fail control_dir
+[ "$(cat $MNT/.ffs/dirty)" = "false" ] || fail clean
+[ "$(cat $MNT/.ffs/synced)" = "false" ] || fail unsynced
+[ "$(cat $MNT/.ffs/input)" = "$FILE" ] || fail input
+[ "$(cat $MNT/.ffs/output)" = "$FILE" ] || fail output
+[ "$(cat $MNT/.ffs/format)" = "json" ] || fail format
+[ "$(cat $MNT/.ffs/inode_count)" -eq 5 ] || fail inode_count
+
+# the control directory can't be changed
+touch "$MNT"/.ffs/new 2>/dev/null && fail touch
+rm "$MNT"/.ffs/sync 2>/dev/null && fail rm
+mv "$MNT"/.ffs "$MNT"/control 2>/dev/null && fail mv
+
+# sync
+echo 3 >"$MNT"/eyes
+[ "$(cat $MNT/.ffs/dirty)" = "true" ] || fail dirty
+echo >"$MNT"/.ffs/sync || fail sync
+[ "$(cat $MNT/.ffs/dirty)" = "false" ] || fail clean_after_sync
+[ "$(cat $MNT/.ffs/synced)" = "true" ] || fail synced
+grep -e '"eyes":3' "$FILE" >/dev/null || fail saved
+grep -e 'ffs' "$FILE" >/dev/null && fail saved_control
+
+# reload picks up outside changes
+echo '{"eyes":4,"nose":1}' >"$FILE"
+echo >"$MNT"/.ffs/reload || fail reload
+[ "$(cat $MNT/eyes)" -eq 4 ] || fail reloaded_eyes
+[ "$(cat $MNT/nose)" -eq 1 ] || fail reloaded_nose
+[ -e "$MNT"/name ] && fail reloaded_name
+
+# reload won't discard changes, but revert will
+echo 5 >"$MNT"/eyes
+echo >"$MNT"/.ffs/reload 2>/dev/null && fail reload_dirty
+[ "$(cat $MNT/eyes)" -eq 5 ] || fail kept
+echo >"$MNT"/.ffs/revert || fail revert
+[ "$(cat $MNT/eyes)" -eq 4 ] || fail reverted
+[ "$(cat $MNT/.ffs/dirty)" = "false" ] || fail clean_after_revert
+
+# reverting when the input is gone is an error, not the end of the mount
+echo 6 >"$MNT"/eyes
+mv "$FILE" "$FILE".gone
+echo >"$MNT"/.ffs/revert 2>/dev/null && fail revert_missing
+[ "$(cat $MNT/eyes)" -eq 6 ] || fail kept_missing
+mv "$FILE".gone "$FILE"
+echo >"$MNT"/.ffs/revert || fail revert_again
+[ "$(cat $MNT/eyes)" -eq 4 ] || fail reverted_again
+
+"$WAITFOR" umount "$MNT" || fail unmount
+"$WAITFOR" exit $PID || fail process
+
+[ "$(cat $FILE)" = '{"eyes":4,"nose":1}' ] || fail final
+
+rmdir "$MNT" || fail mount
+rm "$FILE"
@@ -0,0 +1,76 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    # commands run in the mount, so this can't be a function
+    TYPEOF="getfattr -n user.type --only-values"
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    TYPEOF="xattr -p user.type"
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+OBJECT="$(pwd)/../json/object.json"
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+
+cat >people.csv <<'EOF2'
+name,zip,age,active
+Alice,02139,30,true
+"Bob, Jr.",10001,,false
+EOF2
+
+# rows are named directories; cells are typed automatically
+[ "$(ffs --no-output people.csv -- cat 1/name)" = "Bob, Jr." ] || fail name
+[ "$(ffs --no-output people.csv -- $TYPEOF 0/age)" = "integer" ] || fail integer
+[ "$(ffs --no-output people.csv -- $TYPEOF 0/zip)" = "string" ] || fail zip
+[ "$(ffs --no-output people.csv -- $TYPEOF 1/age)" = "null" ] || fail null
+
+# columns keep their order; new ones go at the end
+ffs -i people.csv -- sh -c '
+    echo 31 >0/age
+    echo bob@example.com >1/email' || fail edit
+cat >expected.csv <<'EOF2'
+name,zip,age,active,email
+Alice,02139,31,true,
+"Bob, Jr.",10001,,false,bob@example.com
+EOF2
+diff expected.csv people.csv || fail columns
+
+# ...unless new columns are errors
+ffs -i --new-columns error people.csv -- sh -c 'echo 1 >0/extra' 2>/dev/null && fail new_columns
+diff expected.csv people.csv || fail unchanged
+
+# cells can't be directories
+ffs -i people.csv -- mkdir 0/pets 2>/dev/null && fail nested
+diff expected.csv people.csv || fail nested_unchanged
+
+# cells we don't edit are saved as they were, even numbers
+printf 'n,m\n1e3,1.50\n-0,0x1F\n' >numbers.csv
+ffs -i numbers.csv -- sh -c 'echo 2 >1/m' || fail edit_numbers
+[ "$(cat numbers.csv)" = "$(printf 'n,m\n1e3,1.50\n-0,2')" ] || fail numbers
+[ "$(ffs --no-output numbers.csv -- $TYPEOF 0/m)" = "float" ] || fail float
+
+# without a header, rows are lists
+printf '1\t2\t3\n4\t5\n' >rows.tsv
+[ "$(ffs --no-output --no-header rows.tsv -- $TYPEOF 0)" = "list" ] || fail list
+[ "$(ffs --no-output --no-header rows.tsv -- cat 1/1)" = "5" ] || fail cell
+ffs -i --no-header rows.tsv -- sh -c 'echo 6 >1/2' || fail edit_tsv
+[ "$(cat rows.tsv)" = "$(printf '1\t2\t3\n4\t5\t6')" ] || fail tsv
+
+# converting from other formats
+# documents must be lists of rows
+ffs -o object.csv "$OBJECT" -- true 2>msg
+grep -e "lists of rows" msg >/dev/null || fail object
+[ -e object.csv ] && fail object_saved
+printf '[{"a": 1, "b": "two"}, {"a": 3, "b": "four"}]' >list.json
+ffs -o list.csv --delimiter ';' list.json -- true || fail convert
+[ "$(cat list.csv)" = "$(printf 'a;b\n1;two\n3;four')" ] || fail delimiter
+
+cd - >/dev/null
+rm -r "$DIR"
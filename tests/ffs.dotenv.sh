@@ -0,0 +1,82 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    # commands run in the mount, so this can't be a function
+    TYPEOF="getfattr -n user.type --only-values"
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    TYPEOF="xattr -p user.type"
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+mkdir mnt
+
+cat >.env <<'EOF2'
+# database
+export DB_HOST=localhost
+export DB_PORT=5432   # the default
+export GREETING="hello\nworld"
+export RAW='$HOME stays put'
+export MULTI="one
+two"
+export EMPTY=
+EOF2
+
+# `.env` is dotenv, even without an extension
+[ "$(ffs --no-output -m mnt .env -- cat DB_HOST)" = "localhost" ] || fail unquoted
+[ "$(ffs --no-output -m mnt .env -- cat DB_PORT)" = "5432" ] || fail comment
+[ "$(ffs --no-output -m mnt .env -- $TYPEOF DB_PORT)" = "integer" ] || fail integer
+[ "$(ffs --no-output -m mnt .env -- cat GREETING)" = "hello
+world" ] || fail escape
+[ "$(ffs --no-output -m mnt .env -- cat RAW)" = '$HOME stays put' ] || fail single
+[ "$(ffs --no-output -m mnt .env -- cat MULTI)" = "one
+two" ] || fail multiline
+[ "$(ffs --no-output -m mnt .env -- $TYPEOF EMPTY)" = "null" ] || fail empty
+
+# keys keep their order and `export`; values are quoted when they need to be
+ffs -i -m mnt .env -- sh -c '
+    echo 5433 >DB_PORT
+    echo "a b" >NEW
+    printf "%s" "a # b" >HASH' || fail edit
+cat >expected.env <<'EOF2'
+export DB_HOST=localhost
+export DB_PORT=5433
+export GREETING="hello\nworld"
+export RAW='$HOME stays put'
+export MULTI="one\ntwo"
+export EMPTY=
+export HASH="a # b"
+export NEW="a b"
+EOF2
+diff expected.env .env || fail saved
+
+# so do files like `.env.local`
+printf 'PORT=80\n' >.env.local
+[ "$(ffs --no-output -m mnt .env.local -- cat PORT)" = "80" ] || fail env_local
+
+# unquoted values are saved just as they were written
+printf 'LIMIT=1e3\nZERO=-0\nPORT=80\n' >numbers.env
+ffs -i -m mnt numbers.env -- sh -c 'echo 81 >PORT' || fail edit_numbers
+[ "$(cat numbers.env)" = "$(printf 'LIMIT=1e3\nZERO=-0\nPORT=81')" ] || fail numbers
+
+# dotenv files are flat
+printf '{"a": {"b": 1}}' >nested.json
+ffs -o nested.env nested.json -- true 2>msg
+grep -e "dotenv files only hold values" msg >/dev/null || fail nested
+[ -e nested.env ] && fail nested_saved
+
+printf 'A=1\nnot a line\n' >bad.env
+ffs --no-output bad.env -- true 2>msg
+[ $? -eq 3 ] || fail bad_status
+grep -e "DOTENV parse error at line 2" msg >/dev/null || fail bad
+
+cd - >/dev/null
+rm -r "$DIR"
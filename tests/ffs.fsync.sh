@@ -0,0 +1,29 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+OUT=$(mktemp)
+
+testcase_cleanup() { rm -f "$OUT"; }
+
+ffs -m "$MNT" -o "$OUT" ../json/object.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+echo Mikey Indiana >"$MNT"/name
+sync "$MNT"/name || fail fsync
+grep -e '"name":"Mikey Indiana"' "$OUT" >/dev/null || fail output1
+
+echo 1 >"$MNT"/nose
+sync "$MNT" || fail fsyncdir
+grep -e '"nose":1' "$OUT" >/dev/null || fail output2
+
+"$WAITFOR" umount "$MNT" || fail unmount
+"$WAITFOR" exit $PID || fail process
+
+grep -e '"name":"Mikey Indiana"' "$OUT" >/dev/null || fail output3
+grep -e '"nose":1' "$OUT" >/dev/null || fail output4
+
+rmdir "$MNT" || fail mount
+rm "$OUT"
@@ -32,8 +32,8 @@ PID=$!
 
 [ "$(typeof $MNT)"             = "named"   ] || fail root
 [ "$(typeof $MNT/name)"        = "string"  ] || fail name
-[ "$(typeof $MNT/eyes)"        = "float"   ] || fail eyes
-[ "$(typeof $MNT/fingernails)" = "float"   ] || fail fingernails
+[ "$(typeof $MNT/eyes)"        = "integer" ] || fail eyes
+[ "$(typeof $MNT/fingernails)" = "integer" ] || fail fingernails
 [ "$(typeof $MNT/human)"       = "boolean" ] || fail human
 
 "$WAITFOR" umount "$MNT" || fail unmount
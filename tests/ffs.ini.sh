@@ -0,0 +1,85 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    # commands run in the mount, so this can't be a function
+    TYPEOF="getfattr -n user.type --only-values"
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    TYPEOF="xattr -p user.type"
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+
+cat >app.ini <<'EOF2'
+; global settings
+name = frontend
+debug: true
+
+[server]
+host = alpha.example.com
+port = 8080
+url = http://example.com:8080/?a=b
+
+[database]
+user = admin
+password =
+EOF2
+
+# sections are named directories; global keys are at the root
+[ "$(ffs --no-output app.ini -- cat name)" = "frontend" ] || fail global
+[ "$(ffs --no-output app.ini -- $TYPEOF server)" = "named" ] || fail section
+[ "$(ffs --no-output app.ini -- $TYPEOF server/port)" = "integer" ] || fail integer
+[ "$(ffs --no-output app.ini -- cat server/url)" = "http://example.com:8080/?a=b" ] || fail url
+[ "$(ffs --no-output app.ini -- $TYPEOF database/password)" = "null" ] || fail null
+
+# ...or in their own directory
+[ "$(ffs --no-output --global-section global app.ini -- cat global/debug)" = "true" ] || fail global_section
+ffs --no-output --global-section global app.ini -- test -e name && fail global_root
+
+# sections and keys keep their order; new ones go at the end
+ffs -i app.ini -- sh -c '
+    echo 9090 >server/port
+    echo info >level
+    mkdir logging
+    echo warn >logging/level' || fail edit
+cat >expected.ini <<'EOF2'
+name = frontend
+debug = true
+level = info
+
+[server]
+host = alpha.example.com
+port = 9090
+url = http://example.com:8080/?a=b
+
+[database]
+user = admin
+password =
+
+[logging]
+level = warn
+EOF2
+diff expected.ini app.ini || fail saved
+
+# keys can only be set once per section
+printf '[s]\na = 1\na = 2\n' >twice.ini
+ffs --no-output twice.ini -- true 2>msg
+[ $? -eq 3 ] || fail twice_status
+grep -e "'a' is set twice in section 's'" msg >/dev/null || fail twice
+
+# INI has no room for lists or values deeper than a section
+printf '{"a": [1, 2]}' >list.json
+ffs -o list.ini list.json -- true 2>msg
+grep -e "INI files only have sections and keys" msg >/dev/null || fail list
+[ -e list.ini ] && fail list_saved
+
+cd - >/dev/null
+rm -r "$DIR"
@@ -0,0 +1,110 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    # commands run in the mount, so this can't be a function
+    TYPEOF="getfattr -n user.type --only-values"
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    TYPEOF="xattr -p user.type"
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+
+cat >tsconfig.jsonc <<'EOF2'
+// TypeScript settings
+{
+    /* compiler options */
+    "compilerOptions": {
+        "target": "es2020", // keep in sync with node
+        "strict": true,
+        // where builds go
+        "outDir": "dist",
+    },
+    "include": [
+        "src", // sources
+        "test",
+    ],
+}
+EOF2
+
+# comments and trailing commas are fine
+[ "$(ffs --no-output tsconfig.jsonc -- cat compilerOptions/target)" = "es2020" ] || fail target
+[ "$(ffs --no-output tsconfig.jsonc -- $TYPEOF compilerOptions/strict)" = "boolean" ] || fail strict
+[ "$(ffs --no-output tsconfig.jsonc -- $TYPEOF include)" = "list" ] || fail include
+
+# comments stay with their keys; keys keep their order
+ffs -i tsconfig.jsonc -- sh -c '
+    echo es2022 >compilerOptions/target
+    echo true >compilerOptions/noEmit
+    rm compilerOptions/outDir include/1' || fail edit
+cat >expected.jsonc <<'EOF2'
+// TypeScript settings
+{
+    /* compiler options */
+    "compilerOptions": {
+        "target": "es2022", // keep in sync with node
+        "strict": true,
+        "noEmit": true
+    },
+    "include": [
+        "src" // sources
+    ]
+}
+EOF2
+diff expected.jsonc tsconfig.jsonc || fail saved
+
+cat >app.json5 <<'EOF2'
+{
+  // unquoted keys and single quotes
+  name: 'frontend',
+  port: 0x1F90,
+  ratio: .5,
+  motd: 'it\'s fine',
+}
+EOF2
+
+# JSON5 numbers and strings are read the JavaScript way
+[ "$(ffs --no-output app.json5 -- cat port)" = "8080" ] || fail hex
+[ "$(ffs --no-output app.json5 -- cat ratio)" = "0.5" ] || fail ratio
+[ "$(ffs --no-output app.json5 -- cat motd)" = "it's fine" ] || fail motd
+
+# unquoted keys stay unquoted
+ffs -i app.json5 -- sh -c 'echo 9090 >port' || fail edit_json5
+cat >expected.json5 <<'EOF2'
+{
+  // unquoted keys and single quotes
+  name: "frontend",
+  port: 9090,
+  ratio: 0.5,
+  motd: "it's fine"
+}
+EOF2
+diff expected.json5 app.json5 || fail saved_json5
+
+# Infinity and NaN are floats, and stay bare
+printf '{ low: -Infinity, high: +Infinity, odd: NaN, name: "NaN" }\n' >limits.json5
+[ "$(ffs --no-output limits.json5 -- $TYPEOF low)" = "float" ] || fail infinity_type
+[ "$(ffs --no-output limits.json5 -- cat low)" = "-Infinity" ] || fail infinity
+[ "$(ffs --no-output limits.json5 -- $TYPEOF odd)" = "float" ] || fail nan_type
+[ "$(ffs --no-output limits.json5 -- $TYPEOF name)" = "string" ] || fail nan_string
+ffs -i limits.json5 -- sh -c 'echo -Infinity >high' || fail edit_infinity
+cat >expected.json5 <<'EOF2'
+{
+  low: -Infinity,
+  high: -Infinity,
+  odd: NaN,
+  name: "NaN"
+}
+EOF2
+diff expected.json5 limits.json5 || fail saved_infinity
+
+cd - >/dev/null
+rm -r "$DIR"
@@ -0,0 +1,61 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    typeof() {
+        getfattr -n user.type --only-values "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    typeof() {
+        xattr -p user.type "$@"
+    }
+else
+    fail os
+fi
+
+MNT=$(mktemp -d)
+FILE=$(mktemp --suffix=.json)
+TOML=$(mktemp --suffix=.toml)
+
+testcase_cleanup() { rm -f "$FILE" "$TOML"; }
+
+echo '{"id":9007199254740993,"big":18446744073709551615,"huge":123456789012345678901234567890,"neg":-42,"ratio":2.50,"tiny":1e-7}' >"$FILE"
+
+ffs -m "$MNT" -t toml -o "$TOML" "$FILE" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+
+[ "$(typeof $MNT/id)"    = "integer" ] || fail id_type
+[ "$(typeof $MNT/big)"   = "integer" ] || fail big_type
+[ "$(typeof $MNT/huge)"  = "integer" ] || fail huge_type
+[ "$(typeof $MNT/neg)"   = "integer" ] || fail neg_type
+[ "$(typeof $MNT/ratio)" = "float"   ] || fail ratio_type
+[ "$(typeof $MNT/tiny)"  = "float"   ] || fail tiny_type
+
+# numbers are exactly as written
+[ "$(cat $MNT/id)"    = "9007199254740993" ] || fail id
+[ "$(cat $MNT/huge)"  = "123456789012345678901234567890" ] || fail huge
+[ "$(cat $MNT/ratio)" = "2.50" ] || fail ratio
+
+"$WAITFOR" umount "$MNT" || fail unmount
+"$WAITFOR" exit $PID || fail process
+
+# integers stay integers in other formats
+grep -e '^id = 9007199254740993$' "$TOML" >/dev/null || fail toml_id
+grep -e '^neg = -42$' "$TOML" >/dev/null || fail toml_neg
+
+# ... and everything round trips exactly
+ffs -m "$MNT" -i "$FILE" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+echo 1 >"$MNT"/neg
+"$WAITFOR" umount "$MNT" || fail unmount_inplace
+"$WAITFOR" exit $PID || fail process_inplace
+
+[ "$(cat $FILE)" = '{"big":18446744073709551615,"huge":123456789012345678901234567890,"id":9007199254740993,"neg":1,"ratio":2.50,"tiny":1e-7}' ] || fail roundtrip
+
+rmdir "$MNT" || fail mount
+testcase_cleanup
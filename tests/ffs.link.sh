@@ -0,0 +1,61 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    num_links() {
+        stat --format %h "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    num_links() {
+        stat -f %l "$@"
+    }
+else
+    fail os
+fi
+
+inum() {
+    ls -id "$1" | awk '{print $1}'
+}
+
+MNT=$(mktemp -d)
+TGT=$(mktemp)
+
+testcase_cleanup() { rm -f "$TGT"; }
+
+# hard links share an inode
+ffs -m "$MNT" -o "$TGT" --target yaml ../json/object.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+ln "$MNT"/name "$MNT"/handle || fail link
+[ "$(inum $MNT/name)" = "$(inum $MNT/handle)" ] || fail inum
+[ $(num_links $MNT/name) -eq 2 ] || fail nlink
+echo "Mike" >"$MNT"/handle
+[ "$(cat $MNT/name)" = "Mike" ] || fail shared
+ln "$MNT"/name "$MNT"/eyes 2>/dev/null && fail exists
+mkdir "$MNT"/dir
+ln "$MNT"/name "$MNT"/dir/name || fail link_subdir
+[ $(num_links $MNT/handle) -eq 3 ] || fail nlink_subdir
+rm "$MNT"/dir/name
+[ $(num_links $MNT/handle) -eq 2 ] || fail nlink_unlink
+"$WAITFOR" umount "$MNT" || fail unmount1
+"$WAITFOR" exit $PID || fail process1
+
+# YAML saves the value once, with an alias
+[ $(grep -c "Mike" "$TGT") -eq 1 ] || fail saved_once
+grep -e '&handle Mike' "$TGT" >/dev/null 2>&1 || fail anchor
+grep -e 'name: \*handle' "$TGT" >/dev/null 2>&1 || fail alias
+
+# other formats save copies
+ffs -m "$MNT" -o "$TGT" --target json ../json/object.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+ln "$MNT"/name "$MNT"/handle || fail link2
+"$WAITFOR" umount "$MNT" || fail unmount2
+"$WAITFOR" exit $PID || fail process2
+
+[ $(grep -o "Michael Greenberg" "$TGT" | wc -l) -eq 2 ] || fail copies
+
+rmdir "$MNT" || fail mount
+rm "$TGT"
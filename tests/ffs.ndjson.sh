@@ -0,0 +1,41 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+D=$(mktemp -d)
+
+testcase_cleanup() { rm -r "$D"; }
+
+printf '{"id": 1, "msg": "started"}\n{"id": 2, "msg": "working"}\n' >"$D"/log.ndjson
+
+ffs -i -m "$MNT" "$D"/log.ndjson &
+PID=$!
+"$WAITFOR" mount "$MNT"
+[ "$(cat $MNT/0/msg)" = "started" ] || fail started
+[ "$(cat $MNT/1/id)" = "2" ] || fail id
+mkdir "$MNT"/2
+echo 3 >"$MNT"/2/id
+echo done >"$MNT"/2/msg
+"$WAITFOR" umount "$MNT" || fail unmount1
+"$WAITFOR" exit $PID || fail process1
+
+cat >"$D"/expected <<'EOT'
+{"id":1,"msg":"started"}
+{"id":2,"msg":"working"}
+{"id":3,"msg":"done"}
+EOT
+diff "$D"/expected "$D"/log.ndjson || fail saved
+
+# converting a JSON list into JSON Lines
+ffs -o "$D"/list.jsonl -m "$MNT" ../json/list.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+"$WAITFOR" umount "$MNT" || fail unmount2
+"$WAITFOR" exit $PID || fail process2
+printf '1\n2\n"3"\nfalse\n' >"$D"/expected
+diff "$D"/expected "$D"/list.jsonl || fail lines
+
+rmdir "$MNT" || fail mount
+rm -r "$D"
@@ -26,7 +26,7 @@ case $(ls) in
     (*) fail ls;;
 esac
 [ -d . ] && [ -d child1 ] && [ -f child2 ] && [ -d child3 ] || fail filetypes
-[ $(num_links      .) -eq 4 ] || fail root   # parent + self + child1 + child3
+[ $(num_links      .) -eq 5 ] || fail root   # parent + self + child1 + child3 + .ffs
 [ $(num_links child1) -eq 2 ] || fail child1 # parent + self
 [ $(num_links child2) -eq 1 ] || fail child2 # parent
 [ $(num_links child3) -eq 2 ] || fail child3 # parent + self
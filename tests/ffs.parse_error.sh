@@ -0,0 +1,22 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+D=$(mktemp -d)
+
+testcase_cleanup() { rm -rf "$D"; }
+
+printf '{\n  "name": "Michael Greenberg",\n  "eyes": \n}\n' >"$D"/bad.json
+ffs -m "$MNT" "$D"/bad.json >"$D"/out 2>"$D"/msg &
+PID=$!
+"$WAITFOR" exit $PID || fail process
+wait $PID
+[ $? -eq 3 ] || fail status
+grep -i -e "JSON parse error at line 4, column 1" "$D"/msg >/dev/null 2>&1 || fail msg
+grep -i -e "panicked" "$D"/msg >/dev/null 2>&1 && fail panic
+[ -f "$D"/out ] && ! [ -s "$D"/out ] || fail output
+
+rmdir "$MNT" || fail mount
+rm -r "$D"
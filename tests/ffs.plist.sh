@@ -0,0 +1,78 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    # commands run in the mount, so this can't be a function
+    TYPEOF="getfattr -n user.type --only-values"
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    TYPEOF="xattr -p user.type"
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cp ../binary/settings.plist "$DIR"
+cd "$DIR"
+
+cat >Info.plist <<'EOF2'
+<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>CFBundleName</key>
+	<string>Example</string>
+	<key>LSRequiresIPhoneOS</key>
+	<true/>
+	<key>Released</key>
+	<date>2024-01-02T03:04:05Z</date>
+	<key>Icon</key>
+	<data>AAEC/w==</data>
+	<key>Versions</key>
+	<array>
+		<integer>1</integer>
+		<real>1.5</real>
+	</array>
+</dict>
+</plist>
+EOF2
+
+[ "$(ffs --no-output Info.plist -- cat CFBundleName)" = "Example" ] || fail string
+[ "$(ffs --no-output Info.plist -- $TYPEOF LSRequiresIPhoneOS)" = "boolean" ] || fail boolean
+[ "$(ffs --no-output Info.plist -- $TYPEOF Released)" = "datetime" ] || fail date_type
+[ "$(ffs --no-output Info.plist -- cat Released)" = "2024-01-02T03:04:05Z" ] || fail date
+[ "$(ffs --no-output Info.plist -- $TYPEOF Icon)" = "bytes" ] || fail data_type
+[ "$(ffs --no-output Info.plist -- od -An -tx1 Icon | tr -d ' \n')" = "000102ff" ] || fail data
+[ "$(ffs --no-output Info.plist -- $TYPEOF Versions/1)" = "float" ] || fail real
+
+# XML stays XML, with its types
+ffs -i Info.plist -- sh -c 'echo 2 >Versions/2' || fail edit_xml
+grep -e '<date>2024-01-02T03:04:05Z</date>' Info.plist >/dev/null || fail saved_date
+grep -e 'AAEC/w==' Info.plist >/dev/null || fail saved_data
+grep -e '<integer>2</integer>' Info.plist >/dev/null || fail saved_integer
+
+# binary stays binary
+[ "$(ffs --no-output settings.plist -- cat name)" = "Preview" ] || fail binary
+[ "$(ffs --no-output settings.plist -- cat 'archive/$top/CF$UID')" = "1" ] || fail uid
+ffs -i settings.plist -- sh -c 'echo Preview.app >name; echo 4 >version' || fail edit_binary
+[ "$(head -c 8 settings.plist)" = "bplist00" ] || fail still_binary
+[ "$(ffs --no-output settings.plist -- cat name)" = "Preview.app" ] || fail binary_name
+[ "$(ffs --no-output settings.plist -- $TYPEOF version)" = "integer" ] || fail binary_version
+[ "$(ffs --no-output settings.plist -- $TYPEOF saved)" = "datetime" ] || fail binary_date
+[ "$(ffs --no-output settings.plist -- cat 'archive/$top/CF$UID')" = "1" ] || fail binary_uid
+
+# ...unless we ask for something else
+ffs --no-output -o settings.json settings.plist -- true || fail to_json
+grep -e '"name": *"Preview.app"' settings.json >/dev/null || fail json
+
+printf '<plist><dict><key>a</key><string>b</dict></plist>' >bad.plist
+ffs --no-output bad.plist -- true 2>msg
+[ $? -eq 3 ] || fail bad_status
+grep -e "PLIST parse error at line 1" msg >/dev/null || fail bad
+
+cd - >/dev/null
+rm -r "$DIR"
@@ -0,0 +1,129 @@
+#!/bin/sh
+
+. ./fail.def
+
+DIR=$(mktemp -d)
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+
+cat >Cargo.toml <<'EOF'
+# the package
+[package]
+name = "demo"    # its name
+version = "0.1.0"
+authors = ['Someone <someone@example.com>']
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"] } # inline
+rand = "0.8"
+
+[[bin]]
+name = "one"
+
+[[bin]]
+name = "two"
+EOF
+
+cat >expected.toml <<'EOF'
+# the package
+[package]
+name = "demo"    # its name
+version = "0.2.0"
+authors = ['Someone <someone@example.com>']
+
+[dependencies]
+serde = { version = "1.1", features = ["derive"] } # inline
+
+[[bin]]
+name = "one"
+
+[[bin]]
+name = "three"
+
+[profile]
+opt = 3
+EOF
+
+ffs --preserve-format -i Cargo.toml -- sh -c '
+    echo 0.2.0 >package/version
+    echo 1.1 >dependencies/serde/version
+    rm dependencies/rand
+    echo three >bin/1/name
+    mkdir profile
+    echo 3 >profile/opt' || fail toml_edit
+cmp Cargo.toml expected.toml || fail toml
+
+# no changes, no difference
+ffs --preserve-format -i Cargo.toml -- true || fail toml_noop
+cmp Cargo.toml expected.toml || fail toml_unchanged
+
+# without the flag, the comments are gone
+ffs -i Cargo.toml -- true || fail toml_plain
+grep -e '#' Cargo.toml >/dev/null && fail toml_comments
+
+cat >ci.yml <<'EOF'
+# CI config
+name: CI   # the name
+on: [push, pull_request]
+
+jobs:
+  build:
+    runs-on: 'ubuntu-latest'
+    steps:
+      - uses: actions/checkout@v4
+      - run: "cargo build"   # build it
+EOF
+
+cat >expected.yml <<'EOF'
+# CI config
+name: Build   # the name
+on: [push, workflow_dispatch]
+
+jobs:
+  build:
+    runs-on: 'macos-latest'
+    steps:
+      - uses: actions/checkout@v4
+      - run: "cargo test"   # build it
+EOF
+
+ffs --preserve-format -i ci.yml -- sh -c '
+    echo Build >name
+    echo workflow_dispatch >on/1
+    echo macos-latest >jobs/build/runs-on
+    echo "cargo test" >jobs/build/steps/1/run' || fail yaml_edit
+cmp ci.yml expected.yml || fail yaml
+
+# entries come and go in block collections, too...
+cat >expected.yml <<'EOF'
+# CI config
+name: Build   # the name
+on: [push, workflow_dispatch]
+
+jobs:
+  build:
+    steps:
+      - uses: actions/checkout@v4
+      - run: "cargo test"   # build it
+      - run: cargo fmt
+new: x
+EOF
+
+ffs --preserve-format -i ci.yml -- sh -c '
+    echo x >new
+    rm jobs/build/runs-on
+    mkdir jobs/build/steps/2
+    echo cargo fmt >jobs/build/steps/2/run' 2>msg || fail yaml_add
+cmp ci.yml expected.yml || fail yaml_entries
+grep -e "Couldn't keep the input's formatting" msg >/dev/null && fail yaml_add_warned
+
+# ...but not flow collections, which we rewrite with a warning
+ffs --preserve-format -i ci.yml -- sh -c 'echo x >on/2' 2>msg || fail yaml_flow
+grep -e "Couldn't keep the input's formatting" msg >/dev/null || fail yaml_flow_warning
+[ "$(ffs --no-output ci.yml -- cat on/2)" = "x" ] || fail yaml_saved
+[ "$(ffs --no-output ci.yml -- cat name)" = "Build" ] || fail yaml_kept
+
+cd - >/dev/null
+rm -r "$DIR"
@@ -0,0 +1,59 @@
+#!/bin/sh
+
+. ./fail.def
+
+DIR=$(mktemp -d)
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+
+cat >app.properties <<'EOF2'
+# application settings
+app.name = My App
+app.version=1.2
+app.greeting = Hello, \
+    World
+db.url: jdbc\:mysql://localhost/db
+key\ with\ spaces = v
+unicode = café
+EOF2
+
+# keys are files, with escapes and continued lines read the Java way
+[ "$(ffs --no-output app.properties -- cat app.name)" = "My App" ] || fail name
+[ "$(ffs --no-output app.properties -- cat app.greeting)" = "Hello, World" ] || fail continued
+[ "$(ffs --no-output app.properties -- cat db.url)" = "jdbc:mysql://localhost/db" ] || fail escape
+[ "$(ffs --no-output app.properties -- cat 'key with spaces')" = "v" ] || fail spaces
+[ "$(ffs --no-output app.properties -- cat unicode)" = "café" ] || fail unicode
+
+# with --nest-keys, dotted keys are nested directories
+[ "$(ffs --no-output --nest-keys app.properties -- cat app/version)" = "1.2" ] || fail nested
+[ "$(ffs --no-output --nest-keys app.properties -- cat db/url)" = "jdbc:mysql://localhost/db" ] || fail nested_db
+
+# keys keep their order; new ones go at the end, joined with dots; non-ASCII
+# characters are escaped, as Java does
+ffs -i --nest-keys app.properties -- sh -c '
+    echo 2.0 >app/version
+    mkdir log
+    echo warn >log/level' || fail edit
+cat >expected.properties <<'EOF2'
+app.name=My App
+app.version=2.0
+app.greeting=Hello, World
+db.url=jdbc:mysql://localhost/db
+key\ with\ spaces=v
+unicode=caf\u00E9
+log.level=warn
+EOF2
+diff expected.properties app.properties || fail saved
+
+# a key can't be both a value and a directory
+printf 'a=1\na.b=2\n' >clash.properties
+ffs --no-output --nest-keys clash.properties -- true 2>msg
+[ $? -eq 3 ] || fail clash_status
+grep -e "'a.b' puts keys under 'a'" msg >/dev/null || fail clash
+cp clash.properties flat.properties
+ffs --no-output flat.properties -- true || fail flat
+
+cd - >/dev/null
+rm -r "$DIR"
@@ -0,0 +1,43 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+TGT=$(mktemp)
+
+testcase_cleanup() { rm -f "$TGT"; }
+
+# symlinks to values in the document are JSON References
+ffs -m "$MNT" -o "$TGT" --target json ../json/object.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+ln -s name "$MNT"/alias || fail symlink
+[ -L "$MNT"/alias ] || fail is_link
+[ "$(readlink $MNT/alias)" = "name" ] || fail readlink
+[ "$(cat $MNT/alias)" = "Michael Greenberg" ] || fail follow
+mkdir "$MNT"/dir
+ln -s ../eyes "$MNT"/dir/eyes || fail symlink_subdir
+[ "$(cat $MNT/dir/eyes)" -eq 2 ] || fail follow_subdir
+ln -s /nonexistent "$MNT"/dangling || fail symlink_dangling
+"$WAITFOR" umount "$MNT" || fail unmount1
+"$WAITFOR" exit $PID || fail process1
+
+grep -e '"alias":{"$ref":"#/name"}' "$TGT" >/dev/null 2>&1 || fail ref
+grep -e '"eyes":{"$ref":"#/eyes"}' "$TGT" >/dev/null 2>&1 || fail ref_subdir
+grep -e '"dangling":{"$ref":"/nonexistent"}' "$TGT" >/dev/null 2>&1 || fail ref_dangling
+
+# YAML saves symlinks as aliases
+ffs -m "$MNT" -o "$TGT" --target yaml ../json/object.json &
+PID=$!
+"$WAITFOR" mount "$MNT"
+ln -s name "$MNT"/alias || fail symlink2
+"$WAITFOR" umount "$MNT" || fail unmount2
+"$WAITFOR" exit $PID || fail process2
+
+[ $(grep -c "Michael Greenberg" "$TGT") -eq 1 ] || fail saved_once
+grep -e '&name Michael Greenberg' "$TGT" >/dev/null 2>&1 || fail anchor
+grep -e '\*name' "$TGT" >/dev/null 2>&1 || fail alias
+
+rmdir "$MNT" || fail mount
+rm "$TGT"
@@ -0,0 +1,80 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+MNT=$(mktemp -d)
+FILE=$(mktemp --suffix=.json)
+
+testcase_cleanup() { rm -f "$FILE" "$FILE".ffs-conflict; }
+
+# waits for the watcher to notice, which can take a second
+eventually() {
+    for i in 1 2 3 4 5 6 7 8 9 10; do
+        eval "$1" && return 0
+        sleep 0.5
+    done
+    return 1
+}
+
+echo '{"eyes":2,"name":"Mike","pets":{"cat":"Dorothy"}}' >"$FILE"
+
+ffs --watch -m "$MNT" -i "$FILE" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+
+[ "$(cat $MNT/eyes)" -eq 2 ] || fail eyes
+EYES=$(stat -c %i "$MNT"/eyes)
+PETS=$(stat -c %i "$MNT"/pets)
+
+# outside changes show up, and unchanged paths keep their inodes
+echo '{"eyes":3,"nose":1,"pets":{"cat":"Dorothy","dog":"Toto"}}' >"$FILE"
+eventually '[ "$(cat $MNT/eyes)" -eq 3 ]' || fail reloaded_eyes
+[ "$(cat $MNT/nose)" -eq 1 ] || fail reloaded_nose
+[ "$(cat $MNT/pets/dog)" = "Toto" ] || fail reloaded_dog
+[ -e "$MNT"/name ] && fail reloaded_name
+[ "$(stat -c %i $MNT/eyes)" = "$EYES" ] || fail inode_eyes
+[ "$(stat -c %i $MNT/pets)" = "$PETS" ] || fail inode_pets
+[ "$(cat $MNT/.ffs/dirty)" = "false" ] || fail clean
+
+# our own saves aren't outside changes
+echo 4 >"$MNT"/eyes
+echo >"$MNT"/.ffs/sync || fail sync
+sleep 1.5
+[ "$(cat $MNT/.ffs/conflict)" = "false" ] || fail own_save
+
+# outside changes never clobber local ones
+echo 5 >"$MNT"/eyes
+echo '{"eyes":6}' >"$FILE"
+eventually '[ "$(cat $MNT/.ffs/conflict)" = "true" ]' || fail conflict
+[ "$(cat $MNT/eyes)" -eq 5 ] || fail kept
+[ "$(cat $MNT/nose)" -eq 1 ] || fail kept_nose
+sync "$MNT"/eyes 2>/dev/null && fail fsync_conflict
+[ "$(cat $FILE)" = '{"eyes":6}' ] || fail not_overwritten
+
+# reverting takes the outside changes
+echo >"$MNT"/.ffs/revert || fail revert
+[ "$(cat $MNT/eyes)" -eq 6 ] || fail reverted
+[ -e "$MNT"/nose ] && fail reverted_nose
+[ "$(cat $MNT/.ffs/conflict)" = "false" ] || fail resolved_revert
+
+# syncing explicitly keeps the local changes
+echo 7 >"$MNT"/eyes
+echo '{"eyes":8}' >"$FILE"
+eventually '[ "$(cat $MNT/.ffs/conflict)" = "true" ]' || fail conflict_again
+echo >"$MNT"/.ffs/sync || fail sync_conflict
+[ "$(cat $MNT/.ffs/conflict)" = "false" ] || fail resolved_sync
+[ "$(cat $FILE)" = '{"eyes":7}' ] || fail overwritten
+
+# unmounting with a conflict saves the local changes alongside the input
+echo 9 >"$MNT"/eyes
+echo '{"eyes":10}' >"$FILE"
+eventually '[ "$(cat $MNT/.ffs/conflict)" = "true" ]' || fail conflict_unmount
+"$WAITFOR" umount "$MNT" || fail unmount
+wait $PID
+[ $? -eq 1 ] || fail unmount_status
+[ "$(cat $FILE)" = '{"eyes":10}' ] || fail unmount_overwritten
+[ "$(cat $FILE.ffs-conflict)" = '{"eyes":9}' ] || fail conflict_copy
+
+rmdir "$MNT" || fail mount
+rm "$FILE" "$FILE".ffs-conflict
@@ -0,0 +1,79 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    # commands run in the mount, so this can't be a function
+    TYPEOF="getfattr -n user.type --only-values"
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    TYPEOF="xattr -p user.type"
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+CONFIG="$(pwd)/../xml/config.xml"
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+cp "$CONFIG" config.xml
+
+# elements are directories or files; attributes are @ files
+[ "$(ffs --no-output config.xml -- cat config/name)" = "frontend" ] || fail name
+[ "$(ffs --no-output config.xml -- cat config/@version)" = "2" ] || fail attribute
+[ "$(ffs --no-output config.xml -- $TYPEOF config/@version)" = "integer" ] || fail integer
+[ "$(ffs --no-output config.xml -- cat config/timeout/#text)" = "30" ] || fail text
+[ "$(ffs --no-output config.xml -- $TYPEOF config/debug)" = "null" ] || fail empty
+
+# repeated siblings are a list
+[ "$(ffs --no-output config.xml -- $TYPEOF config/server)" = "list" ] || fail list
+[ "$(ffs --no-output config.xml -- cat config/server/1/@id)" = "b" ] || fail server
+
+# namespace prefixes stay put; mixed content is a list
+[ "$(ffs --no-output config.xml -- cat config/server/0/@v:role)" = "primary" ] || fail prefix
+[ "$(ffs --no-output config.xml -- $TYPEOF config/v:motd/#content)" = "list" ] || fail content
+[ "$(ffs --no-output config.xml -- cat config/v:motd/#content/1/b)" = "frontend" ] || fail mixed
+
+# siblings stay in order; new elements go at the end
+ffs -i --pretty config.xml -- sh -c '
+    echo 9090 >config/server/0/port
+    echo pals >"config/v:motd/#content/3/i"
+    echo info >config/level' || fail edit
+cat >expected.xml <<'EOF2'
+<?xml version="1.0" encoding="UTF-8"?>
+<config version="2" xmlns:v="http://example.com/vendor">
+  <name>frontend</name>
+  <server id="a" v:role="primary">
+    <host>alpha.example.com</host>
+    <port>9090</port>
+  </server>
+  <debug/>
+  <server id="b">
+    <host>beta.example.com</host>
+    <port>8081</port>
+  </server>
+  <v:motd lang="en">Welcome to <b>frontend</b> &amp; <i>pals</i>!</v:motd>
+  <timeout unit="s">30</timeout>
+  <level>info</level>
+</config>
+EOF2
+diff expected.xml config.xml || fail saved
+
+# text is saved just as it was written
+printf '<r><a>1e3</a><b>-0</b><c>1.50</c></r>\n' >numbers.xml
+ffs -i numbers.xml -- sh -c 'echo 2 >r/c' || fail edit_numbers
+[ "$(tail -n 1 numbers.xml)" = "<r><a>1e3</a><b>-0</b><c>2</c></r>" ] || fail numbers
+
+# documents hold exactly one root element, and names must be valid
+printf '{"a": 1, "b": 2}' >roots.json
+ffs -o roots.xml roots.json -- true 2>msg
+grep -e "root element" msg >/dev/null || fail roots
+[ -e roots.xml ] && fail roots_saved
+printf '{"a": {"b c": 1}}' >name.json
+ffs -o name.xml name.json -- true 2>msg
+grep -e "isn't a valid XML name" msg >/dev/null || fail invalid_name
+
+cd - >/dev/null
+rm -r "$DIR"
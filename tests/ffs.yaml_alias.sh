@@ -0,0 +1,82 @@
+#!/bin/sh
+
+WAITFOR="$(cd ../utils; pwd)/waitfor"
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    num_links() {
+        stat --format %h "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    num_links() {
+        stat -f %l "$@"
+    }
+else
+    fail os
+fi
+
+inum() {
+    ls -id "$1" | awk '{print $1}'
+}
+
+MNT=$(mktemp -d)
+CI=$(mktemp)
+TGT=$(mktemp)
+
+testcase_cleanup() { rm -f "$CI" "$TGT"; }
+
+# aliases of scalars are hard links
+ffs -m "$MNT" -o "$TGT" --target yaml ../yaml/alias.yaml &
+PID=$!
+"$WAITFOR" mount "$MNT"
+[ "$(inum $MNT/hr/1)" = "$(inum $MNT/rbi/0)" ] || fail inum1
+[ $(num_links $MNT/hr/1) -eq 2 ] || fail nlink1
+echo "Samuel Sosa" >"$MNT"/rbi/0
+[ "$(cat $MNT/hr/1)" = "Samuel Sosa" ] || fail shared1
+"$WAITFOR" umount "$MNT" || fail unmount1
+"$WAITFOR" exit $PID || fail process1
+
+[ $(grep -c "Samuel Sosa" "$TGT") -eq 1 ] || fail saved_once
+grep -e '&SS Samuel Sosa' "$TGT" >/dev/null 2>&1 || fail anchor
+grep -e '- \*SS' "$TGT" >/dev/null 2>&1 || fail alias
+
+# aliases of mappings are shared directories
+cat >"$CI" <<YAML
+.defaults: &defaults
+  image: ruby
+build:
+  <<: *defaults
+  script: make
+test:
+  <<: *defaults
+  script: make test
+YAML
+
+ffs -m "$MNT" -o "$TGT" --source yaml --target yaml "$CI" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+[ "$(inum $MNT/.defaults)" = "$(inum $MNT/build/'<<')" ] || fail inum2
+[ "$(inum $MNT/.defaults)" = "$(inum $MNT/test/'<<')" ] || fail inum3
+echo alpine >"$MNT"/test/'<<'/image
+[ "$(cat $MNT/build/'<<'/image)" = "alpine" ] || fail shared2
+"$WAITFOR" umount "$MNT" || fail unmount2
+"$WAITFOR" exit $PID || fail process2
+
+[ $(grep -c "alpine" "$TGT") -eq 1 ] || fail saved_once2
+[ $(grep -c "<<: \*defaults" "$TGT") -eq 2 ] || fail merge
+
+# renaming one alias of a directory leaves the others where they were
+ffs -m "$MNT" -o "$TGT" --source yaml --target yaml "$CI" &
+PID=$!
+"$WAITFOR" mount "$MNT"
+mv "$MNT"/test/'<<' "$MNT"/test/base || fail rename
+[ "$(cat "$MNT"/build/'<<'/image)" = "ruby" ] || fail renamed_other
+[ "$(cat "$MNT"/test/base/image)" = "ruby" ] || fail renamed
+"$WAITFOR" umount "$MNT" || fail unmount3
+"$WAITFOR" exit $PID || fail process3
+
+grep -e '<<: \*defaults' "$TGT" >/dev/null 2>&1 || fail renamed_merge
+grep -e 'base: \*defaults' "$TGT" >/dev/null 2>&1 || fail renamed_alias
+
+rmdir "$MNT" || fail mount
+rm "$CI" "$TGT"
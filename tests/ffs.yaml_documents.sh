@@ -0,0 +1,54 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    # commands run in the mount, so these can't be functions
+    TYPEOF="getfattr -n user.type --only-values"
+    SETTYPE="setfattr -n user.type -v"
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    TYPEOF="xattr -p user.type"
+    SETTYPE="xattr -w user.type"
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+DOCS="$(pwd)/../yaml/documents.yaml"
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+cp "$DOCS" documents.yaml
+
+# each document is an element of the root
+[ "$(ffs --no-output documents.yaml -- $TYPEOF .)" = "documents" ] || fail type
+[ "$(ffs --no-output documents.yaml -- ls)" = "$(printf '0\n1')" ] || fail ls
+[ "$(ffs --no-output documents.yaml -- cat 1/kind)" = "Deployment" ] || fail kind
+
+# saving keeps the documents separate
+ffs -i documents.yaml -- sh -c 'echo 3 >1/spec/replicas' || fail edit
+[ "$(grep -c -e '^---$' documents.yaml)" -eq 2 ] || fail separators
+grep -e '^- ' documents.yaml >/dev/null && fail list
+[ "$(ffs --no-output documents.yaml -- cat 1/spec/replicas)" = "3" ] || fail saved
+[ "$(ffs --no-output documents.yaml -- cat 0/metadata/name)" = "web" ] || fail other
+
+# other formats have no streams, so the documents are a list
+ffs -o documents.json documents.yaml -- true || fail json
+[ "$(head -c 1 documents.json)" = "[" ] || fail json_list
+
+# a single document is just that document
+printf 'a: 1\n' >one.yaml
+[ "$(ffs --no-output one.yaml -- $TYPEOF .)" = "named" ] || fail one_type
+ffs -i one.yaml -- true || fail one_save
+[ "$(grep -c -e '^---$' one.yaml)" -eq 1 ] || fail one_separators
+
+# any list can become a stream
+printf -- '- a: 1\n- b: 2\n' >list.yaml
+ffs -i list.yaml -- $SETTYPE documents . || fail settype
+[ "$(grep -c -e '^---$' list.yaml)" -eq 2 ] || fail list_separators
+[ "$(ffs --no-output list.yaml -- cat 1/b)" = "2" ] || fail list_saved
+
+cd - >/dev/null
+rm -r "$DIR"
@@ -0,0 +1,46 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    if [ "$MNT" ]
+    then
+        rm -r "$MNT"
+        rm -r "$D"
+    fi
+    exit 1
+}
+
+MNT=$(mktemp -d)
+D=$(mktemp -d)
+
+unpack --into "$MNT" ../json/object.json || fail unpack1
+
+# permissions on the output are preserved
+echo '{}' >"$D"/out.json
+chmod 600 "$D"/out.json
+pack -o "$D"/out.json "$MNT" || fail pack1
+ls -l "$D"/out.json | grep -e 'rw-------' >/dev/null 2>&1 || fail mode
+grep -e '"name":"Michael Greenberg"' "$D"/out.json >/dev/null 2>&1 || fail output1
+[ "$(ls -A "$D")" = "out.json" ] || fail tmpfile1
+rm -r "$MNT"
+
+# a failed save leaves the old output alone
+unpack --into "$MNT" ../json/list.json || fail unpack2
+echo 'title = "original"' >"$D"/out.toml
+pack -o "$D"/out.toml "$MNT" 2>/dev/null && fail pack2
+[ "$(cat "$D"/out.toml)" = 'title = "original"' ] || fail output2
+case $(ls -A "$D") in
+    (out.json*out.toml) ;;
+    (*) fail tmpfile2;;
+esac
+
+
+# special files (like a pipe) are written directly, and can't be synced
+{ pack -o /dev/stdout "$MNT" 2>"$D"/msg; echo $? >"$D"/status; } | cat >"$D"/piped.json
+[ "$(cat "$D"/status)" -eq 0 ] || fail pipe
+grep -e "Couldn't save" "$D"/msg >/dev/null && fail pipe_msg
+[ -s "$D"/piped.json ] || fail piped_output
+rm "$D"/status "$D"/msg "$D"/piped.json
+
+rm -r "$MNT" || fail mount
+rm -r "$D"
@@ -0,0 +1,60 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$MNT0" "$MNT1"
+    rm -f "$BIN" "$EXPECTED" "$GOT"
+    exit 1
+}
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    typeof() {
+        getfattr -n user.type --only-values "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    typeof() {
+        xattr -p user.type "$@"
+    }
+else
+    fail os
+fi
+
+MNT0=$(mktemp -d)
+MNT1=$(mktemp -d)
+BIN=$(mktemp)
+EXPECTED=$(mktemp)
+GOT=$(mktemp)
+
+unpack --into "$MNT0" ../toml/eg.toml || fail unpack0
+cp ../binary/twitter.ico "$MNT0"/favicon
+echo 2.5 >"$MNT0"/ratio
+pack -t json "$MNT0" >"$EXPECTED" || fail pack0
+
+for fmt in cbor msgpack
+do
+    rm -r "$MNT1"
+    pack -t $fmt "$MNT0" >"$BIN" || fail "pack $fmt"
+    # bytes are stored natively, not as base64
+    grep AAABAAEAEBAA "$BIN" >/dev/null 2>&1 && fail "base64 $fmt"
+    unpack -t $fmt --into "$MNT1" "$BIN" || fail "unpack $fmt"
+
+    [ "$(typeof $MNT1/database/connection_max)" = "integer" ] || fail "integer $fmt"
+    [ "$(typeof $MNT1/ratio)"                   = "float"   ] || fail "float $fmt"
+    [ "$(typeof $MNT1/favicon)"                 = "bytes"   ] || fail "bytes $fmt"
+    cmp ../binary/twitter.ico "$MNT1"/favicon || fail "favicon $fmt"
+
+    pack -t json "$MNT1" >"$GOT" || fail "repack $fmt"
+    diff "$EXPECTED" "$GOT" || fail "diff $fmt"
+done
+
+# CBOR has a tag for datetimes, so TOML survives the trip exactly
+pack -t toml "$MNT0" >"$EXPECTED" || fail pack_toml0
+pack -t cbor "$MNT0" >"$BIN" || fail pack_cbor
+rm -r "$MNT1"
+unpack -t cbor --into "$MNT1" "$BIN" || fail unpack_cbor
+pack -t toml "$MNT1" >"$GOT" || fail pack_toml1
+diff "$EXPECTED" "$GOT" || fail diff_toml
+
+rm -r "$MNT0" "$MNT1"
+rm "$BIN" "$EXPECTED" "$GOT"
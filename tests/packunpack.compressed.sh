@@ -0,0 +1,51 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+D=$(mktemp -d)
+
+cp ../json/object.json "$D"/object.json
+gzip -c ../json/object.json >"$D"/object.json.gz
+bzip2 -c ../yaml/invoice.yaml >"$D"/invoice.yaml.bz2
+
+# the compression extension is skipped when inferring formats and directories
+cd "$D"
+unpack object.json.gz || fail unpack_gz
+[ -d object ] || fail infer_dir
+[ "$(cat object/name)" = "Michael Greenberg" ] || fail gz
+unpack invoice.yaml.bz2 || fail unpack_bz2
+[ "$(cat invoice/bill-to/given)" = "Chris" ] || fail bz2
+cd - >/dev/null
+
+# output is compressed by extension...
+pack -o "$D"/packed.json.gz "$D"/object || fail pack_gz
+gzip -dc "$D"/packed.json.gz >"$D"/packed.json || fail gunzip
+pack -t json "$D"/object >"$D"/expected.json || fail pack_json
+diff "$D"/expected.json "$D"/packed.json || fail packed_gz
+
+# ...and every algorithm round trips
+for ext in bz2 gz xz zst
+do
+    pack -o "$D"/loop.json.$ext "$D"/object || fail pack_$ext
+    unpack --into "$D"/unpacked.$ext "$D"/loop.json.$ext || fail unpack_$ext
+    pack -t json "$D"/unpacked.$ext >"$D"/got.json || fail repack_$ext
+    diff "$D"/expected.json "$D"/got.json || fail roundtrip_$ext
+done
+[ "$(od -An -tx1 -N4 "$D"/loop.json.zst | tr -d ' \n')" = "28b52ffd" ] || fail zstd_magic
+[ "$(od -An -tx1 -N6 "$D"/loop.json.xz | tr -d ' \n')" = "fd377a585a00" ] || fail xz_magic
+
+# `--compress` works on stdin and stdout, too
+pack --compress gzip -t json "$D"/object | gzip -dc >"$D"/stdout.json || fail stdout
+diff "$D"/expected.json "$D"/stdout.json || fail compressed_stdout
+gzip -c "$D"/expected.json | unpack --compress gzip --into "$D"/stdin || fail stdin
+[ "$(cat "$D"/stdin/name)" = "Michael Greenberg" ] || fail compressed_stdin
+
+# data that isn't compressed is an error, not garbage
+unpack --into "$D"/bad --compress gzip "$D"/object.json 2>"$D"/msg && fail uncompressed
+grep -i -e "invalid gzip header" "$D"/msg >/dev/null 2>&1 || fail uncompressed_msg
+
+rm -r "$D"
@@ -0,0 +1,71 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    typeof() {
+        getfattr -n user.type --only-values "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    typeof() {
+        xattr -p user.type "$@"
+    }
+else
+    fail os
+fi
+
+D=$(mktemp -d)
+
+cat >"$D"/in.csv <<'EOT'
+id,price,label
+1,2.5,"a, b"
+2,,"say ""hi"""
+EOT
+
+unpack --into "$D"/out "$D"/in.csv || fail unpack
+[ "$(typeof "$D"/out)" = "list" ] || fail list
+[ "$(typeof "$D"/out/0)" = "named" ] || fail named
+[ "$(typeof "$D"/out/0/price)" = "float" ] || fail float
+[ "$(typeof "$D"/out/1/price)" = "null" ] || fail null
+[ "$(cat "$D"/out/1/label)" = 'say "hi"' ] || fail quotes
+
+# quoting survives the trip
+pack -t csv "$D"/out >"$D"/got.csv || fail pack
+grep -e '"a, b"' "$D"/got.csv >/dev/null || fail comma
+grep -e '"say ""hi"""' "$D"/got.csv >/dev/null || fail quote
+
+# ...and so does the order of the columns
+printf 'z,a,m\n1,2,3\n' >"$D"/columns.csv
+unpack --into "$D"/columns "$D"/columns.csv || fail unpack_columns
+pack -t csv "$D"/columns >"$D"/got_columns.csv || fail pack_columns
+diff "$D"/columns.csv "$D"/got_columns.csv || fail columns
+
+# a header with no rows is still a header
+printf 'a,b\n' >"$D"/header.csv
+unpack --into "$D"/header "$D"/header.csv || fail unpack_header
+pack -t csv "$D"/header >"$D"/got_header.csv || fail pack_header
+diff "$D"/header.csv "$D"/got_header.csv || fail header_only
+
+# TSV is CSV with tabs
+pack -t tsv "$D"/out >"$D"/got.tsv || fail pack_tsv
+unpack -t tsv --into "$D"/again "$D"/got.tsv || fail unpack_tsv
+pack -t json "$D"/out >"$D"/expected.json || fail json1
+pack -t json "$D"/again >"$D"/got.json || fail json2
+diff "$D"/expected.json "$D"/got.json || fail roundtrip
+
+# without a header, the header is just another row
+unpack --no-header --into "$D"/rows "$D"/in.csv || fail no_header
+[ "$(cat "$D"/rows/0/1)" = "price" ] || fail header_row
+[ "$(typeof "$D"/rows/1)" = "list" ] || fail row_list
+
+# a custom delimiter
+printf 'x|y\n1|2\n' >"$D"/pipes.csv
+unpack --delimiter '|' --into "$D"/pipes "$D"/pipes.csv || fail delimiter
+[ "$(cat "$D"/pipes/0/y)" = "2" ] || fail pipes
+
+rm -r "$D"
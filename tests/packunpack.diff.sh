@@ -0,0 +1,65 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$MNT"
+    rm -f "$OLD" "$OUT"
+    exit 1
+}
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which setfattr || fail setfattr
+    setattr() {
+        attr="$1"
+        val="$2"
+        shift 2
+        setfattr -n "$attr" -v "$val" "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    setattr() {
+        attr="$1"
+        val="$2"
+        shift 2
+        xattr -w "$attr" "$val" "$@"
+    }
+else
+    fail os
+fi
+
+MNT=$(mktemp -d)
+OLD=$(mktemp)
+OUT=$(mktemp)
+
+echo '{"name":"Michael","eyes":2,"gone":{"x":1},"list":["a","b","c"]}' >"$OLD"
+unpack -t json --into "$MNT" "$OLD" || fail unpack
+
+# no differences, no output
+pack --diff "$OLD" "$MNT" >"$OUT" || fail same
+[ -s "$OUT" ] && fail same_output
+
+echo Mike >"$MNT"/name
+rm -r "$MNT"/gone "$MNT"/list/1 "$MNT"/list/2
+echo '["ok"]' >"$MNT"/new
+setattr user.type string "$MNT"/new
+pack --diff "$OLD" "$MNT" >"$OUT" || fail diff
+[ "$(cat $OUT)" = '- /gone: {"x":1}
+- /list/2: "c"
+- /list/1: "b"
+~ /name: "Michael" -> "Mike"
++ /new: "[\"ok\"]"' ] || fail diff_output
+
+# the other document may be in another format
+printf 'name = "Mike"\neyes = 2\nnew = "[\\"ok\\"]"\nlist = ["a"]\n' >"$OLD".toml
+pack --diff "$OLD".toml -t json "$MNT" >"$OUT" || fail toml
+[ -s "$OUT" ] && fail toml_output
+
+# ...and may be compressed
+gzip -c "$OLD".toml >"$OLD".toml.gz
+pack --diff "$OLD".toml.gz -t json "$MNT" >"$OUT" || fail gz
+[ -s "$OUT" ] && fail gz_output
+
+# --diff doesn't save
+pack --diff "$OLD" -o "$OUT" "$MNT" 2>/dev/null && fail output
+
+rm -r "$MNT" || fail mount
+rm "$OLD" "$OLD".toml "$OLD".toml.gz "$OUT"
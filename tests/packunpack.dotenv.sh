@@ -0,0 +1,23 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+D=$(mktemp -d)
+
+cat >"$D"/app.env <<'EOF2'
+export PORT=8080
+export LIMIT=1e3
+export NAME='a b'
+EOF2
+
+# dotenv files keep their order, exports, and values through a round trip
+unpack --into "$D"/app "$D"/app.env || fail unpack
+[ "$(cat "$D"/app/PORT)" -eq 8080 ] || fail port
+pack -t dotenv "$D"/app >"$D"/got.env || fail pack
+diff "$D"/app.env "$D"/got.env || fail roundtrip
+
+rm -r "$D"
@@ -0,0 +1,46 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$MNT"
+    rm -f "$JSON" "$OUT"
+    exit 1
+}
+
+MNT=$(mktemp -d)
+JSON=$(mktemp)
+OUT=$(mktemp)
+rmdir "$MNT"
+
+echo '{"name":"Michael","eyes":2,"list":["a"]}' >"$JSON"
+
+# a dry run doesn't even make the directory
+unpack --dry-run --into "$MNT" "$JSON" >"$OUT" || fail dry_run
+[ -e "$MNT" ] && fail created
+grep -qx "mkdir $MNT" "$OUT" || fail mkdir_root
+grep -qx "create $MNT/eyes" "$OUT" || fail create
+grep -qx "xattr $MNT/eyes user.type=integer" "$OUT" || fail xattr
+grep -qx "mkdir $MNT/list" "$OUT" || fail mkdir
+grep -qx "xattr $MNT/list user.type=list" "$OUT" || fail xattr_list
+grep -qx "create $MNT/list/0" "$OUT" || fail create_list
+
+unpack --into "$MNT" "$JSON" || fail unpack
+
+# with --merge, only the changes are listed
+echo '{"name":"Mike","eyes":"2","list":["a"]}' >"$JSON"
+unpack -n --merge --delete --into "$MNT" "$JSON" >"$OUT" || fail merge
+[ "$(cat $OUT)" = "xattr $MNT/eyes user.type=string
+write $MNT/name" ] || fail merge_output
+[ "$(cat $MNT/name)" = "Michael" ] || fail unchanged
+
+echo '{"list":"flat"}' >"$JSON"
+unpack -n --merge --delete --into "$MNT" "$JSON" >"$OUT" || fail delete
+[ "$(cat $OUT)" = "remove $MNT/eyes
+remove $MNT/name
+remove $MNT/list
+create $MNT/list
+xattr $MNT/list user.type=string" ] || fail delete_output
+[ -d "$MNT"/list ] || fail still_list
+
+rm -r "$MNT" || fail mount
+rm "$JSON" "$OUT"
@@ -0,0 +1,50 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$MNT"
+    rm -f "$OUT"
+    exit 1
+}
+
+MNT=$(mktemp -d)
+OUT=$(mktemp)
+
+mkdir -p "$MNT"/.git/objects "$MNT"/src/build "$MNT"/docs
+echo ref >"$MNT"/.git/HEAD
+echo 1 >"$MNT"/src/main
+echo 2 >"$MNT"/src/main~
+echo 3 >"$MNT"/src/.main.swp
+echo 4 >"$MNT"/src/build/out
+echo 5 >"$MNT"/docs/readme
+echo 6 >"$MNT"/docs/notes.log
+echo 7 >"$MNT"/docs/keep.log
+
+# excluded paths are skipped entirely
+pack --exclude .git --exclude '*~' --exclude '*.swp' --exclude src/build "$MNT" >"$OUT" || fail exclude
+[ "$(cat $OUT)" = '{"docs":{"keep.log":7,"notes.log":6,"readme":5},"src":{"main":1}}' ] || fail exclude_output
+
+# patterns match paths relative to the directory being packed
+pack --exclude build "$MNT" >"$OUT" || fail relative
+grep -e '"build"' "$OUT" >/dev/null || fail relative_output
+
+# only included files are packed, but directories are still searched
+pack --include 'docs/*' --exclude '**/notes.log' "$MNT" >"$OUT" || fail include
+[ "$(cat $OUT)" = '{".git":{"objects":{}},"docs":{"keep.log":7,"readme":5},"src":{"build":{}}}' ] || fail include_output
+
+# ignore files apply to their own directories, and .git is skipped
+printf 'build/\n*.swp\n' >"$MNT"/.gitignore
+printf '*.log\n!keep.log\n' >"$MNT"/docs/.ignore
+pack --respect-gitignore --exclude '**/*~' "$MNT" >"$OUT" || fail gitignore
+[ "$(cat $OUT)" = '{".gitignore":"build/\n*.swp","docs":{".ignore":"*.log\n!keep.log","keep.log":7,"readme":5},"src":{"main":1}}' ] || fail gitignore_output
+
+# ...but only when asked
+pack "$MNT" >"$OUT" || fail default
+grep -e '"HEAD"' "$OUT" >/dev/null || fail default_git
+grep -e '"notes.log"' "$OUT" >/dev/null || fail default_ignore
+
+pack --exclude '[' "$MNT" >"$OUT" 2>/dev/null
+[ $? -eq 2 ] || fail bad_pattern
+
+rm -r "$MNT"
+rm "$OUT"
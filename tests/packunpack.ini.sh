@@ -0,0 +1,63 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+D=$(mktemp -d)
+
+cat >"$D"/app.ini <<'EOF2'
+name = frontend
+
+[server]
+host = alpha.example.com
+port = 8080
+EOF2
+
+cat >"$D"/app.properties <<'EOF2'
+server.host=alpha.example.com
+server.port=8080
+name=frontend
+EOF2
+
+# INI and properties files migrate to JSON
+unpack --into "$D"/ini "$D"/app.ini || fail unpack_ini
+pack -t json "$D"/ini >"$D"/ini.json || fail pack_ini
+printf '{"name":"frontend","server":{"host":"alpha.example.com","port":8080}}' >"$D"/expected.json
+diff "$D"/expected.json "$D"/ini.json || fail ini_json
+
+unpack --nest-keys --into "$D"/properties "$D"/app.properties || fail unpack_properties
+pack -t json "$D"/properties >"$D"/properties.json || fail pack_properties
+diff "$D"/expected.json "$D"/properties.json || fail properties_json
+
+# ...and back again
+pack -t ini "$D"/properties >"$D"/got.ini || fail pack_ini_again
+diff "$D"/app.ini "$D"/got.ini || fail ini_roundtrip
+pack -t properties "$D"/ini >"$D"/got.properties || fail pack_properties_again
+printf 'name=frontend\nserver.host=alpha.example.com\nserver.port=8080\n' >"$D"/expected.properties
+diff "$D"/expected.properties "$D"/got.properties || fail properties_roundtrip
+
+# each format keeps its own order (and values as written) through a round trip
+cat >"$D"/order.ini <<'EOF2'
+zone = west
+
+[server]
+port = 8080
+host = alpha.example.com
+limit = 1e3
+
+[client]
+retries = -0
+EOF2
+unpack --into "$D"/order "$D"/order.ini || fail unpack_order_ini
+pack -t ini "$D"/order >"$D"/order.got.ini || fail pack_order_ini
+diff "$D"/order.ini "$D"/order.got.ini || fail ini_order
+
+printf 'z=1\nm=1e3\na=-0\n' >"$D"/order.properties
+unpack --into "$D"/order_properties "$D"/order.properties || fail unpack_order_properties
+pack -t properties "$D"/order_properties >"$D"/order.got.properties || fail pack_order_properties
+diff "$D"/order.properties "$D"/order.got.properties || fail properties_order
+
+rm -r "$D"
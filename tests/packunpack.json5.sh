@@ -0,0 +1,56 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+D=$(mktemp -d)
+
+cat >"$D"/settings.jsonc <<'EOF2'
+{
+    // the editor
+    "editor.fontSize": 14,
+    "files.exclude": {
+        "**/.git": true, /* hidden */
+    },
+}
+EOF2
+
+# JSONC migrates to JSON...
+unpack --into "$D"/settings "$D"/settings.jsonc || fail unpack
+[ "$(cat "$D"/settings/editor.fontSize)" -eq 14 ] || fail font_size
+pack -t json "$D"/settings >"$D"/settings.json || fail pack
+printf '{"editor.fontSize":14,"files.exclude":{"**/.git":true}}' >"$D"/expected.json
+diff "$D"/expected.json "$D"/settings.json || fail json
+
+# ...and JSON5 comes back as JSON5
+pack -t json5 "$D"/settings >"$D"/got.json5 || fail pack_json5
+unpack --into "$D"/again "$D"/got.json5 || fail unpack_json5
+pack -t json "$D"/again >"$D"/again.json || fail pack_again
+diff "$D"/expected.json "$D"/again.json || fail roundtrip
+
+# JSON5 keeps its comments and key order through a round trip
+unpack --into "$D"/loop "$D"/settings.jsonc || fail unpack_loop
+pack -t json5 "$D"/loop >"$D"/loop.json5 || fail pack_loop
+cat >"$D"/expected.json5 <<'EOF2'
+{
+    // the editor
+    "editor.fontSize": 14,
+    "files.exclude": {
+        "**/.git": true /* hidden */
+    }
+}
+EOF2
+diff "$D"/expected.json5 "$D"/loop.json5 || fail loop
+
+# Infinity and NaN are floats, but JSON has no such numbers
+printf '{"n": NaN, "z": -Infinity}' >"$D"/limits.json5
+unpack --into "$D"/limits "$D"/limits.json5 || fail unpack_limits
+pack -t json5 "$D"/limits | grep -e '"z": -Infinity' >/dev/null || fail limits_json5
+pack -t json "$D"/limits >"$D"/limits.json || fail pack_limits
+printf '{"n":"NaN","z":"-Infinity"}' >"$D"/expected.json
+diff "$D"/expected.json "$D"/limits.json || fail limits_json
+
+rm -r "$D"
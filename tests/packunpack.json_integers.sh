@@ -0,0 +1,33 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$MNT"
+    rm -f "$FILE" "$GOT"
+    exit 1
+}
+
+MNT=$(mktemp -d)
+FILE=$(mktemp)
+GOT=$(mktemp)
+
+echo '{"id":9007199254740993,"huge":123456789012345678901234567890,"ratio":2.5}' >"$FILE"
+
+rmdir "$MNT"
+unpack -t json --into "$MNT" "$FILE" || fail unpack
+
+# integers stay integers in other formats...
+for fmt in cbor msgpack toml yaml
+do
+    pack -t $fmt "$MNT" >"$GOT" || fail "pack $fmt"
+    unpack -t $fmt --into "$MNT.$fmt" "$GOT" || fail "unpack $fmt"
+    [ "$(cat $MNT.$fmt/id)" = "9007199254740993" ] || fail "id $fmt"
+    rm -r "$MNT.$fmt"
+done
+
+# ... and JSON keeps them exactly
+pack -t json "$MNT" >"$GOT" || fail pack
+[ "$(cat $GOT)" = '{"huge":123456789012345678901234567890,"id":9007199254740993,"ratio":2.5}' ] || fail exact
+
+rm -r "$MNT"
+rm "$FILE" "$GOT"
@@ -70,8 +70,8 @@ unpack --into "$MNT" --no-xattr ../json/object.json || fail unpack1
 
 [ "$(typeof $MNT)"             = "named"   ] && fail root
 [ "$(typeof $MNT/name)"        = "string"  ] && fail name
-[ "$(typeof $MNT/eyes)"        = "float"   ] && fail eyes
-[ "$(typeof $MNT/fingernails)" = "float"   ] && fail fingernails
+[ "$(typeof $MNT/eyes)"        = "integer" ] && fail eyes
+[ "$(typeof $MNT/fingernails)" = "integer" ] && fail fingernails
 [ "$(typeof $MNT/human)"       = "boolean" ] && fail human
 
 setattr user.type list "$MNT" || fail set1
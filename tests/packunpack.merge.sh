@@ -0,0 +1,83 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$MNT"
+    rm -f "$JSON" "$OUT"
+    exit 1
+}
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    typeof() {
+        getfattr -n user.type --only-values "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    typeof() {
+        xattr -p user.type "$@"
+    }
+else
+    fail os
+fi
+
+MNT=$(mktemp -d)
+JSON=$(mktemp)
+OUT=$(mktemp)
+
+echo '{"name":"Michael","eyes":2,"dir":{"a":1,"b":2},"list":[1,2]}' >"$JSON"
+unpack --into "$MNT" "$JSON" || fail unpack
+
+# a non-empty directory needs --merge
+echo '{"name":"Mike"}' | unpack --into "$MNT" 2>/dev/null && fail nonempty
+[ "$(cat $MNT/name)" = "Michael" ] || fail nonempty_unchanged
+
+# unchanged files keep their mtimes
+touch -t 200101010000 "$MNT"/eyes "$MNT"/name
+touch -t 200101010001 "$OUT"
+echo local >"$MNT"/local
+mkdir "$MNT"/.git
+echo ref >"$MNT"/.git/HEAD
+
+echo '{"name":"Mike","eyes":2,"dir":{"a":1,"c":3},"list":"flat"}' >"$JSON"
+unpack --merge --into "$MNT" "$JSON" || fail merge
+[ "$(cat $MNT/name)" = "Mike" ] || fail name
+[ "$MNT"/eyes -nt "$OUT" ] && fail mtime
+[ "$MNT"/name -nt "$OUT" ] || fail name_mtime
+[ "$(cat $MNT/dir/c)" = "3" ] || fail new_file
+[ -f "$MNT"/list ] || fail list_to_file
+[ "$(cat $MNT/list)" = "flat" ] || fail list_contents
+# without --delete, other files stay
+[ -f "$MNT"/dir/b ] || fail kept_b
+[ -f "$MNT"/local ] || fail kept_local
+
+# types are updated, too
+echo '{"name":"Mike","eyes":"2","dir":{"a":1,"c":3},"list":"flat"}' >"$JSON"
+unpack --overwrite --into "$MNT" "$JSON" || fail overwrite
+[ "$(typeof $MNT/eyes)" = "string" ] || fail type
+
+# --delete removes everything that isn't in the input... except .git
+unpack --merge --delete --into "$MNT" "$JSON" || fail delete
+[ -e "$MNT"/dir/b ] && fail deleted_b
+[ -e "$MNT"/local ] && fail deleted_local
+[ "$(cat $MNT/.git/HEAD)" = "ref" ] || fail kept_git
+rm -r "$MNT"/.git
+pack "$MNT" >"$OUT" || fail pack
+[ "$(cat $OUT)" = '{"dir":{"a":1,"c":3},"eyes":"2","list":"flat","name":"Mike"}' ] || fail pack_output
+
+# lists are always merged exactly: when the padding changes, the old
+# elements go even without --delete
+echo '[1,2,3,4,5,6,7,8,9,10]' >"$JSON"
+rm -r "$MNT"/*
+unpack --merge --into "$MNT" "$JSON" || fail list10
+echo '[1,2,3,4,5,6,7,8,9,10,11]' >"$JSON"
+unpack --merge --into "$MNT" "$JSON" || fail list11
+[ -e "$MNT"/0 ] && fail stale_element
+[ "$(cat $MNT/00)" = "1" ] || fail padded_element
+pack "$MNT" >"$OUT" || fail pack_list
+[ "$(cat $OUT)" = '[1,2,3,4,5,6,7,8,9,10,11]' ] || fail pack_list_output
+
+# --delete only makes sense when merging
+unpack --delete --into "$MNT" "$JSON" 2>/dev/null && fail delete_without_merge
+
+rm -r "$MNT" || fail mount
+rm "$JSON" "$OUT"
@@ -0,0 +1,40 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+D=$(mktemp -d)
+
+printf '{"name": "Alice", "age": 30}\n\n{"name": "Bob", "tags": ["a", "b"]}\n"just a string"\n' >"$D"/in.jsonl
+
+# each line is an element of the root list
+unpack --into "$D"/out "$D"/in.jsonl || fail unpack
+[ "$(cat "$D"/out/0/name)" = "Alice" ] || fail alice
+[ "$(cat "$D"/out/1/tags/1)" = "b" ] || fail tags
+[ "$(cat "$D"/out/2)" = "just a string" ] || fail string
+[ "$(ls "$D"/out | wc -l | tr -d ' ')" = "3" ] || fail count
+
+# one compact value per line
+echo 31 >"$D"/out/0/age
+cat >"$D"/expected <<'EOT'
+{"age":31,"name":"Alice"}
+{"name":"Bob","tags":["a","b"]}
+"just a string"
+EOT
+pack -t ndjson "$D"/out >"$D"/got || fail pack
+diff "$D"/expected "$D"/got || fail diff
+
+# converting from JSON, a list becomes lines
+pack -t ndjson "$D"/out | unpack -t ndjson --into "$D"/again - || fail roundtrip
+pack -t json "$D"/out >"$D"/expected.json || fail pack_json1
+pack -t json "$D"/again >"$D"/got.json || fail pack_json2
+diff "$D"/expected.json "$D"/got.json || fail diff_json
+
+# anything else is a single line
+pack -t ndjson "$D"/out/0 >"$D"/got || fail pack_object
+[ "$(wc -l <"$D"/got | tr -d ' ')" = "1" ] || fail object_lines
+
+rm -r "$D"
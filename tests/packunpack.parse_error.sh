@@ -0,0 +1,113 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    if [ "$MNT" ]
+    then
+        rm -r "$MNT"
+        rm -r "$D"
+    fi
+    exit 1
+}
+
+MNT=$(mktemp -d)
+D=$(mktemp -d)
+
+printf '{\n  "name": "Michael Greenberg",\n  "eyes": \n}\n' >"$D"/bad.json
+unpack --into "$MNT" "$D"/bad.json 2>"$D"/msg
+[ $? -eq 3 ] || fail json_status
+grep -i -e "JSON parse error at line 4, column 1" "$D"/msg >/dev/null 2>&1 || fail json_msg
+grep -i -e "panicked" "$D"/msg >/dev/null 2>&1 && fail json_panic
+
+printf 'title = "TOML Example"\nowner = \n' >"$D"/bad.toml
+unpack --into "$MNT" "$D"/bad.toml 2>"$D"/msg
+[ $? -eq 3 ] || fail toml_status
+grep -i -e "TOML parse error at line 2" "$D"/msg >/dev/null 2>&1 || fail toml_msg
+
+printf 'a: [1, 2\nb: 3\n' >"$D"/bad.yaml
+unpack --into "$MNT" "$D"/bad.yaml 2>"$D"/msg
+[ $? -eq 3 ] || fail yaml_status
+grep -i -e "YAML parse error at line" "$D"/msg >/dev/null 2>&1 || fail yaml_msg
+
+# a map of two entries with only one present
+printf '\242\141a\001' >"$D"/bad.cbor
+unpack --into "$MNT" "$D"/bad.cbor 2>"$D"/msg
+[ $? -eq 3 ] || fail cbor_status
+grep -i -e "CBOR parse error" "$D"/msg >/dev/null 2>&1 || fail cbor_msg
+
+# tags other than datetimes and keys that aren't text can't be saved, so
+# they're refused rather than lost: {"a": 5(h'01')} and {1: 2}
+printf '\241\141a\305\101\001' >"$D"/tag.cbor
+unpack --into "$MNT" "$D"/tag.cbor 2>"$D"/msg
+[ $? -eq 3 ] || fail cbor_tag_status
+grep -i -e "'/a' has tag 5" "$D"/msg >/dev/null 2>&1 || fail cbor_tag_msg
+
+printf '\241\001\002' >"$D"/key.cbor
+unpack --into "$MNT" "$D"/key.cbor 2>"$D"/msg
+[ $? -eq 3 ] || fail cbor_key_status
+grep -i -e "only text keys" "$D"/msg >/dev/null 2>&1 || fail cbor_key_msg
+
+printf '\202\001' >"$D"/bad.msgpack
+unpack --into "$MNT" "$D"/bad.msgpack 2>"$D"/msg
+[ $? -eq 3 ] || fail msgpack_status
+grep -i -e "MSGPACK parse error" "$D"/msg >/dev/null 2>&1 || fail msgpack_msg
+
+# {"a": ext(5, 01)} and {1: 2}
+printf '\201\241a\324\005\001' >"$D"/ext.msgpack
+unpack --into "$MNT" "$D"/ext.msgpack 2>"$D"/msg
+[ $? -eq 3 ] || fail msgpack_ext_status
+grep -i -e "'/a' has extension type 5" "$D"/msg >/dev/null 2>&1 || fail msgpack_ext_msg
+
+printf '\201\001\002' >"$D"/key.msgpack
+unpack --into "$MNT" "$D"/key.msgpack 2>"$D"/msg
+[ $? -eq 3 ] || fail msgpack_key_status
+grep -i -e "only string keys" "$D"/msg >/dev/null 2>&1 || fail msgpack_key_msg
+
+printf '{"a": 1}\n\n{"a":\n' >"$D"/bad.ndjson
+unpack --into "$MNT" "$D"/bad.ndjson 2>"$D"/msg
+[ $? -eq 3 ] || fail ndjson_status
+grep -i -e "NDJSON parse error at line 3" "$D"/msg >/dev/null 2>&1 || fail ndjson_msg
+
+printf 'a,b\n1,2\n3\n' >"$D"/bad.csv
+unpack --into "$MNT" "$D"/bad.csv 2>"$D"/msg
+[ $? -eq 3 ] || fail csv_status
+grep -i -e "CSV parse error at line 3" "$D"/msg >/dev/null 2>&1 || fail csv_msg
+
+printf '<a><b></a>' >"$D"/bad.xml
+unpack --into "$MNT" "$D"/bad.xml 2>"$D"/msg
+[ $? -eq 3 ] || fail xml_status
+grep -i -e "XML parse error" "$D"/msg >/dev/null 2>&1 || fail xml_msg
+
+printf '[section\n' >"$D"/bad.ini
+unpack --into "$MNT" "$D"/bad.ini 2>"$D"/msg
+[ $? -eq 3 ] || fail ini_status
+grep -i -e "INI parse error at line 1" "$D"/msg >/dev/null 2>&1 || fail ini_msg
+
+printf 'a=1\nb=\\u00zz\n' >"$D"/bad.properties
+unpack --into "$MNT" "$D"/bad.properties 2>"$D"/msg
+[ $? -eq 3 ] || fail properties_status
+grep -i -e "PROPERTIES parse error at line 2" "$D"/msg >/dev/null 2>&1 || fail properties_msg
+
+printf '{\n  // fine\n  a: [1 2],\n}\n' >"$D"/bad.json5
+unpack --into "$MNT" "$D"/bad.json5 2>"$D"/msg
+[ $? -eq 3 ] || fail json5_status
+grep -i -e "JSON5 parse error at line 3" "$D"/msg >/dev/null 2>&1 || fail json5_msg
+
+printf 'bplist00\0\0' >"$D"/bad.plist
+unpack --into "$MNT" "$D"/bad.plist 2>"$D"/msg
+[ $? -eq 3 ] || fail plist_status
+grep -i -e "PLIST parse error" "$D"/msg >/dev/null 2>&1 || fail plist_msg
+
+printf 'A=1\nB="open\n' >"$D"/bad.env
+unpack --into "$MNT" "$D"/bad.env 2>"$D"/msg
+[ $? -eq 3 ] || fail dotenv_status
+grep -i -e "DOTENV parse error at line 2" "$D"/msg >/dev/null 2>&1 || fail dotenv_msg
+
+# output that can't be represented isn't a parse error
+unpack --into "$MNT" ../json/list.json || fail unpack
+pack -t toml "$MNT" 2>"$D"/msg
+[ $? -eq 1 ] || fail toml_output_status
+grep -i -e "couldn't save as TOML" "$D"/msg >/dev/null 2>&1 || fail toml_output_msg
+
+rm -r "$MNT" || fail mount
+rm -r "$D"
@@ -0,0 +1,22 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+D=$(mktemp -d)
+
+# binary plists come back binary...
+unpack --into "$D"/settings ../binary/settings.plist || fail unpack_binary
+pack -t plist "$D"/settings >"$D"/settings.plist || fail pack_binary
+[ "$(head -c 8 "$D"/settings.plist)" = "bplist00" ] || fail binary
+
+# ...and everything else comes back as XML
+printf '<plist><dict><key>a</key><integer>1</integer></dict></plist>' >"$D"/small.plist
+unpack --into "$D"/small "$D"/small.plist || fail unpack_xml
+pack -t plist "$D"/small >"$D"/got.plist || fail pack_xml
+grep -e '<integer>1</integer>' "$D"/got.plist >/dev/null || fail xml
+
+rm -r "$D"
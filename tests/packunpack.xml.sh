@@ -0,0 +1,53 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    rm -r "$D"
+    exit 1
+}
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    typeof() {
+        getfattr -n user.type --only-values "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    typeof() {
+        xattr -p user.type "$@"
+    }
+else
+    fail os
+fi
+
+D=$(mktemp -d)
+
+unpack --into "$D"/out ../xml/config.xml || fail unpack
+[ "$(typeof "$D"/out/config)" = "named" ] || fail named
+[ "$(typeof "$D"/out/config/server)" = "list" ] || fail list
+[ "$(cat "$D"/out/config/@xmlns:v)" = "http://example.com/vendor" ] || fail xmlns
+[ "$(cat "$D"/out/config/v:motd/#content/2)" = " & " ] || fail entity
+
+# mixed content keeps its order, even outside of ffs
+pack -t xml "$D"/out >"$D"/got.xml || fail pack
+grep -e 'Welcome to <b>frontend</b> &amp; <i>friends</i>!' "$D"/got.xml >/dev/null || fail mixed
+grep -e 'v:role="primary"' "$D"/got.xml >/dev/null || fail prefix
+
+# everything survives the trip
+unpack --into "$D"/again "$D"/got.xml || fail unpack_again
+pack -t json "$D"/out >"$D"/expected.json || fail json1
+pack -t json "$D"/again >"$D"/got.json || fail json2
+diff "$D"/expected.json "$D"/got.json || fail roundtrip
+
+# interleaved siblings keep their places, and text and the prolog are kept
+# as they were
+cat >"$D"/siblings.xml <<'EOT'
+<?xml version="1.0"?>
+<!DOCTYPE r>
+<r><z/><a>1e3</a><b>-0</b><a>2</a><n/></r>
+EOT
+unpack --into "$D"/siblings "$D"/siblings.xml || fail unpack_siblings
+[ "$(typeof "$D"/siblings/r/a)" = "list" ] || fail siblings_list
+pack -t xml "$D"/siblings >"$D"/got_siblings.xml || fail pack_siblings
+diff "$D"/siblings.xml "$D"/got_siblings.xml || fail siblings
+
+rm -r "$D"
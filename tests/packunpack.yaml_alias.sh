@@ -0,0 +1,38 @@
+#!/bin/sh
+
+fail() {
+    echo FAILED: $1
+    if [ "$MNT" ]
+    then
+        rm -r "$MNT"
+        rm "$TGT"
+    fi
+    exit 1
+}
+
+inum() {
+    ls -id "$1" | awk '{print $1}'
+}
+
+MNT=$(mktemp -d)
+TGT=$(mktemp)
+
+unpack --into "$MNT" ../yaml/alias.yaml || fail unpack1
+[ "$(inum $MNT/hr/1)" = "$(inum $MNT/rbi/0)" ] || fail link1
+[ "$(cat $MNT/rbi/0)" = "Sammy Sosa" ] || fail contents
+
+pack -t yaml "$MNT" >"$TGT" || fail pack1
+[ $(grep -c "Sammy Sosa" "$TGT") -eq 1 ] || fail packed_once
+grep -e '- \*' "$TGT" >/dev/null 2>&1 || fail alias
+
+rm -r "$MNT"
+MNT=$(mktemp -d)
+unpack -t yaml --into "$MNT" "$TGT" || fail unpack2
+[ "$(inum $MNT/hr/1)" = "$(inum $MNT/rbi/0)" ] || fail link2
+
+# formats without aliases get copies
+pack -t json "$MNT" >"$TGT" || fail pack2
+[ "$(cat $TGT)" = '{"hr":["Mark McGwire","Sammy Sosa"],"rbi":["Sammy Sosa","Ken Griffey"]}' ] || fail json
+
+rm -r "$MNT" || fail mount
+rm "$TGT"
@@ -0,0 +1,39 @@
+#!/bin/sh
+
+. ./fail.def
+
+if [ "$RUNNER_OS" = "Linux" ] || [ "$(uname)" = "Linux" ]; then
+    which getfattr || fail getfattr
+    typeof() {
+        getfattr -n user.type --only-values "$@"
+    }
+elif [ "$RUNNER_OS" = "macOS" ] || [ "$(uname)" = "Darwin" ]; then
+    typeof() {
+        xattr -p user.type "$@"
+    }
+else
+    fail os
+fi
+
+DIR=$(mktemp -d)
+DOCS="$(pwd)/../yaml/documents.yaml"
+
+testcase_cleanup() { cd /; rm -rf "$DIR"; }
+
+cd "$DIR"
+
+unpack --into docs "$DOCS" || fail unpack
+[ "$(typeof docs)" = "documents" ] || fail type
+[ "$(cat docs/1/kind)" = "Deployment" ] || fail kind
+
+pack -o packed.yaml docs || fail pack
+[ "$(grep -c -e '^---$' packed.yaml)" -eq 2 ] || fail separators
+grep -e '^- ' packed.yaml >/dev/null && fail list
+
+# round trip
+unpack --into again packed.yaml || fail unpack_again
+[ "$(typeof again)" = "documents" ] || fail type_again
+[ "$(cat again/0/metadata/name)" = "web" ] || fail name_again
+
+cd - >/dev/null
+rm -r "$DIR"
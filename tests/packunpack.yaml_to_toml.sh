@@ -19,8 +19,9 @@ fail() {
 ERR_MSG=$(mktemp)
 # reasons for skipping:
 # eg2.7.yaml unpacks into lists in json format because toml doesn't support it.
+# documents.yaml is a stream of documents, i.e., a list, which toml can't have at the root.
 # invoice.yaml's floats get their decimals truncated. Everything else is perfect after unpacking the packed toml version
-for f in $(find ../yaml -maxdepth 1 -name '*.yaml' ! -name 'eg2.7.yaml' ! -name 'invoice.yaml'); do
+for f in $(find ../yaml -maxdepth 1 -name '*.yaml' ! -name 'eg2.7.yaml' ! -name 'documents.yaml' ! -name 'invoice.yaml'); do
     UNPACK_MNT0=$(mktemp -d)
     unpack $f --exact --into "$UNPACK_MNT0" 2>"$ERR_MSG"
     # skip the issue where it doesn't unpack into a directory structure
@@ -0,0 +1,496 @@
+//! Turns semi-structured data into directories; the inverse of `pack`.
+//!
+//! The `unpack` binary is a thin command-line interface to `unpack_into`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+use nodelike::config::{Config, ERROR_STATUS_FUSE, Munge};
+use nodelike::{Node, Nodelike, Typ};
+
+/// Errors from unpacking a value.
+#[derive(Debug)]
+pub enum UnpackError {
+    /// Only directories can be unpacked, since the root must be one.
+    NotADirectory(PathBuf),
+    /// The directory to unpack into already has something in it.
+    NotEmpty(PathBuf),
+    /// Writing the files failed.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for UnpackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            UnpackError::NotADirectory(path) => write!(
+                f,
+                "The root of the unpacked form must be a directory, but '{}' only unpacks into a single file.",
+                path.display()
+            ),
+            UnpackError::NotEmpty(path) => write!(
+                f,
+                "Directory `{}` already exists and is not empty.",
+                path.display()
+            ),
+            UnpackError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl UnpackError {
+    /// The exit status for a tool that fails with this error.
+    pub fn exit_status(&self) -> i32 {
+        ERROR_STATUS_FUSE
+    }
+}
+
+impl std::error::Error for UnpackError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UnpackError::Io(e) => Some(e),
+            UnpackError::NotADirectory(_) | UnpackError::NotEmpty(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for UnpackError {
+    fn from(e: std::io::Error) -> Self {
+        UnpackError::Io(e)
+    }
+}
+
+/// A change that `unpack_into` would make in a dry run (see
+/// `Config::dry_run`). Each displays as a line of `unpack --dry-run`'s
+/// output, e.g., `create PATH` or `xattr PATH user.type=integer`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// Making a directory
+    Mkdir(PathBuf),
+    /// Making a new file
+    Create(PathBuf),
+    /// Overwriting an existing file
+    Write(PathBuf),
+    /// Making `path` a hard link to `target`
+    Link { path: PathBuf, target: PathBuf },
+    /// Setting the extended attribute `name`
+    Xattr {
+        path: PathBuf,
+        name: String,
+        value: String,
+    },
+    /// Removing a file, or a directory and everything in it
+    Remove(PathBuf),
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Change::Mkdir(path) => write!(f, "mkdir {}", path.display()),
+            Change::Create(path) => write!(f, "create {}", path.display()),
+            Change::Write(path) => write!(f, "write {}", path.display()),
+            Change::Link { path, target } => {
+                write!(f, "link {} {}", path.display(), target.display())
+            }
+            Change::Xattr { path, name, value } => {
+                write!(f, "xattr {} {name}={value}", path.display())
+            }
+            Change::Remove(path) => write!(f, "remove {}", path.display()),
+        }
+    }
+}
+
+/// Unpacks the value `root` into the directory `path`, which is created if
+/// it doesn't exist and must be empty if it does.
+///
+/// With `config.merge`, `path` may have things in it already: changed files
+/// are overwritten, new ones are created, and files whose contents are
+/// already right are left alone (so their mtimes don't change). With
+/// `config.delete`, too, files that aren't in `root` are removed.
+///
+/// With `config.dry_run`, nothing is changed; the changes that would have
+/// been made are returned instead, in order. Otherwise, there are none.
+pub fn unpack_into<V: Nodelike>(
+    root: V,
+    path: impl AsRef<Path>,
+    config: &Config,
+) -> Result<Vec<Change>, UnpackError> {
+    let root_path = path.as_ref().to_path_buf();
+
+    if !root.is_dir() {
+        return Err(UnpackError::NotADirectory(root_path));
+    }
+
+    let layout = root.layout();
+    let mut plan = Vec::new();
+    if config.dry_run && existing(&root_path)?.is_none() {
+        plan.push(Change::Mkdir(root_path.clone()));
+    } else {
+        match fs::create_dir(&root_path) {
+            Ok(()) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                if !config.merge && root_path.read_dir()?.next().is_some() {
+                    return Err(UnpackError::NotEmpty(root_path));
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // `pack` lays the document back out this way (see `Nodelike::layout`)
+    if config.allow_xattr
+        && let Some(layout) = layout
+    {
+        set_xattr(
+            &root_path,
+            "user.layout",
+            layout.as_bytes(),
+            config,
+            &mut plan,
+        )?;
+    }
+
+    unpack(root, root_path, config, &mut plan)?;
+    Ok(plan)
+}
+
+fn unpack<V: Nodelike>(
+    root: V,
+    root_path: PathBuf,
+    config: &Config,
+    plan: &mut Vec<Change>,
+) -> std::io::Result<()> {
+    let mut queue: VecDeque<(V, PathBuf, Option<String>)> = VecDeque::new();
+    queue.push_back((root, root_path.clone(), None));
+
+    // the first file unpacked for each anchor; later ones are hard links to it
+    let mut anchors: HashMap<String, PathBuf> = HashMap::new();
+
+    while let Some((v, path, original_name)) = queue.pop_front() {
+        let anchor = v.anchor();
+        let documents = v.is_documents();
+        if let Some(anchor) = &anchor {
+            if let Some(target) = anchors.get(anchor) {
+                hard_link(target, &path, config, plan)?;
+                continue;
+            }
+            if v.is_dir() {
+                // directories can't be hard links, so we unpack a copy
+                debug!("copying anchor {anchor} into {}", path.display());
+            } else {
+                anchors.insert(anchor.clone(), path.clone());
+            }
+        }
+
+        match v.node(config) {
+            Node::String(t, s) => {
+                // make a regular file at `path` with `s` in it
+                write_file(&path, s.as_bytes(), config, plan)?;
+
+                // set metadata according to `t`
+                if config.allow_xattr {
+                    set_xattr(&path, "user.type", t.to_string().as_bytes(), config, plan)?;
+                }
+            }
+            Node::Bytes(b) => {
+                // make a regular file at `path` with `b` in it
+                write_file(&path, b.as_slice(), config, plan)?;
+
+                // set metadata to bytes
+                if config.allow_xattr {
+                    set_xattr(
+                        &path,
+                        "user.type",
+                        Typ::Bytes.to_string().as_bytes(),
+                        config,
+                        plan,
+                    )?;
+                }
+            }
+            Node::List(vs) => {
+                // if not root path, make directory
+                if path != root_path.clone() {
+                    create_dir(&path, config, plan)?;
+                }
+                if config.allow_xattr {
+                    let typ = if documents { "documents" } else { "list" };
+                    set_xattr(&path, "user.type", typ.as_bytes(), config, plan)?;
+                }
+
+                // enqueue children with appropriate names
+                let num_elts = vs.len() as f64;
+                let width = num_elts.log10().ceil() as usize;
+
+                let mut child_names = HashSet::new();
+                for (i, child) in vs.into_iter().enumerate() {
+                    // TODO(mmg) 2021-06-08 ability to add prefixes
+                    let name = if config.pad_element_names {
+                        format!("{i:0width$}")
+                    } else {
+                        format!("{i}")
+                    };
+                    let child_path = path.join(&name);
+                    child_names.insert(name);
+
+                    queue.push_back((child, child_path, None));
+                }
+
+                // every file in a list is an element, so stale ones (e.g., `0`
+                // when there are now eleven elements, named `00` to `10`) must
+                // go, even without `--delete`
+                if config.merge {
+                    delete_others(&path, &child_names, config, plan)?;
+                }
+            }
+            Node::Map(fvs) => {
+                // if not root path, make directory
+                if path != root_path.clone() {
+                    create_dir(&path, config, plan)?;
+                }
+                if config.allow_xattr {
+                    set_xattr(&path, "user.type", "named".as_bytes(), config, plan)?;
+                }
+
+                // enqueue children with appropriate names
+                let mut child_names = HashSet::new();
+                for (field, child) in fvs.into_iter() {
+                    let original = field.clone();
+
+                    // munge name to be valid and unique
+                    let name = if !config.valid_name(&original) {
+                        match config.munge {
+                            Munge::Rename => {
+                                let mut nfield = config.normalize_name(field);
+
+                                while child_names.contains(&nfield) {
+                                    nfield.push('_');
+                                }
+
+                                nfield
+                            }
+                            Munge::Filter => {
+                                // TODO(mmg) 2023-03-06 support logging
+                                warn!("skipping '{field}'");
+                                continue;
+                            }
+                        }
+                    } else {
+                        field
+                    };
+                    child_names.insert(name.clone());
+
+                    let child_path = path.join(name);
+                    queue.push_back((child, child_path, Some(original)));
+                }
+
+                if config.delete {
+                    delete_others(&path, &child_names, config, plan)?;
+                }
+            }
+        }
+
+        if let Some(original_name) = original_name
+            && config.allow_xattr
+        {
+            set_xattr(
+                &path,
+                "user.original_name",
+                original_name.as_bytes(),
+                config,
+                plan,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes a regular file at `path` with `contents` in it.
+///
+/// When merging, whatever is at `path` already is replaced---unless it's a
+/// file with exactly `contents`, which we leave alone.
+fn write_file(
+    path: &Path,
+    contents: &[u8],
+    config: &Config,
+    plan: &mut Vec<Change>,
+) -> std::io::Result<()> {
+    if config.merge {
+        match existing(path)? {
+            Some(meta) if meta.is_file() && meta.nlink() == 1 => {
+                if meta.len() == contents.len() as u64 && fs::read(path)? == contents {
+                    debug!("leaving {} alone", path.display());
+                    return Ok(());
+                }
+
+                debug!("overwriting {}", path.display());
+                if config.dry_run {
+                    plan.push(Change::Write(path.to_path_buf()));
+                    return Ok(());
+                }
+                let mut f = fs::OpenOptions::new()
+                    .write(true)
+                    .truncate(true)
+                    .open(path)?;
+                return f.write_all(contents);
+            }
+            // hard links get a fresh file, so we don't change the other links
+            Some(_) => remove(path, config, plan)?,
+            None => (),
+        }
+    }
+
+    if config.dry_run {
+        plan.push(Change::Create(path.to_path_buf()));
+        return Ok(());
+    }
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    f.write_all(contents)
+}
+
+/// Makes a directory at `path`; when merging, an existing one is fine.
+fn create_dir(path: &Path, config: &Config, plan: &mut Vec<Change>) -> std::io::Result<()> {
+    if config.merge {
+        match existing(path)? {
+            Some(meta) if meta.is_dir() => return Ok(()),
+            Some(_) => remove(path, config, plan)?,
+            None => (),
+        }
+    }
+
+    if config.dry_run {
+        plan.push(Change::Mkdir(path.to_path_buf()));
+        return Ok(());
+    }
+    fs::create_dir(path)
+}
+
+/// Makes `path` a hard link to `target`; when merging, whatever is at `path`
+/// is replaced, unless it's already a link to `target`.
+fn hard_link(
+    target: &Path,
+    path: &Path,
+    config: &Config,
+    plan: &mut Vec<Change>,
+) -> std::io::Result<()> {
+    if config.merge
+        && let Some(meta) = existing(path)?
+    {
+        if let Some(target) = existing(target)?
+            && meta.dev() == target.dev()
+            && meta.ino() == target.ino()
+        {
+            return Ok(());
+        }
+        remove(path, config, plan)?;
+    }
+
+    debug!("linking {} to {}", path.display(), target.display());
+    if config.dry_run {
+        plan.push(Change::Link {
+            path: path.to_path_buf(),
+            target: target.to_path_buf(),
+        });
+        return Ok(());
+    }
+    fs::hard_link(target, path)
+}
+
+/// Sets the extended attribute `name` on `path`; when merging, only if it
+/// would change.
+fn set_xattr(
+    path: &Path,
+    name: &str,
+    value: &[u8],
+    config: &Config,
+    plan: &mut Vec<Change>,
+) -> std::io::Result<()> {
+    // in a dry run, `path` may not really be there
+    let exists = !config.dry_run || existing(path)?.is_some();
+    if config.merge && exists && xattr::get(path, name)?.as_deref() == Some(value) {
+        return Ok(());
+    }
+
+    if config.dry_run {
+        plan.push(Change::Xattr {
+            path: path.to_path_buf(),
+            name: name.to_string(),
+            value: String::from_utf8_lossy(value).into_owned(),
+        });
+        return Ok(());
+    }
+    xattr::set(path, name, value)
+}
+
+/// Removes whatever is at `path`, which may be a (non-empty) directory.
+fn remove(path: &Path, config: &Config, plan: &mut Vec<Change>) -> std::io::Result<()> {
+    debug!("removing {}", path.display());
+    if config.dry_run {
+        plan.push(Change::Remove(path.to_path_buf()));
+        return Ok(());
+    }
+
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Returns the metadata of whatever is at `path`, if anything is.
+fn existing(path: &Path) -> std::io::Result<Option<fs::Metadata>> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) => Ok(Some(meta)),
+        // in a dry run, a parent may be a file we'd have replaced
+        Err(e)
+            if e.kind() == std::io::ErrorKind::NotFound
+                || e.kind() == std::io::ErrorKind::NotADirectory =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Removes everything in the directory `path` that isn't in `keep`.
+///
+/// Files that `pack` would ignore are left alone, as is `.git`, so a merged
+/// directory can be kept under version control.
+fn delete_others(
+    path: &Path,
+    keep: &HashSet<String>,
+    config: &Config,
+    plan: &mut Vec<Change>,
+) -> std::io::Result<()> {
+    // in a dry run, `path` may not really be there
+    if !existing(path)?.is_some_and(|meta| meta.is_dir()) {
+        return Ok(());
+    }
+
+    // in order, so dry runs are predictable
+    let mut names = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    names.sort();
+
+    for name in names {
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+
+        if keep.contains(name) || name == ".git" || config.ignored_file(name) {
+            continue;
+        }
+
+        remove(&path.join(name), config, plan)?;
+    }
+
+    Ok(())
+}
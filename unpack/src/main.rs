@@ -1,15 +1,18 @@
 use clap::{Arg, ArgAction, Command};
 use tracing::{debug, error, info, warn};
 
-use std::collections::VecDeque;
-use std::fs;
-use std::io::Write;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use nodelike::config::{
-    Config, ERROR_STATUS_CLI, ERROR_STATUS_FUSE, Input, Munge, POSSIBLE_FORMATS,
+    Compression, Config, ERROR_STATUS_CLI, ERROR_STATUS_FUSE, Input, POSSIBLE_FORMATS,
 };
-use nodelike::{Format, Node, Nodelike, ParseFormatError, Typ, json, toml, yaml};
+use nodelike::{
+    Error, Format, Nodelike, ParseFormatError, cbor, csv, dotenv, ini, json, json5, msgpack,
+    ndjson, plist, properties, toml, tsv, xml, yaml,
+};
+
+use unpack::unpack_into;
 
 pub fn unpack_cli() -> Command {
     nodelike::config::cli_base("unpack")
@@ -37,6 +40,27 @@ pub fn unpack_cli() -> Command {
                 .short('i')
                 .value_name("INTO")
         )
+        .arg(
+            Arg::new("MERGE")
+                .help("Update an existing directory in place: overwrite changed files and create new ones, leaving files that are already right alone")
+                .long("merge")
+                .visible_alias("overwrite")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("DELETE")
+                .help("With --merge, remove files that aren't in the input (`.git` is always kept); stale list elements are always removed")
+                .long("delete")
+                .requires("MERGE")
+                .action(ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("DRY_RUN")
+                .help("Print what would be done (one line per file, directory, or extended attribute created, changed, or removed) without doing it")
+                .long("dry-run")
+                .short('n')
+                .action(ArgAction::SetTrue)
+        )
         .arg(
             Arg::new("INPUT")
                 .help("Sets the input file ('-' means STDIN)")
@@ -50,6 +74,9 @@ pub fn config_from_unpack_args() -> Config {
 
     // simple flags
     config.pad_element_names = !args.get_flag("UNPADDED");
+    config.merge = args.get_flag("MERGE");
+    config.delete = args.get_flag("DELETE");
+    config.dry_run = args.get_flag("DRY_RUN");
 
     // configure input
     config.input = match args.get_one::<String>("INPUT") {
@@ -70,23 +97,8 @@ pub fn config_from_unpack_args() -> Config {
 
     // infer and create mountpoint from filename as possible
     config.mount = match args.get_one::<String>("INTO") {
-        Some(mount_point) => {
-            match std::fs::create_dir(mount_point) {
-                Ok(_) => Some(PathBuf::from(mount_point)),
-                Err(_) => {
-                    // if dir is empty then we can use it
-                    let mount = PathBuf::from(mount_point);
-                    if mount.read_dir().unwrap().next().is_none() {
-                        // dir exists and is empty
-                        Some(PathBuf::from(mount_point))
-                    } else {
-                        // dir exists but is not empty
-                        error!("Directory `{mount_point}` already exists and is not empty.");
-                        std::process::exit(ERROR_STATUS_FUSE);
-                    }
-                }
-            }
-        }
+        // `unpack_into` creates it (or checks that it's empty, unless merging)
+        Some(mount_point) => Some(PathBuf::from(mount_point)),
         None => {
             match &config.input {
                 Input::Stdin => {
@@ -100,16 +112,19 @@ pub fn config_from_unpack_args() -> Config {
                     std::process::exit(ERROR_STATUS_CLI);
                 }
                 Input::File(file) => {
-                    // If the input is from a file foo.EXT, then try to make a directory foo.
-                    let stem = file.file_stem().unwrap_or_else(|| {
+                    // If the input is from a file foo.EXT (or foo.EXT.gz, etc.), then try to make a directory foo.
+                    let name = Compression::strip(file);
+                    let stem = name.file_stem().unwrap_or_else(|| {
                             error!("Couldn't infer the directory to unpack into from input '{}'. Use `--into DIRECTORY` to specify a directory.", file.display());
                             std::process::exit(ERROR_STATUS_FUSE);
                         });
                     let mount_dir = PathBuf::from(stem);
                     debug!("inferred mount_dir {}", mount_dir.display());
 
-                    // If that file already exists, give up and tell the user about --mount.
-                    if mount_dir.exists() {
+                    // If that file already exists, give up and tell the user about --mount
+                    // (unless we're merging into it).
+                    let merging = config.merge && mount_dir.is_dir();
+                    if mount_dir.exists() && !merging {
                         error!(
                             "Inferred directory '{mount}' for input file '{file}', but '{mount}' already exists. Use `--into DIRECTORY` to specify a directory.",
                             mount = mount_dir.display(),
@@ -118,7 +133,11 @@ pub fn config_from_unpack_args() -> Config {
                         std::process::exit(ERROR_STATUS_FUSE);
                     }
                     // If the mountpoint can't be created, give up and tell the user about --mount.
-                    if let Err(e) = std::fs::create_dir(&mount_dir) {
+                    // (In a dry run, `unpack_into` just says it would create it.)
+                    if !merging
+                        && !config.dry_run
+                        && let Err(e) = std::fs::create_dir(&mount_dir)
+                    {
                         error!(
                             "Couldn't create directory '{}': {e}. Use `--into DIRECTORY` to specify a directory.",
                             mount_dir.display()
@@ -159,12 +178,7 @@ pub fn config_from_unpack_args() -> Config {
             match &config.input {
                 Input::Stdin => Format::Json,
                 Input::Empty => Format::Json,
-                Input::File(input_source) => match input_source
-                    .extension()
-                    .and_then(|s| s.to_str())
-                    .ok_or(ParseFormatError::NoFormatProvided)
-                    .and_then(|s| s.parse::<Format>())
-                {
+                Input::File(input_source) => match Format::from_path(input_source) {
                     Ok(format) => format,
                     Err(_) => {
                         warn!(
@@ -181,121 +195,7 @@ pub fn config_from_unpack_args() -> Config {
     config
 }
 
-fn unpack<V: Nodelike>(root: V, root_path: PathBuf, config: &Config) -> std::io::Result<()> {
-    let mut queue: VecDeque<(V, PathBuf, Option<String>)> = VecDeque::new();
-    queue.push_back((root, root_path.clone(), None));
-
-    while let Some((v, path, original_name)) = queue.pop_front() {
-        match v.node(config) {
-            Node::String(t, s) => {
-                // make a regular file at `path`
-                let mut f = fs::OpenOptions::new()
-                    .write(true)
-                    .create_new(true) // TODO(mmg) 2023-03-06 allow truncation?
-                    .open(&path)?;
-
-                // write `s` into that file
-                f.write_all(s.as_bytes())?;
-
-                // set metadata according to `t`
-                if config.allow_xattr {
-                    xattr::set(&path, "user.type", t.to_string().as_bytes())?;
-                }
-            }
-            Node::Bytes(b) => {
-                // make a regular file at `path`
-                let mut f = fs::OpenOptions::new()
-                    .write(true)
-                    .create_new(true) // TODO(mmg) 2023-03-06 allow truncation?
-                    .open(&path)?;
-
-                // write `b` into that file
-                f.write_all(b.as_slice())?;
-
-                // set metadata to bytes
-                if config.allow_xattr {
-                    xattr::set(&path, "user.type", Typ::Bytes.to_string().as_bytes())?;
-                }
-            }
-            Node::List(vs) => {
-                // if not root path, make directory
-                if path != root_path.clone() {
-                    fs::create_dir(&path)?;
-                }
-                if config.allow_xattr {
-                    xattr::set(&path, "user.type", "list".as_bytes())?;
-                }
-
-                // enqueue children with appropriate names
-                let num_elts = vs.len() as f64;
-                let width = num_elts.log10().ceil() as usize;
-
-                for (i, child) in vs.into_iter().enumerate() {
-                    // TODO(mmg) 2021-06-08 ability to add prefixes
-                    let name = if config.pad_element_names {
-                        format!("{i:0width$}")
-                    } else {
-                        format!("{i}")
-                    };
-                    let child_path = path.join(name);
-
-                    queue.push_back((child, child_path, None));
-                }
-            }
-            Node::Map(fvs) => {
-                // if not root path, make directory
-                if path != root_path.clone() {
-                    fs::create_dir(&path)?;
-                }
-                if config.allow_xattr {
-                    xattr::set(&path, "user.type", "named".as_bytes())?;
-                }
-
-                // enqueue children with appropriate names
-                let mut child_names = std::collections::HashSet::new();
-                for (field, child) in fvs.into_iter() {
-                    let original = field.clone();
-
-                    // munge name to be valid and unique
-                    let name = if !config.valid_name(&original) {
-                        match config.munge {
-                            Munge::Rename => {
-                                let mut nfield = config.normalize_name(field);
-
-                                while child_names.contains(&nfield) {
-                                    nfield.push('_');
-                                }
-
-                                nfield
-                            }
-                            Munge::Filter => {
-                                // TODO(mmg) 2023-03-06 support logging
-                                warn!("skipping '{field}'");
-                                continue;
-                            }
-                        }
-                    } else {
-                        field
-                    };
-                    child_names.insert(name.clone());
-
-                    let child_path = path.join(name);
-                    queue.push_back((child, child_path, Some(original)));
-                }
-            }
-        }
-
-        if let Some(original_name) = original_name
-            && config.allow_xattr
-        {
-            xattr::set(&path, "user.original_name", original_name.as_bytes())?;
-        }
-    }
-
-    Ok(())
-}
-
-fn main() -> std::io::Result<()> {
+fn main() {
     let config = config_from_unpack_args();
 
     let mount = match &config.mount {
@@ -307,30 +207,56 @@ fn main() -> std::io::Result<()> {
     };
     info!("mount: {mount:?}");
 
+    fn run_unpack<V: Nodelike>(value: Result<V, Error>, mount: PathBuf, config: &Config) {
+        let value = match value {
+            Ok(value) => value,
+            Err(e) => {
+                error!("Couldn't load {}: {e}", config.input);
+                std::process::exit(e.exit_status());
+            }
+        };
+
+        match unpack_into(value, mount, config) {
+            Ok(plan) => {
+                for change in plan {
+                    println!("{change}");
+                }
+            }
+            Err(e) => {
+                error!("{e}");
+                std::process::exit(e.exit_status());
+            }
+        }
+    }
+
     let reader = match config.input_reader() {
         Some(reader) => reader,
         None => {
-            // Empty input: the mount directory already exists, so just
-            // leave it as an empty directory.
-            info!("Empty input; unpacked into empty directory {mount:?}");
-            return Ok(());
+            // Empty input: unpack into an empty directory
+            info!("Empty input; unpacking into empty directory {mount:?}");
+            let empty = json::Value::from_named_dir(BTreeMap::new(), &config);
+            return run_unpack(Ok(empty), mount, &config);
         }
     };
 
-    fn run_unpack<V: Nodelike>(value: V, mount: PathBuf, config: &Config) -> std::io::Result<()> {
-        if value.is_dir() {
-            unpack(value, mount, config)
-        } else {
-            error!(
-                "The root of the unpacked form must be a directory, but '{}' only unpacks into a single file.",
-                mount.display()
-            );
-            std::process::exit(ERROR_STATUS_FUSE);
-        }
-    }
     match config.input_format {
-        Format::Json => run_unpack(json::Value::from_reader(reader), mount, &config),
-        Format::Toml => run_unpack(toml::Value::from_reader(reader), mount, &config),
-        Format::Yaml => run_unpack(yaml::Value::from_reader(reader), mount, &config),
+        Format::Cbor => run_unpack(cbor::Value::from_reader(reader, &config), mount, &config),
+        Format::Csv => run_unpack(csv::Value::from_reader(reader, &config), mount, &config),
+        Format::Dotenv => run_unpack(dotenv::Value::from_reader(reader, &config), mount, &config),
+        Format::Ini => run_unpack(ini::Value::from_reader(reader, &config), mount, &config),
+        Format::Json => run_unpack(json::Value::from_reader(reader, &config), mount, &config),
+        Format::Json5 => run_unpack(json5::Value::from_reader(reader, &config), mount, &config),
+        Format::MsgPack => run_unpack(msgpack::Value::from_reader(reader, &config), mount, &config),
+        Format::Ndjson => run_unpack(ndjson::Value::from_reader(reader, &config), mount, &config),
+        Format::Plist => run_unpack(plist::Value::from_reader(reader, &config), mount, &config),
+        Format::Properties => run_unpack(
+            properties::Value::from_reader(reader, &config),
+            mount,
+            &config,
+        ),
+        Format::Toml => run_unpack(toml::Value::from_reader(reader, &config), mount, &config),
+        Format::Tsv => run_unpack(tsv::Value::from_reader(reader, &config), mount, &config),
+        Format::Xml => run_unpack(xml::Value::from_reader(reader, &config), mount, &config),
+        Format::Yaml => run_unpack(yaml::Value::from_reader(reader, &config), mount, &config),
     }
 }